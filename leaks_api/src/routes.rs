@@ -0,0 +1,439 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use leaks_store::{
+    group_rows, CredentialRow, HashRangeStore, LeakStore, PwnedHash, PwnedStore, Stats,
+    HASH_PREFIX_LEN,
+};
+use lib::{parse_domain, LeakData, PublicSuffixList};
+use log::{error, warn};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+static DEFAULT_PER_PAGE: usize = 100;
+static MAX_PER_PAGE: usize = 1000;
+
+/// Lines a padded range response is filled up to, picked at random
+/// per response like the HaveIBeenPwned range api does
+static PADDED_RANGE_LINES: std::ops::RangeInclusive<usize> = 800..=1000;
+
+pub struct AppState<S> {
+    pub store: S,
+    /// Splits emails into username, subdomain and domain
+    pub st: PublicSuffixList,
+    /// Accepted bearer tokens
+    pub tokens: HashSet<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Pagination {
+    /// 1-based page number
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+/// Paged response, `total` counts credentials over all pages
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize,
+    pub items: T,
+}
+
+pub enum ApiError {
+    BadRequest(String),
+    NotFound,
+    Store(String),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::NotFound => (StatusCode::NOT_FOUND, "nothing found".to_string()),
+            ApiError::Store(message) => {
+                error!("Store failed: {}", message);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "store failed".to_string(),
+                )
+            }
+        };
+
+        (status, Json(serde_json::json!({ "error": message }))).into_response()
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for ApiError {
+    fn from(e: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        ApiError::Store(e.to_string())
+    }
+}
+
+/// Cuts a page out of `rows` and groups it back into documents per domain
+fn paginate(rows: Vec<CredentialRow>, pagination: &Pagination) -> Page<Vec<LeakData>> {
+    let page = pagination.page.unwrap_or(1).max(1);
+    let per_page = pagination
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+    let total = rows.len();
+
+    let mut items: Vec<LeakData> = Vec::new();
+    let mut domain_rows: Vec<CredentialRow> = Vec::new();
+    for row in rows.into_iter().skip((page - 1) * per_page).take(per_page) {
+        if domain_rows.first().map(|x| x.domain != row.domain) == Some(true) {
+            let domain = domain_rows[0].domain.clone();
+            items.push(group_rows(&domain, domain_rows.drain(..)));
+        }
+        domain_rows.push(row);
+    }
+    if !domain_rows.is_empty() {
+        let domain = domain_rows[0].domain.clone();
+        items.push(group_rows(&domain, domain_rows));
+    }
+
+    Page {
+        page,
+        per_page,
+        total,
+        items,
+    }
+}
+
+fn flatten(leaks: Vec<LeakData>) -> Vec<CredentialRow> {
+    leaks
+        .into_iter()
+        .flat_map(|leak_data| {
+            let domain = leak_data.domain;
+            leak_data.credentials.into_iter().flat_map(move |x| {
+                let domain = domain.clone();
+                x.data
+                    .into_iter()
+                    .map(move |(username, password)| CredentialRow {
+                        domain: domain.clone(),
+                        subdomain: x.subdomain.clone(),
+                        username,
+                        password,
+                    })
+            })
+        })
+        .collect()
+}
+
+async fn get_domain<S: LeakStore>(
+    State(state): State<Arc<AppState<S>>>,
+    Path(domain): Path<String>,
+    Query(pagination): Query<Pagination>,
+) -> Result<Json<Page<Vec<LeakData>>>, ApiError> {
+    let domain = domain.trim().to_lowercase();
+    let rows = flatten(state.store.find_domain(&domain).await?);
+
+    if rows.is_empty() {
+        return Err(ApiError::NotFound);
+    }
+    Ok(Json(paginate(rows, &pagination)))
+}
+
+async fn get_email<S: LeakStore>(
+    State(state): State<Arc<AppState<S>>>,
+    Path(email): Path<String>,
+    Query(pagination): Query<Pagination>,
+) -> Result<Json<Page<Vec<LeakData>>>, ApiError> {
+    let (username, host) = match email.trim().rsplit_once('@') {
+        Some((username, host)) if !username.is_empty() && !host.is_empty() => (username, host),
+        _ => {
+            return Err(ApiError::BadRequest(
+                "expected an email like user@domain.tld".to_string(),
+            ))
+        }
+    };
+    let host = host.to_lowercase();
+    let (subdomain, domain) = parse_domain(&host, &state.st);
+
+    let rows = state.store.find_email(domain, subdomain, username).await?;
+    if rows.is_empty() {
+        return Err(ApiError::NotFound);
+    }
+    Ok(Json(paginate(rows, &pagination)))
+}
+
+/// Random hash suffix with a count of 0, clients skip them
+fn padding_line(rng: &mut impl Rng) -> String {
+    let suffix: String = (0..40 - HASH_PREFIX_LEN)
+        .map(|_| char::from_digit(rng.gen_range(0..16), 16).unwrap())
+        .collect();
+    format!("{}:0", suffix.to_uppercase())
+}
+
+/// HaveIBeenPwned style k-anonymity lookup: the client sends the first 5 hex
+/// digits of the SHA-1 of a lowercased email and gets every stored hash
+/// with that prefix as SUFFIX:COUNT lines, so the email itself never leaves it.
+/// An `Add-Padding: true` header hides the number of real matches
+async fn get_range<S: LeakStore + HashRangeStore>(
+    State(state): State<Arc<AppState<S>>>,
+    Path(prefix): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    if prefix.len() != HASH_PREFIX_LEN || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ApiError::BadRequest(format!(
+            "expected the first {} hex digits of a SHA-1 hash",
+            HASH_PREFIX_LEN
+        )));
+    }
+
+    let range = state.store.hash_range(&prefix.to_uppercase()).await?;
+    let mut lines: Vec<String> = range
+        .into_iter()
+        .map(|x| format!("{}:{}", x.suffix, x.credentials))
+        .collect();
+
+    let padding = headers
+        .get("Add-Padding")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("true"));
+    if padding {
+        let mut rng = rand::thread_rng();
+        let size = rng.gen_range(PADDED_RANGE_LINES.clone());
+        while lines.len() < size {
+            lines.push(padding_line(&mut rng));
+        }
+        lines.sort();
+    }
+
+    let mut body = lines.join("\r\n");
+    if !body.is_empty() {
+        body.push_str("\r\n");
+    }
+    Ok(([(header::CONTENT_TYPE, "text/plain")], body).into_response())
+}
+
+/// Whether the password of a SHA-1 or NTLM `hash` is in the loaded
+/// Pwned Passwords files, and how often it was seen
+async fn get_password<S: PwnedStore>(
+    State(state): State<Arc<AppState<S>>>,
+    Path(hash): Path<String>,
+) -> Result<Json<PwnedHash>, ApiError> {
+    let valid = matches!(hash.len(), 32 | 40) && hash.chars().all(|c| c.is_ascii_hexdigit());
+    if !valid {
+        return Err(ApiError::BadRequest(
+            "expected a SHA-1 or NTLM password hash in hex".to_string(),
+        ));
+    }
+
+    let hash = hash.to_uppercase();
+    match state.store.pwned_count(&hash).await? {
+        Some(count) => Ok(Json(PwnedHash { hash, count })),
+        None => Err(ApiError::NotFound),
+    }
+}
+
+async fn get_stats<S: LeakStore>(
+    State(state): State<Arc<AppState<S>>>,
+) -> Result<Json<Stats>, ApiError> {
+    Ok(Json(state.store.stats().await?))
+}
+
+async fn authorize<S>(
+    State(state): State<Arc<AppState<S>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match token {
+        Some(token) if state.tokens.contains(token) => next.run(request).await,
+        _ => {
+            warn!("Rejected unauthorized request to {}", request.uri().path());
+            StatusCode::UNAUTHORIZED.into_response()
+        }
+    }
+}
+
+pub fn router<S>(state: Arc<AppState<S>>) -> Router
+where
+    S: LeakStore + HashRangeStore + PwnedStore + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/domains/{domain}", get(get_domain::<S>))
+        .route("/emails/{email}", get(get_email::<S>))
+        .route("/range/{prefix}", get(get_range::<S>))
+        .route("/passwords/{hash}", get(get_password::<S>))
+        .route("/stats", get(get_stats::<S>))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            authorize::<S>,
+        ))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::{to_bytes, Body};
+    use leaks_store::SqliteStore;
+    use lib::{CredentialData, DOCUMENT_VERSION};
+    use tower::ServiceExt;
+
+    async fn app() -> Router {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        store
+            .insert(&LeakData {
+                domain: "corp.com".to_string(),
+                credentials: vec![CredentialData {
+                    subdomain: "vpn".to_string(),
+                    data: (0..5)
+                        .map(|i| (format!("user{}", i), "pass".to_string()))
+                        .collect(),
+                }],
+                breaches: Vec::new(),
+                org: String::new(),
+                version: DOCUMENT_VERSION,
+            })
+            .await
+            .unwrap();
+
+        store
+            .insert_pwned(&[PwnedHash {
+                hash: "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8".to_string(),
+                count: 52,
+            }])
+            .await
+            .unwrap();
+
+        router(Arc::new(AppState {
+            store,
+            st: PublicSuffixList::new("com"),
+            tokens: HashSet::from(["secret".to_string()]),
+        }))
+    }
+
+    async fn get(app: Router, uri: &str, token: Option<&str>) -> (StatusCode, Vec<u8>) {
+        get_with(app, uri, token, &[]).await
+    }
+
+    async fn get_with(
+        app: Router,
+        uri: &str,
+        token: Option<&str>,
+        headers: &[(&str, &str)],
+    ) -> (StatusCode, Vec<u8>) {
+        let mut request = Request::builder().uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn unauthorized() {
+        let (status, _) = get(app().await, "/stats", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = get(app().await, "/stats", Some("wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn domain_pages() {
+        let uri = "/domains/corp.com?page=2&per_page=2";
+        let (status, body) = get(app().await, uri, Some("secret")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let page: Page<Vec<LeakData>> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(page.total, 5);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(
+            page.items[0].credentials[0].data,
+            vec![
+                ("user2".to_string(), "pass".to_string()),
+                ("user3".to_string(), "pass".to_string())
+            ]
+        );
+
+        let (status, _) = get(app().await, "/domains/nope.com", Some("secret")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn email_and_stats() {
+        let uri = "/emails/user1@VPN.corp.com";
+        let (status, body) = get(app().await, uri, Some("secret")).await;
+        assert_eq!(status, StatusCode::OK);
+        let page: Page<Vec<LeakData>> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].credentials[0].subdomain, "vpn");
+
+        let (status, _) = get(app().await, "/emails/broken", Some("secret")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (_, body) = get(app().await, "/stats", Some("secret")).await;
+        let stats: Stats = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stats.credentials, 5);
+    }
+
+    #[tokio::test]
+    async fn hash_range() {
+        let hash = leaks_store::email_hash("user1", "vpn", "corp.com");
+        let uri = format!("/range/{}", hash[..5].to_lowercase());
+        let (status, body) = get(app().await, &uri, Some("secret")).await;
+        assert_eq!(status, StatusCode::OK);
+        let body = String::from_utf8(body).unwrap();
+        assert!(body.contains(&format!("{}:1\r\n", &hash[5..])));
+
+        let (_, body) = get_with(
+            app().await,
+            &uri,
+            Some("secret"),
+            &[("Add-Padding", "true")],
+        )
+        .await;
+        let body = String::from_utf8(body).unwrap();
+        assert!(PADDED_RANGE_LINES.contains(&body.lines().count()));
+        assert!(body.contains(&format!("{}:1\r\n", &hash[5..])));
+
+        let (status, _) = get(app().await, "/range/ABCD", Some("secret")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get(app().await, "/range/ABCDZ", Some("secret")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn pwned_password() {
+        let uri = "/passwords/5baa61e4c9b93f3f0682250b6cf8331b7ee68fd8";
+        let (status, body) = get(app().await, uri, Some("secret")).await;
+        assert_eq!(status, StatusCode::OK);
+        let pwned: PwnedHash = serde_json::from_slice(&body).unwrap();
+        assert_eq!(pwned.count, 52);
+
+        let uri = "/passwords/31D6CFE0D16AE931B73C59D7E0C089C0";
+        let (status, _) = get(app().await, uri, Some("secret")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get(app().await, "/passwords/password", Some("secret")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
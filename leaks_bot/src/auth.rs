@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use teloxide::types::UserId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    User,
+    /// Can grant and revoke access of other users
+    Admin,
+}
+
+/// Allow-list of telegram users, runtime grants are lost on restart
+pub struct Auth {
+    users: HashMap<UserId, Role>,
+}
+
+impl Auth {
+    pub fn new(admins: &[u64], users: &[u64]) -> Auth {
+        let mut auth = Auth {
+            users: HashMap::new(),
+        };
+        for id in users {
+            auth.users.insert(UserId(*id), Role::User);
+        }
+        // Admins listed as users too keep the admin role
+        for id in admins {
+            auth.users.insert(UserId(*id), Role::Admin);
+        }
+        auth
+    }
+
+    pub fn role(&self, user: UserId) -> Option<Role> {
+        self.users.get(&user).copied()
+    }
+
+    /// Allows `user` to query the bot, existing roles are kept
+    pub fn grant(&mut self, user: UserId) {
+        self.users.entry(user).or_insert(Role::User);
+    }
+
+    /// Removes `user` from the allow-list, admins can't be revoked.
+    /// Returns whether the user had access
+    pub fn revoke(&mut self, user: UserId) -> bool {
+        match self.users.get(&user) {
+            Some(Role::User) => {
+                self.users.remove(&user);
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    num::NonZeroUsize,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use lib::LeakData;
+use lru::LruCache;
+
+struct Entry {
+    stored: Instant,
+    /// Credentials of `leaks`, counted against the budget of the cache
+    credentials: usize,
+    leaks: Arc<Vec<LeakData>>,
+}
+
+/// Documents of recently looked up domains, so popular domains don't hit
+/// the store on every lookup. Entries expire after `ttl`. The cache holds
+/// up to `size` domains and `max_credentials` credentials across them, the
+/// least recently used domains make room when either is reached. Lookups
+/// share the cached documents instead of copying them
+pub struct QueryCache {
+    ttl: Duration,
+    max_credentials: usize,
+    /// Credentials of the cached domains
+    credentials: usize,
+    /// None when the cache is turned off
+    entries: Option<LruCache<String, Entry>>,
+}
+
+impl QueryCache {
+    /// Cache of up to `size` domains and `max_credentials` credentials,
+    /// 0 for either turns it off
+    pub fn new(size: usize, max_credentials: usize, ttl: Duration) -> QueryCache {
+        let size = NonZeroUsize::new(size).filter(|_| max_credentials > 0);
+        QueryCache {
+            ttl,
+            max_credentials,
+            credentials: 0,
+            entries: size.map(LruCache::new),
+        }
+    }
+
+    /// Cached documents of `domain`, unless they have expired
+    pub fn get(&mut self, domain: &str) -> Option<Arc<Vec<LeakData>>> {
+        let entries = self.entries.as_mut()?;
+        match entries.get(domain) {
+            Some(entry) if entry.stored.elapsed() < self.ttl => Some(entry.leaks.clone()),
+            Some(_) => {
+                self.invalidate(domain);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Caches the documents of `domain`, unless they alone have more
+    /// credentials than the cache holds
+    pub fn insert(&mut self, domain: &str, leaks: Arc<Vec<LeakData>>) {
+        self.invalidate(domain);
+        let Some(entries) = &mut self.entries else {
+            return;
+        };
+        let credentials = leaks
+            .iter()
+            .flat_map(|x| &x.credentials)
+            .map(|x| x.data.len())
+            .sum();
+        if credentials > self.max_credentials {
+            return;
+        }
+
+        let entry = Entry {
+            stored: Instant::now(),
+            credentials,
+            leaks,
+        };
+        if let Some((_, evicted)) = entries.push(domain.to_string(), entry) {
+            self.credentials -= evicted.credentials;
+        }
+        self.credentials += credentials;
+        while self.credentials > self.max_credentials {
+            match entries.pop_lru() {
+                Some((_, evicted)) => self.credentials -= evicted.credentials,
+                None => break,
+            }
+        }
+    }
+
+    /// Drops every domain, after leaks were imported outside of the bot
+    pub fn clear(&mut self) {
+        if let Some(entries) = &mut self.entries {
+            entries.clear();
+        }
+        self.credentials = 0;
+    }
+
+    /// Drops `domain` after credentials of it were imported
+    pub fn invalidate(&mut self, domain: &str) {
+        if let Some(entries) = &mut self.entries {
+            if let Some(entry) = entries.pop(domain) {
+                self.credentials -= entry.credentials;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::CredentialData;
+
+    use super::*;
+
+    fn leaks(domain: &str, credentials: usize) -> Arc<Vec<LeakData>> {
+        Arc::new(vec![LeakData {
+            domain: domain.to_string(),
+            credentials: vec![CredentialData {
+                subdomain: String::new(),
+                data: vec![("user".to_string(), "pass".to_string()); credentials],
+            }],
+            breaches: Vec::new(),
+            org: String::new(),
+            version: 3,
+        }])
+    }
+
+    #[test]
+    fn bounded_by_credentials() {
+        let mut cache = QueryCache::new(10, 100, Duration::from_secs(60));
+        cache.insert("a.com", leaks("a.com", 40));
+        cache.insert("b.com", leaks("b.com", 40));
+        assert!(cache.get("a.com").is_some());
+
+        // b.com is the least recently used one
+        cache.insert("c.com", leaks("c.com", 40));
+        assert!(cache.get("b.com").is_none());
+        assert!(cache.get("a.com").is_some());
+        assert!(cache.get("c.com").is_some());
+
+        cache.insert("d.com", leaks("d.com", 101));
+        assert!(cache.get("d.com").is_none());
+
+        cache.invalidate("a.com");
+        cache.insert("e.com", leaks("e.com", 60));
+        assert!(cache.get("c.com").is_some());
+        assert!(cache.get("e.com").is_some());
+
+        cache.clear();
+        assert!(cache.get("c.com").is_none());
+        cache.insert("f.com", leaks("f.com", 100));
+        assert!(cache.get("f.com").is_some());
+    }
+
+    #[test]
+    fn shares_documents() {
+        let mut cache = QueryCache::new(10, 100, Duration::from_secs(60));
+        let documents = leaks("a.com", 1);
+        cache.insert("a.com", documents.clone());
+        assert!(Arc::ptr_eq(&cache.get("a.com").unwrap(), &documents));
+
+        let mut off = QueryCache::new(10, 0, Duration::from_secs(60));
+        off.insert("a.com", documents);
+        assert!(off.get("a.com").is_none());
+    }
+}
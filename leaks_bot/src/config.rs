@@ -1,6 +1,13 @@
+use std::{collections::HashMap, env, time::Duration};
+
 use dotenv::dotenv;
 use lazy_static::lazy_static;
-use serde::Deserialize;
+use lib::{config::SuiteConfig, n1ql::Keyspace, redact::Redaction};
+use serde::{Deserialize, Deserializer};
+
+fn default_store() -> String {
+    "couchbase".to_string()
+}
 
 fn default_namespace() -> String {
     "default".to_string()
@@ -18,10 +25,118 @@ fn default_collection() -> String {
     "leaks".to_string()
 }
 
+fn default_watch_collection() -> String {
+    "watchlist".to_string()
+}
+
+fn default_subscription_collection() -> String {
+    "subscriptions".to_string()
+}
+
+fn default_couch_pool_size() -> usize {
+    4
+}
+
+fn default_couch_timeout_secs() -> u64 {
+    10
+}
+
+fn default_couch_retries() -> u32 {
+    3
+}
+
+fn default_couch_retry_backoff_ms() -> u64 {
+    200
+}
+
+fn default_max_pages() -> usize {
+    20
+}
+
+fn default_rate_limit_burst() -> u32 {
+    5
+}
+
+fn default_rate_limit_per_minute() -> u32 {
+    10
+}
+
+// Telegram refuses documents over 50 mb from bots
+fn default_max_document_size() -> u64 {
+    50 * 1024 * 1024
+}
+
+// Couchbase refuses documents over 20 mb, ctj splits at 16 mb by default
+fn default_max_json_size() -> usize {
+    16 * 1024 * 1024
+}
+
+fn default_stats_cache_minutes() -> u64 {
+    10
+}
+
+fn default_max_batch_domains() -> usize {
+    200
+}
+
+fn default_history_size() -> usize {
+    20
+}
+
+fn default_max_watchlist() -> usize {
+    50
+}
+
+fn default_max_subscriptions() -> usize {
+    20
+}
+
+fn default_monitor_interval_minutes() -> u64 {
+    15
+}
+
+fn default_query_cache_size() -> usize {
+    1000
+}
+
+fn default_query_cache_credentials() -> usize {
+    1_000_000
+}
+
+fn default_query_cache_ttl_secs() -> u64 {
+    300
+}
+
+fn default_password_redaction() -> Option<Redaction> {
+    Some(Redaction::Mask)
+}
+
+/// full shows passwords as they are, otherwise a redaction of lib::redact
+fn deserialize_redaction<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Redaction>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    match name.as_str() {
+        "full" => Ok(None),
+        _ => name.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
+    pub teloxide_token: String,
+    /// Storage backend: couchbase, postgres or sqlite
+    #[serde(default = "default_store")]
+    pub store: String,
+    #[serde(default)]
+    pub postgres_url: String,
+    #[serde(default)]
+    pub sqlite_path: String,
+    #[serde(default)]
     pub couch_uri: String,
+    #[serde(default)]
     pub couch_username: String,
+    #[serde(default)]
     pub couch_password: String,
     #[serde(default = "default_namespace")]
     pub couch_namespace: String,
@@ -31,14 +146,157 @@ pub struct Config {
     pub couch_scope: String,
     #[serde(default = "default_collection")]
     pub couch_collection: String,
+    /// Collection of the domains saved with /save, in the same bucket and scope
+    #[serde(default = "default_watch_collection")]
+    pub couch_watch_collection: String,
+    /// Collection of the /subscribe subscriptions, in the same bucket and scope
+    #[serde(default = "default_subscription_collection")]
+    pub couch_subscription_collection: String,
+    /// Full text search index over the domain field of the leaks collection.
+    /// /search matches domains fuzzily with it, otherwise by substring
+    #[serde(default)]
+    pub couch_search_index: Option<String>,
+    /// Cluster connections queries are spread over
+    #[serde(default = "default_couch_pool_size")]
+    pub couch_pool_size: usize,
+    /// Longest a single couchbase query attempt may take
+    #[serde(default = "default_couch_timeout_secs")]
+    pub couch_timeout_secs: u64,
+    /// Attempts made after a query timed out or the cluster was unreachable
+    #[serde(default = "default_couch_retries")]
+    pub couch_retries: u32,
+    /// Wait before the first retry, doubled for every next one
+    #[serde(default = "default_couch_retry_backoff_ms")]
+    pub couch_retry_backoff_ms: u64,
+    /// Maximum size in bytes of a document rewritten by /append, bigger domains are split
+    #[serde(default = "default_max_json_size")]
+    pub max_json_size: usize,
     pub tld_path: String,
+    /// Results with more pages are sent as a file instead
+    #[serde(default = "default_max_pages")]
+    pub max_pages: usize,
+    /// Maximum size in bytes of a result sent as a file
+    #[serde(default = "default_max_document_size")]
+    pub max_document_size: u64,
+    /// Comma separated telegram user ids allowed to query the bot
+    #[serde(default)]
+    pub allowed_users: Vec<u64>,
+    /// Comma separated telegram user ids that can also /grant and /revoke access
+    #[serde(default)]
+    pub admin_users: Vec<u64>,
+    /// Queries a user can make in a row before being rate limited
+    #[serde(default = "default_rate_limit_burst")]
+    pub rate_limit_burst: u32,
+    /// Sustained queries per minute allowed for a user
+    #[serde(default = "default_rate_limit_per_minute")]
+    pub rate_limit_per_minute: u32,
+    /// Minutes /stats answers from the last computed aggregates
+    #[serde(default = "default_stats_cache_minutes")]
+    pub stats_cache_minutes: u64,
+    /// Domains looked up from a single uploaded .txt file, the rest are ignored
+    #[serde(default = "default_max_batch_domains")]
+    pub max_batch_domains: usize,
+    /// Lookups of each user listed by /history
+    #[serde(default = "default_history_size")]
+    pub history_size: usize,
+    /// Domains a user can keep on their watchlist
+    #[serde(default = "default_max_watchlist")]
+    pub max_watchlist: usize,
+    /// Domains a user can subscribe to
+    #[serde(default = "default_max_subscriptions")]
+    pub max_subscriptions: usize,
+    /// Minutes between checks of subscribed domains for new credentials,
+    /// 0 turns them off and only /append notifies subscribers
+    #[serde(default = "default_monitor_interval_minutes")]
+    pub monitor_interval_minutes: u64,
+    /// Domains whose documents are kept in memory after a lookup, 0 turns
+    /// the cache off. Domains gaining credentials through /append or seen
+    /// growing by the subscription check are dropped from it right away,
+    /// /clearcache drops them all after an import outside of the bot
+    #[serde(default = "default_query_cache_size")]
+    pub query_cache_size: usize,
+    /// Credentials kept in the query cache across its domains, the least
+    /// recently used domains are dropped to stay under it. Domains with more
+    /// credentials than that aren't cached
+    #[serde(default = "default_query_cache_credentials")]
+    pub query_cache_credentials: usize,
+    /// Seconds a cached lookup is served, which bounds how long imports made
+    /// outside of the bot take to show up unless an admin runs /clearcache
+    #[serde(default = "default_query_cache_ttl_secs")]
+    pub query_cache_ttl_secs: u64,
+    /// Address of the /healthz and /metrics listener, like 0.0.0.0:9090.
+    /// The listener is off unless set
+    #[serde(default)]
+    pub health_listen: Option<String>,
+    /// How lookups show passwords: full, mask or hmac, which needs
+    /// LEAKS_HMAC_KEY. Admins see them in full with /domain_full and in
+    /// the files of /export, /wordlist and /combolist
+    #[serde(
+        default = "default_password_redaction",
+        deserialize_with = "deserialize_redaction"
+    )]
+    pub password_redaction: Option<Redaction>,
+}
+
+impl Config {
+    /// N1QL keyspace of the leaks collection
+    pub fn keyspace(&self) -> Keyspace {
+        Keyspace::new(
+            &self.couch_namespace,
+            &self.couch_bucket,
+            &self.couch_scope,
+            &self.couch_collection,
+        )
+    }
+
+    /// N1QL keyspace of the watchlist collection
+    pub fn watch_keyspace(&self) -> Keyspace {
+        self.keyspace().sibling(&self.couch_watch_collection)
+    }
+
+    /// N1QL keyspace of the subscriptions collection
+    pub fn subscription_keyspace(&self) -> Keyspace {
+        self.keyspace().sibling(&self.couch_subscription_collection)
+    }
+
+    pub fn couch_timeout(&self) -> Duration {
+        Duration::from_secs(self.couch_timeout_secs)
+    }
+
+    // Names are quoted by the query builder, only empty ones are refused
+    fn validate(&self) -> Result<(), String> {
+        let keyspaces = [
+            self.keyspace(),
+            self.watch_keyspace(),
+            self.subscription_keyspace(),
+        ];
+        for keyspace in keyspaces {
+            if let Some(name) = keyspace.empty_name() {
+                return Err(format!("Empty couchbase {} name", name));
+            }
+        }
+        if self.couch_search_index.as_deref() == Some("") {
+            return Err("Empty couchbase search index name".to_string());
+        }
+        Ok(())
+    }
 }
 
 fn init_config() -> Config {
     dotenv().ok();
 
-    match envy::from_env::<Config>() {
-        Ok(config) => config,
+    // Environment variables override the values of leaks-suite.toml
+    let suite = SuiteConfig::load().unwrap_or_else(|err| panic!("{}", err));
+    let mut vars: HashMap<String, String> = suite.bot_env().into_iter().collect();
+    vars.extend(env::vars());
+
+    match envy::from_iter::<_, Config>(vars) {
+        Ok(config) => {
+            if let Err(err) = config.validate() {
+                panic!("{}", err);
+            }
+            config
+        }
         Err(err) => panic!("Couldn't process env variables: {:#?}", err),
     }
 }
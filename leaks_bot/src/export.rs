@@ -0,0 +1,71 @@
+use std::{
+    error::Error,
+    io::{BufWriter, Write},
+    str::FromStr,
+};
+
+use lib::LeakData;
+
+/// File format of /export, for spreadsheets and SIEMs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// domain,subdomain,username,password records with a header
+    Csv,
+    /// The stored documents as a json array
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(format!("unknown export format {}, expected csv or json", s)),
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    /// Writes every credential of `documents`
+    pub fn write(
+        &self,
+        documents: &[LeakData],
+        writer: impl Write,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut writer = BufWriter::new(writer);
+        match self {
+            ExportFormat::Csv => {
+                let mut wrt = csv::Writer::from_writer(&mut writer);
+                wrt.write_record(["domain", "subdomain", "username", "password"])?;
+                for leak_data in documents {
+                    for credential in &leak_data.credentials {
+                        for (username, password) in &credential.data {
+                            wrt.write_record([
+                                &leak_data.domain,
+                                &credential.subdomain,
+                                username,
+                                password,
+                            ])?;
+                        }
+                    }
+                }
+                wrt.flush()?;
+            }
+            ExportFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, documents)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
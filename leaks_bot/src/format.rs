@@ -0,0 +1,196 @@
+use std::str::FromStr;
+
+use leaks_store::CredentialRow;
+use lib::{redact::Redaction, LeakData};
+
+/// Widest host and username column of the table layout,
+/// longer values push the rest of their line to the right
+static MAX_COLUMN_WIDTH: usize = 32;
+
+/// Layout of lookup results, picked per user with /format
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResultFormat {
+    /// user:pass lines, user@host:pass when the results span several domains
+    #[default]
+    Plain,
+    /// Aligned host, username and password columns
+    Table,
+    /// domain,subdomain,username,password records with a header
+    Csv,
+    /// A json object per credential
+    Json,
+    /// user:pass lines under a line per subdomain
+    Grouped,
+}
+
+impl FromStr for ResultFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(ResultFormat::Plain),
+            "table" => Ok(ResultFormat::Table),
+            "csv" => Ok(ResultFormat::Csv),
+            "json" => Ok(ResultFormat::Json),
+            "grouped" => Ok(ResultFormat::Grouped),
+            _ => Err(format!(
+                "unknown format {}, expected plain, table, csv, json or grouped",
+                s
+            )),
+        }
+    }
+}
+
+fn host(row: &CredentialRow) -> String {
+    if row.subdomain.is_empty() {
+        row.domain.clone()
+    } else {
+        format!("{}.{}", row.subdomain, row.domain)
+    }
+}
+
+fn csv_line(fields: [&str; 4]) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(fields).unwrap();
+    let line = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    line.trim_end_matches('\n').to_string()
+}
+
+impl ResultFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResultFormat::Plain => "plain",
+            ResultFormat::Table => "table",
+            ResultFormat::Csv => "csv",
+            ResultFormat::Json => "json",
+            ResultFormat::Grouped => "grouped",
+        }
+    }
+
+    /// Extension of the file results are sent as when there are too many pages
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ResultFormat::Csv => "csv",
+            ResultFormat::Json => "jsonl",
+            _ => "txt",
+        }
+    }
+
+    /// Renders `rows` into message lines. Plain lines of a lookup
+    /// within a single domain leave the host out
+    pub fn lines(&self, rows: &[CredentialRow], single_domain: bool) -> Vec<String> {
+        match self {
+            ResultFormat::Plain if single_domain => rows
+                .iter()
+                .map(|row| format!("{}:{}", row.username, row.password))
+                .collect(),
+            ResultFormat::Plain => rows.iter().map(CredentialRow::format).collect(),
+            ResultFormat::Table => table(rows),
+            ResultFormat::Csv => {
+                let header = csv_line(["domain", "subdomain", "username", "password"]);
+                let records = rows.iter().map(|row| {
+                    csv_line([&row.domain, &row.subdomain, &row.username, &row.password])
+                });
+                std::iter::once(header).chain(records).collect()
+            }
+            ResultFormat::Json => rows
+                .iter()
+                .map(|row| serde_json::to_string(row).unwrap())
+                .collect(),
+            ResultFormat::Grouped => grouped(rows),
+        }
+    }
+}
+
+fn table(rows: &[CredentialRow]) -> Vec<String> {
+    let hosts: Vec<String> = rows.iter().map(host).collect();
+    let width = |values: &mut dyn Iterator<Item = &str>, header: &str| {
+        values
+            .map(|x| x.chars().count())
+            .chain([header.len()])
+            .max()
+            .unwrap_or_default()
+            .min(MAX_COLUMN_WIDTH)
+    };
+    let host_width = width(&mut hosts.iter().map(String::as_str), "host");
+    let username_width = width(&mut rows.iter().map(|x| x.username.as_str()), "username");
+
+    let line = |host: &str, username: &str, password: &str| {
+        format!(
+            "{:<host_width$}  {:<username_width$}  {}",
+            host, username, password
+        )
+    };
+    std::iter::once(line("host", "username", "password"))
+        .chain(
+            hosts
+                .iter()
+                .zip(rows)
+                .map(|(host, row)| line(host, &row.username, &row.password)),
+        )
+        .collect()
+}
+
+/// Subdomains keep the order they first appear in
+fn grouped(rows: &[CredentialRow]) -> Vec<String> {
+    let mut groups: Vec<(String, Vec<&CredentialRow>)> = Vec::new();
+    for row in rows {
+        let host = host(row);
+        match groups.iter_mut().find(|(x, _)| *x == host) {
+            Some((_, group)) => group.push(row),
+            None => groups.push((host, vec![row])),
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (host, group) in groups {
+        lines.push(format!("{} ({})", host, group.len()));
+        lines.extend(
+            group
+                .iter()
+                .map(|row| format!("  {}:{}", row.username, row.password)),
+        );
+    }
+    lines
+}
+
+/// How the passwords of a lookup are shown to the user asking for it
+#[derive(Clone, Debug, Default)]
+pub struct PasswordView {
+    /// None shows them in full
+    redaction: Option<Redaction>,
+}
+
+impl PasswordView {
+    pub fn new(redaction: Option<Redaction>) -> PasswordView {
+        PasswordView { redaction }
+    }
+
+    pub fn password(&self, password: &str) -> String {
+        match &self.redaction {
+            Some(redaction) => redaction.redact(password),
+            None => password.to_string(),
+        }
+    }
+
+    pub fn rows(&self, mut rows: Vec<CredentialRow>) -> Vec<CredentialRow> {
+        if self.redaction.is_some() {
+            for row in &mut rows {
+                row.password = self.password(&row.password);
+            }
+        }
+        rows
+    }
+
+    /// Copies of `documents` with their passwords as they are shown
+    pub fn documents(&self, documents: &[LeakData]) -> Vec<LeakData> {
+        let mut documents = documents.to_vec();
+        if self.redaction.is_some() {
+            let credentials = documents.iter_mut().flat_map(|x| &mut x.credentials);
+            for (_, password) in credentials.flat_map(|x| &mut x.data) {
+                *password = self.password(password);
+            }
+        }
+        documents
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use lazy_static::lazy_static;
+use leaks_store::LeakStore;
+use log::{info, warn};
+use serde::Serialize;
+use teloxide::prelude::*;
+
+use crate::store::Store;
+
+/// Upper bounds in seconds of the latency histogram buckets
+static LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Kind of update a query came in as
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryKind {
+    Command,
+    Inline,
+    /// Uploaded file of domains
+    Batch,
+}
+
+impl QueryKind {
+    fn label(&self) -> &'static str {
+        match self {
+            QueryKind::Command => "command",
+            QueryKind::Inline => "inline",
+            QueryKind::Batch => "batch",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    queries: AtomicU64,
+    errors: AtomicU64,
+}
+
+/// Counters exported on /metrics, updated by the update handlers
+#[derive(Default)]
+pub struct Metrics {
+    commands: Counters,
+    inline: Counters,
+    batch: Counters,
+    /// Cumulative count of queries per LATENCY_BUCKETS bound
+    latency: [AtomicU64; 10],
+    latency_count: AtomicU64,
+    latency_sum_micros: AtomicU64,
+}
+
+lazy_static! {
+    pub static ref METRICS: Metrics = Metrics::default();
+}
+
+impl Metrics {
+    /// Records a served query, `ok` is false when its handler failed
+    pub fn observe(&self, kind: QueryKind, elapsed: Duration, ok: bool) {
+        let counters = match kind {
+            QueryKind::Command => &self.commands,
+            QueryKind::Inline => &self.inline,
+            QueryKind::Batch => &self.batch,
+        };
+        counters.queries.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            counters.errors.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&self.latency) {
+            if seconds <= *bound {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Prometheus text exposition of the counters
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let kinds = [
+            (QueryKind::Command, &self.commands),
+            (QueryKind::Inline, &self.inline),
+            (QueryKind::Batch, &self.batch),
+        ];
+
+        out.push_str("# HELP leaks_bot_queries_total Queries served.\n");
+        out.push_str("# TYPE leaks_bot_queries_total counter\n");
+        for (kind, counters) in kinds {
+            let value = counters.queries.load(Ordering::Relaxed);
+            writeln!(
+                out,
+                "leaks_bot_queries_total{{kind=\"{}\"}} {}",
+                kind.label(),
+                value
+            )
+            .unwrap();
+        }
+
+        out.push_str("# HELP leaks_bot_errors_total Queries whose handler failed.\n");
+        out.push_str("# TYPE leaks_bot_errors_total counter\n");
+        for (kind, counters) in kinds {
+            let value = counters.errors.load(Ordering::Relaxed);
+            writeln!(
+                out,
+                "leaks_bot_errors_total{{kind=\"{}\"}} {}",
+                kind.label(),
+                value
+            )
+            .unwrap();
+        }
+
+        let count = self.latency_count.load(Ordering::Relaxed);
+        out.push_str("# HELP leaks_bot_query_duration_seconds Time spent answering a query.\n");
+        out.push_str("# TYPE leaks_bot_query_duration_seconds histogram\n");
+        for (bound, value) in LATENCY_BUCKETS.iter().zip(&self.latency) {
+            let value = value.load(Ordering::Relaxed);
+            writeln!(
+                out,
+                "leaks_bot_query_duration_seconds_bucket{{le=\"{}\"}} {}",
+                bound, value
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "leaks_bot_query_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            count
+        )
+        .unwrap();
+        let sum = self.latency_sum_micros.load(Ordering::Relaxed) as f64 / 1e6;
+        writeln!(out, "leaks_bot_query_duration_seconds_sum {}", sum).unwrap();
+        writeln!(out, "leaks_bot_query_duration_seconds_count {}", count).unwrap();
+
+        out
+    }
+}
+
+struct HealthState {
+    bot: Bot,
+    store: Arc<Store>,
+}
+
+#[derive(Serialize)]
+struct Health {
+    telegram: bool,
+    store: bool,
+}
+
+/// Probes that don't answer within this time count as failed
+static PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn healthz(State(state): State<Arc<HealthState>>) -> impl IntoResponse {
+    let telegram = tokio::time::timeout(PROBE_TIMEOUT, state.bot.get_me()).await;
+    let store = tokio::time::timeout(PROBE_TIMEOUT, state.store.ping()).await;
+
+    let health = Health {
+        telegram: matches!(telegram, Ok(Ok(_))),
+        store: matches!(store, Ok(Ok(_))),
+    };
+    let status = if health.telegram && health.store {
+        StatusCode::OK
+    } else {
+        warn!(
+            "Health check failed, telegram: {}, store: {}",
+            health.telegram, health.store
+        );
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(health))
+}
+
+async fn metrics() -> impl IntoResponse {
+    (
+        [("content-type", "text/plain; version=0.0.4")],
+        METRICS.render(),
+    )
+}
+
+/// Serves /healthz and /metrics on `listen` until the process exits
+pub async fn serve(
+    listen: String,
+    bot: Bot,
+    store: Arc<Store>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let state = Arc::new(HealthState { bot, store });
+    let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/metrics", get(metrics))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(&listen).await?;
+    info!("Health and metrics listening on {}", listen);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use std::collections::{HashMap, VecDeque};
+
+use teloxide::types::UserId;
+
+/// Last lookups of every user, newest first. Kept in memory only,
+/// so it's lost on restart like runtime grants
+pub struct History {
+    size: usize,
+    queries: HashMap<UserId, VecDeque<String>>,
+}
+
+impl History {
+    pub fn new(size: usize) -> History {
+        History {
+            size,
+            queries: HashMap::new(),
+        }
+    }
+
+    /// Records `query` of `user`, a repeated query moves to the front
+    pub fn push(&mut self, user: UserId, query: &str) {
+        if self.size == 0 {
+            return;
+        }
+
+        let queries = self.queries.entry(user).or_default();
+        queries.retain(|x| x != query);
+        queries.push_front(query.to_string());
+        queries.truncate(self.size);
+    }
+
+    pub fn get(&self, user: UserId) -> Vec<String> {
+        self.queries
+            .get(&user)
+            .map(|queries| queries.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
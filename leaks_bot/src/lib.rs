@@ -0,0 +1,14 @@
+//! Store, settings and result formatting of the bot, shared with the
+//! end-to-end tests of leaks_e2e
+
+pub mod auth;
+pub mod cache;
+pub mod config;
+pub mod export;
+pub mod format;
+pub mod health;
+pub mod history;
+pub mod lookup;
+pub mod rate_limit;
+pub mod stats;
+pub mod store;
@@ -0,0 +1,77 @@
+//! Lookups behind the bot commands, apart from telegram so the end-to-end
+//! tests run them against a real store
+
+use std::sync::{Arc, Mutex};
+
+use leaks_store::{CredentialRow, LeakStore, StoreResult};
+use lib::{Breach, BreachSet, CredentialData, LeakData};
+
+use crate::{cache::QueryCache, format::PasswordView};
+
+/// Flattens the credentials of a `domain` document into rows
+pub fn credential_rows<'a>(
+    domain: &str,
+    credentials: impl IntoIterator<Item = &'a CredentialData>,
+) -> Vec<CredentialRow> {
+    credentials
+        .into_iter()
+        .flat_map(|x| {
+            x.data
+                .iter()
+                .map(move |(username, password)| CredentialRow {
+                    domain: domain.to_string(),
+                    subdomain: x.subdomain.clone(),
+                    username: username.clone(),
+                    password: password.clone(),
+                })
+        })
+        .collect()
+}
+
+/// Breaches of the documents of a domain, each listed once
+pub fn document_breaches(documents: &[LeakData]) -> Vec<Breach> {
+    let mut breaches = BreachSet::default();
+    breaches.extend(documents.iter().flat_map(|x| x.breaches.iter().cloned()));
+    breaches.into_vec()
+}
+
+/// Documents of `domain`, served from `cache` when it has them
+pub async fn find_domain<S: LeakStore>(
+    store: &S,
+    cache: &Mutex<QueryCache>,
+    domain: &str,
+) -> StoreResult<Arc<Vec<LeakData>>> {
+    let cached = cache.lock().unwrap().get(domain);
+    if let Some(leaks) = cached {
+        return Ok(leaks);
+    }
+
+    let leaks = Arc::new(store.find_domain(domain).await?);
+    cache.lock().unwrap().insert(domain, leaks.clone());
+    Ok(leaks)
+}
+
+/// Credentials found by /domain and the breaches they came from
+pub struct DomainResults {
+    /// Credentials with their passwords as they are shown
+    pub rows: Vec<CredentialRow>,
+    pub breaches: Vec<Breach>,
+}
+
+/// Looks a domain up like /domain does
+pub async fn lookup_domain<S: LeakStore>(
+    store: &S,
+    cache: &Mutex<QueryCache>,
+    domain: &str,
+    passwords: &PasswordView,
+) -> StoreResult<DomainResults> {
+    let documents = find_domain(store, cache, domain).await?;
+    let rows = documents
+        .iter()
+        .flat_map(|leak_data| credential_rows(&leak_data.domain, &leak_data.credentials))
+        .collect();
+    Ok(DomainResults {
+        rows: passwords.rows(rows),
+        breaches: document_breaches(&documents),
+    })
+}
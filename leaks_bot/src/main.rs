@@ -1,145 +1,1896 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufReader, Write},
+    num::NonZeroUsize,
+    process::ExitCode,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
 
-use couchbase::{Cluster, QueryOptions};
-use dotenv::dotenv;
-use futures::StreamExt;
-use lib::LeakData;
-use log::error;
+use chrono::Utc;
+use leaks_store::{
+    domain_credentials, CredentialRow, DomainCount, LeakStore, StoreResult, Subscription,
+    SubscriptionStore, WatchStore,
+};
+use lib::{
+    errors::{self, BoxError, Context},
+    parse_domain, parse_tld,
+    redact::Redaction,
+    telemetry::{self, LogFormat},
+    wordlist::{wordlist, WordlistFormat},
+    Breach, LeakData, PublicSuffixList, SuffixProvider,
+};
+use log::{error, info, warn};
+use lru::LruCache;
 use teloxide::{
     dispatching::{DpHandlerDescription, UpdateFilterExt},
+    net::Download,
     prelude::*,
+    types::{
+        Document, InlineKeyboardButton, InlineKeyboardMarkup, InlineQueryResult,
+        InlineQueryResultArticle, InputFile, InputMessageContent, InputMessageContentText,
+        MessageId, ParseMode,
+    },
     utils::command::BotCommands,
     utils::markdown,
 };
-use tokio::sync::Mutex;
+use tempfile::NamedTempFile;
+
+use leaks_bot::auth::{Auth, Role};
+use leaks_bot::cache::QueryCache;
+use leaks_bot::config::CONFIG;
+use leaks_bot::export::ExportFormat;
+use leaks_bot::format::{PasswordView, ResultFormat};
+use leaks_bot::health::{self, QueryKind, METRICS};
+use leaks_bot::history::History;
+use leaks_bot::lookup::{credential_rows, document_breaches, find_domain, lookup_domain};
+use leaks_bot::rate_limit::RateLimiter;
+use leaks_bot::stats::{domain_risk, render_risk, StatsCache, Summary};
+use leaks_bot::store::{Store, StoreUnavailable};
 
-mod config;
-use crate::config::CONFIG;
+mod monitor;
 
-#[derive(BotCommands, Clone)]
-#[command(rename = "lowercase", description = "These commands are supported:")]
+#[derive(BotCommands, Clone, Debug)]
+#[command(
+    rename_rule = "lowercase",
+    description = "These commands are supported, a .txt file of domains sent to the bot \
+                   is looked up as a batch:"
+)]
 enum Command {
     #[command(description = "display this text.")]
     Help,
-    #[command(description = "Find leaks with domain")]
+    #[command(
+        description = "Find leaks with domain, *.domain lists subdomains only, \
+                             example.* matches every tld"
+    )]
     Domain(String),
+    #[command(
+        rename = "domain_full",
+        description = "Like /domain with passwords shown in full, admins only"
+    )]
+    DomainFull(String),
+    #[command(description = "Find domains resembling a name, like /search acme, and pick one")]
+    Search(String),
+    #[command(description = "Find leaks of a single subdomain, like vpn.corp.com")]
+    Subdomain(String),
+    #[command(
+        description = "Credentials per domain of an organization, like /org Corp Inc, and pick one"
+    )]
+    Org(String),
+    #[command(description = "Find leaks of an email, like user@corp.com")]
+    Email(String),
+    #[command(description = "Find leaks of a username across all domains")]
+    User(String),
+    #[command(
+        description = "Deduplicated passwords of a domain as a hashcat wordlist file, \
+                       shown like /domain unless you are an admin"
+    )]
+    Wordlist(String),
+    #[command(
+        description = "Deduplicated username:password pairs of a domain as a file, \
+                       shown like /domain unless you are an admin"
+    )]
+    Combolist(String),
+    #[command(
+        description = "Number of credentials, usernames and subdomains of a domain, no passwords"
+    )]
+    Count(String),
+    #[command(
+        description = "Credentials of a domain as a csv or json file, like /export corp.com csv. \
+                       Passwords are shown like /domain, admins get them in full"
+    )]
+    Export(String),
+    #[command(description = "Layout of your results: plain, table, csv, json or grouped")]
+    Format(String),
+    #[command(description = "Your last lookups")]
+    History,
+    #[command(description = "Save a domain to your watchlist")]
+    Save(String),
+    #[command(description = "Remove a domain from your watchlist")]
+    Unsave(String),
+    #[command(description = "/watch list shows your saved domains with their credential counts")]
+    Watch(String),
+    #[command(description = "Get a message when new credentials of a domain are imported")]
+    Subscribe(String),
+    #[command(description = "Stop the messages about a domain")]
+    Unsubscribe(String),
+    #[command(description = "Domains you are subscribed to")]
+    Subscriptions,
+    #[command(
+        description = "Number of stored documents and credentials, top domains. \
+                       /stats corp.com scores the exposure risk of a domain from its passwords"
+    )]
+    Stats(String),
+    #[command(
+        description = "Reply to a ctj jsonl file to merge its credentials into the store, admins only"
+    )]
+    Append,
+    #[command(
+        description = "Drop cached lookups and /stats after leaks were imported outside of \
+                       the bot, admins only"
+    )]
+    ClearCache,
+    #[command(description = "Allow a telegram user id to use the bot, admins only")]
+    Grant(u64),
+    #[command(description = "Take access away from a telegram user id, admins only")]
+    Revoke(u64),
 }
 
 type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
 
+/// Raw text size of a result page, leaves room for markdown escaping
+/// within the 4096 chars telegram allows per message
+static PAGE_SIZE: usize = 3500;
+
+/// Credentials shown in the message of an inline result
+static INLINE_PREVIEW_LINES: usize = 10;
+
+/// Result of the last lookup of a chat split into messages
+struct Pages {
+    title: String,
+    pages: Vec<String>,
+    /// Extension of the file the pages are sent as
+    extension: &'static str,
+}
+
+impl Pages {
+    fn render(&self, page: usize) -> String {
+        let title = format!("{} page {}/{}", self.title, page + 1, self.pages.len());
+        format!(
+            "{}\n{}",
+            markdown::escape(&title),
+            markdown::code_block(self.pages[page].trim_end())
+        )
+    }
+}
+
+fn paginate(lines: Vec<String>) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+
+    for line in lines {
+        if !page.is_empty() && page.len() + line.len() + 1 > PAGE_SIZE {
+            pages.push(std::mem::take(&mut page));
+        }
+        page.push_str(&line);
+        page.push('\n');
+    }
+
+    if !page.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+fn page_keyboard(page: usize, total: usize) -> InlineKeyboardMarkup {
+    let mut row = Vec::new();
+    if page > 0 {
+        row.push(InlineKeyboardButton::callback(
+            "« Prev",
+            format!("page:{}", page - 1),
+        ));
+    }
+    if page + 1 < total {
+        row.push(InlineKeyboardButton::callback(
+            "Next »",
+            format!("page:{}", page + 1),
+        ));
+    }
+    InlineKeyboardMarkup::new(vec![row])
+}
+
+/// Replies with `rows` in the format of `user` as a single message, pages
+/// or a file depending on their size. `single_domain` lookups can leave the host out
+async fn send_results(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+    title: &str,
+    rows: Vec<CredentialRow>,
+    single_domain: bool,
+) -> HandlerResult {
+    if rows.is_empty() {
+        bot.send_message(msg.chat.id, "Nothing found :(").await?;
+        return Ok(());
+    }
+
+    let format = app_data
+        .formats
+        .read()
+        .unwrap()
+        .get(&user)
+        .copied()
+        .unwrap_or_default();
+    let pages = Pages {
+        title: title.to_string(),
+        pages: paginate(format.lines(&rows, single_domain)),
+        extension: format.extension(),
+    };
+
+    if pages.pages.len() > CONFIG.max_pages {
+        return send_document(bot, msg, &pages).await;
+    }
+
+    let request = bot
+        .send_message(msg.chat.id, pages.render(0))
+        .parse_mode(ParseMode::MarkdownV2);
+    if pages.pages.len() == 1 {
+        request.await?;
+        return Ok(());
+    }
+
+    let sent = request
+        .reply_markup(page_keyboard(0, pages.pages.len()))
+        .await?;
+    // The buttons of a message only page through its own results
+    app_data
+        .pages
+        .lock()
+        .unwrap()
+        .put((sent.chat.id, sent.id), pages);
+
+    Ok(())
+}
+
+/// Paged results kept, older messages answer "Results expired"
+static PAGED_RESULTS: usize = 1000;
+
+/// Breaches listed in a sources message, the rest are counted
+static MAX_SOURCES: usize = 20;
+
+/// Name of a breach followed by its date and file, as far as they're known
+fn describe_breach(breach: &Breach) -> String {
+    let name = if breach.name.is_empty() {
+        "unnamed"
+    } else {
+        &breach.name
+    };
+    let details: Vec<&str> = [&breach.date, &breach.source_file]
+        .into_iter()
+        .filter(|x| !x.is_empty())
+        .map(String::as_str)
+        .collect();
+
+    if details.is_empty() {
+        name.to_string()
+    } else {
+        format!("{} ({})", name, details.join(", "))
+    }
+}
+
+/// Tells where the credentials of a lookup came from, unless
+/// the documents were stored without their breaches
+async fn send_sources(bot: &Bot, msg: &Message, breaches: &[Breach]) -> HandlerResult {
+    if breaches.is_empty() {
+        return Ok(());
+    }
+
+    let mut lines: Vec<String> = breaches
+        .iter()
+        .take(MAX_SOURCES)
+        .map(describe_breach)
+        .collect();
+    if breaches.len() > MAX_SOURCES {
+        lines.push(format!("and {} more", breaches.len() - MAX_SOURCES));
+    }
+    bot.send_message(msg.chat.id, format!("Sources:\n{}", lines.join("\n")))
+        .await?;
+    Ok(())
+}
+
+/// Turns a user supplied domain pattern into a N1QL LIKE pattern,
+/// both * and % match any run of characters. Returns None for characters
+/// that can't be part of a domain, or when the pattern is too broad
+fn like_pattern(pattern: &str) -> Option<String> {
+    let valid = pattern
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '*' | '%'));
+    let literal = pattern.chars().filter(char::is_ascii_alphanumeric).count();
+
+    if !valid || literal < 3 {
+        return None;
+    }
+    Some(pattern.replace('*', "%"))
+}
+
 async fn handle_domain(
-    bot: &AutoSend<Bot>,
+    bot: &Bot,
     msg: &Message,
     app_data: &AppData,
+    user: UserId,
     domain: &str,
+    passwords: &PasswordView,
 ) -> HandlerResult {
-    let params = [domain];
-    let options = QueryOptions::default().positional_parameters(params);
+    let domain = domain.trim().to_lowercase();
+
+    // *.corp.com lists everything below corp.com within its document
+    if let Some(parent) = domain.strip_prefix("*.") {
+        if !parent.contains(['*', '%']) {
+            return handle_subdomains(bot, msg, app_data, user, &domain, parent, passwords).await;
+        }
+    }
 
-    let query = format!(
-        "SELECT domain, credentials FROM {}:`{}`.`{}`.`{}` WHERE domain = $1 LIMIT 1",
-        CONFIG.couch_namespace, CONFIG.couch_bucket, CONFIG.couch_scope, CONFIG.couch_collection
+    if domain.contains(['*', '%']) {
+        let pattern = match like_pattern(&domain) {
+            Some(pattern) => pattern,
+            None => {
+                bot.send_message(
+                    msg.chat.id,
+                    "Invalid pattern, use letters, digits, dots, dashes and * \
+                     with at least 3 letters or digits",
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let rows = passwords.rows(app_data.store.find_domain_like(&pattern).await?);
+        return send_results(bot, msg, app_data, user, &domain, rows, false).await;
+    }
+
+    let results = lookup_domain(
+        app_data.store.as_ref(),
+        &app_data.query_cache,
+        &domain,
+        passwords,
+    )
+    .await?;
+    let found = !results.rows.is_empty();
+
+    send_results(bot, msg, app_data, user, &domain, results.rows, true).await?;
+    if found {
+        send_sources(bot, msg, &results.breaches).await?;
+    }
+    Ok(())
+}
+
+/// Domains listed by /search
+static SEARCH_RESULTS: usize = 10;
+
+/// Telegram limit of the data sent back by a button
+static CALLBACK_DATA_SIZE: usize = 64;
+
+/// Search terms are parts of domain names, short ones would match most of the store
+fn is_search_term(term: &str) -> bool {
+    let literal = term.chars().filter(char::is_ascii_alphanumeric).count();
+    literal >= 3
+        && term
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-'))
+}
+
+/// A button per found domain that looks it up like /domain,
+/// names too long for the callback data are left to /domain
+fn search_keyboard(domains: &[DomainCount]) -> InlineKeyboardMarkup {
+    let buttons = domains
+        .iter()
+        .map(|x| (x.domain.clone(), format!("domain:{}", x.domain)))
+        .filter(|(_, data)| data.len() <= CALLBACK_DATA_SIZE)
+        .map(|(domain, data)| vec![InlineKeyboardButton::callback(domain, data)]);
+    InlineKeyboardMarkup::new(buttons)
+}
+
+async fn handle_search(bot: &Bot, msg: &Message, app_data: &AppData, term: &str) -> HandlerResult {
+    let term = term.trim().to_lowercase();
+    if !is_search_term(&term) {
+        bot.send_message(
+            msg.chat.id,
+            "Expected a part of a domain name with at least 3 letters or digits, like /search acme",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let domains = app_data.store.search_domains(&term, SEARCH_RESULTS).await?;
+    if domains.is_empty() {
+        bot.send_message(msg.chat.id, "Nothing found :(").await?;
+        return Ok(());
+    }
+
+    let width = domains
+        .iter()
+        .map(|x| x.domain.len())
+        .max()
+        .unwrap_or_default();
+    let lines: Vec<String> = domains
+        .iter()
+        .map(|x| format!("{:<width$}  {}", x.domain, x.credentials))
+        .collect();
+    let title = format!(
+        "Domains like {}, credentials per domain. Pick one to look it up:",
+        term
+    );
+    let text = format!(
+        "{}\n{}",
+        markdown::escape(&title),
+        markdown::code_block(&lines.join("\n"))
     );
 
-    let mut res = match app_data.cluster.query(query, options).await {
-        Ok(res) => res,
-        Err(e) => {
-            error! {"{:#?}", e};
-            return Err(Box::new(e));
-        }
-    };
-    let _md = res.meta_data().await;
-    let mut rows = res.rows::<LeakData>();
-    let mut rtn_msg = String::new();
-
-    while let Some(leak_data) = rows.next().await {
-        let leak_data = leak_data?;
-        let creds: Vec<String> = leak_data
-            .credentials
-            .into_iter()
-            .flat_map(|x| {
-                x.data
-                    .into_iter()
-                    .map(|(username, password)| format!("{}:{}", username, password))
-            })
-            .collect();
-        let fmt_str = creds.join("\n");
-        rtn_msg.push_str(&fmt_str);
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .reply_markup(search_keyboard(&domains))
+        .await?;
+    Ok(())
+}
+
+async fn handle_subdomains(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+    title: &str,
+    parent: &str,
+    passwords: &PasswordView,
+) -> HandlerResult {
+    let (parent_subdomain, domain) = parse_domain(parent, &app_data.st);
+    let suffix = format!(".{}", parent_subdomain);
+
+    let documents = app_data.find_domain(domain).await?;
+    let breaches = document_breaches(&documents);
+    let rows: Vec<CredentialRow> = documents
+        .iter()
+        .flat_map(|leak_data| {
+            credential_rows(
+                &leak_data.domain,
+                leak_data.credentials.iter().filter(|x| {
+                    !x.subdomain.is_empty()
+                        && (parent_subdomain.is_empty() || x.subdomain.ends_with(&suffix))
+                }),
+            )
+        })
+        .collect();
+    let found = !rows.is_empty();
+
+    let rows = passwords.rows(rows);
+    send_results(bot, msg, app_data, user, title, rows, true).await?;
+    if found {
+        send_sources(bot, msg, &breaches).await?;
     }
+    Ok(())
+}
+
+async fn handle_subdomain(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+    name: &str,
+    passwords: &PasswordView,
+) -> HandlerResult {
+    let name = name.trim().to_lowercase();
+    let (subdomain, domain) = parse_domain(&name, &app_data.st);
 
-    if rtn_msg.is_empty() {
+    let documents = app_data.find_domain(domain).await?;
+    let breaches = document_breaches(&documents);
+    let rows: Vec<CredentialRow> = documents
+        .iter()
+        .flat_map(|leak_data| {
+            credential_rows(
+                &leak_data.domain,
+                leak_data
+                    .credentials
+                    .iter()
+                    .filter(|x| x.subdomain == subdomain),
+            )
+        })
+        .collect();
+    let found = !rows.is_empty();
+
+    let rows = passwords.rows(rows);
+    send_results(bot, msg, app_data, user, &name, rows, true).await?;
+    if found {
+        send_sources(bot, msg, &breaches).await?;
+    }
+    Ok(())
+}
+
+/// Domains of an organization listed by /org, the rest are only counted
+static ORG_DOMAINS: usize = 50;
+
+/// Credentials per domain of an organization, as mapped by ctj --org-map,
+/// with buttons looking up the biggest domains like /domain
+async fn handle_org(bot: &Bot, msg: &Message, app_data: &AppData, org: &str) -> HandlerResult {
+    let org = org.trim();
+    if org.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "Expected the name of an organization, like /org Corp Inc",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let owned = app_data.store.org_domains(org).await?;
+    let domains = app_data.store.count_domains(&owned).await?;
+    if domains.is_empty() {
         bot.send_message(msg.chat.id, "Nothing found :(").await?;
-    } else if rtn_msg.len() > 5000 {
-        bot.send_message(msg.chat.id, "To much data for tg. WIP")
+        return Ok(());
+    }
+
+    let total: u64 = domains.iter().map(|x| x.credentials).sum();
+    let shown = &domains[..domains.len().min(ORG_DOMAINS)];
+    let width = shown
+        .iter()
+        .map(|x| x.domain.len())
+        .max()
+        .unwrap_or_default();
+    let mut lines: Vec<String> = shown
+        .iter()
+        .map(|x| format!("{:<width$}  {}", x.domain, x.credentials))
+        .collect();
+    if domains.len() > shown.len() {
+        lines.push(format!("{} more domains", domains.len() - shown.len()));
+    }
+    let title = format!(
+        "{} has {} credentials over {} domains, credentials per domain. Pick one to look it up:",
+        org,
+        total,
+        domains.len()
+    );
+    let text = format!(
+        "{}\n{}",
+        markdown::escape(&title),
+        markdown::code_block(&lines.join("\n"))
+    );
+
+    let buttons = &domains[..domains.len().min(SEARCH_RESULTS)];
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .reply_markup(search_keyboard(buttons))
+        .await?;
+    Ok(())
+}
+
+async fn handle_email(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+    email: &str,
+    passwords: &PasswordView,
+) -> HandlerResult {
+    let email = email.trim();
+    let (username, host) = match email.rsplit_once('@') {
+        Some((username, host)) if !username.is_empty() && !host.is_empty() => (username, host),
+        _ => {
+            bot.send_message(msg.chat.id, "Expected an email like user@domain.tld")
+                .await?;
+            return Ok(());
+        }
+    };
+    let host = host.to_lowercase();
+    let (subdomain, domain) = parse_domain(&host, &app_data.st);
+
+    let rows = app_data
+        .store
+        .find_email(domain, subdomain, username)
+        .await?;
+    let rows = passwords.rows(rows);
+
+    send_results(bot, msg, app_data, user, email, rows, false).await
+}
+
+async fn handle_user(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+    username: &str,
+    passwords: &PasswordView,
+) -> HandlerResult {
+    let username = username.trim();
+
+    let rows = passwords.rows(app_data.store.find_username(username).await?);
+
+    send_results(bot, msg, app_data, user, username, rows, false).await
+}
+
+async fn handle_format(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+    name: &str,
+) -> HandlerResult {
+    let name = name.trim().to_lowercase();
+    let reply = if name.is_empty() {
+        let format = app_data
+            .formats
+            .read()
+            .unwrap()
+            .get(&user)
+            .copied()
+            .unwrap_or_default();
+        format!(
+            "Results are shown as {}, pick another one with /format plain, table, csv, json or grouped",
+            format.name()
+        )
+    } else {
+        match name.parse::<ResultFormat>() {
+            Ok(format) => {
+                app_data.formats.write().unwrap().insert(user, format);
+                format!("Results are shown as {} from now on", format.name())
+            }
+            Err(e) => e,
+        }
+    };
+
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+async fn handle_history(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+) -> HandlerResult {
+    let queries = app_data.history.lock().unwrap().get(user);
+    let reply = if queries.is_empty() {
+        "No lookups yet".to_string()
+    } else {
+        let lines: Vec<String> = queries
+            .iter()
+            .enumerate()
+            .map(|(i, query)| format!("{}. {}", i + 1, query))
+            .collect();
+        format!("Your last lookups, newest first:\n{}", lines.join("\n"))
+    };
+
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+/// Saved domains are looked up as is, so patterns aren't accepted
+fn is_domain(name: &str) -> bool {
+    name.contains('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-'))
+}
+
+async fn handle_save(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+    domain: &str,
+) -> HandlerResult {
+    let domain = domain.trim().to_lowercase();
+    if !is_domain(&domain) {
+        bot.send_message(msg.chat.id, "Expected a domain like corp.com")
             .await?;
+        return Ok(());
+    }
+
+    let saved = app_data.store.watchlist(user.0).await?;
+    let reply = if saved.contains(&domain) {
+        format!("{} is on your watchlist already", domain)
+    } else if saved.len() >= CONFIG.max_watchlist {
+        format!(
+            "Your watchlist is full, the limit is {} domains. Make room with /unsave",
+            CONFIG.max_watchlist
+        )
+    } else {
+        app_data.store.watch(user.0, &domain).await?;
+        format!("Saved {}, check it again with /watch list", domain)
+    };
+
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+async fn handle_unsave(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+    domain: &str,
+) -> HandlerResult {
+    let domain = domain.trim().to_lowercase();
+    let reply = if app_data.store.unwatch(user.0, &domain).await? {
+        format!("Removed {} from your watchlist", domain)
     } else {
-        let rtn_msg = markdown::code_block(rtn_msg.trim_end());
-        bot.parse_mode(teloxide::types::ParseMode::MarkdownV2)
-            .send_message(msg.chat.id, rtn_msg)
+        format!("{} is not on your watchlist", domain)
+    };
+
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+// Every saved domain is looked up again, MAX_WATCHLIST bounds the cost
+async fn handle_watch(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+    arg: &str,
+) -> HandlerResult {
+    if !matches!(arg.trim(), "" | "list") {
+        bot.send_message(
+            msg.chat.id,
+            "Use /watch list, domains are added with /save and removed with /unsave",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let domains = app_data.store.watchlist(user.0).await?;
+    if domains.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "Your watchlist is empty, add domains with /save",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut counts = Vec::new();
+    for domain in &domains {
+        counts.push(domain_credentials(app_data.store.as_ref(), domain).await?);
+    }
+
+    let width = domains.iter().map(String::len).max().unwrap_or_default();
+    let lines: Vec<String> = domains
+        .iter()
+        .zip(counts)
+        .map(|(domain, credentials)| format!("{:<width$}  {}", domain, credentials))
+        .collect();
+    let text = format!(
+        "{}\n{}",
+        markdown::escape("Watchlist, credentials per domain:"),
+        markdown::code_block(&lines.join("\n"))
+    );
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+async fn handle_subscribe(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+    domain: &str,
+) -> HandlerResult {
+    let domain = domain.trim().to_lowercase();
+    if !is_domain(&domain) {
+        bot.send_message(msg.chat.id, "Expected a domain like corp.com")
             .await?;
+        return Ok(());
+    }
+
+    let subscriptions = app_data.store.subscriptions(Some(user.0)).await?;
+    if subscriptions.iter().any(|x| x.domain == domain) {
+        bot.send_message(
+            msg.chat.id,
+            format!("You are subscribed to {} already", domain),
+        )
+        .await?;
+        return Ok(());
+    }
+    if subscriptions.len() >= CONFIG.max_subscriptions {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "You can subscribe to {} domains at most, make room with /unsubscribe",
+                CONFIG.max_subscriptions
+            ),
+        )
+        .await?;
+        return Ok(());
     }
 
+    // Only credentials imported from now on are notified
+    let credentials = domain_credentials(app_data.store.as_ref(), &domain).await?;
+    let subscription = Subscription {
+        user_id: user.0,
+        chat_id: msg.chat.id.0,
+        domain: domain.clone(),
+        credentials,
+    };
+    app_data.store.subscribe(&subscription).await?;
+    info!("User {} subscribed to {}", user, domain);
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "Subscribed to {}, it has {} credentials now. \
+             You will get a message here when new ones are imported",
+            domain, credentials
+        ),
+    )
+    .await?;
     Ok(())
 }
 
-async fn handle_command(
-    bot: AutoSend<Bot>,
+async fn handle_unsubscribe(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+    domain: &str,
+) -> HandlerResult {
+    let domain = domain.trim().to_lowercase();
+    let reply = if app_data.store.unsubscribe(user.0, &domain).await? {
+        format!("Unsubscribed from {}", domain)
+    } else {
+        format!("You are not subscribed to {}", domain)
+    };
+
+    bot.send_message(msg.chat.id, reply).await?;
+    Ok(())
+}
+
+async fn handle_subscriptions(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    user: UserId,
+) -> HandlerResult {
+    let subscriptions = app_data.store.subscriptions(Some(user.0)).await?;
+    if subscriptions.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "You have no subscriptions, add one with /subscribe",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let width = subscriptions
+        .iter()
+        .map(|x| x.domain.len())
+        .max()
+        .unwrap_or_default();
+    let lines: Vec<String> = subscriptions
+        .iter()
+        .map(|x| format!("{:<width$}  {}", x.domain, x.credentials))
+        .collect();
+    let text = format!(
+        "{}\n{}",
+        markdown::escape("Subscriptions, credentials at the last check:"),
+        markdown::code_block(&lines.join("\n"))
+    );
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+// Wordlists are meant for cracking tools, so they are always sent as a file
+async fn handle_wordlist(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    domain: &str,
+    format: WordlistFormat,
+    passwords: &PasswordView,
+) -> HandlerResult {
+    let domain = domain.trim().to_lowercase();
+    let leaks = passwords.documents(&app_data.find_domain(&domain).await?);
+
+    let credentials = leaks.iter().flat_map(|leak_data| {
+        leak_data.credentials.iter().flat_map(|x| {
+            x.data
+                .iter()
+                .map(|(username, password)| (username.as_str(), password.as_str()))
+        })
+    });
+    let lines = wordlist(credentials, format);
+
+    if lines.is_empty() {
+        bot.send_message(msg.chat.id, "Nothing found :(").await?;
+        return Ok(());
+    }
+
+    let kind = match format {
+        WordlistFormat::Passwords => "passwords",
+        WordlistFormat::Combo => "combo",
+    };
+    let pages = Pages {
+        title: format!("{}.{}", domain, kind),
+        pages: paginate(lines),
+        extension: "txt",
+    };
+    send_document(bot, msg, &pages).await
+}
+
+// Counted by the store, so no credential is read by the bot
+async fn handle_count(bot: &Bot, msg: &Message, app_data: &AppData, domain: &str) -> HandlerResult {
+    let domain = domain.trim().to_lowercase();
+    if !is_domain(&domain) {
+        bot.send_message(msg.chat.id, "Expected a domain like corp.com")
+            .await?;
+        return Ok(());
+    }
+
+    let exposure = app_data.store.count_domain(&domain).await?;
+    if exposure.credentials == 0 {
+        bot.send_message(msg.chat.id, "Nothing found :(").await?;
+        return Ok(());
+    }
+
+    let lines = [
+        format!("credentials  {}", exposure.credentials),
+        format!("usernames    {}", exposure.usernames),
+        format!("subdomains   {}", exposure.subdomains),
+    ];
+    let text = format!(
+        "{}\n{}",
+        markdown::escape(&format!("Leaks of {}:", domain)),
+        markdown::code_block(&lines.join("\n"))
+    );
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+// The stored documents are sent as a file whatever their size
+async fn handle_export(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    arg: &str,
+    passwords: &PasswordView,
+) -> HandlerResult {
+    let args: Vec<&str> = arg.split_whitespace().collect();
+    let (domain, format) = match args.as_slice() {
+        [domain, format] => (domain.to_lowercase(), format.parse::<ExportFormat>()),
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                "Use /export <domain> csv or /export <domain> json",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let format = match format {
+        Ok(format) => format,
+        Err(e) => {
+            bot.send_message(msg.chat.id, e).await?;
+            return Ok(());
+        }
+    };
+    if !is_domain(&domain) {
+        bot.send_message(msg.chat.id, "Expected a domain like corp.com")
+            .await?;
+        return Ok(());
+    }
+
+    let documents = app_data.find_domain(&domain).await?;
+    if documents.iter().all(|x| x.credentials.is_empty()) {
+        bot.send_message(msg.chat.id, "Nothing found :(").await?;
+        return Ok(());
+    }
+
+    let documents = passwords.documents(&documents);
+    let mut file = NamedTempFile::new()?;
+    format.write(&documents, file.as_file_mut())?;
+    send_file(
+        bot,
+        msg,
+        &file,
+        format!("{}.{}", domain, format.extension()),
+    )
+    .await
+}
+
+/// Domains listed by /stats
+static TOP_DOMAINS: usize = 10;
+
+async fn handle_stats(bot: &Bot, msg: &Message, app_data: &AppData) -> HandlerResult {
+    let cached = app_data
+        .stats_cache
+        .lock()
+        .unwrap()
+        .get()
+        .map(|(summary, age)| summary.render(age));
+
+    // Concurrent /stats can both miss the cache, the last one to finish fills it
+    let text = match cached {
+        Some(text) => text,
+        None => {
+            let summary = Summary {
+                stats: app_data.store.stats().await?,
+                top_domains: app_data.store.top_domains(TOP_DOMAINS).await?,
+                updated: Utc::now(),
+            };
+            let text = summary.render(Duration::ZERO);
+            app_data.stats_cache.lock().unwrap().set(summary);
+            text
+        }
+    };
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+/// Strength and reuse of the passwords of a domain, no password is shown
+async fn handle_risk(bot: &Bot, msg: &Message, app_data: &AppData, domain: &str) -> HandlerResult {
+    let domain = domain.trim().to_lowercase();
+    if !is_domain(&domain) {
+        bot.send_message(msg.chat.id, "Expected a domain like corp.com")
+            .await?;
+        return Ok(());
+    }
+
+    let documents = app_data.find_domain(&domain).await?;
+    // Estimating the strength of thousands of passwords would stall the runtime
+    let risk = tokio::task::spawn_blocking(move || domain_risk(domain, &documents)).await?;
+    if risk.credentials == 0 {
+        bot.send_message(msg.chat.id, "Nothing found :(").await?;
+        return Ok(());
+    }
+
+    bot.send_message(msg.chat.id, render_risk(&risk))
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+// Merges every LeakData line of the replied to document into the store,
+// credentials that are stored already are skipped
+async fn handle_append(bot: &Bot, msg: &Message, app_data: &AppData) -> HandlerResult {
+    let document = match msg.reply_to_message().and_then(|x| x.document()) {
+        Some(document) => document,
+        None => {
+            bot.send_message(msg.chat.id, "Reply to a ctj jsonl file with /append")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let file = bot.get_file(&document.file.id).await?;
+    let mut contents = Vec::new();
+    bot.download_file(&file.path, &mut contents).await?;
+
+    // Appends read, merge and rewrite documents, so they run one at a time
+    let appending = app_data.append_lock.lock().await;
+    let mut documents = 0;
+    let mut added = 0;
+    let mut grown = HashSet::new();
+    let appended: HandlerResult = async {
+        for line in contents.split(|&c| c == b'\n').filter(|x| !x.is_empty()) {
+            let leak: LeakData = serde_json::from_slice(line)?;
+            let count = app_data.store.append(&leak).await?;
+            if count > 0 {
+                app_data
+                    .query_cache
+                    .lock()
+                    .unwrap()
+                    .invalidate(&leak.domain);
+                grown.insert(leak.domain);
+            }
+            added += count;
+            documents += 1;
+        }
+        Ok(())
+    }
+    .await;
+    // A /stats during the append, or one before it failed partway, would
+    // keep serving the numbers from before it
+    app_data.stats_cache.lock().unwrap().invalidate();
+    drop(appending);
+    appended?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "Added {} new credentials from {} documents",
+            added, documents
+        ),
+    )
+    .await?;
+
+    // Subscribers hear about the new credentials right away
+    // rather than at the next periodic check
+    if !grown.is_empty() {
+        if let Err(e) = monitor::check(bot, app_data, Some(&grown)).await {
+            error!("Subscription check after append failed: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Domains of an uploaded batch file, one per line. Blank lines,
+/// # comments and repeated domains are skipped
+fn batch_domains(contents: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    contents
+        .lines()
+        .map(|line| line.trim().to_lowercase())
+        .filter(|domain| !domain.is_empty() && !domain.starts_with('#'))
+        .filter(|domain| seen.insert(domain.clone()))
+        .collect()
+}
+
+// Looks up every domain of an uploaded .txt file like /domain does,
+// all hits are sent back as a single csv file
+async fn handle_batch(
+    bot: &Bot,
+    msg: &Message,
+    app_data: &AppData,
+    document: &Document,
+    passwords: &PasswordView,
+) -> HandlerResult {
+    let file = bot.get_file(&document.file.id).await?;
+    let mut contents = Vec::new();
+    bot.download_file(&file.path, &mut contents).await?;
+
+    let mut domains = batch_domains(&String::from_utf8_lossy(&contents));
+    let ignored = domains.len().saturating_sub(CONFIG.max_batch_domains);
+    domains.truncate(CONFIG.max_batch_domains);
+
+    let mut output = NamedTempFile::new()?;
+    let mut writer = csv::Writer::from_writer(output.as_file_mut());
+    writer.write_record(["domain", "subdomain", "username", "password"])?;
+
+    let mut credentials = 0;
+    let mut found = 0;
+    for domain in &domains {
+        let before = credentials;
+        for leak_data in passwords.documents(&app_data.find_domain(domain).await?) {
+            for credential in leak_data.credentials {
+                for (username, password) in &credential.data {
+                    writer.write_record([
+                        &leak_data.domain,
+                        &credential.subdomain,
+                        username,
+                        password,
+                    ])?;
+                    credentials += 1;
+                }
+            }
+        }
+        if credentials > before {
+            found += 1;
+        }
+    }
+    writer.flush()?;
+    drop(writer);
+
+    let mut summary = format!(
+        "Found {} credentials for {} of {} domains",
+        credentials,
+        found,
+        domains.len()
+    );
+    if ignored > 0 {
+        summary.push_str(&format!(
+            ", {} more domains were ignored, the limit is {}",
+            ignored, CONFIG.max_batch_domains
+        ));
+    }
+    bot.send_message(msg.chat.id, summary).await?;
+
+    if credentials > 0 {
+        let name = document.file_name.as_deref().unwrap_or("batch.txt");
+        let name = format!("{}.csv", name.trim_end_matches(".txt"));
+        send_file(bot, msg, &output, name).await?;
+    }
+    Ok(())
+}
+
+// Too many pages to click through, the whole result is sent as a .txt file
+async fn send_document(bot: &Bot, msg: &Message, pages: &Pages) -> HandlerResult {
+    let mut file = NamedTempFile::new()?;
+    for page in &pages.pages {
+        file.write_all(page.as_bytes())?;
+    }
+    file.flush()?;
+
+    send_file(
+        bot,
+        msg,
+        &file,
+        format!("{}.{}", pages.title, pages.extension),
+    )
+    .await
+}
+
+/// Sends `file` as a document named `name` unless it's over the size limit
+async fn send_file(bot: &Bot, msg: &Message, file: &NamedTempFile, name: String) -> HandlerResult {
+    let size = file.as_file().metadata()?.len();
+    if size > CONFIG.max_document_size {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "Result is too big to send: {} mb, the limit is {} mb",
+                size / 1024 / 1024,
+                CONFIG.max_document_size / 1024 / 1024
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let document = InputFile::file(file.path()).file_name(name);
+    bot.send_document(msg.chat.id, document).await?;
+
+    Ok(())
+}
+
+// A domain picked from the /search results is looked up like /domain,
+// in a new message so the list stays around to pick another one
+async fn handle_search_pick(
+    bot: Bot,
+    q: CallbackQuery,
+    app_data: Arc<AppData>,
+    domain: &str,
+) -> HandlerResult {
+    let user = q.from.id;
+    let message = match q.message {
+        Some(message) => message,
+        None => {
+            bot.answer_callback_query(q.id).await?;
+            return Ok(());
+        }
+    };
+
+    if app_data.auth.read().unwrap().role(user).is_none() {
+        warn!("Denied search pick to user {}", user);
+        bot.answer_callback_query(q.id)
+            .text("Access denied")
+            .await?;
+        return Ok(());
+    }
+    let limited = app_data.rate_limiter.lock().unwrap().acquire(user);
+    if limited.is_err() {
+        warn!("Rate limited search pick of user {}", user);
+        bot.answer_callback_query(q.id)
+            .text("Slow down please, try again later")
+            .await?;
+        return Ok(());
+    }
+    bot.answer_callback_query(q.id).await?;
+
+    app_data
+        .history
+        .lock()
+        .unwrap()
+        .push(user, &format!("/domain {}", domain));
+    let passwords = PasswordView::new(CONFIG.password_redaction.clone());
+    handle_domain(&bot, &message, &app_data, user, domain, &passwords).await
+}
+
+async fn handle_callback(bot: Bot, q: CallbackQuery, app_data: Arc<AppData>) -> HandlerResult {
+    if let Some(domain) = q.data.as_deref().and_then(|x| x.strip_prefix("domain:")) {
+        let domain = domain.to_string();
+        return handle_search_pick(bot, q, app_data, &domain).await;
+    }
+
+    let page = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("page:"))
+        .and_then(|page| page.parse::<usize>().ok());
+
+    let (message, page) = match (q.message, page) {
+        (Some(message), Some(page)) => (message, page),
+        _ => {
+            bot.answer_callback_query(q.id).await?;
+            return Ok(());
+        }
+    };
+
+    if app_data.auth.read().unwrap().role(q.from.id).is_none() {
+        warn!("Denied paging to user {}", q.from.id);
+        bot.answer_callback_query(q.id)
+            .text("Access denied")
+            .await?;
+        return Ok(());
+    }
+
+    // Rendered up front, the lock can't be held across the requests below
+    let rendered = match app_data
+        .pages
+        .lock()
+        .unwrap()
+        .get(&(message.chat.id, message.id))
+    {
+        Some(pages) if page < pages.pages.len() => Some((pages.render(page), pages.pages.len())),
+        _ => None,
+    };
+    let (text, total) = match rendered {
+        Some(rendered) => rendered,
+        None => {
+            bot.answer_callback_query(q.id)
+                .text("Results expired, run /domain again")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    bot.edit_message_text(message.chat.id, message.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .reply_markup(page_keyboard(page, total))
+        .await?;
+    bot.answer_callback_query(q.id).await?;
+
+    Ok(())
+}
+
+/// Passwords are shown as PASSWORD_REDACTION says, except to admins
+/// asking for them with /domain_full or in the files of /export,
+/// /wordlist and /combolist, which are meant for other tools
+fn password_view(cmd: &Command, role: Role, redaction: Option<Redaction>) -> PasswordView {
+    let full = role == Role::Admin
+        && matches!(
+            cmd,
+            Command::DomainFull(_)
+                | Command::Export(_)
+                | Command::Wordlist(_)
+                | Command::Combolist(_)
+        );
+    PasswordView::new(redaction.filter(|_| !full))
+}
+
+async fn run_command(
+    bot: Bot,
     msg: Message,
     cmd: Command,
-    app_data: Arc<Mutex<AppData>>,
+    app_data: Arc<AppData>,
 ) -> HandlerResult {
+    let user = match msg.from() {
+        Some(user) => user.id,
+        None => return Ok(()),
+    };
+    let role = app_data.auth.read().unwrap().role(user);
+    let role = match role {
+        Some(role) => role,
+        None => {
+            warn!("Denied access to user {} in chat {}", user, msg.chat.id);
+            bot.send_message(msg.chat.id, "Access denied").await?;
+            return Ok(());
+        }
+    };
+
+    if !matches!(cmd, Command::Help | Command::Format(_) | Command::History) {
+        let limited = app_data.rate_limiter.lock().unwrap().acquire(user);
+        if let Err(wait) = limited {
+            warn!("Rate limited user {}", user);
+            let reply = match wait.as_secs() {
+                secs if secs < 3600 => format!("Slow down please, try again in {} s", secs + 1),
+                _ => "Slow down please, you are out of queries".to_string(),
+            };
+            bot.send_message(msg.chat.id, reply).await?;
+            return Ok(());
+        }
+    }
+
+    let lookup = matches!(
+        cmd,
+        Command::Domain(_)
+            | Command::DomainFull(_)
+            | Command::Search(_)
+            | Command::Subdomain(_)
+            | Command::Org(_)
+            | Command::Email(_)
+            | Command::User(_)
+            | Command::Wordlist(_)
+            | Command::Combolist(_)
+            | Command::Count(_)
+            | Command::Export(_)
+    );
+    if let Some(text) = msg.text().filter(|_| lookup) {
+        app_data.history.lock().unwrap().push(user, text.trim());
+    }
+    let passwords = password_view(&cmd, role, CONFIG.password_redaction.clone());
+
     match cmd {
         Command::Help => {
             bot.send_message(msg.chat.id, Command::descriptions().to_string())
                 .await?;
         }
         Command::Domain(domain) => {
-            let app_data = app_data.lock().await;
-            handle_domain(&bot, &msg, &*app_data, &domain).await?;
+            handle_domain(&bot, &msg, &app_data, user, &domain, &passwords).await?;
+        }
+        Command::DomainFull(_) if role != Role::Admin => {
+            warn!("Denied {:?} to non admin user {}", cmd, user);
+            bot.send_message(msg.chat.id, "Only admins can see full passwords")
+                .await?;
+        }
+        Command::DomainFull(domain) => {
+            info!("User {} looked up {} with full passwords", user, domain);
+            handle_domain(&bot, &msg, &app_data, user, &domain, &passwords).await?;
+        }
+        Command::Search(term) => {
+            handle_search(&bot, &msg, &app_data, &term).await?;
+        }
+        Command::Subdomain(name) => {
+            handle_subdomain(&bot, &msg, &app_data, user, &name, &passwords).await?;
+        }
+        Command::Org(org) => {
+            handle_org(&bot, &msg, &app_data, &org).await?;
+        }
+        Command::Email(email) => {
+            handle_email(&bot, &msg, &app_data, user, &email, &passwords).await?;
+        }
+        Command::User(username) => {
+            handle_user(&bot, &msg, &app_data, user, &username, &passwords).await?;
+        }
+        Command::Wordlist(domain) => {
+            handle_wordlist(
+                &bot,
+                &msg,
+                &app_data,
+                &domain,
+                WordlistFormat::Passwords,
+                &passwords,
+            )
+            .await?;
+        }
+        Command::Combolist(domain) => {
+            handle_wordlist(
+                &bot,
+                &msg,
+                &app_data,
+                &domain,
+                WordlistFormat::Combo,
+                &passwords,
+            )
+            .await?;
+        }
+        Command::Count(domain) => {
+            handle_count(&bot, &msg, &app_data, &domain).await?;
+        }
+        Command::Export(arg) => {
+            if role == Role::Admin {
+                info!("User {} exported {} with full passwords", user, arg.trim());
+            }
+            handle_export(&bot, &msg, &app_data, &arg, &passwords).await?;
+        }
+        Command::Format(name) => {
+            handle_format(&bot, &msg, &app_data, user, &name).await?;
+        }
+        Command::History => {
+            handle_history(&bot, &msg, &app_data, user).await?;
+        }
+        Command::Save(domain) => {
+            handle_save(&bot, &msg, &app_data, user, &domain).await?;
+        }
+        Command::Unsave(domain) => {
+            handle_unsave(&bot, &msg, &app_data, user, &domain).await?;
+        }
+        Command::Watch(arg) => {
+            handle_watch(&bot, &msg, &app_data, user, &arg).await?;
+        }
+        Command::Subscribe(domain) => {
+            handle_subscribe(&bot, &msg, &app_data, user, &domain).await?;
+        }
+        Command::Unsubscribe(domain) => {
+            handle_unsubscribe(&bot, &msg, &app_data, user, &domain).await?;
+        }
+        Command::Subscriptions => {
+            handle_subscriptions(&bot, &msg, &app_data, user).await?;
+        }
+        Command::Append if role != Role::Admin => {
+            warn!("Denied {:?} to non admin user {}", cmd, user);
+            bot.send_message(msg.chat.id, "Only admins can add leaks")
+                .await?;
+        }
+        Command::Stats(domain) if domain.trim().is_empty() => {
+            handle_stats(&bot, &msg, &app_data).await?;
+        }
+        Command::Stats(domain) => {
+            handle_risk(&bot, &msg, &app_data, &domain).await?;
+        }
+        Command::Append => {
+            handle_append(&bot, &msg, &app_data).await?;
+            info!("User {} appended a leak file", user);
+        }
+        Command::ClearCache if role != Role::Admin => {
+            warn!("Denied {:?} to non admin user {}", cmd, user);
+            bot.send_message(msg.chat.id, "Only admins can clear the cache")
+                .await?;
+        }
+        Command::ClearCache => {
+            app_data.query_cache.lock().unwrap().clear();
+            app_data.stats_cache.lock().unwrap().invalidate();
+            info!("User {} cleared the cache", user);
+            bot.send_message(msg.chat.id, "Cleared cached lookups and stats")
+                .await?;
+        }
+        Command::Grant(_) | Command::Revoke(_) if role != Role::Admin => {
+            warn!("Denied {:?} to non admin user {}", cmd, user);
+            bot.send_message(msg.chat.id, "Only admins can manage access")
+                .await?;
+        }
+        Command::Grant(id) => {
+            app_data.auth.write().unwrap().grant(UserId(id));
+            info!("User {} granted access to {}", user, id);
+            bot.send_message(msg.chat.id, format!("Granted access to {}", id))
+                .await?;
+        }
+        Command::Revoke(id) => {
+            let revoked = app_data.auth.write().unwrap().revoke(UserId(id));
+            let reply = if revoked {
+                info!("User {} revoked access of {}", user, id);
+                format!("Revoked access of {}", id)
+            } else {
+                format!("{} is not a revocable user", id)
+            };
+            bot.send_message(msg.chat.id, reply).await?;
         }
     }
     Ok(())
 }
 
-fn schema() -> Handler<'static, DependencyMap, HandlerResult, DpHandlerDescription> {
-    Update::filter_message().branch(
-        dptree::entry()
-            .filter_command::<Command>()
-            .endpoint(handle_command),
+async fn run_batch(bot: Bot, msg: Message, app_data: Arc<AppData>) -> HandlerResult {
+    let (user, document) = match (msg.from(), msg.document()) {
+        (Some(user), Some(document)) => (user.id, document),
+        _ => return Ok(()),
+    };
+
+    if app_data.auth.read().unwrap().role(user).is_none() {
+        warn!(
+            "Denied batch lookup to user {} in chat {}",
+            user, msg.chat.id
+        );
+        bot.send_message(msg.chat.id, "Access denied").await?;
+        return Ok(());
+    }
+    // A whole file counts as a single query, MAX_BATCH_DOMAINS bounds its cost
+    let limited = app_data.rate_limiter.lock().unwrap().acquire(user);
+    if limited.is_err() {
+        warn!("Rate limited batch lookup of user {}", user);
+        bot.send_message(msg.chat.id, "Slow down please, try again later")
+            .await?;
+        return Ok(());
+    }
+
+    // Batch lookups are like /domain, no role sees their passwords in full
+    let passwords = PasswordView::new(CONFIG.password_redaction.clone());
+    handle_batch(&bot, &msg, &app_data, document, &passwords).await?;
+    info!("User {} looked up a batch file", user);
+    Ok(())
+}
+
+/// Credential lines of a domain or an email looked up inline
+async fn inline_lookup(app_data: &AppData, query: &str) -> StoreResult<Vec<String>> {
+    // Previews land in any chat, so no role sees their passwords in full
+    let passwords = PasswordView::new(CONFIG.password_redaction.clone());
+    if let Some((username, host)) = query.rsplit_once('@') {
+        let (subdomain, domain) = parse_domain(host, &app_data.st);
+        let rows = app_data
+            .store
+            .find_email(domain, subdomain, username)
+            .await?;
+        let rows = passwords.rows(rows);
+        return Ok(ResultFormat::Plain.lines(&rows, false));
+    }
+
+    let rows: Vec<CredentialRow> = app_data
+        .find_domain(query)
+        .await?
+        .iter()
+        .flat_map(|leak_data| credential_rows(&leak_data.domain, &leak_data.credentials))
+        .collect();
+    let rows = passwords.rows(rows);
+    Ok(ResultFormat::Plain.lines(&rows, true))
+}
+
+/// Single article with the first credentials found, the full result
+/// is left to the commands of the bot chat
+fn inline_preview(query: &str, lines: &[String]) -> InlineQueryResult {
+    let mut preview = String::new();
+    for line in lines.iter().take(INLINE_PREVIEW_LINES) {
+        if preview.len() + line.len() + 1 > PAGE_SIZE {
+            break;
+        }
+        preview.push_str(line);
+        preview.push('\n');
+    }
+
+    let mut title = format!("{}: {} credentials", query, lines.len());
+    let shown = preview.lines().count();
+    if shown < lines.len() {
+        title.push_str(&format!(", showing {}", shown));
+    }
+    let text = format!(
+        "{}\n{}",
+        markdown::escape(&title),
+        markdown::code_block(preview.trim_end())
+    );
+
+    let content = InputMessageContent::Text(
+        InputMessageContentText::new(text).parse_mode(ParseMode::MarkdownV2),
+    );
+    // Result ids are limited to 64 bytes, a single result needs no meaningful one
+    let article = InlineQueryResultArticle::new("0", title, content)
+        .description(lines.first().cloned().unwrap_or_default());
+    InlineQueryResult::Article(article)
+}
+
+// Inline mode (@bot example.com) pulls a preview of the results into any chat.
+// Denied, rate limited and empty lookups answer with no results
+async fn run_inline_query(bot: Bot, q: InlineQuery, app_data: Arc<AppData>) -> HandlerResult {
+    let user = q.from.id;
+    let query = q.query.trim().to_lowercase();
+    let mut results = Vec::new();
+
+    // Telegram sends a query per keystroke, half typed names aren't looked up
+    if query.contains('.') {
+        let allowed = app_data.auth.read().unwrap().role(user).is_some();
+        if !allowed {
+            warn!("Denied inline query of user {}", user);
+        } else if app_data.rate_limiter.lock().unwrap().acquire(user).is_err() {
+            warn!("Rate limited inline query of user {}", user);
+        } else {
+            let lines = inline_lookup(&app_data, &query).await?;
+            if !lines.is_empty() {
+                results.push(inline_preview(&query, &lines));
+            }
+        }
+    }
+
+    // Answers depend on the access of the user, so they are neither shared nor cached
+    bot.answer_inline_query(q.id, results)
+        .is_personal(true)
+        .cache_time(0)
+        .await?;
+    Ok(())
+}
+
+/// Tells the user the store is down once its retries ran out,
+/// rather than leaving the command unanswered
+async fn report_unavailable(bot: &Bot, chat: ChatId, result: &HandlerResult) -> HandlerResult {
+    if matches!(result, Err(e) if e.is::<StoreUnavailable>()) {
+        bot.send_message(
+            chat,
+            "The store is temporarily unavailable, try again in a minute",
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn handle_command(
+    bot: Bot,
+    msg: Message,
+    cmd: Command,
+    app_data: Arc<AppData>,
+) -> HandlerResult {
+    let started = Instant::now();
+    let chat = msg.chat.id;
+    let result = run_command(bot.clone(), msg, cmd, app_data).await;
+    METRICS.observe(QueryKind::Command, started.elapsed(), result.is_ok());
+    report_unavailable(&bot, chat, &result).await?;
+    result
+}
+
+async fn handle_batch_upload(bot: Bot, msg: Message, app_data: Arc<AppData>) -> HandlerResult {
+    let started = Instant::now();
+    let chat = msg.chat.id;
+    let result = run_batch(bot.clone(), msg, app_data).await;
+    METRICS.observe(QueryKind::Batch, started.elapsed(), result.is_ok());
+    report_unavailable(&bot, chat, &result).await?;
+    result
+}
+
+/// Uploaded .txt files are lists of domains to look up
+fn is_batch_upload(msg: Message) -> bool {
+    matches!(
+        msg.document().and_then(|x| x.file_name.as_deref()),
+        Some(name) if name.ends_with(".txt")
     )
 }
 
+async fn handle_inline_query(bot: Bot, q: InlineQuery, app_data: Arc<AppData>) -> HandlerResult {
+    let started = Instant::now();
+    let result = run_inline_query(bot, q, app_data).await;
+    METRICS.observe(QueryKind::Inline, started.elapsed(), result.is_ok());
+    result
+}
+
+fn schema() -> Handler<'static, DependencyMap, HandlerResult, DpHandlerDescription> {
+    dptree::entry()
+        .branch(
+            Update::filter_message()
+                .branch(
+                    dptree::entry()
+                        .filter_command::<Command>()
+                        .endpoint(handle_command),
+                )
+                .branch(dptree::filter(is_batch_upload).endpoint(handle_batch_upload)),
+        )
+        .branch(Update::filter_callback_query().endpoint(handle_callback))
+        .branch(Update::filter_inline_query().endpoint(handle_inline_query))
+}
+
+/// State shared by all handlers. Lookups only read the store, so handlers
+/// run concurrently and the locks guard short synchronous sections only,
+/// they are never held across an await
 struct AppData {
-    pub cluster: Cluster,
+    /// Shared with the health check listener
+    pub store: Arc<Store>,
+    /// Paged results by the message showing them, the oldest are dropped
+    pub pages: Mutex<LruCache<(ChatId, MessageId), Pages>>,
+    /// Splits searched names into subdomain and domain
+    pub st: PublicSuffixList,
+    pub auth: RwLock<Auth>,
+    pub rate_limiter: Mutex<RateLimiter>,
+    pub stats_cache: Mutex<StatsCache>,
+    /// Result format picked by each user with /format,
+    /// lost on restart like runtime grants
+    pub formats: RwLock<HashMap<UserId, ResultFormat>>,
+    /// Lookups listed by /history
+    pub history: Mutex<History>,
+    pub query_cache: Mutex<QueryCache>,
+    pub append_lock: tokio::sync::Mutex<()>,
+    /// Held by subscription checks, see monitor::check
+    pub monitor_lock: tokio::sync::Mutex<()>,
 }
 
-async fn init_db() -> Result<Cluster, Box<dyn std::error::Error + Send + Sync>> {
-    let cluster = Cluster::connect(
-        &CONFIG.couch_uri,
-        &CONFIG.couch_username,
-        &CONFIG.couch_password,
-    );
+impl AppData {
+    /// Documents of `domain`, served from the query cache when it has them
+    async fn find_domain(&self, domain: &str) -> StoreResult<Arc<Vec<LeakData>>> {
+        find_domain(self.store.as_ref(), &self.query_cache, domain).await
+    }
+}
+
+fn read_tld(tld_path: &str) -> Result<String, std::io::Error> {
+    if tld_path == "auto" {
+        let psl = SuffixProvider::default().fetch();
+        return Ok(parse_tld(&mut psl.as_bytes()));
+    }
 
-    Ok(cluster)
+    let file = File::open(tld_path)?;
+    Ok(parse_tld(&mut BufReader::new(file)))
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    dotenv().ok();
-    env_logger::init();
+async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     log::info!("Starting command bot...");
 
-    let cluster = init_db().await?;
+    if CONFIG.admin_users.is_empty() && CONFIG.allowed_users.is_empty() {
+        warn!("No allowed or admin users configured, every request will be denied");
+    }
+
+    let store = Arc::new(Store::from_config().await?);
+    let tlds = read_tld(&CONFIG.tld_path)
+        .with_context(|| format!("can't read the tld file {}", CONFIG.tld_path))?;
+    let st = PublicSuffixList::new(&tlds);
 
-    let app_data = AppData { cluster };
-    let app_data = Arc::new(Mutex::new(app_data));
+    let app_data = AppData {
+        store: store.clone(),
+        pages: Mutex::new(LruCache::new(NonZeroUsize::new(PAGED_RESULTS).unwrap())),
+        st,
+        auth: RwLock::new(Auth::new(&CONFIG.admin_users, &CONFIG.allowed_users)),
+        rate_limiter: Mutex::new(RateLimiter::new(
+            CONFIG.rate_limit_burst,
+            CONFIG.rate_limit_per_minute,
+        )),
+        stats_cache: Mutex::new(StatsCache::new(Duration::from_secs(
+            CONFIG.stats_cache_minutes * 60,
+        ))),
+        formats: RwLock::new(HashMap::new()),
+        history: Mutex::new(History::new(CONFIG.history_size)),
+        query_cache: Mutex::new(QueryCache::new(
+            CONFIG.query_cache_size,
+            CONFIG.query_cache_credentials,
+            Duration::from_secs(CONFIG.query_cache_ttl_secs),
+        )),
+        append_lock: tokio::sync::Mutex::new(()),
+        monitor_lock: tokio::sync::Mutex::new(()),
+    };
+    let app_data = Arc::new(app_data);
+
+    let bot = Bot::with_client(&CONFIG.teloxide_token, teloxide::net::client_from_env());
+    if let Some(listen) = &CONFIG.health_listen {
+        let health = health::serve(listen.clone(), bot.clone(), store.clone());
+        tokio::spawn(async move {
+            if let Err(e) = health.await {
+                error!("Health listener failed: {}", e);
+            }
+        });
+    }
+
+    if CONFIG.monitor_interval_minutes > 0 {
+        let interval = Duration::from_secs(CONFIG.monitor_interval_minutes * 60);
+        tokio::spawn(monitor::run(bot.clone(), app_data.clone(), interval));
+    }
 
-    let bot = Bot::from_env().auto_send();
     Dispatcher::builder(bot, schema())
         .dependencies(dptree::deps![app_data])
+        .enable_ctrlc_handler()
         .build()
-        .setup_ctrlc_handler()
         .dispatch()
         .await;
     Ok(())
 }
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    telemetry::init(LogFormat::Text);
+    errors::exit_code(run().await.map_err(|e| e as BoxError))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands() -> Vec<Command> {
+        let domain = || "corp.com".to_string();
+        vec![
+            Command::Help,
+            Command::Domain(domain()),
+            Command::DomainFull(domain()),
+            Command::Search(domain()),
+            Command::Subdomain(domain()),
+            Command::Org(domain()),
+            Command::Email(domain()),
+            Command::User(domain()),
+            Command::Wordlist(domain()),
+            Command::Combolist(domain()),
+            Command::Count(domain()),
+            Command::Export(domain()),
+            Command::Format(domain()),
+            Command::History,
+            Command::Save(domain()),
+            Command::Unsave(domain()),
+            Command::Watch(domain()),
+            Command::Subscribe(domain()),
+            Command::Unsubscribe(domain()),
+            Command::Subscriptions,
+            Command::Stats(domain()),
+            Command::Append,
+            Command::ClearCache,
+            Command::Grant(1),
+            Command::Revoke(1),
+        ]
+    }
+
+    #[test]
+    fn users_never_see_full_passwords() {
+        for cmd in commands() {
+            let passwords = password_view(&cmd, Role::User, Some(Redaction::Mask));
+            assert_ne!(passwords.password("secret"), "secret", "{:?}", cmd);
+        }
+    }
+
+    #[test]
+    fn admins_see_full_passwords_on_request() {
+        for cmd in commands() {
+            let passwords = password_view(&cmd, Role::Admin, Some(Redaction::Mask));
+            let full = matches!(
+                cmd,
+                Command::DomainFull(_)
+                    | Command::Export(_)
+                    | Command::Wordlist(_)
+                    | Command::Combolist(_)
+            );
+            assert_eq!(passwords.password("secret") == "secret", full, "{:?}", cmd);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use leaks_store::{domain_credentials, StoreResult, Subscription, SubscriptionStore};
+use log::{error, info, warn};
+use teloxide::{prelude::*, types::UserId};
+use tokio::time::MissedTickBehavior;
+
+use crate::AppData;
+
+/// Compares the credentials of subscribed domains with the last check and
+/// notifies the subscribers of the domains that grew. Only `domains` are
+/// checked when given
+pub async fn check(
+    bot: &Bot,
+    app_data: &AppData,
+    domains: Option<&HashSet<String>>,
+) -> StoreResult<()> {
+    // A check after /append can overlap the periodic one, both would notify
+    let _checking = app_data.monitor_lock.lock().await;
+
+    let mut by_domain: BTreeMap<String, Vec<Subscription>> = BTreeMap::new();
+    for subscription in app_data.store.subscriptions(None).await? {
+        if domains.is_none_or(|x| x.contains(&subscription.domain)) {
+            by_domain
+                .entry(subscription.domain.clone())
+                .or_default()
+                .push(subscription);
+        }
+    }
+
+    for (domain, subscriptions) in by_domain {
+        let credentials = domain_credentials(app_data.store.as_ref(), &domain).await?;
+        for subscription in &subscriptions {
+            if credentials > subscription.credentials {
+                notify(bot, app_data, subscription, credentials).await;
+            }
+        }
+
+        // Shrunk domains are recorded too, so a later import isn't missed
+        if subscriptions.iter().any(|x| x.credentials != credentials) {
+            app_data.query_cache.lock().unwrap().invalidate(&domain);
+            app_data.store.set_checked(&domain, credentials).await?;
+        }
+    }
+    Ok(())
+}
+
+// Failed sends are only logged, a user who blocked the bot
+// mustn't keep the others from being notified
+async fn notify(bot: &Bot, app_data: &AppData, subscription: &Subscription, credentials: u64) {
+    let user = UserId(subscription.user_id);
+    // Revoked users keep their subscriptions, but aren't told about new leaks
+    if app_data.auth.read().unwrap().role(user).is_none() {
+        return;
+    }
+
+    let text = format!(
+        "{} gained {} new credentials, {} in total. Look them up with /domain {}",
+        subscription.domain,
+        credentials - subscription.credentials,
+        credentials,
+        subscription.domain
+    );
+    match bot.send_message(ChatId(subscription.chat_id), text).await {
+        Ok(_) => info!("Notified user {} about {}", user, subscription.domain),
+        Err(e) => warn!(
+            "Couldn't notify user {} about {}: {}",
+            user, subscription.domain, e
+        ),
+    }
+}
+
+/// Checks every subscription each `interval`, which catches leaks
+/// imported outside of the bot. The first check runs on start
+pub async fn run(bot: Bot, app_data: Arc<AppData>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        if let Err(e) = check(&bot, &app_data, None).await {
+            error!("Subscription check failed: {}", e);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use teloxide::types::UserId;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per-user token bucket, every query takes a token and tokens refill
+/// at a constant rate up to `burst`
+pub struct RateLimiter {
+    burst: f64,
+    per_second: f64,
+    buckets: HashMap<UserId, Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(burst: u32, per_minute: u32) -> RateLimiter {
+        RateLimiter {
+            burst: burst.max(1) as f64,
+            per_second: per_minute as f64 / 60.0,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Takes a token of `user`, or returns how long to wait for the next one
+    pub fn acquire(&mut self, user: UserId) -> Result<(), Duration> {
+        let now = Instant::now();
+        let bucket = self.buckets.entry(user).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if self.per_second > 0.0 {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.per_second,
+            ))
+        } else {
+            Err(Duration::MAX)
+        }
+    }
+}
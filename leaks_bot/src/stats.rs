@@ -0,0 +1,107 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use leaks_stats::risk::{DomainRisk, RiskCounter, Strength};
+use leaks_store::{DomainCount, Stats};
+use lib::LeakData;
+use teloxide::utils::markdown;
+
+/// Store wide numbers shown by /stats
+pub struct Summary {
+    pub stats: Stats,
+    pub top_domains: Vec<DomainCount>,
+    /// When the numbers were read from the store
+    pub updated: DateTime<Utc>,
+}
+
+impl Summary {
+    /// Renders the summary as a MarkdownV2 message, `age` is how long ago it was computed
+    pub fn render(&self, age: Duration) -> String {
+        let mut text = format!(
+            "Documents: {}\nDomains: {}\nCredentials: {}\n\nTop domains:\n",
+            self.stats.documents, self.stats.domains, self.stats.credentials
+        );
+        for (i, x) in self.top_domains.iter().enumerate() {
+            text.push_str(&format!("{:>2}. {} {}\n", i + 1, x.domain, x.credentials));
+        }
+
+        let freshness = match age.as_secs() / 60 {
+            0 => "just now".to_string(),
+            minutes => format!("{} min ago", minutes),
+        };
+        let freshness = format!(
+            "Last updated {} UTC, {}",
+            self.updated.format("%Y-%m-%d %H:%M"),
+            freshness
+        );
+        format!(
+            "{}\n{}",
+            markdown::code_block(text.trim_end()),
+            markdown::escape(&freshness)
+        )
+    }
+}
+
+/// Last computed summary, the aggregate queries scan the whole store
+/// so they run at most once per `ttl`
+pub struct StatsCache {
+    ttl: Duration,
+    cached: Option<(Instant, Summary)>,
+}
+
+impl StatsCache {
+    pub fn new(ttl: Duration) -> StatsCache {
+        StatsCache { ttl, cached: None }
+    }
+
+    /// The cached summary and its age, unless it has expired
+    pub fn get(&self) -> Option<(&Summary, Duration)> {
+        let (computed, summary) = self.cached.as_ref()?;
+        let age = computed.elapsed();
+        (age < self.ttl).then_some((summary, age))
+    }
+
+    pub fn set(&mut self, summary: Summary) {
+        self.cached = Some((Instant::now(), summary));
+    }
+
+    /// Drops the summary after the store has changed
+    pub fn invalidate(&mut self) {
+        self.cached = None;
+    }
+}
+
+/// Strength and reuse of the credentials of `documents`, the strength
+/// of every distinct password is estimated once
+pub fn domain_risk(domain: String, documents: &[LeakData]) -> DomainRisk {
+    let mut strengths: HashMap<&str, Strength> = HashMap::new();
+    let mut counter = RiskCounter::default();
+    for credential_data in documents.iter().flat_map(|x| &x.credentials) {
+        for (username, password) in &credential_data.data {
+            let strength = *strengths
+                .entry(password)
+                .or_insert_with(|| Strength::of(password));
+            counter.add(username, password, strength);
+        }
+    }
+    counter.risk(domain)
+}
+
+/// Renders the exposure risk shown by /stats <domain> as a MarkdownV2 message
+pub fn render_risk(risk: &DomainRisk) -> String {
+    let mut lines = vec![format!("{:<11}  {}", "credentials", risk.credentials)];
+    for (strength, count) in &risk.strength {
+        lines.push(format!("{:<11}  {}", strength, count));
+    }
+    lines.push(format!("{:<11}  {}", "reused", risk.reused));
+
+    let title = format!("Exposure risk of {}: {}/100", risk.domain, risk.score);
+    format!(
+        "{}\n{}",
+        markdown::escape(&title),
+        markdown::code_block(&lines.join("\n"))
+    )
+}
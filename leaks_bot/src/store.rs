@@ -0,0 +1,705 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use couchbase::{Cluster, CouchbaseError, PingOptions, QueryOptions};
+use futures::StreamExt;
+use leaks_store::{
+    contains_pattern, CredentialRow, DomainCount, DomainExposure, LeakStore, PostgresStore,
+    SqliteStore, Stats, StoreResult, Subscription, SubscriptionStore, WatchStore,
+};
+use lib::{
+    document::{fit_document, merge_documents, SplitStrategy},
+    n1ql::Query,
+    LeakData,
+};
+use log::{error, warn};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+use crate::config::CONFIG;
+
+/// Options of an attempt of a query, parameters are kept as json
+/// so that they can be built again for every attempt
+fn query_options(params: &Value) -> QueryOptions {
+    let options = QueryOptions::default().timeout(CONFIG.couch_timeout());
+    match params {
+        Value::Null => options,
+        params => options.positional_parameters(params),
+    }
+}
+
+/// Returned once the retries of a query run out on transient failures,
+/// the bot answers it with a friendly message instead of the raw error
+#[derive(Debug)]
+pub struct StoreUnavailable;
+
+impl fmt::Display for StoreUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "store temporarily unavailable")
+    }
+}
+
+impl std::error::Error for StoreUnavailable {}
+
+/// Failure of a single query attempt
+enum QueryError {
+    /// Timeouts and unreachable services, worth a retry on a fresh connection
+    Transient(String),
+    Fatal(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl From<CouchbaseError> for QueryError {
+    fn from(e: CouchbaseError) -> Self {
+        match e {
+            CouchbaseError::Timeout { .. }
+            | CouchbaseError::RequestCanceled { .. }
+            | CouchbaseError::ServiceNotAvailable { .. }
+            | CouchbaseError::TemporaryFailure { .. } => QueryError::Transient(e.to_string()),
+            e => QueryError::Fatal(Box::new(e)),
+        }
+    }
+}
+
+fn connect() -> Arc<Cluster> {
+    Arc::new(Cluster::connect(
+        &CONFIG.couch_uri,
+        &CONFIG.couch_username,
+        &CONFIG.couch_password,
+    ))
+}
+
+/// Pool of cluster connections used in turns, a connection
+/// that failed a query is replaced before the query is retried
+pub struct CouchbaseStore {
+    pool: Vec<RwLock<Arc<Cluster>>>,
+    next: AtomicUsize,
+}
+
+/// LeakData document along with its key
+#[derive(Deserialize)]
+struct StoredDocument {
+    id: String,
+    #[serde(flatten)]
+    leak: LeakData,
+}
+
+impl CouchbaseStore {
+    pub fn connect() -> CouchbaseStore {
+        let pool = (0..CONFIG.couch_pool_size.max(1))
+            .map(|_| RwLock::new(connect()))
+            .collect();
+
+        CouchbaseStore {
+            pool,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Index and connection of the next pool slot
+    fn cluster(&self) -> (usize, Arc<Cluster>) {
+        let slot = self.next.fetch_add(1, Ordering::Relaxed) % self.pool.len();
+        (slot, self.pool[slot].read().unwrap().clone())
+    }
+
+    fn reconnect(&self, slot: usize) {
+        *self.pool[slot].write().unwrap() = connect();
+    }
+
+    async fn query_once<T: DeserializeOwned>(
+        cluster: &Cluster,
+        query: &Query,
+    ) -> Result<Vec<T>, QueryError> {
+        let options = query_options(&query.params());
+        let mut res = cluster.query(query.statement(), options).await?;
+        let _md = res.meta_data().await;
+        let mut rows = res.rows::<T>();
+        let mut result = Vec::new();
+
+        while let Some(row) = rows.next().await {
+            result.push(row?);
+        }
+
+        Ok(result)
+    }
+
+    /// Runs `query`, bounded by COUCH_TIMEOUT_SECS. Transient failures are retried
+    /// up to COUCH_RETRIES times with an exponential backoff when `retry` is set,
+    /// writes that aren't idempotent leave it off
+    async fn run<T: DeserializeOwned>(&self, query: Query, retry: bool) -> StoreResult<Vec<T>> {
+        let retries = if retry { CONFIG.couch_retries } else { 0 };
+        let mut backoff = Duration::from_millis(CONFIG.couch_retry_backoff_ms);
+
+        for attempt in 0..=retries {
+            let (slot, cluster) = self.cluster();
+            let result =
+                tokio::time::timeout(CONFIG.couch_timeout(), Self::query_once(&cluster, &query))
+                    .await
+                    .unwrap_or_else(|_| Err(QueryError::Transient("query timed out".to_string())));
+
+            match result {
+                Ok(rows) => return Ok(rows),
+                Err(QueryError::Fatal(e)) => {
+                    error!("{:#?}", e);
+                    return Err(e);
+                }
+                Err(QueryError::Transient(e)) => {
+                    warn!("Query attempt {} failed: {}", attempt + 1, e);
+                    self.reconnect(slot);
+                    if attempt < retries {
+                        tokio::time::sleep(backoff).await;
+                        backoff *= 2;
+                    }
+                }
+            }
+        }
+
+        Err(Box::new(StoreUnavailable))
+    }
+
+    async fn query<T: DeserializeOwned>(&self, query: Query) -> StoreResult<Vec<T>> {
+        self.run(query, true).await
+    }
+
+    /// Unnests credentials of all documents and keeps the ones matching `filter`,
+    /// which can refer to l (document), c (CredentialData) and d (username, password pair)
+    /// and takes `params` as its placeholders
+    async fn fetch_credentials(
+        &self,
+        filter: &str,
+        params: &[&str],
+    ) -> StoreResult<Vec<CredentialRow>> {
+        let query = Query::new(format!(
+            "SELECT l.domain, c.subdomain, d[0] AS username, d[1] AS password \
+             FROM {} AS l UNNEST l.credentials AS c UNNEST c.data AS d \
+             WHERE {}",
+            CONFIG.keyspace(),
+            filter
+        ));
+
+        self.query(params.iter().fold(query, |query, x| query.bind(x)))
+            .await
+    }
+
+    /// Merges `leak` into the stored documents of its domain and rewrites them,
+    /// splitting the merged document when it outgrows MAX_JSON_SIZE.
+    /// They are rewritten for a new organization too. Returns the number of credentials added
+    pub async fn append(&self, leak: &LeakData) -> StoreResult<usize> {
+        let query = Query::new(format!(
+            "SELECT META(l).id AS id, l.domain, l.credentials, l.breaches, l.org \
+             FROM {} AS l WHERE l.domain = $1",
+            CONFIG.keyspace()
+        ))
+        .bind(&leak.domain);
+        let stored: Vec<StoredDocument> = self.query(query).await?;
+        let (ids, documents): (Vec<String>, Vec<LeakData>) =
+            stored.into_iter().map(|x| (x.id, x.leak)).unzip();
+
+        let new_org = !leak.org.is_empty() && documents.iter().all(|x| x.org != leak.org);
+        let (merged, added) = merge_documents(documents, leak);
+        if added == 0 && !new_org {
+            return Ok(0);
+        }
+
+        // Keys of the stored documents are reused, extra splits get new ones
+        let splits = fit_document(merged, CONFIG.max_json_size, SplitStrategy::Even);
+        for (i, document) in splits.iter().enumerate() {
+            let query = match ids.get(i) {
+                Some(id) => Query::new(format!(
+                    "UPSERT INTO {} (KEY, VALUE) VALUES ($1, $2)",
+                    CONFIG.keyspace()
+                ))
+                .bind(id)
+                .bind(document),
+                None => Query::new(format!(
+                    "INSERT INTO {} (KEY, VALUE) VALUES (UUID(), $1)",
+                    CONFIG.keyspace()
+                ))
+                .bind(document),
+            };
+            // A retried insert could store the document twice under new keys
+            let retry = i < ids.len();
+            self.run::<Value>(query, retry).await?;
+        }
+
+        // The merged document can need fewer splits than were stored
+        if ids.len() > splits.len() {
+            let query = Query::new(format!(
+                "DELETE FROM {} AS l WHERE META(l).id IN $1",
+                CONFIG.keyspace()
+            ))
+            .bind(&ids[splits.len()..]);
+            self.query::<Value>(query).await?;
+        }
+
+        Ok(added)
+    }
+}
+
+impl LeakStore for CouchbaseStore {
+    async fn find_domain(&self, domain: &str) -> StoreResult<Vec<LeakData>> {
+        let query = Query::new(format!(
+            "SELECT domain, credentials, breaches, org FROM {} WHERE domain = $1",
+            CONFIG.keyspace()
+        ))
+        .bind(domain);
+
+        self.query(query).await
+    }
+
+    async fn find_domain_like(&self, pattern: &str) -> StoreResult<Vec<CredentialRow>> {
+        self.fetch_credentials("l.domain LIKE $1", &[pattern]).await
+    }
+
+    async fn find_email(
+        &self,
+        domain: &str,
+        subdomain: &str,
+        username: &str,
+    ) -> StoreResult<Vec<CredentialRow>> {
+        self.fetch_credentials(
+            "l.domain = $1 AND c.subdomain = $2 AND d[0] = $3",
+            &[domain, subdomain, username],
+        )
+        .await
+    }
+
+    async fn find_username(&self, username: &str) -> StoreResult<Vec<CredentialRow>> {
+        self.fetch_credentials("d[0] = $1", &[username]).await
+    }
+
+    async fn stats(&self) -> StoreResult<Stats> {
+        let query = Query::new(format!(
+            "SELECT COUNT(*) AS documents, COUNT(DISTINCT domain) AS domains, \
+             SUM(ARRAY_SUM(ARRAY ARRAY_LENGTH(c.data) FOR c IN credentials END)) AS credentials \
+             FROM {}",
+            CONFIG.keyspace()
+        ));
+
+        let stats: Vec<Stats> = self.query(query).await?;
+        Ok(stats.into_iter().next().unwrap_or_default())
+    }
+
+    async fn top_domains(&self, n: usize) -> StoreResult<Vec<DomainCount>> {
+        let query = Query::new(format!(
+            "SELECT l.domain, \
+             SUM(ARRAY_SUM(ARRAY ARRAY_LENGTH(c.data) FOR c IN l.credentials END)) AS credentials \
+             FROM {} AS l GROUP BY l.domain ORDER BY credentials DESC, l.domain LIMIT $1",
+            CONFIG.keyspace()
+        ))
+        .bind(n);
+
+        self.query(query).await
+    }
+
+    async fn search_domains(&self, term: &str, n: usize) -> StoreResult<Vec<DomainCount>> {
+        let filter = match CONFIG.couch_search_index {
+            Some(_) => "SEARCH(l, $2, $3)",
+            None => "l.domain LIKE $2",
+        };
+        let query = Query::new(format!(
+            "SELECT l.domain, \
+             SUM(ARRAY_SUM(ARRAY ARRAY_LENGTH(c.data) FOR c IN l.credentials END)) AS credentials \
+             FROM {} AS l WHERE {} \
+             GROUP BY l.domain ORDER BY credentials DESC, l.domain LIMIT $1",
+            CONFIG.keyspace(),
+            filter
+        ))
+        .bind(n);
+        let query = match &CONFIG.couch_search_index {
+            // Edit distance 2 finds typos like acne for acme, the analyzer
+            // splits acme-corp.com into words so parts of a name match too
+            Some(index) => query
+                .bind(json!({ "query": { "match": term, "field": "domain", "fuzziness": 2 } }))
+                .bind(json!({ "index": index })),
+            None => query.bind(contains_pattern(term)),
+        };
+
+        self.query(query).await
+    }
+
+    async fn count_domains(&self, domains: &[String]) -> StoreResult<Vec<DomainCount>> {
+        let query = Query::new(format!(
+            "SELECT l.domain, \
+             SUM(ARRAY_SUM(ARRAY ARRAY_LENGTH(c.data) FOR c IN l.credentials END)) AS credentials \
+             FROM {} AS l WHERE l.domain IN $1 \
+             GROUP BY l.domain ORDER BY credentials DESC, l.domain",
+            CONFIG.keyspace()
+        ))
+        .bind(domains);
+
+        self.query(query).await
+    }
+
+    async fn count_domain(&self, domain: &str) -> StoreResult<DomainExposure> {
+        let query = Query::new(format!(
+            "SELECT COUNT(1) AS credentials, COUNT(DISTINCT d[0]) AS usernames, \
+             COUNT(DISTINCT NULLIF(c.subdomain, \"\")) AS subdomains \
+             FROM {} AS l UNNEST l.credentials AS c UNNEST c.data AS d \
+             WHERE l.domain = $1",
+            CONFIG.keyspace()
+        ))
+        .bind(domain);
+
+        let exposure: Vec<DomainExposure> = self.query(query).await?;
+        Ok(exposure.into_iter().next().unwrap_or_default())
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        let (slot, cluster) = self.cluster();
+        let bucket = cluster.bucket(&CONFIG.couch_bucket);
+        let ping = bucket.ping(PingOptions::default());
+        match tokio::time::timeout(CONFIG.couch_timeout(), ping).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => {
+                self.reconnect(slot);
+                Err(Box::new(e))
+            }
+            Err(_) => {
+                self.reconnect(slot);
+                Err(Box::new(StoreUnavailable))
+            }
+        }
+    }
+
+    async fn org_domains(&self, org: &str) -> StoreResult<Vec<String>> {
+        let query = Query::new(format!(
+            "SELECT DISTINCT RAW l.domain FROM {} AS l WHERE LOWER(l.org) = LOWER($1) \
+             ORDER BY l.domain",
+            CONFIG.keyspace()
+        ))
+        .bind(org);
+
+        self.query(query).await
+    }
+
+    async fn insert(&self, leak: &LeakData) -> StoreResult<()> {
+        let query = Query::new(format!(
+            "INSERT INTO {} (KEY, VALUE) VALUES (UUID(), $1)",
+            CONFIG.keyspace()
+        ))
+        .bind(leak);
+
+        self.run::<Value>(query, false).await?;
+        Ok(())
+    }
+}
+
+/// Watchlist and subscription documents are keyed by user and domain,
+/// so saving twice keeps one
+fn user_domain_key(user: u64, domain: &str) -> String {
+    format!("{}:{}", user, domain)
+}
+
+impl WatchStore for CouchbaseStore {
+    async fn watchlist(&self, user: u64) -> StoreResult<Vec<String>> {
+        let query = Query::new(format!(
+            "SELECT RAW w.domain FROM {} AS w WHERE w.user_id = $1 ORDER BY w.domain",
+            CONFIG.watch_keyspace()
+        ))
+        .bind(user);
+
+        self.query(query).await
+    }
+
+    // Checked before the upsert, so both stay safe to retry
+    async fn watch(&self, user: u64, domain: &str) -> StoreResult<bool> {
+        let key = user_domain_key(user, domain);
+        let query = Query::new(format!(
+            "SELECT RAW META(w).id FROM {} AS w USE KEYS $1",
+            CONFIG.watch_keyspace()
+        ))
+        .bind(&key);
+        let existing: Vec<String> = self.query(query).await?;
+        if !existing.is_empty() {
+            return Ok(false);
+        }
+
+        let query = Query::new(format!(
+            "UPSERT INTO {} (KEY, VALUE) VALUES ($1, $2)",
+            CONFIG.watch_keyspace()
+        ))
+        .bind(&key)
+        .bind(json!({ "user_id": user, "domain": domain }));
+        self.query::<Value>(query).await?;
+        Ok(true)
+    }
+
+    async fn unwatch(&self, user: u64, domain: &str) -> StoreResult<bool> {
+        let query = Query::new(format!(
+            "DELETE FROM {} AS w USE KEYS $1 RETURNING RAW META(w).id",
+            CONFIG.watch_keyspace()
+        ))
+        .bind(user_domain_key(user, domain));
+        let deleted: Vec<String> = self.query(query).await?;
+        Ok(!deleted.is_empty())
+    }
+}
+
+impl SubscriptionStore for CouchbaseStore {
+    async fn subscribe(&self, subscription: &Subscription) -> StoreResult<bool> {
+        let key = user_domain_key(subscription.user_id, &subscription.domain);
+        let query = Query::new(format!(
+            "SELECT RAW META(s).id FROM {} AS s USE KEYS $1",
+            CONFIG.subscription_keyspace()
+        ))
+        .bind(&key);
+        let existing: Vec<String> = self.query(query).await?;
+        if !existing.is_empty() {
+            return Ok(false);
+        }
+
+        let query = Query::new(format!(
+            "UPSERT INTO {} (KEY, VALUE) VALUES ($1, $2)",
+            CONFIG.subscription_keyspace()
+        ))
+        .bind(&key)
+        .bind(subscription);
+        self.query::<Value>(query).await?;
+        Ok(true)
+    }
+
+    async fn unsubscribe(&self, user: u64, domain: &str) -> StoreResult<bool> {
+        let query = Query::new(format!(
+            "DELETE FROM {} AS s USE KEYS $1 RETURNING RAW META(s).id",
+            CONFIG.subscription_keyspace()
+        ))
+        .bind(user_domain_key(user, domain));
+        let deleted: Vec<String> = self.query(query).await?;
+        Ok(!deleted.is_empty())
+    }
+
+    async fn subscriptions(&self, user: Option<u64>) -> StoreResult<Vec<Subscription>> {
+        let filter = if user.is_some() {
+            "WHERE s.user_id = $1"
+        } else {
+            ""
+        };
+        let query = Query::new(format!(
+            "SELECT s.user_id, s.chat_id, s.domain, s.credentials FROM {} AS s {} \
+             ORDER BY s.domain, s.user_id",
+            CONFIG.subscription_keyspace(),
+            filter
+        ));
+        let query = match user {
+            Some(user) => query.bind(user),
+            None => query,
+        };
+
+        self.query(query).await
+    }
+
+    async fn set_checked(&self, domain: &str, credentials: u64) -> StoreResult<()> {
+        let query = Query::new(format!(
+            "UPDATE {} AS s SET s.credentials = $2 WHERE s.domain = $1",
+            CONFIG.subscription_keyspace()
+        ))
+        .bind(domain)
+        .bind(credentials);
+
+        self.query::<Value>(query).await?;
+        Ok(())
+    }
+}
+
+/// Backend selected by the STORE setting
+pub enum Store {
+    Couchbase(CouchbaseStore),
+    Postgres(PostgresStore),
+    Sqlite(SqliteStore),
+}
+
+impl Store {
+    /// Adds the credentials of `leak` that aren't stored yet,
+    /// returns the number of credentials added
+    pub async fn append(&self, leak: &LeakData) -> StoreResult<usize> {
+        match self {
+            Store::Couchbase(store) => store.append(leak).await,
+            Store::Postgres(store) => leaks_store::append(store, leak).await,
+            Store::Sqlite(store) => leaks_store::append(store, leak).await,
+        }
+    }
+
+    pub async fn from_config() -> StoreResult<Store> {
+        match CONFIG.store.as_str() {
+            "couchbase" => Ok(Store::Couchbase(CouchbaseStore::connect())),
+            "postgres" => Ok(Store::Postgres(
+                PostgresStore::connect(&CONFIG.postgres_url).await?,
+            )),
+            "sqlite" => Ok(Store::Sqlite(SqliteStore::open(&CONFIG.sqlite_path).await?)),
+            store => Err(format!(
+                "unknown store {}, expected couchbase, postgres or sqlite",
+                store
+            )
+            .into()),
+        }
+    }
+}
+
+impl LeakStore for Store {
+    async fn find_domain(&self, domain: &str) -> StoreResult<Vec<LeakData>> {
+        match self {
+            Store::Couchbase(store) => store.find_domain(domain).await,
+            Store::Postgres(store) => store.find_domain(domain).await,
+            Store::Sqlite(store) => store.find_domain(domain).await,
+        }
+    }
+
+    async fn find_domain_like(&self, pattern: &str) -> StoreResult<Vec<CredentialRow>> {
+        match self {
+            Store::Couchbase(store) => store.find_domain_like(pattern).await,
+            Store::Postgres(store) => store.find_domain_like(pattern).await,
+            Store::Sqlite(store) => store.find_domain_like(pattern).await,
+        }
+    }
+
+    async fn find_email(
+        &self,
+        domain: &str,
+        subdomain: &str,
+        username: &str,
+    ) -> StoreResult<Vec<CredentialRow>> {
+        match self {
+            Store::Couchbase(store) => store.find_email(domain, subdomain, username).await,
+            Store::Postgres(store) => store.find_email(domain, subdomain, username).await,
+            Store::Sqlite(store) => store.find_email(domain, subdomain, username).await,
+        }
+    }
+
+    async fn find_username(&self, username: &str) -> StoreResult<Vec<CredentialRow>> {
+        match self {
+            Store::Couchbase(store) => store.find_username(username).await,
+            Store::Postgres(store) => store.find_username(username).await,
+            Store::Sqlite(store) => store.find_username(username).await,
+        }
+    }
+
+    async fn stats(&self) -> StoreResult<Stats> {
+        match self {
+            Store::Couchbase(store) => store.stats().await,
+            Store::Postgres(store) => store.stats().await,
+            Store::Sqlite(store) => store.stats().await,
+        }
+    }
+
+    async fn top_domains(&self, n: usize) -> StoreResult<Vec<DomainCount>> {
+        match self {
+            Store::Couchbase(store) => store.top_domains(n).await,
+            Store::Postgres(store) => store.top_domains(n).await,
+            Store::Sqlite(store) => store.top_domains(n).await,
+        }
+    }
+
+    async fn search_domains(&self, term: &str, n: usize) -> StoreResult<Vec<DomainCount>> {
+        match self {
+            Store::Couchbase(store) => store.search_domains(term, n).await,
+            Store::Postgres(store) => store.search_domains(term, n).await,
+            Store::Sqlite(store) => store.search_domains(term, n).await,
+        }
+    }
+
+    async fn count_domains(&self, domains: &[String]) -> StoreResult<Vec<DomainCount>> {
+        match self {
+            Store::Couchbase(store) => store.count_domains(domains).await,
+            Store::Postgres(store) => store.count_domains(domains).await,
+            Store::Sqlite(store) => store.count_domains(domains).await,
+        }
+    }
+
+    async fn count_domain(&self, domain: &str) -> StoreResult<DomainExposure> {
+        match self {
+            Store::Couchbase(store) => store.count_domain(domain).await,
+            Store::Postgres(store) => store.count_domain(domain).await,
+            Store::Sqlite(store) => store.count_domain(domain).await,
+        }
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        match self {
+            Store::Couchbase(store) => store.ping().await,
+            Store::Postgres(store) => store.ping().await,
+            Store::Sqlite(store) => store.ping().await,
+        }
+    }
+
+    async fn org_domains(&self, org: &str) -> StoreResult<Vec<String>> {
+        match self {
+            Store::Couchbase(store) => store.org_domains(org).await,
+            Store::Postgres(store) => store.org_domains(org).await,
+            Store::Sqlite(store) => store.org_domains(org).await,
+        }
+    }
+
+    async fn insert(&self, leak: &LeakData) -> StoreResult<()> {
+        match self {
+            Store::Couchbase(store) => store.insert(leak).await,
+            Store::Postgres(store) => store.insert(leak).await,
+            Store::Sqlite(store) => store.insert(leak).await,
+        }
+    }
+}
+
+impl WatchStore for Store {
+    async fn watchlist(&self, user: u64) -> StoreResult<Vec<String>> {
+        match self {
+            Store::Couchbase(store) => store.watchlist(user).await,
+            Store::Postgres(store) => store.watchlist(user).await,
+            Store::Sqlite(store) => store.watchlist(user).await,
+        }
+    }
+
+    async fn watch(&self, user: u64, domain: &str) -> StoreResult<bool> {
+        match self {
+            Store::Couchbase(store) => store.watch(user, domain).await,
+            Store::Postgres(store) => store.watch(user, domain).await,
+            Store::Sqlite(store) => store.watch(user, domain).await,
+        }
+    }
+
+    async fn unwatch(&self, user: u64, domain: &str) -> StoreResult<bool> {
+        match self {
+            Store::Couchbase(store) => store.unwatch(user, domain).await,
+            Store::Postgres(store) => store.unwatch(user, domain).await,
+            Store::Sqlite(store) => store.unwatch(user, domain).await,
+        }
+    }
+}
+
+impl SubscriptionStore for Store {
+    async fn subscribe(&self, subscription: &Subscription) -> StoreResult<bool> {
+        match self {
+            Store::Couchbase(store) => store.subscribe(subscription).await,
+            Store::Postgres(store) => store.subscribe(subscription).await,
+            Store::Sqlite(store) => store.subscribe(subscription).await,
+        }
+    }
+
+    async fn unsubscribe(&self, user: u64, domain: &str) -> StoreResult<bool> {
+        match self {
+            Store::Couchbase(store) => store.unsubscribe(user, domain).await,
+            Store::Postgres(store) => store.unsubscribe(user, domain).await,
+            Store::Sqlite(store) => store.unsubscribe(user, domain).await,
+        }
+    }
+
+    async fn subscriptions(&self, user: Option<u64>) -> StoreResult<Vec<Subscription>> {
+        match self {
+            Store::Couchbase(store) => store.subscriptions(user).await,
+            Store::Postgres(store) => store.subscriptions(user).await,
+            Store::Sqlite(store) => store.subscriptions(user).await,
+        }
+    }
+
+    async fn set_checked(&self, domain: &str, credentials: u64) -> StoreResult<()> {
+        match self {
+            Store::Couchbase(store) => store.set_checked(domain, credentials).await,
+            Store::Postgres(store) => store.set_checked(domain, credentials).await,
+            Store::Sqlite(store) => store.set_checked(domain, credentials).await,
+        }
+    }
+}
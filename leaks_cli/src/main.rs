@@ -0,0 +1,63 @@
+use std::{env, path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+use lib::{
+    config::CONFIG_VAR,
+    errors::{self, BoxError},
+    telemetry::{self, LogFormat},
+};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    /// leaks-suite.toml to read shared settings from,
+    /// instead of looking it up in the working and user config directories
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Log format on stderr: text or json. RUST_LOG sets the level, warn by default
+    #[clap(long, global = true, default_value = "text")]
+    log_format: LogFormat,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Parse leak dumps into csv, jsonl or parquet records, same as indexer
+    Index(Box<indexer::Args>),
+    /// Convert indexer csv into a json document per domain, same as ctj
+    Ctj(ctj::Args),
+    /// Load ctj or indexer output into a leaks store, same as leaks_import
+    Import(leaks_import::Args),
+    /// Password statistics over the indexer csv output, same as leaks_stats
+    Stats(leaks_stats::Args),
+    /// Export ctj output to Elasticsearch or OpenSearch, same as leaks_export
+    Export(leaks_export::Args),
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    telemetry::init(cli.log_format);
+
+    // Read by every subcommand through SuiteConfig::load
+    if let Some(path) = &cli.config {
+        env::set_var(CONFIG_VAR, path);
+    }
+
+    let result = match cli.command {
+        Command::Index(args) => indexer::run(*args),
+        Command::Ctj(args) => ctj::run(args),
+        Command::Import(args) => tokio::runtime::Runtime::new()
+            .map_err(BoxError::from)
+            .and_then(|runtime| {
+                runtime
+                    .block_on(leaks_import::run(args))
+                    .map_err(|e| e as BoxError)
+            }),
+        Command::Stats(args) => leaks_stats::run(args),
+        Command::Export(args) => leaks_export::run(args),
+    };
+    errors::exit_code(result)
+}
@@ -0,0 +1,239 @@
+use std::{
+    borrow::Cow,
+    error::Error,
+    io::{BufRead, BufReader, Read},
+    str::FromStr,
+};
+
+use csv::ByteRecord;
+use lib::{
+    output::{CsvColumns, CsvOptions},
+    Breach, BreachSet,
+};
+use serde::Deserialize;
+
+/// Errors of reading rows, sendable across the conversion threads
+pub type RowError = Box<dyn Error + Send + Sync>;
+
+/// Encoding of the indexer output ctj reads
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// Indexer csv output. Columns are found by the names of the header, so any
+    /// --output-columns of the indexer are read. Without a header they are taken
+    /// at the positions of the default indexer columns, see [`CsvColumns`]
+    #[default]
+    Csv,
+    /// A json object per line with domain, subdomain, username and password keys
+    Jsonl,
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "jsonl" => Ok(InputFormat::Jsonl),
+            _ => Err(format!("unknown input format {}, expected csv or jsonl", s)),
+        }
+    }
+}
+
+/// Encoding of the input rows
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InputOptions {
+    pub format: InputFormat,
+    /// Dialect of csv input
+    pub csv: CsvOptions,
+    /// Where the fields are within csv records, see [`InputOptions::with_header`]
+    pub columns: CsvColumns,
+}
+
+/// Fields of an input row ctj uses, the breach fields are empty
+/// when the input doesn't have them
+pub struct Row<'a> {
+    pub domain: &'a str,
+    pub subdomain: &'a str,
+    pub username: &'a str,
+    pub password: &'a str,
+    pub source: &'a str,
+    pub breach_date: &'a str,
+    pub source_file: &'a str,
+}
+
+impl Row<'_> {
+    /// Adds the breach the row comes from to `breaches`. Rows mostly share
+    /// their breach, so it's compared to the last one before anything is allocated
+    pub fn add_breach(&self, breaches: &mut BreachSet) {
+        let unknown =
+            self.source.is_empty() && self.breach_date.is_empty() && self.source_file.is_empty();
+        let last = breaches.as_slice().last().is_some_and(|x| {
+            x.name == self.source && x.date == self.breach_date && x.source_file == self.source_file
+        });
+        if !unknown && !last && !breaches.is_full() {
+            breaches.insert(Breach {
+                name: self.source.to_string(),
+                date: self.breach_date.to_string(),
+                source_file: self.source_file.to_string(),
+            });
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonLeak<'a> {
+    #[serde(borrow)]
+    domain: Cow<'a, str>,
+    #[serde(borrow)]
+    subdomain: Cow<'a, str>,
+    #[serde(borrow)]
+    username: Cow<'a, str>,
+    #[serde(borrow)]
+    password: Cow<'a, str>,
+    #[serde(borrow, default)]
+    source: Cow<'a, str>,
+    #[serde(borrow, default)]
+    breach_date: Cow<'a, str>,
+    #[serde(borrow, default)]
+    source_file: Cow<'a, str>,
+}
+
+#[derive(Deserialize)]
+struct JsonDomain<'a> {
+    #[serde(borrow)]
+    domain: Cow<'a, str>,
+}
+
+/// Lines of `input` with their byte offsets, blank lines are skipped
+fn for_each_line(
+    input: impl Read,
+    mut f: impl FnMut(usize, &[u8]) -> Result<(), RowError>,
+) -> Result<(), RowError> {
+    let mut input = BufReader::new(input);
+    let mut line = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        line.clear();
+        let read = input.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            return Ok(());
+        }
+        if !line.trim_ascii().is_empty() {
+            f(offset, &line)?;
+        }
+        offset += read;
+    }
+}
+
+impl InputOptions {
+    /// Takes the csv columns from the header of `input`. Input of other
+    /// formats or without a header keeps the columns it has
+    pub fn with_header(self, input: impl Read) -> Result<InputOptions, RowError> {
+        if self.format != InputFormat::Csv || !self.csv.header {
+            return Ok(self);
+        }
+        let mut rdr = self.csv.reader_builder().from_reader(input);
+        let columns = CsvColumns::from_header(rdr.byte_headers()?)?;
+        Ok(InputOptions { columns, ..self })
+    }
+
+    /// Calls `f` with the byte offset and fields of every row of `input`.
+    /// Csv input skips its header row when the dialect has one
+    pub fn read_rows(
+        &self,
+        input: impl Read,
+        mut f: impl FnMut(usize, Row) -> Result<(), RowError>,
+    ) -> Result<(), RowError> {
+        match self.format {
+            InputFormat::Csv => {
+                let mut rdr = self.csv.reader_builder().from_reader(input);
+                let columns = self.columns;
+                let mut record = ByteRecord::new();
+
+                while rdr.read_byte_record(&mut record)? {
+                    let offset = record.position().map_or(0, |x| x.byte() as usize);
+                    let required = |i: usize| match record.get(i) {
+                        Some(x) => Ok(std::str::from_utf8(x)?),
+                        None => Err(RowError::from(format!(
+                            "row at byte {} has no column {}",
+                            offset,
+                            i + 1
+                        ))),
+                    };
+                    // Breach columns of the indexer output, missing from plain csv
+                    let optional = |i: Option<usize>| {
+                        std::str::from_utf8(i.and_then(|i| record.get(i)).unwrap_or_default())
+                    };
+                    let row = Row {
+                        domain: required(columns.domain)?,
+                        subdomain: required(columns.subdomain)?,
+                        username: required(columns.username)?,
+                        password: required(columns.password)?,
+                        source: optional(columns.source)?,
+                        breach_date: optional(columns.breach_date)?,
+                        source_file: optional(columns.source_file)?,
+                    };
+                    f(offset, row)?;
+                }
+                Ok(())
+            }
+            InputFormat::Jsonl => for_each_line(input, |offset, line| {
+                let record: JsonLeak = serde_json::from_slice(line)?;
+                let row = Row {
+                    domain: &record.domain,
+                    subdomain: &record.subdomain,
+                    username: &record.username,
+                    password: &record.password,
+                    source: &record.source,
+                    breach_date: &record.breach_date,
+                    source_file: &record.source_file,
+                };
+                f(offset, row)
+            }),
+        }
+    }
+
+    /// Calls `f` with the byte offset and domain of every row of `data`,
+    /// skipping the csv header when `header` is set. Unless `eof`, the last
+    /// row may be cut, so it's passed as far as it can be read, or not at all
+    pub fn read_domains(
+        &self,
+        data: &[u8],
+        header: bool,
+        eof: bool,
+        mut f: impl FnMut(usize, &[u8]),
+    ) -> Result<(), RowError> {
+        match self.format {
+            InputFormat::Csv => {
+                // A cut row has fewer fields, only the domain is looked at here
+                let mut rdr = self
+                    .csv
+                    .reader_builder()
+                    .has_headers(header)
+                    .flexible(true)
+                    .from_reader(data);
+                let mut record = ByteRecord::new();
+
+                while rdr.read_byte_record(&mut record)? {
+                    let offset = record.position().map_or(0, |x| x.byte() as usize);
+                    f(offset, record.get(self.columns.domain).unwrap_or_default());
+                }
+                Ok(())
+            }
+            InputFormat::Jsonl => {
+                // A cut line isn't valid json
+                let data = match data.iter().rposition(|&x| x == b'\n') {
+                    _ if eof => data,
+                    Some(end) => &data[..=end],
+                    None => &[],
+                };
+                for_each_line(data, |offset, line| {
+                    let record: JsonDomain = serde_json::from_slice(line)?;
+                    f(offset, record.domain.as_bytes());
+                    Ok(())
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,1147 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use indicatif::{ProgressBar, ProgressStyle};
+use lib::{
+    config::SuiteConfig,
+    document::{split_oversized, SplitStrategy},
+    encryption::{Encryption, TempKey},
+    indexer::{decompress, is_compressed},
+    manifest::Manifest,
+    org::{OrgColumns, OrgMap},
+    output::{parse_delimiter, CompressedFile, Compression, CsvOptions, QuoteStyle},
+    progress::file_progress_bar,
+    sort::external_sort,
+    telemetry::FlushOnPanic,
+    BreachSet, CredentialDataRef, LeakDataRef, DOCUMENT_VERSION,
+};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use tempfile::NamedTempFile;
+
+mod input;
+use crate::input::{InputFormat, InputOptions, Row, RowError};
+
+static MAX_JSON_ELEMENTS: usize = 500_000;
+
+// Command line of ctj, also `leaks ctj`
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+pub struct Args {
+    /// Input CSV file, gzip and zstd compressed files are decompressed on the fly
+    #[clap(short, long)]
+    input: String,
+
+    /// Input encoding: csv, or jsonl for the jsonl output of the indexer
+    #[clap(long, default_value = "csv")]
+    input_format: InputFormat,
+
+    /// Output file, relative paths go to output_dir of leaks-suite.toml when set
+    #[clap(short, long)]
+    output: String,
+
+    /// Compress the output on the fly: gzip or zstd.
+    /// The matching extension is appended to the output file name
+    #[clap(long)]
+    compress: Option<Compression>,
+
+    /// Encrypt the output at rest: age:<recipient> encrypts to an age public key,
+    /// passphrase to the passphrase in LEAKS_PASSPHRASE. .age is appended
+    /// to the output file name. The temporary files of --sort are encrypted
+    /// too, with a key of their own that is dropped with them
+    #[clap(long)]
+    encrypt: Option<Encryption>,
+
+    /// Input csv delimiter, use \t or tab for tsv
+    #[clap(long, default_value = ",", value_parser = parse_delimiter)]
+    delimiter: u8,
+
+    /// Input csv quoting, never disables quote handling
+    #[clap(long, default_value = "necessary")]
+    quote_style: QuoteStyle,
+
+    /// Input csv has no header row
+    #[clap(long)]
+    no_header: bool,
+
+    /// Sort the input by domain before converting, so it doesn't have to be
+    /// pre-sorted. Spills sorted chunks to temporary files
+    #[clap(long)]
+    sort: bool,
+
+    /// Memory budget of the sort in megabytes, bigger inputs are spilled to disk
+    #[clap(long, default_value_t = 1024)]
+    sort_memory: usize,
+
+    /// Merge all rows of a domain into a single object, wherever they appear
+    /// in the input, and drop duplicate credentials. Keeps the whole input in memory
+    #[clap(long, conflicts_with = "sort")]
+    merge: bool,
+
+    /// Directory for temporary sort files, defaults to the system temp dir
+    #[clap(long)]
+    tmp_dir: Option<String>,
+
+    /// Maximum size of a json document in bytes, bigger domains are split
+    #[clap(long, default_value_t = 16777216)]
+    max_doc_size: usize,
+
+    /// How oversized domains are split: even spreads credentials evenly,
+    /// subdomain keeps every subdomain within a single document when it fits
+    #[clap(long, default_value = "even")]
+    split_strategy: SplitStrategy,
+
+    /// Number of conversion threads, 0 means one per available core
+    #[clap(long, default_value_t = 0)]
+    threads: usize,
+
+    /// Write a manifest json with the hashes of the input and output,
+    /// the document count and the arguments to this file.
+    /// Relative paths go to output_dir of leaks-suite.toml when set
+    #[clap(long)]
+    manifest: Option<PathBuf>,
+
+    /// Manifest of the stage that wrote the input, like the indexer's.
+    /// Refuses to convert an input that isn't one of its outputs
+    #[clap(long)]
+    verify_manifest: Option<PathBuf>,
+
+    /// Csv mapping domains to the organizations owning them, like a reverse
+    /// WHOIS export. Documents of the domains it lists get their organization
+    #[clap(long)]
+    org_map: Option<PathBuf>,
+
+    /// Header names of the domain and organization columns of --org-map
+    #[clap(long, default_value = "domain,org", requires = "org_map")]
+    org_columns: OrgColumns,
+}
+
+/// How the output file is written
+#[derive(Clone, Copy, Debug, Default)]
+struct OutputOptions<'a> {
+    compression: Option<Compression>,
+    encryption: Option<&'a Encryption>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct DocumentOptions<'a> {
+    max_doc_size: usize,
+    strategy: SplitStrategy,
+    /// Organizations of the domains, empty without --org-map
+    orgs: &'a OrgMap,
+}
+
+/// Rows of a domain group, their fields copied into a single buffer
+/// that is reused from group to group
+#[derive(Default)]
+struct Group {
+    domain: String,
+    fields: String,
+    /// Subdomain, username and password of every row within `fields`
+    rows: Vec<[Range<usize>; 3]>,
+    breaches: BreachSet,
+}
+
+impl Group {
+    fn push(&mut self, row: &Row) {
+        row.add_breach(&mut self.breaches);
+        let mut field = |x: &str| {
+            let start = self.fields.len();
+            self.fields.push_str(x);
+            start..self.fields.len()
+        };
+        let row = [
+            field(row.subdomain),
+            field(row.username),
+            field(row.password),
+        ];
+        self.rows.push(row);
+    }
+
+    /// Credentials by subdomain, subdomains keep the order they first appear in
+    fn leak_data<'a>(&'a self, orgs: &'a OrgMap) -> LeakDataRef<'a> {
+        let mut credentials: Vec<CredentialDataRef> = Vec::new();
+        let mut subdomains: HashMap<&str, usize> = HashMap::new();
+
+        for [subdomain, username, password] in &self.rows {
+            let subdomain = &self.fields[subdomain.clone()];
+            let i = *subdomains.entry(subdomain).or_insert_with(|| {
+                credentials.push(CredentialDataRef {
+                    subdomain: Cow::Borrowed(subdomain),
+                    data: Vec::new(),
+                });
+                credentials.len() - 1
+            });
+            credentials[i].data.push((
+                Cow::Borrowed(&self.fields[username.clone()]),
+                Cow::Borrowed(&self.fields[password.clone()]),
+            ));
+        }
+
+        LeakDataRef {
+            domain: Cow::Borrowed(&self.domain),
+            credentials,
+            breaches: self.breaches.as_slice().to_vec(),
+            org: Cow::Borrowed(orgs.get(&self.domain).unwrap_or_default()),
+            version: DOCUMENT_VERSION,
+        }
+    }
+
+    fn clear(&mut self, domain: &str) {
+        self.domain.clear();
+        self.domain.push_str(domain);
+        self.fields.clear();
+        self.rows.clear();
+        self.breaches.clear();
+    }
+}
+
+/// Writes the documents of a domain, returns how many were written
+fn fflush_object_buffer(
+    leak_data: LeakDataRef,
+    writer: &mut impl Write,
+    pb: &ProgressBar,
+    document_options: DocumentOptions,
+) -> u64 {
+    let max_size = document_options.max_doc_size;
+
+    if leak_data.credentials.is_empty() {
+        return 0;
+    }
+
+    let leak_str = serde_json::to_string(&leak_data).unwrap() + "\n";
+    let leak_str_size = leak_str.len();
+    if leak_str_size > max_size {
+        drop(leak_str);
+
+        pb.println(format!(
+            "{} is oversized - {} mb, splitting...",
+            &leak_data.domain,
+            leak_str_size / 1024 / 1024
+        ));
+
+        // Splitting is rare enough to work on an owned copy
+        let leak_data = leak_data.into_owned();
+        let splits = split_oversized(
+            leak_data,
+            leak_str_size,
+            max_size,
+            document_options.strategy,
+        );
+        let documents = splits.len() as u64;
+        for x in splits {
+            let leak_str = serde_json::to_string(&x).unwrap() + "\n";
+            writer.write_all(leak_str.as_bytes()).unwrap();
+        }
+        documents
+    } else {
+        writer.write_all(leak_str.as_bytes()).unwrap();
+        1
+    }
+}
+
+/// Bytes of input converted per step. Domain groups within it are turned
+/// into json in parallel, so this bounds the json held in memory at once
+static BLOCK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Turns runs of adjacent rows sharing a domain into json documents.
+/// Rows are found by byte offsets, so groups are parsed straight from the input
+struct Converter<'a, W: Write> {
+    input: InputOptions,
+    document_options: DocumentOptions<'a>,
+    /// Set until the header row is consumed
+    skip_header: bool,
+    writer: W,
+    /// Documents written so far
+    documents: u64,
+    pb: &'a ProgressBar,
+    pool: rayon::ThreadPool,
+}
+
+impl<W: Write> Converter<'_, W> {
+    /// Byte ranges of the complete domain groups of `data`. Unless `eof`,
+    /// the last group may continue past `data` and the last row may be cut,
+    /// so both are left for the next call
+    fn groups(&self, data: &[u8], eof: bool) -> Result<Vec<Range<usize>>, Box<dyn Error>> {
+        let mut starts = Vec::new();
+        let mut domain = Vec::new();
+        let mut rows = 0;
+        let mut last = 0;
+
+        self.input
+            .read_domains(data, self.skip_header, eof, |offset, field| {
+                last = offset;
+                if starts.is_empty() || field != domain.as_slice() || rows == MAX_JSON_ELEMENTS {
+                    starts.push(offset);
+                    domain = field.to_vec();
+                    rows = 0;
+                }
+                rows += 1;
+            })
+            .map_err(|e| e as Box<dyn Error>)?;
+
+        let end = if eof {
+            data.len()
+        } else {
+            starts.retain(|&x| x < last);
+            starts.pop().unwrap_or_default()
+        };
+        let ends = starts.iter().skip(1).copied().chain([end]);
+        let groups = starts
+            .iter()
+            .copied()
+            .zip(ends)
+            .map(|(a, b)| a..b)
+            .collect();
+        Ok(groups)
+    }
+
+    /// Converts the complete groups of `data`, returns the amount of bytes consumed
+    fn convert(&mut self, data: &[u8], eof: bool) -> Result<usize, Box<dyn Error>> {
+        let groups = self.groups(data, eof)?;
+        let consumed = match groups.last() {
+            _ if eof => data.len(),
+            Some(group) => group.end,
+            None => 0,
+        };
+        if consumed > 0 {
+            self.skip_header = false;
+        }
+
+        let input = self.input;
+        let document_options = self.document_options;
+        let pb = self.pb;
+        // A few runs per thread, so uneven domains still keep every thread busy
+        let run = groups
+            .len()
+            .div_ceil(self.pool.current_num_threads() * 4)
+            .max(1);
+        let documents: Vec<(Vec<u8>, u64)> = self
+            .pool
+            .install(|| {
+                groups
+                    .par_chunks(run)
+                    .map(|groups| documents(data, groups, input, document_options, pb))
+                    .collect::<Result<_, RowError>>()
+            })
+            .map_err(|e| e as Box<dyn Error>)?;
+
+        for (x, written) in documents {
+            self.writer.write_all(&x)?;
+            self.documents += written;
+        }
+        Ok(consumed)
+    }
+}
+
+/// Json lines of a run of adjacent domain groups. A run shares a single
+/// reader, setting one up costs more than converting a small domain
+fn documents(
+    data: &[u8],
+    groups: &[Range<usize>],
+    input: InputOptions,
+    document_options: DocumentOptions,
+    pb: &ProgressBar,
+) -> Result<(Vec<u8>, u64), RowError> {
+    let start = groups.first().map_or(0, |x| x.start);
+    let end = groups.last().map_or(0, |x| x.end);
+    // Groups start past the header
+    let input = InputOptions {
+        csv: CsvOptions {
+            header: false,
+            ..input.csv
+        },
+        ..input
+    };
+
+    let mut out = Vec::new();
+    let mut written = 0;
+    let mut ends = groups.iter().map(|x| x.end);
+    let mut group_end = start;
+    let mut group = Group::default();
+
+    input.read_rows(&data[start..end], |offset, row| {
+        if start + offset >= group_end {
+            written += fflush_object_buffer(
+                group.leak_data(document_options.orgs),
+                &mut out,
+                pb,
+                document_options,
+            );
+            group.clear(row.domain);
+            group_end = ends.next().unwrap_or(end);
+        }
+        group.push(&row);
+        Ok(())
+    })?;
+
+    written += fflush_object_buffer(
+        group.leak_data(document_options.orgs),
+        &mut out,
+        pb,
+        document_options,
+    );
+    Ok((out, written))
+}
+
+/// Converts sorted input. Plain files are memory mapped and read in place,
+/// compressed ones and the ones `sealed` with a temporary key are decoded
+/// block by block. Returns the amount of documents written
+fn parse(
+    path: &Path,
+    sealed: Option<&TempKey>,
+    out: &Path,
+    output: OutputOptions,
+    input: InputOptions,
+    document_options: DocumentOptions,
+    threads: usize,
+) -> Result<u64, Box<dyn Error>> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let pb = file_progress_bar(len);
+
+    let out_file = CompressedFile::encrypted(out, output.compression, output.encryption)?;
+    let mut converter = Converter {
+        input,
+        document_options,
+        skip_header: input.format == InputFormat::Csv && input.csv.header,
+        writer: FlushOnPanic::new(BufWriter::new(out_file)),
+        documents: 0,
+        pb: &pb,
+        pool: rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()?,
+    };
+
+    // Mapping an empty file fails
+    if len > 0 {
+        // Safety: the input must not be modified while ctj runs
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        if sealed.is_some() || is_compressed(&mmap) {
+            drop(mmap);
+            // The bar counts compressed bytes, so it ends at the size of the file
+            let reader = BufReader::new(pb.wrap_read(file));
+            let mut input: Box<dyn Read> = match sealed {
+                Some(sealed) => Box::new(sealed.decrypt(reader)?),
+                None => decompress(reader)?,
+            };
+            let mut buf = Vec::new();
+            loop {
+                let read = (&mut input).take(BLOCK_SIZE as u64).read_to_end(&mut buf)?;
+                let consumed = converter.convert(&buf, read == 0)?;
+                buf.drain(..consumed);
+                if read == 0 {
+                    break;
+                }
+            }
+        } else {
+            let mut offset = 0;
+            let mut block = BLOCK_SIZE;
+            while offset < mmap.len() {
+                let end = (offset + block).min(mmap.len());
+                let consumed = converter.convert(&mmap[offset..end], end == mmap.len())?;
+                // A domain group longer than the block
+                block = if consumed == 0 { block * 2 } else { BLOCK_SIZE };
+                offset += consumed;
+                pb.set_position(offset as u64);
+            }
+        }
+    }
+
+    converter
+        .writer
+        .into_inner()
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .finish()?;
+    pb.finish();
+
+    Ok(converter.documents)
+}
+
+// Collects the whole input in memory, so rows of a domain don't have to be
+// adjacent. Identical credential pairs of a subdomain are written once
+fn merge(
+    path: &Path,
+    out: &Path,
+    output: OutputOptions,
+    input: InputOptions,
+    document_options: DocumentOptions,
+) -> Result<u64, Box<dyn Error>> {
+    let file = File::open(path)?;
+    let pb = file_progress_bar(file.metadata()?.len());
+    let input_wrap = pb.wrap_read(file);
+
+    // The bar counts compressed bytes, so it ends at the size of the file
+    let reader = decompress(BufReader::new(input_wrap))?;
+
+    type Subdomains = HashMap<String, BTreeSet<(String, String)>>;
+    let mut domains: BTreeMap<String, (Subdomains, BreachSet)> = BTreeMap::new();
+
+    input
+        .read_rows(reader, |_, row| {
+            let (subdomains, breaches) = match domains.get_mut(row.domain) {
+                Some(domain) => domain,
+                None => domains.entry(row.domain.to_string()).or_default(),
+            };
+            row.add_breach(breaches);
+            let pairs = match subdomains.get_mut(row.subdomain) {
+                Some(pairs) => pairs,
+                None => subdomains.entry(row.subdomain.to_string()).or_default(),
+            };
+            pairs.insert((row.username.to_string(), row.password.to_string()));
+            Ok(())
+        })
+        .map_err(|e| e as Box<dyn Error>)?;
+
+    let out_file = CompressedFile::encrypted(out, output.compression, output.encryption)?;
+    let mut writer = FlushOnPanic::new(BufWriter::new(out_file));
+
+    let mut documents = 0;
+    for (domain, (subdomains, breaches)) in &domains {
+        let leak_data = LeakDataRef {
+            domain: Cow::Borrowed(domain),
+            credentials: subdomains
+                .iter()
+                .map(|(subdomain, pairs)| CredentialDataRef {
+                    subdomain: Cow::Borrowed(subdomain),
+                    data: pairs
+                        .iter()
+                        .map(|(username, password)| {
+                            (Cow::Borrowed(&**username), Cow::Borrowed(&**password))
+                        })
+                        .collect(),
+                })
+                .collect(),
+            breaches: breaches.as_slice().to_vec(),
+            org: Cow::Borrowed(document_options.orgs.get(domain).unwrap_or_default()),
+            version: DOCUMENT_VERSION,
+        };
+        documents += fflush_object_buffer(leak_data, &mut writer, &pb, document_options);
+    }
+    writer
+        .into_inner()
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .finish()?;
+    pb.finish();
+
+    Ok(documents)
+}
+
+/// Converts the input, the same for the ctj binary and `leaks ctj`
+pub fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let config = SuiteConfig::load()?;
+    let csv = Path::new(&args.input);
+    let output = config.output_path(Path::new(&args.output));
+    let output = match args.compress {
+        Some(compression) => compression.output_path(&output),
+        None => output,
+    };
+    let output = match &args.encrypt {
+        Some(encryption) => encryption.output_path(&output),
+        None => output,
+    };
+    let output = output.as_path();
+
+    if !csv.exists() {
+        return Err(format!("input {} doesn't exist", csv.display()).into());
+    }
+    if output.exists() {
+        return Err(format!(
+            "output {} exists already, ctj doesn't overwrite it",
+            output.display()
+        )
+        .into());
+    }
+    if args.sort && args.input_format != InputFormat::Csv {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--sort works with csv input only",
+            )
+            .exit();
+    }
+
+    let csv_options = CsvOptions {
+        delimiter: args.delimiter,
+        quote_style: args.quote_style,
+        header: !args.no_header,
+    };
+    let input = InputOptions {
+        format: args.input_format,
+        csv: csv_options,
+        ..Default::default()
+    }
+    .with_header(decompress(BufReader::new(File::open(csv)?))?)
+    .map_err(|e| e as Box<dyn Error>)?;
+    let orgs = match &args.org_map {
+        Some(path) => OrgMap::read(path, &args.org_columns)?,
+        None => OrgMap::default(),
+    };
+    let document_options = DocumentOptions {
+        max_doc_size: args.max_doc_size,
+        strategy: args.split_strategy,
+        orgs: &orgs,
+    };
+
+    if let Some(path) = &args.verify_manifest {
+        Manifest::read(path)?.verify_input(csv)?;
+    }
+
+    let output_options = OutputOptions {
+        compression: args.compress,
+        encryption: args.encrypt.as_ref(),
+    };
+    let documents = if args.merge {
+        merge(csv, output, output_options, input, document_options)?
+    } else if args.sort {
+        sort_and_parse(&args, csv, output, input, document_options)?
+    } else {
+        parse(
+            csv,
+            None,
+            output,
+            output_options,
+            input,
+            document_options,
+            args.threads,
+        )?
+    };
+
+    if let Some(path) = &args.manifest {
+        let mut manifest = Manifest::new("ctj", env!("CARGO_PKG_VERSION"), &[]);
+        manifest.add_input(csv)?;
+        manifest.add_output(output)?;
+        manifest.count("documents", documents);
+        manifest.write(&config.output_path(path))?;
+    }
+
+    Ok(())
+}
+
+/// Sorts the input by domain into a temporary file and converts that.
+/// With encrypted output the temporary files are encrypted too, with a key
+/// that only lives as long as the run
+fn sort_and_parse(
+    args: &Args,
+    csv: &Path,
+    output: &Path,
+    input: InputOptions,
+    document_options: DocumentOptions,
+) -> Result<u64, Box<dyn Error>> {
+    let tmp_dir = args
+        .tmp_dir
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    let sorted = NamedTempFile::new_in(&tmp_dir)?;
+    let sealed = args.encrypt.as_ref().map(|_| TempKey::generate());
+    let encryption = sealed.as_ref().map(TempKey::encryption);
+    let mut sorted_file = CompressedFile::encrypted(sorted.path(), None, encryption.as_ref())?;
+
+    let file = File::open(csv)?;
+    let pb = ProgressBar::new(file.metadata()?.len());
+    pb.enable_steady_tick(Duration::from_millis(500));
+    pb.set_style(ProgressStyle::default_bar().template("{spinner:.green} sorting {wide_bar:40.green/black} {bytes:>11.green}/{total_bytes:<11.green} {bytes_per_sec:>13.red} [{elapsed_precise}]")?
+        .progress_chars("━╾╴─"));
+    external_sort(
+        decompress(BufReader::new(pb.wrap_read(file)))?,
+        BufWriter::new(&mut sorted_file),
+        input.csv,
+        input.columns.domain,
+        args.sort_memory * 1024 * 1024,
+        &tmp_dir,
+        sealed.as_ref(),
+    )?;
+    sorted_file.finish()?;
+    pb.finish();
+
+    // The sorted copy is written without a header
+    let input = InputOptions {
+        csv: CsvOptions {
+            header: false,
+            ..input.csv
+        },
+        ..input
+    };
+    parse(
+        sorted.path(),
+        sealed.as_ref(),
+        output,
+        OutputOptions {
+            compression: args.compress,
+            encryption: args.encrypt.as_ref(),
+        },
+        input,
+        document_options,
+        args.threads,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::{
+        document::{split, split_by_subdomain},
+        CredentialData, LeakData,
+    };
+
+    use super::*;
+
+    fn get_test_data() -> (usize, LeakData) {
+        let arrange: Vec<usize> = vec![100, 200, 300];
+        let total_expected: usize = arrange.iter().sum();
+
+        let test_data = LeakData {
+            domain: "".to_string(),
+            credentials: arrange
+                .iter()
+                .map(|n| CredentialData {
+                    subdomain: "".to_string(),
+                    data: vec![("kek".to_string(), "kek".to_string()); *n],
+                })
+                .collect(),
+            breaches: Vec::new(),
+            org: String::new(),
+            version: DOCUMENT_VERSION,
+        };
+        (total_expected, test_data)
+    }
+
+    #[test]
+    fn split_2() {
+        let n = 2;
+        let (total_expected, test_data) = get_test_data();
+        let total: usize = split(test_data, n)
+            .iter()
+            .map(|x| -> usize { x.credentials.iter().map(|y| y.data.len()).sum() })
+            .sum();
+        assert_eq!(total, total_expected);
+    }
+
+    #[test]
+    fn split_3() {
+        let n = 3;
+        let (total_expected, test_data) = get_test_data();
+        let total: usize = split(test_data, n)
+            .iter()
+            .map(|x| -> usize { x.credentials.iter().map(|y| y.data.len()).sum() })
+            .sum();
+        assert_eq!(total, total_expected);
+    }
+
+    #[test]
+    fn split_4() {
+        let n = 4;
+        let (total_expected, test_data) = get_test_data();
+        let total: usize = split(test_data, n)
+            .iter()
+            .map(|x| -> usize { x.credentials.iter().map(|y| y.data.len()).sum() })
+            .sum();
+        assert_eq!(total, total_expected);
+    }
+
+    #[test]
+    fn merge_repeated_domains() {
+        let mut input = NamedTempFile::new().unwrap();
+        input
+            .write_all(b"a.com,,u1,p1\nb.com,,u2,p2\na.com,,u1,p1\na.com,,u3,p3\n")
+            .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("out.json");
+
+        let csv_options = CsvOptions {
+            header: false,
+            ..Default::default()
+        };
+        let document_options = DocumentOptions {
+            max_doc_size: 16777216,
+            strategy: SplitStrategy::Even,
+            orgs: &OrgMap::default(),
+        };
+        merge(
+            input.path(),
+            &output,
+            OutputOptions::default(),
+            InputOptions {
+                csv: csv_options,
+                ..Default::default()
+            },
+            document_options,
+        )
+        .unwrap();
+
+        let leaks: Vec<LeakData> = std::fs::read_to_string(&output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(leaks.len(), 2);
+        assert_eq!(leaks[0].domain, "a.com");
+        assert_eq!(
+            leaks[0].credentials[0].data,
+            vec![
+                ("u1".to_string(), "p1".to_string()),
+                ("u3".to_string(), "p3".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_compressed_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.csv.gz");
+        let mut file = CompressedFile::create(&input, Some(Compression::Gzip)).unwrap();
+        file.write_all(b"a.com,,u1,p1\na.com,www,u2,p2\nb.com,,u3,p3\n")
+            .unwrap();
+        file.finish().unwrap();
+        let output = dir.path().join("out.json");
+
+        let csv_options = CsvOptions {
+            header: false,
+            ..Default::default()
+        };
+        let document_options = DocumentOptions {
+            max_doc_size: 16777216,
+            strategy: SplitStrategy::Even,
+            orgs: &OrgMap::default(),
+        };
+        let input_options = InputOptions {
+            csv: csv_options,
+            ..Default::default()
+        };
+        parse(
+            &input,
+            None,
+            &output,
+            OutputOptions::default(),
+            input_options,
+            document_options,
+            1,
+        )
+        .unwrap();
+
+        let leaks: Vec<LeakData> = std::fs::read_to_string(&output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(leaks.len(), 2);
+        assert_eq!(leaks[0].domain, "a.com");
+        assert_eq!(leaks[0].credentials.len(), 2);
+    }
+
+    #[test]
+    fn parse_sealed_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("sorted.csv");
+        let key = TempKey::generate();
+        let mut file = CompressedFile::encrypted(&input, None, Some(&key.encryption())).unwrap();
+        file.write_all(b"a.com,,u1,p1\na.com,www,u2,p2\nb.com,,u3,p3\n")
+            .unwrap();
+        file.finish().unwrap();
+        let output = dir.path().join("out.json");
+
+        let document_options = DocumentOptions {
+            max_doc_size: 16777216,
+            strategy: SplitStrategy::Even,
+            orgs: &OrgMap::default(),
+        };
+        let input_options = InputOptions {
+            csv: CsvOptions {
+                header: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        parse(
+            &input,
+            Some(&key),
+            &output,
+            OutputOptions::default(),
+            input_options,
+            document_options,
+            1,
+        )
+        .unwrap();
+
+        let leaks: Vec<LeakData> = std::fs::read_to_string(&output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(leaks.len(), 2);
+        assert_eq!(leaks[0].credentials.len(), 2);
+    }
+
+    #[test]
+    fn parse_mapped_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.csv");
+        std::fs::write(
+            &input,
+            "domain,subdomain,username,password\na.com,,u1,p1\na.com,www,u2,p2\nb.com,,u3,p3",
+        )
+        .unwrap();
+        let output = dir.path().join("out.json");
+
+        let document_options = DocumentOptions {
+            max_doc_size: 16777216,
+            strategy: SplitStrategy::Even,
+            orgs: &OrgMap::default(),
+        };
+        parse(
+            &input,
+            None,
+            &output,
+            OutputOptions::default(),
+            InputOptions::default(),
+            document_options,
+            2,
+        )
+        .unwrap();
+
+        let leaks: Vec<LeakData> = std::fs::read_to_string(&output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(leaks.len(), 2);
+        assert_eq!(leaks[1].domain, "b.com");
+        assert_eq!(
+            leaks[1].credentials[0].data,
+            vec![("u3".to_string(), "p3".to_string())]
+        );
+    }
+
+    #[test]
+    fn org_from_mapping() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.csv");
+        std::fs::write(&input, "a.com,,u1,p1\nb.com,,u2,p2\n").unwrap();
+        let orgs = OrgMap::from_reader(
+            "domain,org\na.com,A Corp\n".as_bytes(),
+            &OrgColumns::default(),
+        )
+        .unwrap();
+        let document_options = DocumentOptions {
+            max_doc_size: 16777216,
+            strategy: SplitStrategy::Even,
+            orgs: &orgs,
+        };
+        let input_options = InputOptions {
+            csv: CsvOptions {
+                header: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        for merged in [false, true] {
+            let output = dir.path().join(format!("out_{}.json", merged));
+            match merged {
+                true => merge(
+                    &input,
+                    &output,
+                    OutputOptions::default(),
+                    input_options,
+                    document_options,
+                ),
+                false => parse(
+                    &input,
+                    None,
+                    &output,
+                    OutputOptions::default(),
+                    input_options,
+                    document_options,
+                    1,
+                ),
+            }
+            .unwrap();
+
+            let json = std::fs::read_to_string(&output).unwrap();
+            let leaks: Vec<LeakData> = json
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            assert_eq!(leaks[0].org, "A Corp");
+            assert_eq!(leaks[1].org, "");
+            assert!(!json.lines().nth(1).unwrap().contains("org"));
+        }
+    }
+
+    // Feeds `input` to a converter `block` bytes at a time, like compressed input
+    fn convert_blocks(input: &[u8], format: InputFormat, block: usize) -> Vec<LeakData> {
+        let pb = ProgressBar::hidden();
+        let mut converter = Converter {
+            input: InputOptions {
+                format,
+                ..Default::default()
+            }
+            .with_header(input)
+            .unwrap(),
+            document_options: DocumentOptions {
+                max_doc_size: 16777216,
+                strategy: SplitStrategy::Even,
+                orgs: &OrgMap::default(),
+            },
+            skip_header: format == InputFormat::Csv,
+            writer: Vec::new(),
+            documents: 0,
+            pb: &pb,
+            pool: rayon::ThreadPoolBuilder::new()
+                .num_threads(2)
+                .build()
+                .unwrap(),
+        };
+
+        let mut buf = Vec::new();
+        for chunk in input.chunks(block).chain([&[][..]]) {
+            buf.extend_from_slice(chunk);
+            let consumed = converter.convert(&buf, chunk.is_empty()).unwrap();
+            buf.drain(..consumed);
+        }
+        assert!(buf.is_empty());
+
+        String::from_utf8(converter.writer)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn groups_across_blocks() {
+        let input = b"domain,subdomain,username,password\n\
+            a.com,,u1,p1\na.com,www,u2,\"p,2\"\nb.com,,u3,p3\n\
+            b.com,,u4,p4\nc.com,mail,u5,p5\n";
+
+        for block in [1, 7, 30, input.len()] {
+            let leaks = convert_blocks(input, InputFormat::Csv, block);
+            let domains: Vec<&str> = leaks.iter().map(|x| x.domain.as_str()).collect();
+            assert_eq!(domains, ["a.com", "b.com", "c.com"], "block {}", block);
+            assert_eq!(leaks[0].credentials.len(), 2);
+            assert_eq!(leaks[1].credentials[0].data.len(), 2);
+        }
+    }
+
+    #[test]
+    fn breaches_from_indexer_columns() {
+        let input = b"domain,subdomain,username,password,password_type,target_domain,source,breach_date,source_file\n\
+            a.com,,u1,p1,plain,,combo,2024-05-01,x.txt\n\
+            a.com,www,u2,p2,plain,,combo,2024-05-01,x.txt\n\
+            a.com,,u3,p3,plain,,combo,2024-05-01,y.txt\n\
+            b.com,,u4,p4,plain,,,,\n";
+
+        let leaks = convert_blocks(input, InputFormat::Csv, input.len());
+        let files: Vec<&str> = leaks[0]
+            .breaches
+            .iter()
+            .map(|x| x.source_file.as_str())
+            .collect();
+        assert_eq!(files, ["x.txt", "y.txt"]);
+        assert_eq!(leaks[0].breaches[0].name, "combo");
+        assert_eq!(leaks[0].breaches[0].date, "2024-05-01");
+        assert!(leaks[1].breaches.is_empty());
+    }
+
+    #[test]
+    fn columns_by_header() {
+        let input = b"email,source_file,password,username,subdomain,domain\n\
+            u1@a.com,x.txt,p1,u1,,a.com\n\
+            u2@www.a.com,y.txt,p2,u2,www,a.com\n\
+            u3@b.com,,p3,u3,,b.com\n";
+
+        let leaks = convert_blocks(input, InputFormat::Csv, 16);
+        assert_eq!(leaks.len(), 2);
+        assert_eq!(leaks[0].domain, "a.com");
+        assert_eq!(
+            leaks[0].credentials[1].data,
+            vec![("u2".to_string(), "p2".to_string())]
+        );
+        let files: Vec<&str> = leaks[0]
+            .breaches
+            .iter()
+            .map(|x| x.source_file.as_str())
+            .collect();
+        assert_eq!(files, ["x.txt", "y.txt"]);
+        assert!(leaks[1].breaches.is_empty());
+
+        let input = InputOptions::default().with_header(&b"domain,user,password\n"[..]);
+        assert!(input.is_err());
+    }
+
+    #[test]
+    fn jsonl_groups_across_blocks() {
+        let line = |domain: &str, subdomain: &str, username: &str| {
+            format!(
+                "{{\"domain\":\"{}\",\"subdomain\":\"{}\",\"username\":\"{}\",\"password\":\"p\\\"1\",\"password_type\":\"plain\"}}\n",
+                domain, subdomain, username
+            )
+        };
+        let input = [
+            line("a.com", "", "u1"),
+            line("a.com", "www", "u2"),
+            "\n".to_string(),
+            line("b.com", "", "u3"),
+            line("c.com", "mail", "u4"),
+        ]
+        .concat();
+
+        for block in [1, 20, 100, input.len()] {
+            let leaks = convert_blocks(input.as_bytes(), InputFormat::Jsonl, block);
+            let domains: Vec<&str> = leaks.iter().map(|x| x.domain.as_str()).collect();
+            assert_eq!(domains, ["a.com", "b.com", "c.com"], "block {}", block);
+            assert_eq!(leaks[0].credentials.len(), 2);
+            assert_eq!(
+                leaks[2].credentials[0].data,
+                vec![("u4".to_string(), "p\"1".to_string())]
+            );
+        }
+    }
+
+    #[test]
+    fn split_by_subdomain_keeps_groups() {
+        let (total_expected, test_data) = get_test_data();
+        // Fits the two smaller subdomains, but not all three
+        let size = |i: usize| {
+            serde_json::to_string(&test_data.credentials[i])
+                .unwrap()
+                .len()
+        };
+        let max_size = size(2) + size(0);
+
+        let splits = split_by_subdomain(test_data, max_size);
+        let sizes: Vec<Vec<usize>> = splits
+            .iter()
+            .map(|x| x.credentials.iter().map(|y| y.data.len()).collect())
+            .collect();
+        assert_eq!(sizes, vec![vec![100, 200], vec![300]]);
+        assert_eq!(sizes.iter().flatten().sum::<usize>(), total_expected);
+    }
+
+    #[test]
+    fn split_by_subdomain_oversized_group() {
+        let (total_expected, test_data) = get_test_data();
+        let max_size = serde_json::to_string(&test_data.credentials[0])
+            .unwrap()
+            .len()
+            + 1;
+
+        let splits = split_by_subdomain(test_data, max_size);
+        assert!(splits.len() > 3);
+        let total: usize = splits
+            .iter()
+            .map(|x| -> usize { x.credentials.iter().map(|y| y.data.len()).sum() })
+            .sum();
+        assert_eq!(total, total_expected);
+    }
+}
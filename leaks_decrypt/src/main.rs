@@ -0,0 +1,61 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use clap::Parser;
+use lib::{
+    encryption::{decrypt, Decryption},
+    errors::{self, Context},
+    telemetry::{self, FlushOnPanic, LogFormat},
+};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// File written with --encrypt, usually ending with .age
+    #[clap(short, long)]
+    input: PathBuf,
+
+    /// Plaintext output, stdout by default. Compressed outputs stay compressed
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// age identity file of the recipient the output was encrypted to.
+    /// Without it the passphrase is read from LEAKS_PASSPHRASE
+    #[clap(long)]
+    identity: Option<PathBuf>,
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let key = match args.identity {
+        Some(path) => Decryption::IdentityFile(path),
+        None => Decryption::Passphrase,
+    };
+
+    let input =
+        File::open(&args.input).with_context(|| format!("can't open {}", args.input.display()))?;
+    let mut reader = decrypt(BufReader::new(input), &key)
+        .with_context(|| format!("can't decrypt {}", args.input.display()))?;
+
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => {
+            let file =
+                File::create(path).with_context(|| format!("can't create {}", path.display()))?;
+            Box::new(FlushOnPanic::new(BufWriter::new(file)))
+        }
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    io::copy(&mut reader, &mut output)?;
+    output.flush()?;
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    telemetry::init(LogFormat::Text);
+    errors::exit_code(run(Args::parse()))
+}
@@ -0,0 +1,2 @@
+//! End-to-end tests of the pipeline, see tests/pipeline.rs. They need docker
+//! and are ignored by default, run them with `cargo test -p leaks_e2e -- --ignored`
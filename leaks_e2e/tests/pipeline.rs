@@ -0,0 +1,262 @@
+use std::{
+    env, fs,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use leaks_bot::{
+    cache::QueryCache,
+    format::{PasswordView, ResultFormat},
+    lookup::lookup_domain,
+    store::CouchbaseStore,
+};
+use leaks_store::{DomainCount, DomainExposure, LeakStore};
+use lib::{n1ql::Keyspace, LeakData};
+use testcontainers::{
+    clients::Cli,
+    core::{ExecCommand, WaitFor},
+    Container, GenericImage,
+};
+
+static USERNAME: &str = "Administrator";
+static PASSWORD: &str = "password";
+static BUCKET: &str = "leaks-bucket";
+
+/// Ports the sdk connects to by their names in the alternate addresses
+/// of the cluster, which tell the sdk the host ports docker mapped them to
+static PORTS: [(&str, u16); 4] = [
+    ("mgmt", 8091),
+    ("capi", 8092),
+    ("n1ql", 8093),
+    ("kv", 11210),
+];
+
+static DUMP: &str = "john.doe@corp.com:p1
+admin@vpn.corp.com:p2
+admin@corp.com:p3
+john@corp.org:p4
+";
+
+fn couchbase() -> GenericImage {
+    let image = GenericImage::new("couchbase/server", "community-7.2.4")
+        .with_wait_for(WaitFor::message_on_stdout("Starting Couchbase Server"));
+    PORTS
+        .iter()
+        .fold(image, |image, &(_, port)| image.with_exposed_port(port))
+}
+
+// Services start a while after the server, every step is retried until it's accepted
+fn setup_cluster(container: &Container<GenericImage>) {
+    let exec = |cmd| {
+        container.exec(ExecCommand {
+            cmd,
+            ready_conditions: Vec::new(),
+        });
+    };
+    let auth = format!("-c localhost -u {} -p {}", USERNAME, PASSWORD);
+    let steps = [
+        format!(
+            "couchbase-cli cluster-init -c localhost --cluster-username {} --cluster-password {} \
+             --services data,index,query --cluster-ramsize 512 --cluster-index-ramsize 256 \
+             --index-storage-setting default",
+            USERNAME, PASSWORD
+        ),
+        format!(
+            "couchbase-cli bucket-create {} --bucket {} --bucket-type couchbase \
+             --bucket-ramsize 256 --wait",
+            auth, BUCKET
+        ),
+        format!(
+            "couchbase-cli collection-manage {} --bucket {} --create-collection _default.leaks",
+            auth, BUCKET
+        ),
+        // The external network of the sdk connects to the mapped ports
+        format!(
+            "curl -sf -u {}:{} -X PUT \
+             localhost:8091/node/controller/setupAlternateAddresses/external \
+             -d hostname=127.0.0.1 {}",
+            USERNAME,
+            PASSWORD,
+            PORTS
+                .iter()
+                .map(|&(name, port)| format!("-d {}={}", name, container.get_host_port_ipv4(port)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+    ];
+    for step in steps {
+        exec(format!("until {}; do sleep 1; done", step));
+    }
+
+    // cbq exits with 0 on failed statements, so its status is checked instead
+    exec(format!(
+        "until cbq -e localhost:8093 -u {} -p {} -q -s 'CREATE PRIMARY INDEX ON {}' \
+         | grep -q '\"status\": \"success\"'; do sleep 1; done",
+        USERNAME,
+        PASSWORD,
+        Keyspace::new("default", BUCKET, "_default", "leaks")
+    ));
+}
+
+// The settings of the bot are read once, on the first use of CONFIG
+fn configure_bot(tld: &Path, kv_port: u16) {
+    let uri = format!("couchbase://127.0.0.1:{}?network=external", kv_port);
+    let vars = [
+        ("TELOXIDE_TOKEN", "0000000000:test"),
+        ("TLD_PATH", tld.to_str().unwrap()),
+        ("STORE", "couchbase"),
+        ("COUCH_URI", &uri),
+        ("COUCH_USERNAME", USERNAME),
+        ("COUCH_PASSWORD", PASSWORD),
+        ("COUCH_BUCKET", BUCKET),
+        ("COUCH_POOL_SIZE", "1"),
+    ];
+    for (name, value) in vars {
+        env::set_var(name, value);
+    }
+}
+
+/// Runs the indexer and ctj over DUMP, returning the ctj documents
+fn run_pipeline(dir: &Path) -> Vec<LeakData> {
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    fs::write(path("dump.txt"), DUMP).unwrap();
+
+    indexer::run(indexer::Args::parse_from([
+        "indexer",
+        "--tld",
+        &path("tld.dat"),
+        "-i",
+        &path("dump.txt"),
+        "-o",
+        &path("leaks.csv"),
+        "-e",
+        &path("errors.txt"),
+        "--source-name",
+        "fixture",
+        "--breach-date",
+        "2024-01-02",
+        "--threads",
+        "1",
+        "--quiet",
+    ]))
+    .unwrap();
+    ctj::run(ctj::Args::parse_from([
+        "ctj",
+        "-i",
+        &path("leaks.csv"),
+        "-o",
+        &path("leaks.jsonl"),
+        "--threads",
+        "1",
+    ]))
+    .unwrap();
+
+    fs::read_to_string(path("leaks.jsonl"))
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+// Queries don't wait for the index to catch up with the inserts
+async fn wait_for_documents(store: &CouchbaseStore, documents: u64) {
+    let start = Instant::now();
+    while store.stats().await.unwrap().documents < documents {
+        assert!(
+            start.elapsed() < Duration::from_secs(60),
+            "documents weren't indexed in time"
+        );
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs docker"]
+async fn indexer_ctj_couchbase() {
+    let docker = Cli::default();
+    let container = docker.run(couchbase());
+    setup_cluster(&container);
+
+    let dir = tempfile::tempdir().unwrap();
+    let tld = dir.path().join("tld.dat");
+    fs::write(&tld, "com\norg\n").unwrap();
+    configure_bot(&tld, container.get_host_port_ipv4(11210));
+
+    let leaks = run_pipeline(dir.path());
+    assert_eq!(leaks.len(), 2);
+
+    let store = CouchbaseStore::connect();
+    for leak in &leaks {
+        store.insert(leak).await.unwrap();
+    }
+    wait_for_documents(&store, 2).await;
+
+    let documents = store.find_domain("corp.com").await.unwrap();
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].credentials.len(), 2);
+    assert_eq!(documents[0].breaches[0].name, "fixture");
+    assert_eq!(documents[0].breaches[0].date, "2024-01-02");
+
+    // What /domain replies with, the documents are cached for the next lookup
+    let cache = Mutex::new(QueryCache::new(10, 1000, Duration::from_secs(60)));
+    let results = lookup_domain(&store, &cache, "corp.com", &PasswordView::new(None))
+        .await
+        .unwrap();
+    let mut lines = ResultFormat::Plain.lines(&results.rows, true);
+    lines.sort();
+    assert_eq!(lines, ["admin:p2", "admin:p3", "john.doe:p1"]);
+    assert_eq!(results.breaches.len(), 1);
+    assert_eq!(results.breaches[0].name, "fixture");
+    assert!(cache.lock().unwrap().get("corp.com").is_some());
+
+    assert_eq!(
+        store.count_domain("corp.com").await.unwrap(),
+        DomainExposure {
+            credentials: 3,
+            usernames: 2,
+            subdomains: 1,
+        }
+    );
+
+    let rows = store.find_email("corp.com", "vpn", "admin").await.unwrap();
+    assert_eq!(
+        ResultFormat::Plain.lines(&rows, false),
+        ["admin@vpn.corp.com:p2"]
+    );
+    assert_eq!(store.find_username("john").await.unwrap()[0].password, "p4");
+    assert_eq!(store.find_domain_like("corp.%").await.unwrap().len(), 4);
+
+    let domains: Vec<String> = store
+        .search_domains("corp", 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.domain)
+        .collect();
+    assert_eq!(domains, ["corp.com", "corp.org"]);
+    assert_eq!(
+        store.top_domains(1).await.unwrap(),
+        [DomainCount {
+            domain: "corp.com".to_string(),
+            credentials: 3,
+        }]
+    );
+
+    let domains = ["corp.org".to_string(), "corp.com".to_string()];
+    let counts: Vec<(String, u64)> = store
+        .count_domains(&domains)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| (x.domain, x.credentials))
+        .collect();
+    assert_eq!(
+        counts,
+        [("corp.com".to_string(), 3), ("corp.org".to_string(), 1)]
+    );
+
+    let stats = store.stats().await.unwrap();
+    assert_eq!((stats.domains, stats.credentials), (2, 4));
+}
@@ -0,0 +1,219 @@
+use std::{error::Error, thread, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{error, warn};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Every field is a keyword, lookups are exact like in the other stores
+static MAPPING: &str = r#"{
+  "mappings": {
+    "properties": {
+      "domain": { "type": "keyword" },
+      "subdomain": { "type": "keyword" },
+      "username": { "type": "keyword" },
+      "email": { "type": "keyword" },
+      "password": { "type": "keyword" }
+    }
+  }
+}"#;
+
+static MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Credential as stored in the index, one document per credential
+#[derive(Serialize)]
+pub struct Credential<'a> {
+    pub domain: &'a str,
+    pub subdomain: &'a str,
+    pub username: &'a str,
+    pub password: &'a str,
+    pub email: String,
+}
+
+pub enum Auth {
+    Basic { user: String, password: String },
+    ApiKey(String),
+}
+
+impl Auth {
+    fn header(&self) -> String {
+        match self {
+            Auth::Basic { user, password } => {
+                format!(
+                    "Basic {}",
+                    STANDARD.encode(format!("{}:{}", user, password))
+                )
+            }
+            Auth::ApiKey(key) => format!("ApiKey {}", key),
+        }
+    }
+}
+
+/// Minimal bulk indexing client, works with OpenSearch as well
+pub struct Elasticsearch {
+    url: String,
+    index: String,
+    auth: Option<Auth>,
+    max_retries: u32,
+}
+
+impl Elasticsearch {
+    pub fn new(url: &str, index: &str, auth: Option<Auth>, max_retries: u32) -> Elasticsearch {
+        Elasticsearch {
+            url: url.trim_end_matches('/').to_string(),
+            index: index.to_string(),
+            auth,
+            max_retries,
+        }
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = ureq::request(method, &format!("{}/{}", self.url, path))
+            .set("Content-Type", "application/json");
+        match &self.auth {
+            Some(auth) => request.set("Authorization", &auth.header()),
+            None => request,
+        }
+    }
+
+    /// Creates the index with the credential mapping, an existing index is kept as is
+    pub fn create_index(&self) -> Result<(), Box<dyn Error>> {
+        match self.request("PUT", &self.index).send_string(MAPPING) {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(400, response)) => {
+                let body = response.into_string()?;
+                if body.contains("resource_already_exists_exception") {
+                    Ok(())
+                } else {
+                    Err(body.into())
+                }
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Indexes `docs`, rejected requests and documents are retried with
+    /// exponential backoff while the cluster pushes back.
+    /// Returns the number of documents that failed for good
+    pub fn bulk(&self, docs: &[Credential]) -> Result<usize, Box<dyn Error>> {
+        let mut pending: Vec<&Credential> = docs.iter().collect();
+        let mut failed = 0;
+        let path = format!("{}/_bulk", self.index);
+
+        for attempt in 0..=self.max_retries {
+            if attempt > 0 {
+                let backoff = Duration::from_millis(500 * 2u64.pow(attempt - 1)).min(MAX_BACKOFF);
+                warn!(
+                    "Retrying {} documents in {:?}, attempt {}",
+                    pending.len(),
+                    backoff,
+                    attempt
+                );
+                thread::sleep(backoff);
+            }
+
+            let body = bulk_body(&pending)?;
+            let response = match self.request("POST", &path).send_string(&body) {
+                Ok(response) => response.into_string()?,
+                // Whole request rejected because of load, try again
+                Err(ureq::Error::Status(code, _)) if code == 429 || code >= 500 => continue,
+                Err(ureq::Error::Transport(e)) => {
+                    warn!("Bulk request failed: {}", e);
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            let (retry, rejected) = parse_bulk_response(&response)?;
+            failed += rejected;
+            pending = retry.into_iter().map(|i| pending[i]).collect();
+
+            if pending.is_empty() {
+                return Ok(failed);
+            }
+        }
+
+        Err(format!(
+            "{} documents were still rejected after {} retries",
+            pending.len(),
+            self.max_retries
+        )
+        .into())
+    }
+}
+
+/// Builds a newline delimited bulk request indexing every document
+pub fn bulk_body(docs: &[&Credential]) -> Result<String, serde_json::Error> {
+    let mut body = String::new();
+    for doc in docs {
+        body.push_str("{\"index\":{}}\n");
+        body.push_str(&serde_json::to_string(doc)?);
+        body.push('\n');
+    }
+    Ok(body)
+}
+
+/// Returns positions of the documents rejected with 429, which are worth
+/// retrying, and the number of documents that failed otherwise
+pub fn parse_bulk_response(body: &str) -> Result<(Vec<usize>, usize), serde_json::Error> {
+    let response: Value = serde_json::from_str(body)?;
+    let mut retry = Vec::new();
+    let mut failed = 0;
+
+    if response["errors"] != Value::Bool(true) {
+        return Ok((retry, failed));
+    }
+
+    let items = response["items"].as_array().cloned().unwrap_or_default();
+    for (i, item) in items.iter().enumerate() {
+        let status = item["index"]["status"].as_u64().unwrap_or(0);
+        match status {
+            200..=299 => {}
+            429 => retry.push(i),
+            _ => {
+                error!("Document rejected: {}", item["index"]["error"]);
+                failed += 1;
+            }
+        }
+    }
+
+    Ok((retry, failed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bulk_body_lines() {
+        let doc = Credential {
+            domain: "corp.com",
+            subdomain: "",
+            username: "john",
+            password: "pass",
+            email: "john@corp.com".to_string(),
+        };
+
+        let body = bulk_body(&[&doc, &doc]).unwrap();
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "{\"index\":{}}");
+        assert!(lines[1].contains("\"email\":\"john@corp.com\""));
+        assert!(body.ends_with('\n'));
+    }
+
+    #[test]
+    fn bulk_response_retries() {
+        let body = r#"{"errors":true,"items":[
+            {"index":{"status":201}},
+            {"index":{"status":429,"error":{"type":"es_rejected_execution_exception"}}},
+            {"index":{"status":400,"error":{"type":"mapper_parsing_exception"}}}
+        ]}"#;
+
+        assert_eq!(parse_bulk_response(body).unwrap(), (vec![1], 1));
+        assert_eq!(
+            parse_bulk_response(r#"{"errors":false,"items":[]}"#).unwrap(),
+            (vec![], 0)
+        );
+    }
+}
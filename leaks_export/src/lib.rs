@@ -0,0 +1,126 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use clap::Parser;
+use lib::{progress::file_progress_bar, LeakData};
+
+mod elasticsearch;
+use crate::elasticsearch::{Auth, Credential, Elasticsearch};
+
+// Command line of leaks_export, also `leaks export`
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+pub struct Args {
+    /// ctj output with a LeakData json object per line
+    #[clap(short, long)]
+    input: String,
+
+    /// Elasticsearch or OpenSearch url
+    #[clap(long, default_value = "http://localhost:9200")]
+    url: String,
+
+    /// Index credentials are written to, created with a keyword mapping if missing
+    #[clap(long, default_value = "leaks")]
+    index: String,
+
+    /// Credentials sent per bulk request
+    #[clap(long, default_value_t = 5000)]
+    bulk_size: usize,
+
+    /// Retries of a bulk request rejected because of load
+    #[clap(long, default_value_t = 8)]
+    max_retries: u32,
+
+    /// Basic auth user
+    #[clap(long, requires = "password")]
+    user: Option<String>,
+
+    /// Basic auth password
+    #[clap(long, requires = "user")]
+    password: Option<String>,
+
+    /// Base64 encoded api key, used instead of basic auth
+    #[clap(long, conflicts_with = "user")]
+    api_key: Option<String>,
+}
+
+fn flush(
+    client: &Elasticsearch,
+    leaks: &mut Vec<LeakData>,
+    bulk_size: usize,
+) -> Result<usize, Box<dyn Error>> {
+    let docs: Vec<Credential> = leaks
+        .iter()
+        .flat_map(|leak_data| {
+            leak_data.credentials.iter().flat_map(move |x| {
+                x.data.iter().map(move |(username, password)| {
+                    let host = if x.subdomain.is_empty() {
+                        leak_data.domain.clone()
+                    } else {
+                        format!("{}.{}", x.subdomain, leak_data.domain)
+                    };
+                    Credential {
+                        domain: &leak_data.domain,
+                        subdomain: &x.subdomain,
+                        username,
+                        password,
+                        email: format!("{}@{}", username, host),
+                    }
+                })
+            })
+        })
+        .collect();
+
+    // A single oversized domain can exceed the bulk size by far
+    let mut failed = 0;
+    for chunk in docs.chunks(bulk_size.max(1)) {
+        failed += client.bulk(chunk)?;
+    }
+    leaks.clear();
+    Ok(failed)
+}
+
+/// Exports the input, the same for the leaks_export binary and `leaks export`
+pub fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let auth = match (args.api_key, args.user, args.password) {
+        (Some(key), _, _) => Some(Auth::ApiKey(key)),
+        (None, Some(user), Some(password)) => Some(Auth::Basic { user, password }),
+        _ => None,
+    };
+    let client = Elasticsearch::new(&args.url, &args.index, auth, args.max_retries);
+    client.create_index()?;
+
+    let file = File::open(&args.input)?;
+    let pb = file_progress_bar(file.metadata()?.len());
+    let reader = BufReader::new(pb.wrap_read(file));
+
+    let mut leaks: Vec<LeakData> = Vec::new();
+    let mut buffered = 0;
+    let mut failed = 0;
+
+    for line in reader.lines() {
+        let leak_data: LeakData = serde_json::from_str(&line?)?;
+        buffered += leak_data
+            .credentials
+            .iter()
+            .map(|x| x.data.len())
+            .sum::<usize>();
+        leaks.push(leak_data);
+
+        if buffered >= args.bulk_size {
+            failed += flush(&client, &mut leaks, args.bulk_size)?;
+            buffered = 0;
+        }
+    }
+    failed += flush(&client, &mut leaks, args.bulk_size)?;
+    pb.finish();
+
+    if failed > 0 {
+        log::error!("{} credentials were rejected", failed);
+    }
+
+    Ok(())
+}
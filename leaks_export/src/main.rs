@@ -0,0 +1,13 @@
+use std::process::ExitCode;
+
+use clap::Parser;
+use leaks_export::{run, Args};
+use lib::{
+    errors,
+    telemetry::{self, LogFormat},
+};
+
+fn main() -> ExitCode {
+    telemetry::init(LogFormat::Text);
+    errors::exit_code(run(Args::parse()))
+}
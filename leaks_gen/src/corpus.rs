@@ -0,0 +1,448 @@
+//! Synthetic combo list entries: usernames built from common names,
+//! domains skewed towards the big mail providers and passwords following
+//! the patterns people actually pick
+
+use encoding_rs::Encoding;
+use lib::entry::EntryFormat;
+use rand::{distributions::WeightedIndex, prelude::*, rngs::StdRng};
+
+/// Mail providers and their share of the accounts of a typical combo list
+static PROVIDERS: &[(&str, u32)] = &[
+    ("gmail.com", 30),
+    ("yahoo.com", 10),
+    ("hotmail.com", 10),
+    ("mail.ru", 8),
+    ("yandex.ru", 6),
+    ("outlook.com", 6),
+    ("icloud.com", 3),
+    ("aol.com", 3),
+    ("gmx.de", 3),
+    ("web.de", 2),
+    ("hotmail.co.uk", 2),
+    ("orange.fr", 2),
+];
+
+/// Share of accounts at the corporate domains, out of the 100 of all the providers
+const CORPORATE_WEIGHT: u32 = 15;
+
+/// Distinct corporate domains, few enough for them to repeat
+const CORPORATE_DOMAINS: usize = 200;
+
+static FIRST_NAMES: &[&str] = &[
+    "james",
+    "mary",
+    "john",
+    "patricia",
+    "robert",
+    "jennifer",
+    "michael",
+    "linda",
+    "david",
+    "elizabeth",
+    "william",
+    "susan",
+    "richard",
+    "jessica",
+    "joseph",
+    "sarah",
+    "thomas",
+    "karen",
+    "ivan",
+    "olga",
+    "dmitry",
+    "anna",
+    "sergey",
+    "elena",
+    "pierre",
+    "marie",
+    "hans",
+    "julia",
+    "carlos",
+    "sofia",
+    "ahmed",
+    "fatima",
+    "wei",
+    "mei",
+    "kenji",
+    "yuki",
+];
+
+static LAST_NAMES: &[&str] = &[
+    "smith",
+    "johnson",
+    "williams",
+    "brown",
+    "jones",
+    "garcia",
+    "miller",
+    "davis",
+    "rodriguez",
+    "martinez",
+    "wilson",
+    "anderson",
+    "taylor",
+    "moore",
+    "ivanov",
+    "petrova",
+    "smirnov",
+    "kuznetsova",
+    "martin",
+    "bernard",
+    "muller",
+    "schmidt",
+    "rossi",
+    "silva",
+    "chen",
+    "wang",
+    "tanaka",
+    "kim",
+    "nguyen",
+    "khan",
+];
+
+static WORDS: &[&str] = &[
+    "dragon", "monkey", "shadow", "master", "sunshine", "princess", "football", "baseball",
+    "soccer", "hunter", "ranger", "summer", "winter", "spring", "autumn", "tiger", "killer",
+    "silver", "golden", "purple", "orange", "cookie", "pepper", "ginger", "charlie", "buster",
+    "matrix", "secret", "freedom", "diamond", "thunder", "phoenix", "cheese", "banana", "flower",
+];
+
+static COMMON_PASSWORDS: &[&str] = &[
+    "123456",
+    "123456789",
+    "12345678",
+    "password",
+    "qwerty",
+    "qwerty123",
+    "111111",
+    "1q2w3e4r",
+    "abc123",
+    "iloveyou",
+    "000000",
+    "password1",
+    "admin",
+    "welcome",
+    "letmein",
+    "zxcvbnm",
+    "1qaz2wsx",
+    "qwertyuiop",
+];
+
+/// Passwords in other scripts and with accents, kept when the output
+/// encoding can represent them
+static NON_ASCII_PASSWORDS: &[&str] = &[
+    "пароль",
+    "пароль123",
+    "солнышко",
+    "любовь1",
+    "contraseña",
+    "passwört",
+    "müller1985",
+    "été2020",
+    "パスワード",
+];
+
+static CORPORATE_SUFFIXES: &[&str] = &["com", "net", "org", "co.uk", "de", "ru", "fr", "io"];
+
+static SITES: &[&str] = &[
+    "https://accounts.google.com/signin",
+    "https://www.facebook.com/login.php",
+    "https://login.live.com/login.srf",
+    "https://www.netflix.com/login",
+    "https://steamcommunity.com/login/home",
+    "https://discord.com/login",
+    "https://www.instagram.com/accounts/login",
+    "https://passport.yandex.ru/auth",
+];
+
+static ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+static HEX: &[u8] = b"0123456789abcdef";
+
+/// A generated line and whether the indexer is expected to parse it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub text: String,
+    pub valid: bool,
+}
+
+/// Tunables of the generated entries
+#[derive(Clone, Debug)]
+pub struct CorpusOptions {
+    /// Formats picked at random for every line
+    pub formats: Vec<EntryFormat>,
+    /// Share of broken lines, between 0 and 1
+    pub error_rate: f64,
+    /// Encoding the lines are written in, passwords it can't represent aren't used
+    pub encoding: &'static Encoding,
+    pub seed: u64,
+}
+
+/// Endless source of lines, the same seed always gives the same lines
+pub struct Corpus {
+    rng: StdRng,
+    formats: Vec<EntryFormat>,
+    error_rate: f64,
+    domains: Vec<String>,
+    domain_weights: WeightedIndex<u32>,
+    non_ascii: Vec<&'static str>,
+}
+
+impl Corpus {
+    pub fn new(options: &CorpusOptions) -> Corpus {
+        let mut rng = StdRng::seed_from_u64(options.seed);
+
+        let mut domains: Vec<String> = PROVIDERS.iter().map(|(x, _)| x.to_string()).collect();
+        // Scaled so that every corporate domain gets an equal part of their share
+        let mut weights: Vec<u32> = PROVIDERS
+            .iter()
+            .map(|(_, x)| x * CORPORATE_DOMAINS as u32)
+            .collect();
+        for _ in 0..CORPORATE_DOMAINS {
+            let suffix = CORPORATE_SUFFIXES.choose(&mut rng).unwrap();
+            let name = format!(
+                "{}{}",
+                WORDS.choose(&mut rng).unwrap(),
+                LAST_NAMES.choose(&mut rng).unwrap()
+            );
+            let domain = match rng.gen_ratio(1, 4) {
+                true => format!("mail.{}.{}", name, suffix),
+                false => format!("{}.{}", name, suffix),
+            };
+            domains.push(domain);
+            weights.push(CORPORATE_WEIGHT);
+        }
+
+        let non_ascii = NON_ASCII_PASSWORDS
+            .iter()
+            .copied()
+            .filter(|x| !options.encoding.output_encoding().encode(x).2)
+            .collect();
+
+        Corpus {
+            rng,
+            formats: options.formats.clone(),
+            error_rate: options.error_rate,
+            domain_weights: WeightedIndex::new(&weights).expect("positive weights"),
+            domains,
+            non_ascii,
+        }
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items.choose(&mut self.rng).unwrap()
+    }
+
+    fn random_string(&mut self, chars: &[u8], len: usize) -> String {
+        (0..len)
+            .map(|_| *chars.choose(&mut self.rng).unwrap() as char)
+            .collect()
+    }
+
+    fn username(&mut self) -> String {
+        let first = self.pick(FIRST_NAMES);
+        let last = self.pick(LAST_NAMES);
+        match self.rng.gen_range(0..6) {
+            0 => format!("{}.{}", first, last),
+            1 => format!("{}{}{}", first, last, self.rng.gen_range(1..100)),
+            2 => format!("{}.{}", &first[..1], last),
+            3 => format!("{}_{}{}", first, last, self.rng.gen_range(1960..2010)),
+            4 => format!("{}{}", self.pick(WORDS), self.rng.gen_range(1..1000)),
+            _ => format!("{}{}", first, self.rng.gen_range(1..10000)),
+        }
+    }
+
+    fn domain(&mut self) -> String {
+        let i = self.domain_weights.sample(&mut self.rng);
+        self.domains[i].clone()
+    }
+
+    fn email(&mut self) -> String {
+        let username = self.username();
+        format!("{}@{}", username, self.domain())
+    }
+
+    fn password(&mut self) -> String {
+        match self.rng.gen_range(0..100) {
+            0..=19 => self.pick(COMMON_PASSWORDS).to_string(),
+            20..=54 => {
+                let word = self.pick(WORDS);
+                let word = match self.rng.gen_bool(0.3) {
+                    true => word[..1].to_uppercase() + &word[1..],
+                    false => word.to_string(),
+                };
+                format!("{}{}", word, self.rng.gen_range(0..100))
+            }
+            55..=74 => format!(
+                "{}{}",
+                self.pick(FIRST_NAMES),
+                self.rng.gen_range(1960..2010)
+            ),
+            75..=94 => {
+                let len = self.rng.gen_range(8..17);
+                self.random_string(ALPHANUMERIC, len)
+            }
+            _ if !self.non_ascii.is_empty() => {
+                let i = self.rng.gen_range(0..self.non_ascii.len());
+                self.non_ascii[i].to_string()
+            }
+            _ => self.pick(COMMON_PASSWORDS).to_string(),
+        }
+    }
+
+    fn hash(&mut self) -> String {
+        let len = *[32, 40, 64].choose(&mut self.rng).unwrap();
+        self.random_string(HEX, len)
+    }
+
+    fn entry(&mut self, format: EntryFormat) -> String {
+        match format {
+            EntryFormat::EmailPass => {
+                let email = self.email();
+                let password = self.password();
+                match self.rng.gen_ratio(1, 10) {
+                    true => format!("{};{}", email, password),
+                    false => format!("{}:{}", email, password),
+                }
+            }
+            EntryFormat::EmailHash => {
+                let email = self.email();
+                format!("{}:{}", email, self.hash())
+            }
+            EntryFormat::EmailHashSalt => {
+                let email = self.email();
+                let hash = self.hash();
+                format!("{}:{}:{}", email, hash, self.random_string(ALPHANUMERIC, 8))
+            }
+            EntryFormat::UserEmailPass => {
+                let nickname = self.username();
+                let email = self.email();
+                format!("{}:{}:{}", nickname, email, self.password())
+            }
+            EntryFormat::UrlLoginPass => {
+                let site = self.pick(SITES);
+                let login = match self.rng.gen_bool(0.6) {
+                    true => self.email(),
+                    false => self.username(),
+                };
+                format!("{}:{}:{}", site, login, self.password())
+            }
+        }
+    }
+
+    /// A line no parser accepts
+    fn broken(&mut self) -> String {
+        match self.rng.gen_range(0..4) {
+            0 => format!("{}{}", self.username(), self.pick(WORDS)),
+            1 => {
+                let email = self.email();
+                format!("{}:", email)
+            }
+            2 => format!(
+                "{}@{}:{}",
+                self.username(),
+                self.pick(WORDS),
+                self.password()
+            ),
+            _ => {
+                let len = self.rng.gen_range(4..20);
+                self.random_string(b"!#$%^&*()[]{}<>=~ ", len)
+            }
+        }
+    }
+
+    pub fn line(&mut self) -> Line {
+        if self.rng.gen_bool(self.error_rate) {
+            return Line {
+                text: self.broken(),
+                valid: false,
+            };
+        }
+        let format = *self.formats.choose(&mut self.rng).unwrap();
+        Line {
+            text: self.entry(format),
+            valid: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib::{
+        entry::{parse_line, ParseOptions},
+        PublicSuffixList,
+    };
+
+    fn options(format: EntryFormat, error_rate: f64) -> CorpusOptions {
+        CorpusOptions {
+            formats: vec![format],
+            error_rate,
+            encoding: encoding_rs::UTF_8,
+            seed: 7,
+        }
+    }
+
+    #[test]
+    fn lines_parse_as_flagged() {
+        let st = PublicSuffixList::new("com net org de ru fr io uk co.uk");
+        let formats = [
+            EntryFormat::EmailPass,
+            EntryFormat::EmailHash,
+            EntryFormat::EmailHashSalt,
+            EntryFormat::UserEmailPass,
+            EntryFormat::UrlLoginPass,
+        ];
+        for format in formats {
+            let parse = ParseOptions {
+                format,
+                unicode_usernames: true,
+                ..Default::default()
+            };
+            for error_rate in [0.0, 1.0] {
+                let mut corpus = Corpus::new(&options(format, error_rate));
+                for _ in 0..2000 {
+                    let line = corpus.line();
+                    assert_eq!(
+                        parse_line(&line.text, &st, &parse).is_ok(),
+                        line.valid,
+                        "{:?} {}",
+                        format,
+                        line.text
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_lines() {
+        let lines = |seed| {
+            let mut corpus = Corpus::new(&CorpusOptions {
+                seed,
+                ..options(EntryFormat::EmailPass, 0.1)
+            });
+            (0..100).map(|_| corpus.line()).collect::<Vec<_>>()
+        };
+        assert_eq!(lines(1), lines(1));
+        assert_ne!(lines(1), lines(2));
+    }
+
+    #[test]
+    fn unencodable_passwords_skipped() {
+        let mut corpus = Corpus::new(&CorpusOptions {
+            encoding: encoding_rs::WINDOWS_1252,
+            ..options(EntryFormat::EmailPass, 0.0)
+        });
+        assert!(corpus.non_ascii.contains(&"contraseña"));
+        assert!(!corpus.non_ascii.contains(&"пароль"));
+        for _ in 0..2000 {
+            let line = corpus.line();
+            assert!(
+                !encoding_rs::WINDOWS_1252.encode(&line.text).2,
+                "{}",
+                line.text
+            );
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use encoding_rs::Encoding;
+use lib::{
+    entry::EntryFormat,
+    errors::{self, Context},
+    telemetry::{self, LogFormat},
+};
+
+mod corpus;
+mod output;
+use crate::{
+    corpus::{Corpus, CorpusOptions},
+    output::{write_archive, write_lines, Archive, LineEncoder, Summary},
+};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Output file, stdout by default. Required with --archive
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// Lines to generate, split evenly over the members of an archive
+    #[clap(short = 'n', long, default_value = "100000")]
+    lines: u64,
+
+    /// Entry formats picked at random for every line, comma separated:
+    /// email:pass, email:hash, email:hash:salt, user:email:pass or url:login:pass
+    #[clap(long, value_delimiter = ',', default_value = "email:pass")]
+    format: Vec<EntryFormat>,
+
+    /// Share of broken lines the indexer rejects, between 0 and 1
+    #[clap(long, default_value = "0.02", value_parser = parse_rate)]
+    error_rate: f64,
+
+    /// Encoding of the output, a label like utf-8, windows-1251 or utf-16le.
+    /// Passwords it can't represent aren't generated
+    #[clap(long, default_value = "utf-8", value_parser = parse_encoding)]
+    encoding: &'static Encoding,
+
+    /// Start every file with a byte order mark, for utf-8 and utf-16 only
+    #[clap(long)]
+    bom: bool,
+
+    /// End lines with \r\n like combo lists put together on windows
+    #[clap(long)]
+    crlf: bool,
+
+    /// Wrap the lines into an archive: none, zip, tar or tar.gz
+    #[clap(long, default_value = "none")]
+    archive: Archive,
+
+    /// Files of the archive the lines are split over
+    #[clap(long, default_value = "1")]
+    members: usize,
+
+    /// Seed of the generator, the same seed and options give the same output
+    #[clap(long, default_value = "0")]
+    seed: u64,
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(rate),
+        _ => Err(format!(
+            "invalid rate {}, expected a number between 0 and 1",
+            s
+        )),
+    }
+}
+
+fn parse_encoding(s: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(s.as_bytes()).ok_or_else(|| {
+        format!(
+            "unknown encoding {}, expected a label like utf-8, windows-1251 or utf-16le",
+            s
+        )
+    })
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let mut corpus = Corpus::new(&CorpusOptions {
+        formats: args.format.clone(),
+        error_rate: args.error_rate,
+        encoding: args.encoding,
+        seed: args.seed,
+    });
+    let encoder = LineEncoder {
+        encoding: args.encoding,
+        bom: args.bom,
+        crlf: args.crlf,
+    };
+
+    let summary = match &args.output {
+        Some(path) => {
+            let file =
+                File::create(path).with_context(|| format!("can't create {}", path.display()))?;
+            write_archive(
+                file,
+                args.archive,
+                args.members,
+                args.lines,
+                &mut corpus,
+                &encoder,
+            )
+            .with_context(|| format!("can't write {}", path.display()))?
+        }
+        None => {
+            let mut summary = Summary::default();
+            let mut writer = BufWriter::new(io::stdout().lock());
+            write_lines(&mut writer, &mut corpus, &encoder, args.lines, &mut summary)?;
+            writer.flush()?;
+            summary
+        }
+    };
+    eprintln!("valid   {}\nbroken  {}", summary.valid, summary.broken);
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    if args.output.is_none() && args.archive != Archive::None {
+        Args::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "--archive needs --output",
+            )
+            .exit();
+    }
+    if args.members > 1 && args.archive == Archive::None {
+        Args::command()
+            .error(ErrorKind::ArgumentConflict, "--members needs --archive")
+            .exit();
+    }
+
+    telemetry::init(LogFormat::Text);
+    errors::exit_code(run(args))
+}
@@ -0,0 +1,299 @@
+//! Encoding of the generated lines and the archives wrapping them
+
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+    str::FromStr,
+};
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+use flate2::{write::GzEncoder, Compression};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::corpus::Corpus;
+
+/// Container the generated members are written to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Archive {
+    /// A single plain text file
+    #[default]
+    None,
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl FromStr for Archive {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Archive::None),
+            "zip" => Ok(Archive::Zip),
+            "tar" => Ok(Archive::Tar),
+            "tar.gz" => Ok(Archive::TarGz),
+            _ => Err(format!(
+                "unknown archive {}, expected one of: none, zip, tar, tar.gz",
+                s
+            )),
+        }
+    }
+}
+
+/// Writes lines in an encoding, with an optional byte order mark
+/// and windows line endings
+#[derive(Clone, Copy, Debug)]
+pub struct LineEncoder {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+    pub crlf: bool,
+}
+
+impl LineEncoder {
+    fn bom(&self) -> &'static [u8] {
+        if !self.bom {
+            return &[];
+        }
+        match self.encoding.name() {
+            "UTF-8" => b"\xEF\xBB\xBF",
+            "UTF-16LE" => b"\xFF\xFE",
+            "UTF-16BE" => b"\xFE\xFF",
+            _ => &[],
+        }
+    }
+
+    /// encoding_rs only decodes UTF-16, its code units are written by hand
+    fn encode<'a>(&self, text: &'a str) -> Cow<'a, [u8]> {
+        if self.encoding == UTF_16LE {
+            Cow::Owned(text.encode_utf16().flat_map(u16::to_le_bytes).collect())
+        } else if self.encoding == UTF_16BE {
+            Cow::Owned(text.encode_utf16().flat_map(u16::to_be_bytes).collect())
+        } else {
+            self.encoding.encode(text).0
+        }
+    }
+
+    fn write_line(&self, writer: &mut impl Write, line: &str) -> io::Result<()> {
+        writer.write_all(&self.encode(line))?;
+        let end = if self.crlf { "\r\n" } else { "\n" };
+        writer.write_all(&self.encode(end))
+    }
+}
+
+/// Lines written, by whether the indexer is expected to parse them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub valid: u64,
+    pub broken: u64,
+}
+
+/// Writes the lines of one file or archive member
+pub fn write_lines(
+    writer: &mut impl Write,
+    corpus: &mut Corpus,
+    encoder: &LineEncoder,
+    lines: u64,
+    summary: &mut Summary,
+) -> io::Result<()> {
+    writer.write_all(encoder.bom())?;
+    for _ in 0..lines {
+        let line = corpus.line();
+        match line.valid {
+            true => summary.valid += 1,
+            false => summary.broken += 1,
+        }
+        encoder.write_line(writer, &line.text)?;
+    }
+    Ok(())
+}
+
+/// Name of the i-th member of an archive
+fn member_name(i: usize) -> String {
+    format!("combo_{:03}.txt", i + 1)
+}
+
+/// Lines of every member, the first ones get the remainder
+fn member_lines(lines: u64, members: usize) -> impl Iterator<Item = u64> {
+    let members = members.max(1) as u64;
+    (0..members).map(move |i| lines / members + u64::from(i < lines % members))
+}
+
+/// Writes `lines` lines split over `members` files of an archive
+pub fn write_archive(
+    file: File,
+    archive: Archive,
+    members: usize,
+    lines: u64,
+    corpus: &mut Corpus,
+    encoder: &LineEncoder,
+) -> io::Result<Summary> {
+    let mut summary = Summary::default();
+    match archive {
+        Archive::None => {
+            let mut writer = io::BufWriter::new(file);
+            write_lines(&mut writer, corpus, encoder, lines, &mut summary)?;
+            writer.flush()?;
+        }
+        Archive::Zip => {
+            let mut zip = ZipWriter::new(file);
+            let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+            for (i, lines) in member_lines(lines, members).enumerate() {
+                zip.start_file(member_name(i), options)?;
+                write_lines(&mut zip, corpus, encoder, lines, &mut summary)?;
+            }
+            zip.finish()?;
+        }
+        Archive::Tar => {
+            let mut builder = tar::Builder::new(file);
+            write_tar(&mut builder, members, lines, corpus, encoder, &mut summary)?;
+            builder.into_inner()?;
+        }
+        Archive::TarGz => {
+            let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+            write_tar(&mut builder, members, lines, corpus, encoder, &mut summary)?;
+            builder.into_inner()?.finish()?;
+        }
+    }
+    Ok(summary)
+}
+
+/// Tar headers need the size of a member up front, so every member
+/// is generated into a temporary file first
+fn write_tar(
+    builder: &mut tar::Builder<impl Write>,
+    members: usize,
+    lines: u64,
+    corpus: &mut Corpus,
+    encoder: &LineEncoder,
+    summary: &mut Summary,
+) -> io::Result<()> {
+    for (i, lines) in member_lines(lines, members).enumerate() {
+        let mut member = tempfile::tempfile()?;
+        {
+            let mut writer = io::BufWriter::new(&mut member);
+            write_lines(&mut writer, corpus, encoder, lines, summary)?;
+            writer.flush()?;
+        }
+        let size = member.stream_position()?;
+        member.seek(SeekFrom::Start(0))?;
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, member_name(i), &mut member)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use lib::entry::EntryFormat;
+
+    use super::*;
+    use crate::corpus::CorpusOptions;
+
+    fn corpus() -> Corpus {
+        Corpus::new(&CorpusOptions {
+            formats: vec![EntryFormat::EmailPass],
+            error_rate: 0.1,
+            encoding: encoding_rs::UTF_8,
+            seed: 3,
+        })
+    }
+
+    #[test]
+    fn splits_members() {
+        assert_eq!(member_lines(10, 3).collect::<Vec<_>>(), [4, 3, 3]);
+        assert_eq!(member_lines(2, 3).collect::<Vec<_>>(), [1, 1, 0]);
+        assert_eq!(member_lines(5, 0).collect::<Vec<_>>(), [5]);
+    }
+
+    #[test]
+    fn utf16_lines() {
+        let encoder = LineEncoder {
+            encoding: UTF_16LE,
+            bom: true,
+            crlf: true,
+        };
+        let mut output = Vec::new();
+        encoder.write_line(&mut output, "a@b.ru:пароль").unwrap();
+        let (text, _) = UTF_16LE.decode_without_bom_handling(&output);
+        assert_eq!(text, "a@b.ru:пароль\r\n");
+        assert_eq!(encoder.bom(), b"\xFF\xFE");
+    }
+
+    #[test]
+    fn zip_members() {
+        let file = tempfile::tempfile().unwrap();
+        let encoder = LineEncoder {
+            encoding: encoding_rs::UTF_8,
+            bom: false,
+            crlf: false,
+        };
+        let summary = write_archive(
+            file.try_clone().unwrap(),
+            Archive::Zip,
+            3,
+            100,
+            &mut corpus(),
+            &encoder,
+        )
+        .unwrap();
+        assert_eq!(summary.valid + summary.broken, 100);
+
+        let mut zip = zip::ZipArchive::new(file).unwrap();
+        assert_eq!(zip.len(), 3);
+        let mut lines = 0;
+        for i in 0..zip.len() {
+            let mut member = zip.by_index(i).unwrap();
+            assert_eq!(member.name(), member_name(i));
+            let mut text = String::new();
+            member.read_to_string(&mut text).unwrap();
+            lines += text.lines().count();
+        }
+        assert_eq!(lines, 100);
+    }
+
+    #[test]
+    fn tar_gz_members() {
+        let mut file = tempfile::tempfile().unwrap();
+        let encoder = LineEncoder {
+            encoding: encoding_rs::UTF_8,
+            bom: false,
+            crlf: false,
+        };
+        write_archive(
+            file.try_clone().unwrap(),
+            Archive::TarGz,
+            2,
+            50,
+            &mut corpus(),
+            &encoder,
+        )
+        .unwrap();
+
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+        let members: Vec<(String, usize)> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut text = String::new();
+                entry.read_to_string(&mut text).unwrap();
+                (name, text.lines().count())
+            })
+            .collect();
+        assert_eq!(
+            members,
+            [
+                ("combo_001.txt".to_string(), 25),
+                ("combo_002.txt".to_string(), 25)
+            ]
+        );
+    }
+}
@@ -0,0 +1,14 @@
+use std::process::ExitCode;
+
+use clap::Parser;
+use leaks_import::{run, Args};
+use lib::{
+    errors::{self, BoxError},
+    telemetry::{self, LogFormat},
+};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    telemetry::init(LogFormat::Text);
+    errors::exit_code(run(Args::parse()).await.map_err(|e| e as BoxError))
+}
@@ -0,0 +1,294 @@
+//! Batch mode: the inputs listed in a file are indexed into a single
+//! output, one after another or by several jobs at once. Records are
+//! tagged with the file name of their input in the source column
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use lib::{
+    error::{Error as IndexError, Result as IndexResult},
+    indexer::{Indexer, IndexerOptions, SharedOutput},
+    report::Stats,
+    PublicSuffixList,
+};
+use serde::Serialize;
+use tracing::{info, warn};
+
+/// Reads the paths of an input list, one per line.
+/// Empty lines and lines starting with # are skipped
+pub fn read_input_list(path: &Path) -> io::Result<Vec<String>> {
+    Ok(parse_input_list(&fs::read_to_string(path)?))
+}
+
+fn parse_input_list(list: &str) -> Vec<String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Counters of one input of a batch
+#[derive(Clone, Debug, Serialize)]
+pub struct InputReport {
+    pub input: String,
+    pub lines_read: u64,
+    pub written: u64,
+    pub rejected: u64,
+    /// Why the input was skipped or stopped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Counters of the whole batch, followed by the ones of every input in
+/// the order of the list. Inputs the batch didn't get to aren't listed
+#[derive(Debug, Serialize)]
+pub struct BatchReport {
+    #[serde(flatten)]
+    pub stats: Stats,
+    pub inputs: Vec<InputReport>,
+}
+
+impl BatchReport {
+    pub fn failed(&self) -> usize {
+        self.inputs.iter().filter(|x| x.error.is_some()).count()
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer_pretty(&mut file, self)?;
+        writeln!(file)?;
+        file.flush()
+    }
+}
+
+/// Name of an input without its directories, its source tag
+fn source_name(input: &str) -> String {
+    Path::new(input)
+        .file_name()
+        .map_or_else(|| input.to_string(), |x| x.to_string_lossy().into_owned())
+}
+
+/// Counters of an input and how its run ended
+type InputResult = (Stats, IndexResult<()>);
+
+fn index_input(
+    input: &str,
+    output: &SharedOutput,
+    st: &PublicSuffixList,
+    options: &IndexerOptions,
+    stop: &Arc<AtomicBool>,
+) -> InputResult {
+    let options = IndexerOptions {
+        source: source_name(input),
+        ..options.clone()
+    };
+    let mut indexer = match Indexer::shared(output, st.clone(), options) {
+        Ok(indexer) => indexer,
+        Err(e) => return (Stats::default(), Err(e)),
+    };
+    indexer.stop_on(stop.clone());
+    let result = indexer.process(input);
+    match indexer.finish() {
+        Ok(stats) => (stats, result),
+        Err(e) => (Stats::default(), Err(e)),
+    }
+}
+
+/// Indexes `inputs` with `jobs` threads, each taking the next input of the
+/// list once done with its last one. Inputs that can't be read are skipped
+/// with --skip-errors, any other failure stops every job. The report covers
+/// the inputs indexed so far, along with the error that stopped the batch
+pub fn index_batch(
+    inputs: &[String],
+    output: &SharedOutput,
+    st: &PublicSuffixList,
+    options: &IndexerOptions,
+    jobs: usize,
+    stop: &Arc<AtomicBool>,
+) -> (BatchReport, IndexResult<()>) {
+    let mut stats = Stats::default();
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<InputResult>>> =
+        Mutex::new(inputs.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, inputs.len().max(1)) {
+            scope.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(input) = inputs.get(i) else {
+                        break;
+                    };
+                    info!(input = %input, "Indexing listed input");
+                    let (input_stats, result) = index_input(input, output, st, options, stop);
+                    match &result {
+                        Err(e) if options.skip_errors && e.is_input() => {
+                            warn!(input = %input, error = %e, "Skipping input");
+                        }
+                        Err(IndexError::Interrupted) | Ok(()) => {}
+                        // Stops the other jobs too
+                        Err(_) => stop.store(true, Ordering::SeqCst),
+                    }
+                    results.lock().unwrap()[i] = Some((input_stats, result));
+                }
+            });
+        }
+    });
+
+    let mut reports = Vec::new();
+    let mut failure = None;
+    let mut interrupted = false;
+    for (input, result) in inputs.iter().zip(results.into_inner().unwrap()) {
+        let Some((input_stats, result)) = result else {
+            continue;
+        };
+        stats.merge(&input_stats);
+        let error = result.err().map(|e| {
+            let message = e.to_string();
+            match e {
+                e if options.skip_errors && e.is_input() => {}
+                IndexError::Interrupted => interrupted = true,
+                e => {
+                    failure.get_or_insert(e);
+                }
+            }
+            message
+        });
+        reports.push(InputReport {
+            input: input.clone(),
+            lines_read: input_stats.lines_read,
+            written: input_stats.written,
+            rejected: input_stats.rejected_total(),
+            error,
+        });
+    }
+    stats.finish();
+
+    // A failure wins over the interruptions it caused in the other jobs
+    let result = match (failure, interrupted) {
+        (Some(e), _) => Err(e),
+        (None, true) => Err(IndexError::Interrupted),
+        (None, false) => Ok(()),
+    };
+    (
+        BatchReport {
+            stats,
+            inputs: reports,
+        },
+        result,
+    )
+}
+
+/// Inputs of the list the report has no finished run of, the ones to
+/// index again after an interruption
+pub fn remaining_inputs(inputs: &[String], report: &BatchReport) -> Vec<String> {
+    let done: Vec<&str> = report
+        .inputs
+        .iter()
+        .filter(|x| x.error.is_none())
+        .map(|x| x.input.as_str())
+        .collect();
+    inputs
+        .iter()
+        .filter(|input| !done.contains(&input.as_str()))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::{indexer::ErrorFormat, output::CsvOptions};
+
+    use super::*;
+
+    fn options() -> IndexerOptions {
+        IndexerOptions {
+            csv: CsvOptions {
+                header: false,
+                ..Default::default()
+            },
+            error_format: ErrorFormat::Csv,
+            threads: 1,
+            dedup: true,
+            skip_errors: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn input_lists() {
+        assert_eq!(
+            parse_input_list("# dumps\n/dumps/a.zip\n\n  /dumps/b.txt  \n"),
+            ["/dumps/a.zip", "/dumps/b.txt"]
+        );
+    }
+
+    #[test]
+    fn batch_into_one_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut inputs = Vec::new();
+        for i in 0..6 {
+            let path = dir.path().join(format!("dump{}.txt", i));
+            let lines = format!(
+                "user{}@corp.com:pass\nshared@corp.com:pass\nbroken line\n",
+                i
+            );
+            fs::write(&path, lines).unwrap();
+            inputs.push(path.to_string_lossy().into_owned());
+        }
+        inputs.insert(2, dir.path().join("missing.txt").to_string_lossy().into());
+
+        let output_path = dir.path().join("out.csv");
+        let error_path = dir.path().join("out.err");
+        let output = SharedOutput::new(&output_path, &error_path, &options()).unwrap();
+        let st = PublicSuffixList::new("com");
+        let stop = Arc::new(AtomicBool::new(false));
+        let (report, result) = index_batch(&inputs, &output, &st, &options(), 3, &stop);
+        output.finish().unwrap();
+        result.unwrap();
+
+        let written = fs::read_to_string(&output_path).unwrap();
+        let mut lines: Vec<&str> = written.lines().collect();
+        lines.sort();
+        assert_eq!(lines.len(), 7);
+        // Whichever job got to it first wrote the shared credentials
+        assert!(lines[0].starts_with("corp.com,,shared,pass,plain,,dump"));
+        assert!(lines[1..]
+            .iter()
+            .all(|x| x.starts_with("corp.com,,user") && x.contains(",dump")));
+        assert!(lines
+            .iter()
+            .any(|x| x.ends_with(",pass,plain,,dump5.txt,,dump5.txt,domain")));
+
+        let errors = fs::read_to_string(&error_path).unwrap();
+        assert_eq!(
+            errors
+                .lines()
+                .filter(|x| x.ends_with("broken line"))
+                .count(),
+            6
+        );
+
+        assert_eq!(report.inputs.len(), 7);
+        assert_eq!(report.failed(), 1);
+        assert!(report.inputs[2]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("missing.txt"));
+        assert_eq!(report.stats.lines_read, 18);
+        assert_eq!(report.stats.written, 7);
+        assert_eq!(report.stats.duplicates, 5);
+        assert_eq!(report.stats.unique_domains, 1);
+        assert_eq!(remaining_inputs(&inputs, &report), [inputs[2].clone()]);
+    }
+}
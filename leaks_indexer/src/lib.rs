@@ -0,0 +1,817 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    thread,
+};
+
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use lib::{
+    checkpoint::Checkpoint,
+    config::SuiteConfig,
+    disposable::DisposableList,
+    domain_filter::{DomainFilter, DomainList},
+    domain_typos::TypoTable,
+    encoding::InputEncoding,
+    encryption::Encryption,
+    entry::{EntryFormat, ParseOptions, Separators},
+    error::Error as IndexError,
+    errors::Context,
+    indexer::{ErrorFormat, Indexer, IndexerOptions, SharedOutput},
+    kafka::KafkaTarget,
+    manifest::Manifest,
+    output::{parse_delimiter, Column, Compression, CsvOptions, OutputFormat, QuoteStyle},
+    parse_psl,
+    parsers::{Delimited, ParserKind, Parsers, RecordField},
+    redact::Redaction,
+    report::Stats,
+    shard::{ShardKey, Sharding, SplitOutput},
+    DomainForm, PublicSuffixList, SuffixProvider, UsernameRules,
+};
+
+mod batch;
+mod worker;
+use crate::{
+    batch::{index_batch, read_input_list, remaining_inputs},
+    worker::{Upload, Worker},
+};
+
+// Command line of the indexer, also `leaks index`
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+pub struct Args {
+    /// TLD file, or auto to download the public suffix list and cache it.
+    /// Defaults to tld_path of leaks-suite.toml
+    #[clap(short, long)]
+    tld: Option<String>,
+
+    /// Input file or directory with entries like username@subdomain.domain.tld:password
+    #[clap(short, long, required_unless_present_any = ["worker", "input_list"])]
+    input: Option<String>,
+
+    /// File listing inputs to index into the same output, one path per line.
+    /// Empty lines and lines starting with # are skipped. Every record is
+    /// tagged with the file name of its input in the source column, the
+    /// report adds the counters of every input to the ones of the batch.
+    /// An interrupted batch writes the inputs it didn't finish to the list
+    /// path with .remaining appended
+    #[clap(
+        long,
+        conflicts_with_all = ["input", "worker", "source_name", "split_output_by", "domain_counts", "checkpoint", "resume"]
+    )]
+    input_list: Option<PathBuf>,
+
+    /// Inputs of --input-list indexed at once. Without --threads every job
+    /// gets an equal share of the cores
+    #[clap(long, default_value_t = 1, requires = "input_list")]
+    jobs: usize,
+
+    /// Input file type: tar, tar.gz, tar.zst, tar.xz, tar.bz2, zip, dir, stealer, plain or auto.
+    /// Compression of tar inputs is detected by magic bytes, dir walks a
+    /// directory tree recursively, stealer walks a tree of stealer log folders
+    /// and parses the URL/Username/Password blocks of their Passwords.txt files.
+    /// auto tells compressed, tar, zip and plain input apart by magic bytes,
+    /// which also works on stdin. Zip on stdin is streamed member by member
+    #[clap(long, default_value = "plain")]
+    input_type: String,
+
+    /// Also use the private domains section of the public suffix list,
+    /// so that user.github.io is treated as a registrable domain
+    #[clap(long)]
+    include_private_domains: bool,
+
+    /// Output file, relative paths go to output_dir of leaks-suite.toml when set.
+    /// kafka://broker1:9092,broker2:9092/topic publishes the records as json
+    /// to a kafka topic instead, whatever --output-format says, keyed by
+    /// domain. Records are batched, query parameters like ?linger.ms=500
+    /// set librdkafka options. Records the brokers don't acknowledge fail the run.
+    /// Workers name their outputs after the job, only kafka:// is accepted
+    #[clap(short, long, required_unless_present = "worker")]
+    output: Option<String>,
+
+    /// Output file format: csv, jsonl or parquet
+    #[clap(long, default_value = "csv")]
+    output_format: OutputFormat,
+
+    /// Compress csv or jsonl output on the fly: gzip or zstd.
+    /// The matching extension is appended to the output file name.
+    /// Unlike plain output, a compressed one can't be read after a crash
+    #[clap(long)]
+    compress: Option<Compression>,
+
+    /// Encrypt the output at rest: age:<recipient> encrypts to an age public key,
+    /// passphrase to the passphrase in LEAKS_PASSPHRASE. The error file holds
+    /// passwords too and is encrypted as well. .age is appended to both file
+    /// names, leaks_decrypt turns them back into plaintext. Like compressed
+    /// output, encrypted files can't be decrypted after a crash
+    #[clap(long)]
+    encrypt: Option<Encryption>,
+
+    /// Csv output delimiter, use \t or tab for tsv
+    #[clap(long, default_value = ",", value_parser = parse_delimiter)]
+    delimiter: u8,
+
+    /// Csv output quoting: always, necessary, non-numeric or never
+    #[clap(long, default_value = "necessary")]
+    quote_style: QuoteStyle,
+
+    /// Don't write a header row to the csv output
+    #[clap(long)]
+    no_header: bool,
+
+    /// Comma separated csv output columns in the order they are written:
+    /// domain, subdomain, username, password, password_type, target_domain,
+    /// source, breach_date, source_file, target_type (domain or ip) and email,
+    /// the reassembled username@subdomain.domain.
+    /// All record fields but email by default
+    #[clap(long, value_delimiter = ',')]
+    output_columns: Option<Vec<Column>>,
+
+    /// Error file, relative paths go to output_dir of leaks-suite.toml when set
+    #[clap(short, long, required_unless_present = "worker")]
+    error: Option<String>,
+
+    /// Number of parsing threads, 0 means one per available core
+    #[clap(long, default_value_t = 0)]
+    threads: usize,
+
+    /// Error file format: plain echoes rejected lines with //member markers,
+    /// csv writes member,reason,line records
+    #[clap(long, default_value = "plain")]
+    error_format: ErrorFormat,
+
+    /// Drop duplicate (domain, subdomain, username, password) tuples.
+    /// Uses a bloom filter, so a tiny share of unique entries may be dropped too
+    #[clap(long)]
+    dedup: bool,
+
+    /// False positive probability of the dedup filter
+    #[clap(long, default_value_t = 0.0001)]
+    dedup_error_rate: f64,
+
+    /// Layout of the input entries: email:pass, email:hash, email:hash:salt,
+    /// user:email:pass or url:login:pass. url:login:pass fills the
+    /// target_domain column with the url host
+    #[clap(long, default_value = "email:pass")]
+    format: EntryFormat,
+
+    /// Comma separated parsers tried in order on every line, the first one
+    /// to parse a line wins: email:pass, user:pass@domain, url:user:pass,
+    /// delimited and phone:pass, which files phone number logins under the
+    /// phone domain. Defaults to the parsers of --format, the password type of
+    /// email parsers still follows --format
+    #[clap(long, value_delimiter = ',')]
+    parsers: Option<Vec<ParserKind>>,
+
+    /// Field delimiter of the delimited parser, use \t or tab for tsv
+    #[clap(long, default_value = ",", value_parser = parse_delimiter)]
+    record_delimiter: u8,
+
+    /// Comma separated fields of the delimited parser: email, username,
+    /// domain, password, url or skip. The last field takes the rest of the line
+    #[clap(long, value_delimiter = ',', default_value = "email,password")]
+    record_fields: Vec<RecordField>,
+
+    /// Pick the parser of every file or archive member from its first lines
+    /// instead of trying each parser on every line. Candidates are the ones of
+    /// --parsers, or all of them. The picked parsers are listed in the report
+    #[clap(long)]
+    detect_parsers: bool,
+
+    /// Number of leading lines the parser is picked from
+    #[clap(long, default_value_t = 1000)]
+    detect_lines: usize,
+
+    /// Convert internationalized domains to ascii (punycode) or unicode
+    /// before suffix matching, by default domains are kept as is
+    #[clap(long)]
+    idn: Option<DomainForm>,
+
+    /// Log and skip archive members or files that can't be read,
+    /// like a corrupt member, instead of aborting the whole run.
+    /// Skipped members are recorded in the error file
+    #[clap(long)]
+    skip_errors: bool,
+
+    /// Levels of archives nested in archive members that are unpacked,
+    /// like a tar.gz inside a zip or a gzipped file inside a tar. Their members
+    /// are named below the member holding them, like inner.tar.gz/b.txt.
+    /// Deeper ones are skipped and recorded in the error file, 0 skips them all
+    #[clap(long, default_value = "5")]
+    max_depth: usize,
+
+    /// Toml file with username/password validation rules: max lengths,
+    /// allowed patterns and blocklists, see rules.toml.template.
+    /// Defaults to rules of leaks-suite.toml
+    #[clap(long)]
+    rules: Option<PathBuf>,
+
+    /// Strip +tag suffixes and dots from usernames of providers
+    /// that ignore them, like gmail.com
+    #[clap(long)]
+    normalize_usernames: bool,
+
+    /// Correct misspelled mail domains like gamil.com or hotmial.com to the
+    /// provider they were meant for. Corrections are counted in the run report
+    #[clap(long)]
+    fix_domain_typos: bool,
+
+    /// Correction table used by --fix-domain-typos: bundled for the table
+    /// shipped with the suite, or a file of "typo canonical" pairs, one per line
+    #[clap(long, default_value = "bundled")]
+    domain_typos: TypoTable,
+
+    /// Accept usernames with letters and digits of any script, like иван@mail.ru.
+    /// By default usernames are ascii only
+    #[clap(long)]
+    unicode_usernames: bool,
+
+    /// Text encoding of the input: auto detects it per file or archive member
+    /// from a byte order mark or the first few kilobytes, otherwise a label
+    /// like utf-8, windows-1251, windows-1252 or utf-16le. Other encodings
+    /// than utf-8 are transcoded before parsing
+    #[clap(long, default_value = "auto")]
+    encoding: InputEncoding,
+
+    /// Take the password after the last : or ; of a line and the email before it,
+    /// dropping leading fields like nicknames or ids. Passwords containing
+    /// separators get cut, lines that don't split this way are parsed as usual
+    #[clap(long)]
+    last_colon: bool,
+
+    /// Characters splitting the username and password of a line, \t stands
+    /// for a tab, like ";\t" for dumps separated by semicolons or tabs. The
+    /// first one also tells login:password@domain lines apart, when it comes
+    /// before the @
+    #[clap(long, default_value = ":;")]
+    separators: Separators,
+
+    /// Label of the leak written to the source column of every record,
+    /// leaks_merge keeps the list of sources per credential
+    #[clap(long, default_value = "")]
+    source_name: String,
+
+    /// Date the leak was acquired, YYYY-MM-DD, written to the breach_date
+    /// column and kept with the documents built from the output
+    #[clap(long, default_value = "", value_parser = parse_breach_date)]
+    breach_date: String,
+
+    /// Keep passwords out of the output: hmac writes their hex HMAC-SHA256
+    /// keyed with LEAKS_HMAC_KEY, mask keeps the first and last two characters.
+    /// Rejected lines are still written to the error file as is
+    #[clap(long)]
+    redact: Option<Redaction>,
+
+    /// File of domains to keep, one per line: exact domains like example.com
+    /// or *.example.com for every subdomain of it. Matched against the full
+    /// domain of the username, other entries are dropped
+    #[clap(long)]
+    only_domains: Option<PathBuf>,
+
+    /// File of domains to drop, in the same format as --only-domains.
+    /// Wins over --only-domains
+    #[clap(long)]
+    exclude_domains: Option<PathBuf>,
+
+    /// Drop the credentials of disposable email providers like
+    /// mailinator.com, they only pollute wordlists. Dropped entries are
+    /// counted as filtered
+    #[clap(long)]
+    drop_disposable: bool,
+
+    /// List of disposable domains used by --drop-disposable: bundled for
+    /// the snapshot shipped with the suite, a file in the format of
+    /// --only-domains, or an http(s) url to download a current list from
+    #[clap(long, default_value = "bundled")]
+    disposable_list: DisposableList,
+
+    /// Split the output into this many files by a hash of --shard-key,
+    /// named like leaks.shard03.csv. Sorting and ctj conversion can then run
+    /// on every shard in parallel, each domain stays within a single shard
+    #[clap(long)]
+    shards: Option<usize>,
+
+    /// Field the output is sharded by: domain
+    #[clap(long, default_value = "domain")]
+    shard_key: ShardKey,
+
+    /// Write a separate output per input unit: member gives every archive
+    /// member or file of a dir or stealer input its own file named after it,
+    /// like leaks.ru_mail.txt.csv, to keep track of where records came from.
+    /// Combines with --shards, plain input still goes to --output
+    #[clap(long)]
+    split_output_by: Option<SplitOutput>,
+
+    /// Also tally the written credentials per registrable domain into this
+    /// domain,credentials csv, sorted by domain, to see which domains matter
+    /// before running ctj or leaks_import. Relative paths go to output_dir of
+    /// leaks-suite.toml when set
+    #[clap(long)]
+    domain_counts: Option<PathBuf>,
+
+    /// Also write the run summary as json to this file,
+    /// relative paths go to output_dir of leaks-suite.toml when set
+    #[clap(long)]
+    report: Option<PathBuf>,
+
+    /// Write a manifest json with the hashes of the input and outputs,
+    /// the run counters and arguments to this file, so ctj and leaks_import
+    /// can check they read this run's output. Hashing reads every file again.
+    /// Relative paths go to output_dir of leaks-suite.toml when set
+    #[clap(long)]
+    manifest: Option<PathBuf>,
+
+    /// Where the checkpoint is written when the run is interrupted by
+    /// Ctrl-C or SIGTERM, the outputs are flushed first. Defaults to the
+    /// output path with .checkpoint.json appended
+    #[clap(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Resume the run a checkpoint was written for, skipping the files,
+    /// members and lines it got through. Needs the same input and a
+    /// different --output, the records go to new outputs
+    #[clap(long)]
+    resume: Option<PathBuf>,
+
+    /// Run as a worker: pull inputs from a list on this redis server, like
+    /// redis://queue.internal:6379/0, index them one at a time and upload
+    /// their outputs, error file and summary named after the input. Inputs
+    /// are local paths or http(s) urls, which are downloaded first. Workers on
+    /// several machines can share a queue, they stop once interrupted
+    #[clap(
+        long,
+        requires = "upload",
+        conflicts_with_all = ["input", "error", "report", "manifest", "checkpoint", "resume", "domain_counts"]
+    )]
+    worker: Option<String>,
+
+    /// Redis list the worker pops inputs from. Inputs being indexed are kept
+    /// in <queue>:processing until they are done, failed ones are moved to
+    /// <queue>:failed along with the error. A worker renews the lease of its
+    /// input while indexing it, inputs whose lease ran out are queued again
+    /// when a worker starts
+    #[clap(long, default_value = "leaks:inputs")]
+    queue: String,
+
+    /// Where the worker uploads the files of every input: a directory,
+    /// like a network share, or an http(s) url the files are PUT under
+    #[clap(long)]
+    upload: Option<Upload>,
+
+    /// Don't print the run summary to stderr once done
+    #[clap(long)]
+    quiet: bool,
+}
+
+/// Flag set by Ctrl-C and SIGTERM. The handler is installed once per
+/// process, a second signal exits right away
+fn stop_signal() -> Arc<AtomicBool> {
+    static STOP: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    let stop = STOP.get_or_init(|| {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handler = stop.clone();
+        let installed = ctrlc::set_handler(move || {
+            if handler.swap(true, Ordering::SeqCst) {
+                process::exit(130);
+            }
+            eprintln!("Interrupted, flushing the outputs. Interrupt again to exit right away");
+        });
+        if let Err(e) = installed {
+            eprintln!("Warning: can't handle interrupts: {}", e);
+        }
+        stop
+    });
+    stop.store(false, Ordering::SeqCst);
+    stop.clone()
+}
+
+/// Checks the shape of a YYYY-MM-DD date, empty means unknown
+fn parse_breach_date(s: &str) -> Result<String, String> {
+    let valid = s.split('-').map(str::len).eq([4, 2, 2])
+        && s.bytes().all(|x| x.is_ascii_digit() || x == b'-');
+    if s.is_empty() || valid {
+        Ok(s.to_string())
+    } else {
+        Err(format!("breach date must be YYYY-MM-DD, got {}", s))
+    }
+}
+
+fn read_tld(tld_path: &Path, include_private: bool) -> Result<String, std::io::Error> {
+    if tld_path == Path::new("auto") {
+        let psl = SuffixProvider::default().fetch();
+        return Ok(parse_psl(&mut psl.as_bytes(), include_private));
+    }
+
+    let file = File::open(tld_path)?;
+    let mut reader = BufReader::new(file);
+    Ok(parse_psl(&mut reader, include_private))
+}
+
+/// Runs a whole indexing job, the same for the indexer binary and `leaks index`
+pub fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let config = SuiteConfig::load()?;
+
+    let tld = match args.tld.clone().or(config.tld_path.clone()) {
+        Some(tld) => tld,
+        None => Args::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "--tld is required unless tld_path is set in leaks-suite.toml",
+            )
+            .exit(),
+    };
+    let tld_path = Path::new(&tld);
+    let include_private_domains =
+        args.include_private_domains || config.include_private_domains == Some(true);
+    let output = args.output.as_deref().unwrap_or_default();
+    let kafka = output
+        .starts_with("kafka://")
+        .then(|| match KafkaTarget::parse(output) {
+            Some(target) => target,
+            None => Args::command()
+                .error(
+                    ErrorKind::InvalidValue,
+                    "kafka output must look like kafka://broker:9092/topic",
+                )
+                .exit(),
+        });
+    let file_only = args.compress.is_some()
+        || args.encrypt.is_some()
+        || args.shards.is_some()
+        || args.split_output_by.is_some()
+        || args.output_columns.is_some();
+    if kafka.is_some() && file_only {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "kafka output can't be compressed, encrypted, sharded, split or limited to --output-columns",
+            )
+            .exit();
+    }
+    if args.worker.is_some() && args.output.is_some() && kafka.is_none() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "workers name their outputs after the input, --output only takes a kafka:// topic",
+            )
+            .exit();
+    }
+
+    if args.compress.is_some() && args.output_format == OutputFormat::Parquet {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "parquet output is compressed already, --compress works with csv and jsonl",
+            )
+            .exit();
+    }
+
+    if args.output_columns.is_some() && args.output_format != OutputFormat::Csv {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--output-columns works with csv output only",
+            )
+            .exit();
+    }
+
+    if args.jobs == 0 {
+        Args::command()
+            .error(ErrorKind::InvalidValue, "--jobs must be at least 1")
+            .exit();
+    }
+
+    if args.shards == Some(0) {
+        Args::command()
+            .error(ErrorKind::InvalidValue, "--shards must be at least 1")
+            .exit();
+    }
+
+    if (args.parsers.is_some() || args.detect_parsers) && args.format == EntryFormat::UrlLoginPass {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--parsers and --detect-parsers replace --format url:login:pass, select the url:user:pass parser instead",
+            )
+            .exit();
+    }
+
+    let all_parsers = [
+        ParserKind::EmailPass,
+        ParserKind::UserPassDomain,
+        ParserKind::UrlUserPass,
+        ParserKind::Delimited,
+        ParserKind::PhonePass,
+    ];
+    let kinds = match &args.parsers {
+        Some(kinds) => Some(kinds.as_slice()),
+        None => args.detect_parsers.then_some(all_parsers.as_slice()),
+    };
+    let parsers = match kinds {
+        Some(kinds) => match Delimited::new(args.record_delimiter, args.record_fields.clone()) {
+            Ok(records) => Parsers::new(kinds, records),
+            Err(e) => Args::command().error(ErrorKind::InvalidValue, e).exit(),
+        },
+        None => Parsers::default(),
+    };
+
+    let domain_filter = DomainFilter {
+        only: args
+            .only_domains
+            .as_deref()
+            .map(DomainList::from_file)
+            .transpose()?,
+        exclude: args
+            .exclude_domains
+            .as_deref()
+            .map(DomainList::from_file)
+            .transpose()?,
+        disposable: args
+            .drop_disposable
+            .then(|| args.disposable_list.load())
+            .transpose()?,
+    };
+
+    let tlds = read_tld(tld_path, include_private_domains)
+        .with_context(|| format!("can't read the tld file {}", tld_path.display()))?;
+    let st = PublicSuffixList::new(&tlds);
+
+    let sharding = args.shards.map(|count| Sharding {
+        count,
+        key: args.shard_key,
+    });
+    let options = IndexerOptions {
+        input_type: args.input_type.clone(),
+        parse: ParseOptions {
+            format: args.format,
+            domain_form: args.idn,
+            username_rules: args.normalize_usernames.then(UsernameRules::default),
+            domain_corrections: args
+                .fix_domain_typos
+                .then(|| args.domain_typos.load())
+                .transpose()?,
+            unicode_usernames: args.unicode_usernames,
+            last_colon: args.last_colon,
+            parsers,
+            separators: args.separators.clone(),
+            ..Default::default()
+        },
+        output_format: args.output_format,
+        compression: args.compress,
+        encryption: args.encrypt.clone(),
+        csv: CsvOptions {
+            delimiter: args.delimiter,
+            quote_style: args.quote_style,
+            header: !args.no_header,
+        },
+        columns: args.output_columns.clone(),
+        error_format: args.error_format,
+        threads: args.threads,
+        dedup: args.dedup,
+        dedup_error_rate: args.dedup_error_rate,
+        skip_errors: args.skip_errors,
+        max_depth: args.max_depth,
+        rules_path: args.rules.clone().or(config.rules.clone()),
+        source: args.source_name.clone(),
+        breach_date: args.breach_date.clone(),
+        redaction: args.redact.clone(),
+        domain_filter,
+        sharding,
+        encoding: args.encoding,
+        split_output: args.split_output_by,
+        detect_lines: args.detect_parsers.then_some(args.detect_lines),
+        domain_counts: args
+            .domain_counts
+            .as_deref()
+            .map(|path| config.output_path(path)),
+        resume: None,
+        kafka,
+    };
+
+    if let (Some(url), Some(upload)) = (&args.worker, &args.upload) {
+        let mut worker = Worker::connect(url, &args.queue, upload.clone())?;
+        return worker.run(&st, &options, &stop_signal(), args.quiet);
+    }
+    if let Some(list) = &args.input_list {
+        return index_list(&args, list, &config, st, options);
+    }
+    index(&args, &config, st, options)
+}
+
+/// Output path of the command line, with the extensions of compression
+/// and encryption appended
+fn output_path(args: &Args, config: &SuiteConfig, options: &IndexerOptions) -> PathBuf {
+    let output = args.output.as_deref().unwrap_or_default();
+    if options.kafka.is_some() {
+        return PathBuf::from(output);
+    }
+    let output_path = config.output_path(Path::new(output));
+    let output_path = match args.compress {
+        Some(compression) => compression.output_path(&output_path),
+        None => output_path,
+    };
+    match &args.encrypt {
+        Some(encryption) => encryption.output_path(&output_path),
+        None => output_path,
+    }
+}
+
+/// Error path of the command line, with the extension of encryption appended
+fn error_path(args: &Args, config: &SuiteConfig, error: &str) -> PathBuf {
+    let error_path = config.output_path(Path::new(error));
+    match &args.encrypt {
+        Some(encryption) => encryption.output_path(&error_path),
+        None => error_path,
+    }
+}
+
+/// Writes the manifest of a finished run to `path`
+fn write_manifest(
+    path: &Path,
+    inputs: &[String],
+    outputs: &[PathBuf],
+    error_path: &Path,
+    stats: &Stats,
+) -> Result<(), Box<dyn Error>> {
+    let mut manifest = Manifest::new("indexer", env!("CARGO_PKG_VERSION"), &[]);
+    for input in inputs {
+        manifest.add_input(Path::new(input))?;
+    }
+    for output in outputs {
+        manifest.add_output(output)?;
+    }
+    manifest.add_output(error_path)?;
+
+    manifest.count("lines_read", stats.lines_read);
+    manifest.count("parsed", stats.parsed);
+    manifest.count("corrected", stats.corrected);
+    manifest.count("written", stats.written);
+    manifest.count("duplicates", stats.duplicates);
+    manifest.count("filtered", stats.filtered);
+    manifest.count("rejected", stats.rejected_total());
+    manifest.write(path)?;
+    Ok(())
+}
+
+/// Indexes the inputs of --input-list into the output of the command line
+fn index_list(
+    args: &Args,
+    list: &Path,
+    config: &SuiteConfig,
+    st: PublicSuffixList,
+    mut options: IndexerOptions,
+) -> Result<(), Box<dyn Error>> {
+    let Some(error) = &args.error else {
+        unreachable!("clap requires it without --worker");
+    };
+    let inputs = read_input_list(list)
+        .with_context(|| format!("can't read the input list {}", list.display()))?;
+    let output_path = output_path(args, config, &options);
+    let error_path = error_path(args, config, error);
+    if args.threads == 0 && args.jobs > 1 {
+        let cores = thread::available_parallelism().map_or(1, usize::from);
+        options.threads = (cores / args.jobs).max(1);
+    }
+
+    let output = SharedOutput::new(&output_path, &error_path, &options)?;
+    let (report, result) = index_batch(&inputs, &output, &st, &options, args.jobs, &stop_signal());
+    let outputs = output.output_paths().to_vec();
+    output.finish()?;
+
+    if !args.quiet {
+        eprintln!(
+            "{}\ninputs          {}, {} failed",
+            report.stats,
+            report.inputs.len(),
+            report.failed()
+        );
+    }
+    if let Some(path) = &args.report {
+        let path = config.output_path(path);
+        report
+            .write(&path)
+            .with_context(|| format!("can't write the report {}", path.display()))?;
+    }
+    if let Err(IndexError::Interrupted) = result {
+        let remaining = remaining_inputs(&inputs, &report);
+        let mut path = list.as_os_str().to_owned();
+        path.push(".remaining");
+        let path = PathBuf::from(path);
+        fs::write(&path, remaining.join("\n") + "\n")
+            .with_context(|| format!("can't write {}", path.display()))?;
+        eprintln!(
+            "Interrupted, index the {} remaining inputs with --input-list {} and a new --output",
+            remaining.len(),
+            path.display()
+        );
+    }
+    result?;
+    if let Some(path) = &args.manifest {
+        write_manifest(
+            &config.output_path(path),
+            &inputs,
+            &outputs,
+            &error_path,
+            &report.stats,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Indexes the input of the command line
+fn index(
+    args: &Args,
+    config: &SuiteConfig,
+    st: PublicSuffixList,
+    mut options: IndexerOptions,
+) -> Result<(), Box<dyn Error>> {
+    let (Some(input_path), Some(error)) = (&args.input, &args.error) else {
+        unreachable!("clap requires them without --worker");
+    };
+    let output_path = output_path(args, config, &options);
+    let error_path = error_path(args, config, error);
+    let checkpoint_path = match (&args.checkpoint, &options.kafka) {
+        (Some(path), _) => config.output_path(path),
+        (None, Some(target)) => {
+            config.output_path(Path::new(&format!("{}.checkpoint.json", target.topic)))
+        }
+        (None, None) => {
+            let mut path = output_path.clone().into_os_string();
+            path.push(".checkpoint.json");
+            PathBuf::from(path)
+        }
+    };
+
+    let resume = args
+        .resume
+        .as_deref()
+        .map(|path| {
+            Checkpoint::read(path)
+                .with_context(|| format!("can't read the checkpoint {}", path.display()))
+        })
+        .transpose()?;
+    if let Some(resume) = &resume {
+        if resume.input != *input_path {
+            Args::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!(
+                        "the checkpoint was written for the input {}, not {}",
+                        resume.input, input_path
+                    ),
+                )
+                .exit();
+        }
+        if resume.outputs.contains(&output_path) {
+            Args::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "--output would overwrite the output of the interrupted run, pick a new one",
+                )
+                .exit();
+        }
+    }
+    options.resume = resume;
+
+    let mut indexer = Indexer::new(&output_path, &error_path, st, options)?;
+    indexer.stop_on(stop_signal());
+    let result = indexer.process(input_path);
+    let checkpoint = indexer.checkpoint();
+    let outputs = indexer.output_paths().to_vec();
+    let stats = indexer.finish()?;
+
+    if !args.quiet {
+        eprintln!("{}", stats);
+    }
+    if let Some(report) = &args.report {
+        stats.write_report(&config.output_path(report))?;
+    }
+    if let Err(IndexError::Interrupted) = result {
+        checkpoint
+            .write(&checkpoint_path)
+            .with_context(|| "can't write the checkpoint")?;
+        eprintln!(
+            "Interrupted, resume with --resume {} and a new --output",
+            checkpoint_path.display()
+        );
+    }
+    result?;
+    if let Some(path) = &args.manifest {
+        write_manifest(
+            &config.output_path(path),
+            std::slice::from_ref(input_path),
+            &outputs,
+            &error_path,
+            &stats,
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,395 @@
+//! Worker mode: inputs are popped from a redis list and indexed one at a
+//! time, their outputs are uploaded once done. Workers on several machines
+//! can share a queue to index large collections of dumps.
+//!
+//! An input being indexed holds a lease that its worker keeps renewing.
+//! Inputs left in the processing list without a lease, by a worker that
+//! crashed or lost its machine, are queued again when a worker starts.
+//! An input may thus be indexed twice, but it isn't lost
+
+use std::{
+    convert::Infallible,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use lib::{
+    error::Error as IndexError,
+    errors::{report, BoxError, Context},
+    indexer::{Indexer, IndexerOptions},
+    PublicSuffixList,
+};
+use redis::{Commands, Direction};
+use sha2::{Digest, Sha256};
+use tracing::{error, info, warn};
+
+/// Seconds a worker blocks on an empty queue before checking for interrupts
+const POP_TIMEOUT: f64 = 1.0;
+
+/// Timeout of connecting to the servers inputs are downloaded from and outputs uploaded to
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest wait for a single read or write of a transfer, the transfer
+/// itself takes as long as the file needs
+const IO_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Seconds a lease lasts unless its worker renews it
+const LEASE_SECONDS: u64 = 60;
+
+/// How often a worker renews the lease of the input it indexes
+const HEARTBEAT: Duration = Duration::from_secs(LEASE_SECONDS / 3);
+
+/// Destination of the files of every input
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Upload {
+    /// Files are copied into a directory
+    Dir(PathBuf),
+    /// Files are PUT under an http(s) url
+    Http(String),
+}
+
+impl FromStr for Upload {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(if s.starts_with("http://") || s.starts_with("https://") {
+            Upload::Http(s.trim_end_matches('/').to_string())
+        } else {
+            Upload::Dir(PathBuf::from(s))
+        })
+    }
+}
+
+impl Upload {
+    fn put(&self, path: &Path) -> Result<(), BoxError> {
+        let name = path.file_name().unwrap_or_default();
+        match self {
+            Upload::Dir(dir) => {
+                let target = dir.join(name);
+                fs::copy(path, &target)
+                    .with_context(|| format!("can't upload to {}", target.display()))?;
+            }
+            Upload::Http(url) => {
+                let url = format!("{}/{}", url, name.to_string_lossy());
+                let file = File::open(path)?;
+                agent()
+                    .put(&url)
+                    .send(file)
+                    .with_context(|| format!("can't upload to {}", url))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(IO_TIMEOUT)
+        .timeout_write(IO_TIMEOUT)
+        .build()
+}
+
+/// File name an input's outputs are named after: its base name and a hash
+/// of the whole input, so equally named inputs of different directories
+/// or urls don't overwrite each other's uploads
+fn input_name(input: &str) -> String {
+    let path = input.split(['?', '#']).next().unwrap_or_default();
+    let name = path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    let name = match name {
+        "" => "input",
+        name => name,
+    };
+    let hash: String = Sha256::digest(input.as_bytes())[..4]
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect();
+    format!("{}-{}", name, hash)
+}
+
+pub struct Worker {
+    client: redis::Client,
+    connection: redis::Connection,
+    queue: String,
+    processing: String,
+    failed: String,
+    upload: Upload,
+}
+
+impl Worker {
+    pub fn connect(url: &str, queue: &str, upload: Upload) -> Result<Worker, BoxError> {
+        let client = redis::Client::open(url)
+            .with_context(|| format!("can't connect to the queue at {}", url))?;
+        let connection = client
+            .get_connection()
+            .with_context(|| format!("can't connect to the queue at {}", url))?;
+        if let Upload::Dir(dir) = &upload {
+            fs::create_dir_all(dir).with_context(|| format!("can't create {}", dir.display()))?;
+        }
+
+        Ok(Worker {
+            client,
+            connection,
+            queue: queue.to_string(),
+            processing: format!("{}:processing", queue),
+            failed: format!("{}:failed", queue),
+            upload,
+        })
+    }
+
+    /// Key of the lease of `input`, set while a worker indexes it
+    fn lease(&self, input: &str) -> String {
+        format!("{}:lease:{}", self.queue, input)
+    }
+
+    /// Next input, moved to the processing list and leased until it's
+    /// acknowledged. Waits for one to be queued, None once `stop` is set
+    fn next(&mut self, stop: &AtomicBool) -> redis::RedisResult<Option<String>> {
+        while !stop.load(Ordering::Relaxed) {
+            let input: Option<String> = self.connection.blmove(
+                &self.queue,
+                &self.processing,
+                Direction::Right,
+                Direction::Left,
+                POP_TIMEOUT,
+            )?;
+            if let Some(input) = &input {
+                let _: () = self
+                    .connection
+                    .set_ex(self.lease(input), 1, LEASE_SECONDS)?;
+                return Ok(Some(input.clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Queues the inputs of the processing list whose lease ran out again,
+    /// returns how many
+    fn requeue_stale(&mut self) -> redis::RedisResult<usize> {
+        let processing: Vec<String> = self.connection.lrange(&self.processing, 0, -1)?;
+        let mut requeued = 0;
+        for input in processing {
+            let leased: bool = self.connection.exists(self.lease(&input))?;
+            if leased {
+                continue;
+            }
+            // Another worker may have requeued it already
+            let removed: usize = self.connection.lrem(&self.processing, 1, &input)?;
+            if removed > 0 {
+                let _: () = self.connection.rpush(&self.queue, &input)?;
+                requeued += 1;
+            }
+        }
+        Ok(requeued)
+    }
+
+    /// Renews the lease of `input` every [`HEARTBEAT`] until `done` is set
+    fn heartbeat(&self, input: &str, done: &AtomicBool) -> redis::RedisResult<()> {
+        let mut connection = self.client.get_connection()?;
+        let lease = self.lease(input);
+        let mut renewed = Instant::now();
+        while !done.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(200));
+            if renewed.elapsed() >= HEARTBEAT {
+                let _: () = connection.expire(&lease, LEASE_SECONDS as i64)?;
+                renewed = Instant::now();
+            }
+        }
+        Ok(())
+    }
+
+    /// Indexes inputs until interrupted. Inputs that fail are moved to the
+    /// failed list, an interrupted one goes back to the queue
+    pub fn run(
+        &mut self,
+        st: &PublicSuffixList,
+        options: &IndexerOptions,
+        stop: &Arc<AtomicBool>,
+        quiet: bool,
+    ) -> Result<(), BoxError> {
+        let requeued = self.requeue_stale()?;
+        if requeued > 0 {
+            warn!(
+                requeued,
+                "Queued the inputs of workers that stopped renewing their lease"
+            );
+        }
+
+        info!(queue = %self.queue, "Waiting for inputs");
+        while let Some(input) = self.next(stop)? {
+            info!(input = %input, "Indexing queued input");
+            let done = AtomicBool::new(false);
+            let result = thread::scope(|scope| {
+                let heartbeat = scope.spawn(|| self.heartbeat(&input, &done));
+                let result = self.index(&input, st, options, stop);
+                done.store(true, Ordering::Relaxed);
+                if let Ok(Err(e)) = heartbeat.join() {
+                    warn!(input = %input, error = %e, "Can't renew the lease");
+                }
+                result
+            });
+            let _: () = self.connection.lrem(&self.processing, 1, &input)?;
+            let _: () = self.connection.del(self.lease(&input))?;
+
+            match result {
+                Ok(stats) => {
+                    info!(input = %input, "Uploaded outputs");
+                    if !quiet {
+                        eprintln!("{}\n{}", input, stats);
+                    }
+                }
+                Err(e) if is_interrupted(e.as_ref()) => {
+                    let _: () = self.connection.rpush(&self.queue, &input)?;
+                    return Err(e);
+                }
+                Err(e) => {
+                    let report = report(e.as_ref());
+                    error!(input = %input, error = %report, "Failed to index queued input");
+                    let failure = serde_json::json!({ "input": input, "error": report });
+                    let _: () = self.connection.lpush(&self.failed, failure.to_string())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Indexes `input` into a temporary directory and uploads the outputs,
+    /// the error file and the run summary
+    fn index(
+        &self,
+        input: &str,
+        st: &PublicSuffixList,
+        options: &IndexerOptions,
+        stop: &Arc<AtomicBool>,
+    ) -> Result<String, BoxError> {
+        let dir = tempfile::tempdir()?;
+        let name = input_name(input);
+        let input_path = match input.starts_with("http://") || input.starts_with("https://") {
+            true => download(input, &dir.path().join(&name))?,
+            false => PathBuf::from(input),
+        };
+
+        let output_path = match &options.kafka {
+            Some(_) => PathBuf::new(),
+            None => {
+                let path =
+                    dir.path()
+                        .join(format!("{}.{}", name, options.output_format.extension()));
+                let path = match options.compression {
+                    Some(compression) => compression.output_path(&path),
+                    None => path,
+                };
+                match &options.encryption {
+                    Some(encryption) => encryption.output_path(&path),
+                    None => path,
+                }
+            }
+        };
+        let error_path = dir.path().join(format!("{}.err", name));
+        let error_path = match &options.encryption {
+            Some(encryption) => encryption.output_path(&error_path),
+            None => error_path,
+        };
+        let report_path = dir.path().join(format!("{}.report.json", name));
+
+        let mut indexer = Indexer::new(&output_path, &error_path, st.clone(), options.clone())?;
+        indexer.stop_on(stop.clone());
+        let result = indexer.process(&input_path.to_string_lossy());
+        let outputs = indexer.output_paths().to_vec();
+        let stats = indexer.finish()?;
+        result?;
+
+        stats.write_report(&report_path)?;
+        for path in outputs.iter().chain([&error_path, &report_path]) {
+            self.upload.put(path)?;
+        }
+        Ok(stats.to_string())
+    }
+}
+
+fn download(url: &str, path: &Path) -> Result<PathBuf, BoxError> {
+    let response = agent()
+        .get(url)
+        .call()
+        .with_context(|| format!("can't download {}", url))?;
+    let mut file = File::create(path)?;
+    io::copy(&mut response.into_reader(), &mut file)
+        .with_context(|| format!("can't download {}", url))?;
+    Ok(path.to_path_buf())
+}
+
+fn is_interrupted(error: &(dyn std::error::Error + 'static)) -> bool {
+    matches!(
+        error.downcast_ref::<IndexError>(),
+        Some(IndexError::Interrupted)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_names() {
+        let base = |input| {
+            let name = input_name(input);
+            let (base, hash) = name.rsplit_once('-').unwrap();
+            assert_eq!(hash.len(), 8);
+            base.to_string()
+        };
+        assert_eq!(base("/dumps/combo.txt"), "combo.txt");
+        assert_eq!(
+            base("https://files.internal/a/combo.zip?token=x"),
+            "combo.zip"
+        );
+        assert_eq!(base("/dumps/logs/"), "logs");
+        assert_eq!(base("https://files.internal/"), "files.internal");
+        assert_eq!(base(""), "input");
+
+        assert_eq!(
+            input_name("/dumps/combo.txt"),
+            input_name("/dumps/combo.txt")
+        );
+        assert_ne!(input_name("/a/combo.txt"), input_name("/b/combo.txt"));
+    }
+
+    #[test]
+    fn uploads() {
+        assert_eq!(
+            "https://files.internal/results/".parse(),
+            Ok(Upload::Http("https://files.internal/results".to_string()))
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("combo.txt.csv");
+        fs::write(&file, "domain\n").unwrap();
+        let upload: Upload = dir
+            .path()
+            .join("results")
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let Upload::Dir(target) = &upload else {
+            panic!("expected a directory");
+        };
+        fs::create_dir(target).unwrap();
+        upload.put(&file).unwrap();
+        assert_eq!(
+            fs::read_to_string(target.join("combo.txt.csv")).unwrap(),
+            "domain\n"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+    process::ExitCode,
+};
+
+use clap::Parser;
+use csv::StringRecord;
+use indicatif::MultiProgress;
+use lib::{
+    errors::{self, Context},
+    output::{parse_delimiter, CsvOptions, QuoteStyle},
+    progress::file_progress_bar,
+    telemetry::{self, FlushOnPanic, LogFormat},
+};
+
+mod merge;
+use crate::merge::Merger;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Indexer csv outputs, tagged with --source-name
+    #[clap(short, long, required = true, num_args = 1..)]
+    input: Vec<String>,
+
+    /// Jsonl output with the list of sources of every credential, stdout by default
+    #[clap(short, long)]
+    output: Option<String>,
+
+    /// Csv input delimiter, use \t or tab for tsv
+    #[clap(long, default_value = ",", value_parser = parse_delimiter)]
+    delimiter: u8,
+
+    /// Csv input quoting, never disables quote handling
+    #[clap(long, default_value = "necessary")]
+    quote_style: QuoteStyle,
+
+    /// Csv inputs have no header row
+    #[clap(long)]
+    no_header: bool,
+}
+
+/// Untagged records are attributed to the file they were read from
+fn default_source(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string())
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let csv_options = CsvOptions {
+        delimiter: args.delimiter,
+        quote_style: args.quote_style,
+        header: !args.no_header,
+    };
+
+    let progress = MultiProgress::new();
+    let mut merger = Merger::default();
+    let mut record = StringRecord::new();
+
+    for input in &args.input {
+        let file = File::open(input).with_context(|| format!("can't open {}", input))?;
+        let pb = progress.add(file_progress_bar(file.metadata()?.len()));
+
+        let default_source = default_source(input);
+        let mut rdr = csv_options
+            .reader_builder()
+            .flexible(true)
+            .from_reader(BufReader::new(pb.wrap_read(file)));
+        let mut skipped = 0;
+
+        while rdr.read_record(&mut record)? {
+            if record.len() < 5 {
+                skipped += 1;
+                continue;
+            }
+
+            let field = |i| record.get(i).unwrap_or_default();
+            let source = match field(6) {
+                "" => default_source.as_str(),
+                source => source,
+            };
+            merger.add(
+                [field(0), field(1), field(2), field(3), field(4), field(5)],
+                source,
+            );
+        }
+        pb.finish();
+
+        if skipped > 0 {
+            log::warn!(
+                "Skipped {} records of {} with less than 5 fields",
+                skipped,
+                input
+            );
+        }
+    }
+
+    log::info!(
+        "Merged {} credentials, dropped {} duplicates",
+        merger.records().len(),
+        merger.duplicates()
+    );
+
+    let writer: Box<dyn Write> = match &args.output {
+        Some(path) => {
+            let file = File::create(path).with_context(|| format!("can't create {}", path))?;
+            Box::new(FlushOnPanic::new(BufWriter::new(file)))
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+    merger.write(writer)?;
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    telemetry::init(LogFormat::Text);
+    errors::exit_code(run(Args::parse()))
+}
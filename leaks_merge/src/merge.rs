@@ -0,0 +1,145 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    error::Error,
+    hash::{Hash, Hasher},
+    io::Write,
+};
+
+use serde::Serialize;
+
+/// Credential seen in one or more leaks
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct MergedRecord {
+    pub domain: String,
+    pub subdomain: String,
+    pub username: String,
+    pub password: String,
+    pub password_type: String,
+    pub target_domain: String,
+    /// Sources in the order they were first seen
+    pub sources: Vec<String>,
+}
+
+impl MergedRecord {
+    fn fields(&self) -> [&str; 6] {
+        [
+            &self.domain,
+            &self.subdomain,
+            &self.username,
+            &self.password,
+            &self.password_type,
+            &self.target_domain,
+        ]
+    }
+}
+
+fn fields_hash(fields: &[&str; 6]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    fields.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Drops exact duplicates of the credential fields, keeping records in
+/// first seen order. Records are indexed by hash so the fields are stored once
+#[derive(Default)]
+pub struct Merger {
+    records: Vec<MergedRecord>,
+    index: HashMap<u64, Vec<usize>>,
+    duplicates: u64,
+}
+
+impl Merger {
+    /// Adds the domain,subdomain,username,password,password_type,target_domain
+    /// fields of an indexer record tagged with `source`
+    pub fn add(&mut self, fields: [&str; 6], source: &str) {
+        let positions = self.index.entry(fields_hash(&fields)).or_default();
+
+        let records = &mut self.records;
+        let existing = positions
+            .iter()
+            .copied()
+            .find(|&i| records[i].fields() == fields);
+        if let Some(i) = existing {
+            let record = &mut records[i];
+            self.duplicates += 1;
+            if !record.sources.iter().any(|x| x == source) {
+                record.sources.push(source.to_string());
+            }
+            return;
+        }
+
+        positions.push(self.records.len());
+        let [domain, subdomain, username, password, password_type, target_domain] =
+            fields.map(str::to_string);
+        self.records.push(MergedRecord {
+            domain,
+            subdomain,
+            username,
+            password,
+            password_type,
+            target_domain,
+            sources: vec![source.to_string()],
+        });
+    }
+
+    /// Records dropped as duplicates so far
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+
+    pub fn records(&self) -> &[MergedRecord] {
+        &self.records
+    }
+
+    /// Writes one json object per credential
+    pub fn write(&self, mut writer: impl Write) -> Result<(), Box<dyn Error>> {
+        for record in &self.records {
+            serde_json::to_writer(&mut writer, record)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields<'a>(username: &'a str, password: &'a str) -> [&'a str; 6] {
+        ["example.com", "", username, password, "plain", ""]
+    }
+
+    #[test]
+    fn duplicates_keep_sources() {
+        let mut merger = Merger::default();
+        merger.add(fields("admin", "secret"), "combolist");
+        merger.add(fields("root", "toor"), "combolist");
+        merger.add(fields("admin", "secret"), "stealer");
+        merger.add(fields("admin", "secret"), "combolist");
+        merger.add(fields("admin", "Secret"), "stealer");
+
+        assert_eq!(merger.duplicates(), 2);
+        let records = merger.records();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].username, "admin");
+        assert_eq!(records[0].sources, ["combolist", "stealer"]);
+        assert_eq!(records[1].sources, ["combolist"]);
+        assert_eq!(records[2].password, "Secret");
+        assert_eq!(records[2].sources, ["stealer"]);
+    }
+
+    #[test]
+    fn jsonl_output() {
+        let mut merger = Merger::default();
+        merger.add(fields("admin", "secret"), "a");
+        merger.add(fields("admin", "secret"), "b");
+
+        let mut output = Vec::new();
+        merger.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"domain\":\"example.com\",\"subdomain\":\"\",\"username\":\"admin\",\"password\":\"secret\",\"password_type\":\"plain\",\"target_domain\":\"\",\"sources\":[\"a\",\"b\"]}\n"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::{collections::HashMap, env, error::Error, process::ExitCode, time::Duration};
+
+use clap::Parser;
+use couchbase::{Cluster, QueryOptions};
+use futures::StreamExt;
+use lib::{
+    config::SuiteConfig,
+    document::upgrade,
+    errors,
+    n1ql::{Keyspace, Query},
+    telemetry::{self, LogFormat},
+    DOCUMENT_VERSION,
+};
+use log::{info, warn};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Documents read per query, each is rewritten on its own
+    #[clap(long, default_value_t = 500)]
+    batch_size: usize,
+
+    /// Count the documents of an older schema without rewriting them
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Longest a single query may take, in seconds
+    #[clap(long, default_value_t = 60)]
+    timeout_secs: u64,
+}
+
+fn default_namespace() -> String {
+    "default".to_string()
+}
+
+fn default_bucket() -> String {
+    "leaks-bucket".to_string()
+}
+
+fn default_scope() -> String {
+    "_default".to_string()
+}
+
+fn default_collection() -> String {
+    "leaks".to_string()
+}
+
+/// Couchbase settings, read from the variables the bot reads them from
+#[derive(Deserialize, Debug)]
+struct Config {
+    couch_uri: String,
+    couch_username: String,
+    couch_password: String,
+    #[serde(default = "default_namespace")]
+    couch_namespace: String,
+    #[serde(default = "default_bucket")]
+    couch_bucket: String,
+    #[serde(default = "default_scope")]
+    couch_scope: String,
+    #[serde(default = "default_collection")]
+    couch_collection: String,
+}
+
+impl Config {
+    /// Environment variables override the values of leaks-suite.toml
+    fn load() -> Result<Config, Box<dyn Error>> {
+        let suite = SuiteConfig::load()?;
+        let mut vars: HashMap<String, String> = suite.bot_env().into_iter().collect();
+        vars.extend(env::vars());
+        Ok(envy::from_iter(vars)?)
+    }
+
+    /// N1QL keyspace of the leaks collection
+    fn keyspace(&self) -> Result<Keyspace, String> {
+        let keyspace = Keyspace::new(
+            &self.couch_namespace,
+            &self.couch_bucket,
+            &self.couch_scope,
+            &self.couch_collection,
+        );
+        match keyspace.empty_name() {
+            Some(name) => Err(format!("Empty couchbase {} name", name)),
+            None => Ok(keyspace),
+        }
+    }
+}
+
+/// Document as stored, along with its key
+#[derive(Deserialize)]
+struct StoredDocument {
+    id: String,
+    document: Value,
+}
+
+async fn query<T: DeserializeOwned>(
+    cluster: &Cluster,
+    query: Query,
+    timeout: Duration,
+) -> Result<Vec<T>, Box<dyn Error>> {
+    let options = QueryOptions::default()
+        .positional_parameters(query.params())
+        .timeout(timeout);
+    let mut res = cluster.query(query.statement(), options).await?;
+    let _md = res.meta_data().await;
+    let mut rows = res.rows::<T>();
+    let mut result = Vec::new();
+
+    while let Some(row) = rows.next().await {
+        result.push(row?);
+    }
+    Ok(result)
+}
+
+// Documents are walked in key order, so the ones rewritten in place and
+// the ones that can't be upgraded aren't read again
+async fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let config = Config::load()?;
+    let keyspace = config.keyspace()?;
+    let timeout = Duration::from_secs(args.timeout_secs);
+    let cluster = Cluster::connect(
+        &config.couch_uri,
+        &config.couch_username,
+        &config.couch_password,
+    );
+
+    let select = format!(
+        "SELECT META(l).id AS id, l AS document FROM {} AS l
+         WHERE IFMISSING(l.version, 1) < $1 AND META(l).id > $2
+         ORDER BY META(l).id LIMIT $3",
+        keyspace
+    );
+    let upsert = format!("UPSERT INTO {} (KEY, VALUE) VALUES ($1, $2)", keyspace);
+
+    let mut last = String::new();
+    let mut upgraded = 0;
+    let mut failed = 0;
+    loop {
+        let select = Query::new(&select)
+            .bind(DOCUMENT_VERSION)
+            .bind(&last)
+            .bind(args.batch_size);
+        let batch: Vec<StoredDocument> = query(&cluster, select, timeout).await?;
+        let Some(document) = batch.last() else {
+            break;
+        };
+        last = document.id.clone();
+
+        for stored in batch {
+            let leak = match upgrade(stored.document) {
+                Ok(leak) => leak,
+                Err(e) => {
+                    warn!("Can't upgrade {}: {}", stored.id, e);
+                    failed += 1;
+                    continue;
+                }
+            };
+            if !args.dry_run {
+                let upsert = Query::new(&upsert).bind(&stored.id).bind(leak);
+                query::<Value>(&cluster, upsert, timeout).await?;
+            }
+            upgraded += 1;
+        }
+        info!("Upgraded {} documents so far", upgraded);
+    }
+
+    let verb = if args.dry_run {
+        "Would upgrade"
+    } else {
+        "Upgraded"
+    };
+    println!(
+        "{} {} documents to version {}, {} failed",
+        verb, upgraded, DOCUMENT_VERSION, failed
+    );
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    telemetry::init(LogFormat::Text);
+    errors::exit_code(run(Args::parse()).await)
+}
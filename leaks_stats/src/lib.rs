@@ -0,0 +1,125 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+};
+
+use clap::Parser;
+use csv::ByteRecord;
+use lib::{
+    disposable::DisposableList,
+    errors::Context,
+    output::{parse_delimiter, CsvOptions, QuoteStyle},
+    progress::file_progress_bar,
+    telemetry::FlushOnPanic,
+};
+
+mod report;
+pub mod risk;
+use crate::report::{Collector, ReportFormat};
+
+// Command line of leaks_stats, also `leaks stats`
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+pub struct Args {
+    /// Indexer csv output
+    #[clap(short, long)]
+    input: String,
+
+    /// Report file, stdout by default
+    #[clap(short, long)]
+    output: Option<String>,
+
+    /// Report format: json or csv
+    #[clap(long, default_value = "json")]
+    format: ReportFormat,
+
+    /// Entries kept in every ranking
+    #[clap(short = 'n', long, default_value_t = 100)]
+    top: usize,
+
+    /// Csv input delimiter, use \t or tab for tsv
+    #[clap(long, default_value = ",", value_parser = parse_delimiter)]
+    delimiter: u8,
+
+    /// Csv input quoting, never disables quote handling
+    #[clap(long, default_value = "necessary")]
+    quote_style: QuoteStyle,
+
+    /// Csv input has no header row
+    #[clap(long)]
+    no_header: bool,
+
+    /// Report the number and share of credentials of disposable email
+    /// providers like mailinator.com
+    #[clap(long)]
+    disposable: bool,
+
+    /// List of disposable domains used by --disposable: bundled for the
+    /// snapshot shipped with the suite, a file of domains, or an http(s)
+    /// url to download a current list from
+    #[clap(long, default_value = "bundled")]
+    disposable_list: DisposableList,
+
+    /// Classify passwords by strength with zxcvbn, count the ones reused
+    /// across usernames and domains, and rank domains by exposure risk.
+    /// The strength of every distinct password is estimated, which is slow
+    #[clap(long)]
+    risk: bool,
+}
+
+/// Writes the report, the same for the leaks_stats binary and `leaks stats`
+pub fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let csv_options = CsvOptions {
+        delimiter: args.delimiter,
+        quote_style: args.quote_style,
+        header: !args.no_header,
+    };
+
+    let file = File::open(&args.input).with_context(|| format!("can't open {}", args.input))?;
+    let pb = file_progress_bar(file.metadata()?.len());
+
+    let mut rdr = csv_options
+        .reader_builder()
+        .from_reader(BufReader::new(pb.wrap_read(file)));
+    let mut record = ByteRecord::new();
+    let mut collector = match args.disposable {
+        true => Collector::with_disposable(args.disposable_list.load()?),
+        false => Collector::default(),
+    };
+    if args.risk {
+        collector.score_risk();
+    }
+    let mut skipped = 0;
+
+    while rdr.read_byte_record(&mut record)? {
+        let (Some(domain), Some(username), Some(password)) =
+            (record.get(0), record.get(2), record.get(3))
+        else {
+            skipped += 1;
+            continue;
+        };
+        collector.add(
+            &String::from_utf8_lossy(domain),
+            &String::from_utf8_lossy(username),
+            &String::from_utf8_lossy(password),
+        );
+    }
+    pb.finish();
+
+    if skipped > 0 {
+        log::warn!("Skipped {} records with less than 4 fields", skipped);
+    }
+
+    let report = collector.report(args.top);
+    let writer: Box<dyn Write> = match &args.output {
+        Some(path) => {
+            let file = File::create(path).with_context(|| format!("can't create {}", path))?;
+            Box::new(FlushOnPanic::new(BufWriter::new(file)))
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+    report.write(writer, args.format)?;
+
+    Ok(())
+}
@@ -0,0 +1,460 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    error::Error,
+    hash::{Hash, Hasher},
+    io::Write,
+    str::FromStr,
+};
+
+use lib::domain_filter::DomainList;
+use serde::Serialize;
+
+use crate::risk::{DomainRisk, RiskCounter, Strength};
+
+/// Domains with fewer credentials are left out of the risk ranking,
+/// a couple of passwords don't make a meaningful score
+static MIN_RISK_CREDENTIALS: u64 = 10;
+
+/// Encoding of the report
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    /// section,key,count records
+    Csv,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ReportFormat::Json),
+            "csv" => Ok(ReportFormat::Csv),
+            _ => Err(format!("unknown report format {}, expected json or csv", s)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct DomainCounter {
+    credentials: u64,
+    // Hashes keep memory per distinct password fixed
+    passwords: HashSet<u64>,
+}
+
+/// A distinct password when risk is scored: its strength, the first
+/// domain it was seen in and whether another domain has it too
+struct PasswordRisk {
+    strength: Strength,
+    domain: u64,
+    shared: bool,
+}
+
+#[derive(Default)]
+struct RiskCollector {
+    /// By password hash
+    passwords: HashMap<u64, PasswordRisk>,
+    domains: HashMap<String, RiskCounter>,
+    strength: BTreeMap<Strength, u64>,
+}
+
+/// Accumulates statistics over credentials one at a time.
+/// Keeps a counter per distinct password and a password hash set per domain
+#[derive(Default)]
+pub struct Collector {
+    credentials: u64,
+    passwords: HashMap<String, u64>,
+    lengths: BTreeMap<usize, u64>,
+    domains: HashMap<String, DomainCounter>,
+    tlds: HashMap<String, u64>,
+    /// Disposable email providers, their credentials are counted when set
+    disposable_list: Option<DomainList>,
+    disposable: u64,
+    /// Strength and reuse of the passwords, only kept when risk is scored
+    risk: Option<RiskCollector>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Count {
+    pub key: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct DomainReuse {
+    pub domain: String,
+    pub credentials: u64,
+    pub unique_passwords: u64,
+    /// Credentials sharing a password with another credential of the domain
+    pub reused: u64,
+}
+
+/// Credentials of disposable email providers
+#[derive(Debug, Serialize, PartialEq)]
+pub struct DisposableShare {
+    pub credentials: u64,
+    /// Fraction of all the credentials, between 0 and 1
+    pub share: f64,
+}
+
+/// Strength and reuse of the passwords
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RiskReport {
+    /// Credentials by the strength of their password
+    pub strength: BTreeMap<Strength, u64>,
+    /// Credentials whose password is used in another domain too
+    pub shared_across_domains: u64,
+    /// Domains with the highest exposure risk, the biggest first on ties
+    pub domains: Vec<DomainRisk>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Report {
+    pub credentials: u64,
+    pub unique_passwords: u64,
+    pub top_passwords: Vec<Count>,
+    /// Password length in characters to the number of credentials
+    pub lengths: BTreeMap<usize, u64>,
+    /// Domains with the most reused passwords
+    pub reuse: Vec<DomainReuse>,
+    pub tlds: Vec<Count>,
+    /// Only reported when a disposable domain list is given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disposable: Option<DisposableShare>,
+    /// Only reported when risk is scored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub risk: Option<RiskReport>,
+}
+
+fn password_hash(password: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    password.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Highest counts first, ties are broken by key to keep reports stable
+fn top(counts: impl Iterator<Item = (String, u64)>, n: usize) -> Vec<Count> {
+    let mut counts: Vec<Count> = counts.map(|(key, count)| Count { key, count }).collect();
+    counts.sort_unstable_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)));
+    counts.truncate(n);
+    counts
+}
+
+impl Collector {
+    /// Also counts the credentials of the domains on `disposable`
+    pub fn with_disposable(disposable: DomainList) -> Collector {
+        Collector {
+            disposable_list: Some(disposable),
+            ..Default::default()
+        }
+    }
+
+    /// Also scores the exposure risk of every domain. The strength of
+    /// every distinct password is estimated, which is slow on big inputs
+    pub fn score_risk(&mut self) {
+        self.risk = Some(RiskCollector::default());
+    }
+
+    pub fn add(&mut self, domain: &str, username: &str, password: &str) {
+        self.credentials += 1;
+        if self
+            .disposable_list
+            .as_ref()
+            .is_some_and(|list| list.matches(domain))
+        {
+            self.disposable += 1;
+        }
+
+        match self.passwords.get_mut(password) {
+            Some(count) => *count += 1,
+            None => {
+                self.passwords.insert(password.to_string(), 1);
+            }
+        }
+        *self.lengths.entry(password.chars().count()).or_default() += 1;
+
+        let counter = match self.domains.get_mut(domain) {
+            Some(counter) => counter,
+            None => self.domains.entry(domain.to_string()).or_default(),
+        };
+        counter.credentials += 1;
+        let hash = password_hash(password);
+        counter.passwords.insert(hash);
+
+        if let Some(risk) = &mut self.risk {
+            let domain_hash = password_hash(domain);
+            let password_risk = risk.passwords.entry(hash).or_insert_with(|| PasswordRisk {
+                strength: Strength::of(password),
+                domain: domain_hash,
+                shared: false,
+            });
+            password_risk.shared |= password_risk.domain != domain_hash;
+            let strength = password_risk.strength;
+            *risk.strength.entry(strength).or_default() += 1;
+            match risk.domains.get_mut(domain) {
+                Some(counter) => counter,
+                None => risk.domains.entry(domain.to_string()).or_default(),
+            }
+            .add(username, password, strength);
+        }
+
+        // The indexer domain is registrable, so everything after the first label is the suffix
+        let tld = domain.split_once('.').map(|x| x.1).unwrap_or(domain);
+        match self.tlds.get_mut(tld) {
+            Some(count) => *count += 1,
+            None => {
+                self.tlds.insert(tld.to_string(), 1);
+            }
+        }
+    }
+
+    /// Builds the report keeping the `n` biggest entries of every ranking
+    pub fn report(self, n: usize) -> Report {
+        let mut reuse: Vec<DomainReuse> = self
+            .domains
+            .into_iter()
+            .map(|(domain, counter)| {
+                let unique_passwords = counter.passwords.len() as u64;
+                DomainReuse {
+                    domain,
+                    credentials: counter.credentials,
+                    unique_passwords,
+                    reused: counter.credentials - unique_passwords,
+                }
+            })
+            .filter(|x| x.reused > 0)
+            .collect();
+        reuse.sort_unstable_by(|a, b| {
+            b.reused
+                .cmp(&a.reused)
+                .then_with(|| a.domain.cmp(&b.domain))
+        });
+        reuse.truncate(n);
+
+        let disposable = self.disposable_list.as_ref().map(|_| DisposableShare {
+            credentials: self.disposable,
+            share: match self.credentials {
+                0 => 0.0,
+                credentials => self.disposable as f64 / credentials as f64,
+            },
+        });
+        let risk = self.risk.map(|risk| {
+            let shared_across_domains = self
+                .passwords
+                .iter()
+                .filter(|(password, _)| risk.passwords[&password_hash(password)].shared)
+                .map(|(_, count)| count)
+                .sum();
+            let mut domains: Vec<DomainRisk> = risk
+                .domains
+                .into_iter()
+                .map(|(domain, counter)| counter.risk(domain))
+                .filter(|x| x.credentials >= MIN_RISK_CREDENTIALS)
+                .collect();
+            domains.sort_unstable_by(|a, b| {
+                b.score
+                    .cmp(&a.score)
+                    .then_with(|| b.credentials.cmp(&a.credentials))
+                    .then_with(|| a.domain.cmp(&b.domain))
+            });
+            domains.truncate(n);
+            RiskReport {
+                strength: risk.strength,
+                shared_across_domains,
+                domains,
+            }
+        });
+        Report {
+            credentials: self.credentials,
+            unique_passwords: self.passwords.len() as u64,
+            top_passwords: top(self.passwords.into_iter(), n),
+            lengths: self.lengths,
+            reuse,
+            tlds: top(self.tlds.into_iter(), n),
+            disposable,
+            risk,
+        }
+    }
+}
+
+impl Report {
+    pub fn write(
+        &self,
+        mut writer: impl Write,
+        format: ReportFormat,
+    ) -> Result<(), Box<dyn Error>> {
+        match format {
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, self)?;
+                writer.write_all(b"\n")?;
+            }
+            ReportFormat::Csv => self.write_csv(writer)?,
+        }
+        Ok(())
+    }
+
+    fn write_csv(&self, writer: impl Write) -> Result<(), Box<dyn Error>> {
+        let mut wrt = csv::Writer::from_writer(writer);
+        wrt.write_record(["section", "key", "count"])?;
+
+        wrt.write_record(["total", "credentials", &self.credentials.to_string()])?;
+        wrt.write_record([
+            "total",
+            "unique_passwords",
+            &self.unique_passwords.to_string(),
+        ])?;
+        for x in &self.top_passwords {
+            wrt.write_record(["password", &x.key, &x.count.to_string()])?;
+        }
+        for (length, count) in &self.lengths {
+            wrt.write_record(["length", &length.to_string(), &count.to_string()])?;
+        }
+        for x in &self.reuse {
+            wrt.write_record(["reuse", &x.domain, &x.reused.to_string()])?;
+        }
+        for x in &self.tlds {
+            wrt.write_record(["tld", &x.key, &x.count.to_string()])?;
+        }
+        if let Some(disposable) = &self.disposable {
+            wrt.write_record([
+                "disposable",
+                "credentials",
+                &disposable.credentials.to_string(),
+            ])?;
+            wrt.write_record(["disposable", "share", &format!("{:.4}", disposable.share)])?;
+        }
+        if let Some(risk) = &self.risk {
+            for (strength, count) in &risk.strength {
+                wrt.write_record(["strength", &strength.to_string(), &count.to_string()])?;
+            }
+            wrt.write_record([
+                "risk",
+                "shared_across_domains",
+                &risk.shared_across_domains.to_string(),
+            ])?;
+            for x in &risk.domains {
+                wrt.write_record(["risk", &x.domain, &x.score.to_string()])?;
+            }
+        }
+
+        wrt.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect() -> Report {
+        let mut collector = Collector::default();
+        for (domain, password) in [
+            ("corp.com", "123456"),
+            ("corp.com", "123456"),
+            ("corp.com", "qwerty"),
+            ("shop.co.uk", "123456"),
+            ("shop.co.uk", "hunter2"),
+        ] {
+            collector.add(domain, "john", password);
+        }
+        collector.report(2)
+    }
+
+    #[test]
+    fn rankings() {
+        let report = collect();
+        assert_eq!(report.credentials, 5);
+        assert_eq!(report.unique_passwords, 3);
+        assert_eq!(
+            report.top_passwords,
+            vec![
+                Count {
+                    key: "123456".to_string(),
+                    count: 3
+                },
+                Count {
+                    key: "hunter2".to_string(),
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!(report.lengths, BTreeMap::from([(6, 4), (7, 1)]));
+        assert_eq!(
+            report.reuse,
+            vec![DomainReuse {
+                domain: "corp.com".to_string(),
+                credentials: 3,
+                unique_passwords: 2,
+                reused: 1,
+            }]
+        );
+        assert_eq!(report.tlds[0].key, "com");
+        assert_eq!(report.tlds[1].key, "co.uk");
+    }
+
+    #[test]
+    fn csv_report() {
+        let mut out = Vec::new();
+        collect().write(&mut out, ReportFormat::Csv).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("section,key,count\ntotal,credentials,5\n"));
+        assert!(out.contains("password,123456,3\n"));
+        assert!(out.contains("reuse,corp.com,1\n"));
+        assert!(!out.contains("disposable"));
+    }
+
+    #[test]
+    fn disposable_share() {
+        let mut collector = Collector::with_disposable(DomainList::new("mailinator.com"));
+        for domain in ["corp.com", "mailinator.com", "mailinator.com", "shop.co.uk"] {
+            collector.add(domain, "john", "123456");
+        }
+        let report = collector.report(2);
+        assert_eq!(
+            report.disposable,
+            Some(DisposableShare {
+                credentials: 2,
+                share: 0.5
+            })
+        );
+
+        let mut out = Vec::new();
+        report.write(&mut out, ReportFormat::Csv).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with("disposable,credentials,2\ndisposable,share,0.5000\n"));
+    }
+
+    #[test]
+    fn risk_scores() {
+        let mut collector = Collector::default();
+        collector.score_risk();
+        for i in 0..10 {
+            collector.add("corp.com", &format!("user{}", i), "123456");
+            collector.add(
+                "shop.co.uk",
+                &format!("user{}", i),
+                &format!("x7#Kq!v{}Lm2@Wz", i),
+            );
+        }
+        collector.add("shop.co.uk", "admin", "123456");
+        collector.add("tiny.com", "admin", "password");
+
+        let report = collector.report(10);
+        assert!(report.credentials > 0);
+        let risk = report.risk.unwrap();
+        assert_eq!(risk.strength[&Strength::VeryWeak], 12);
+        assert_eq!(risk.strength[&Strength::VeryStrong], 10);
+        assert_eq!(risk.shared_across_domains, 11);
+
+        let domains: Vec<(&str, u8)> = risk
+            .domains
+            .iter()
+            .map(|x| (x.domain.as_str(), x.score))
+            .collect();
+        assert_eq!(domains, [("corp.com", 100), ("shop.co.uk", 5)]);
+        assert_eq!(risk.domains[0].reused, 10);
+        assert_eq!(risk.domains[1].reused, 0);
+    }
+}
@@ -0,0 +1,171 @@
+//! Password strength and reuse, summed up as the exposure risk of a domain
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use serde::Serialize;
+use zxcvbn::{zxcvbn, Score};
+
+/// Longer passwords aren't estimated, matching patterns over them gets
+/// slow and they are beyond any guessing attack anyway
+const MAX_ESTIMATED_LEN: usize = 64;
+
+/// Guesses an attacker needs for a password, as estimated by zxcvbn
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strength {
+    /// 10^3 guesses or less, like 123456
+    VeryWeak,
+    /// 10^6 guesses or less
+    Weak,
+    /// 10^8 guesses or less
+    Fair,
+    /// 10^10 guesses or less
+    Strong,
+    /// More than 10^10 guesses
+    VeryStrong,
+}
+
+impl Strength {
+    /// # Example
+    ///
+    /// ```
+    /// use leaks_stats::risk::Strength;
+    ///
+    /// assert_eq!(Strength::of("123456"), Strength::VeryWeak);
+    /// assert_eq!(Strength::of("correct horse battery staple"), Strength::VeryStrong);
+    /// ```
+    pub fn of(password: &str) -> Strength {
+        if password.chars().count() > MAX_ESTIMATED_LEN {
+            return Strength::VeryStrong;
+        }
+        match zxcvbn(password, &[]).score() {
+            Score::Zero => Strength::VeryWeak,
+            Score::One => Strength::Weak,
+            Score::Two => Strength::Fair,
+            Score::Three => Strength::Strong,
+            _ => Strength::VeryStrong,
+        }
+    }
+
+    /// Found by an online attack or the first minutes of an offline one
+    pub fn is_weak(&self) -> bool {
+        *self <= Strength::Weak
+    }
+}
+
+impl fmt::Display for Strength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Strength::VeryWeak => "very_weak",
+            Strength::Weak => "weak",
+            Strength::Fair => "fair",
+            Strength::Strong => "strong",
+            Strength::VeryStrong => "very_strong",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Strength and reuse of the credentials of a domain
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DomainRisk {
+    pub domain: String,
+    pub credentials: u64,
+    /// Credentials by the strength of their password
+    pub strength: BTreeMap<Strength, u64>,
+    /// Credentials whose password is shared with another username of the domain
+    pub reused: u64,
+    /// Exposure risk between 0 and 100, the share of weak passwords weighs
+    /// 60 points and the share of reused ones 40
+    pub score: u8,
+}
+
+impl DomainRisk {
+    pub fn weak(&self) -> u64 {
+        self.strength
+            .iter()
+            .filter(|(strength, _)| strength.is_weak())
+            .map(|(_, count)| count)
+            .sum()
+    }
+}
+
+/// A password of a domain: the first username having it, whether
+/// another username has it too, and its credentials
+struct PasswordUse {
+    username: u64,
+    shared: bool,
+    credentials: u64,
+}
+
+fn hash(x: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    x.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Accumulates the credentials of a domain one at a time. Usernames and
+/// passwords are kept as hashes, the caller estimates the strength of
+/// every password so it can do it once per distinct password
+#[derive(Default)]
+pub struct RiskCounter {
+    credentials: u64,
+    strength: BTreeMap<Strength, u64>,
+    passwords: HashMap<u64, PasswordUse>,
+}
+
+impl RiskCounter {
+    pub fn add(&mut self, username: &str, password: &str, strength: Strength) {
+        self.credentials += 1;
+        *self.strength.entry(strength).or_default() += 1;
+
+        let username = hash(username);
+        let used = self.passwords.entry(hash(password)).or_insert(PasswordUse {
+            username,
+            shared: false,
+            credentials: 0,
+        });
+        used.credentials += 1;
+        used.shared |= used.username != username;
+    }
+
+    /// # Example
+    ///
+    /// ```
+    /// use leaks_stats::risk::{RiskCounter, Strength};
+    ///
+    /// let mut counter = RiskCounter::default();
+    /// counter.add("john", "123456", Strength::VeryWeak);
+    /// counter.add("jane", "123456", Strength::VeryWeak);
+    /// counter.add("admin", "Tr0ub4dor&3x!", Strength::VeryStrong);
+    /// counter.add("root", "correct horse", Strength::VeryStrong);
+    ///
+    /// let risk = counter.risk("corp.com".to_string());
+    /// assert_eq!((risk.weak(), risk.reused), (2, 2));
+    /// assert_eq!(risk.score, 50);
+    /// ```
+    pub fn risk(&self, domain: String) -> DomainRisk {
+        let reused = self
+            .passwords
+            .values()
+            .filter(|x| x.shared)
+            .map(|x| x.credentials)
+            .sum();
+        let mut risk = DomainRisk {
+            domain,
+            credentials: self.credentials,
+            strength: self.strength.clone(),
+            reused,
+            score: 0,
+        };
+        if self.credentials > 0 {
+            let share = |x: u64| x as f64 / self.credentials as f64;
+            risk.score = (share(risk.weak()) * 60.0 + share(reused) * 40.0).round() as u8;
+        }
+        risk
+    }
+}
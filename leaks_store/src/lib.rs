@@ -0,0 +1,321 @@
+use std::{error::Error, future::Future};
+
+use lib::{document::missing_credentials, CredentialData, LeakData, DOCUMENT_VERSION};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+mod postgres;
+mod sqlite;
+
+pub use postgres::PostgresStore;
+pub use sqlite::SqliteStore;
+
+pub type StoreResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Hex digits of an email hash sent by a range query, as in the HaveIBeenPwned range api
+pub static HASH_PREFIX_LEN: usize = 5;
+
+/// Uppercase hex SHA-1 of the lowercased username@subdomain.domain,
+/// the hash range queries are answered from
+///
+/// # Example
+///
+/// ```
+/// use leaks_store::email_hash;
+///
+/// assert_eq!(email_hash("John", "", "corp.com"), email_hash("john", "", "CORP.com"));
+/// assert_eq!(email_hash("john", "", "corp.com").len(), 40);
+/// ```
+pub fn email_hash(username: &str, subdomain: &str, domain: &str) -> String {
+    let email = if subdomain.is_empty() {
+        format!("{}@{}", username, domain)
+    } else {
+        format!("{}@{}.{}", username, subdomain, domain)
+    };
+    hex::encode_upper(Sha1::digest(email.to_lowercase().as_bytes()))
+}
+
+/// Size of a store
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stats {
+    /// Stored LeakData documents, a domain can be split over several
+    pub documents: u64,
+    pub domains: u64,
+    pub credentials: u64,
+}
+
+/// Number of credentials stored for a domain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainCount {
+    pub domain: String,
+    pub credentials: u64,
+}
+
+/// Size of the leak of a domain, told without any of its credentials
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainExposure {
+    pub credentials: u64,
+    /// Distinct usernames over all subdomains
+    pub usernames: u64,
+    /// Distinct subdomains with credentials, the domain itself isn't counted
+    pub subdomains: u64,
+}
+
+/// Domain a telegram chat is notified about when it gains credentials
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub user_id: u64,
+    /// Chat the notifications are sent to
+    pub chat_id: i64,
+    pub domain: String,
+    /// Credentials of the domain at the last check
+    pub credentials: u64,
+}
+
+/// Email hash matching a range query, without the prefix that was queried
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashCount {
+    pub suffix: String,
+    /// Credentials stored for the email
+    pub credentials: u64,
+}
+
+/// Password hash of a Pwned Passwords file, SHA-1 or NTLM in uppercase hex
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PwnedHash {
+    pub hash: String,
+    /// Times the password was seen in breaches
+    pub count: u64,
+}
+
+/// Single credential flattened out of a LeakData document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CredentialRow {
+    pub domain: String,
+    pub subdomain: String,
+    pub username: String,
+    pub password: String,
+}
+
+impl CredentialRow {
+    /// Formats the row as username@host:password
+    pub fn format(&self) -> String {
+        if self.subdomain.is_empty() {
+            format!("{}@{}:{}", self.username, self.domain, self.password)
+        } else {
+            format!(
+                "{}@{}.{}:{}",
+                self.username, self.subdomain, self.domain, self.password
+            )
+        }
+    }
+}
+
+/// LIKE pattern matching values that contain `term`, with the wildcards
+/// of `term` escaped by backslashes
+///
+/// # Example
+///
+/// ```
+/// use leaks_store::contains_pattern;
+///
+/// assert_eq!(contains_pattern("acme"), "%acme%");
+/// assert_eq!(contains_pattern("50%_off"), "%50\\%\\_off%");
+/// ```
+pub fn contains_pattern(term: &str) -> String {
+    let escaped = term
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Groups credentials of a single domain by subdomain,
+/// subdomains keep the order they first appear in. Breaches are stored
+/// apart from the credentials and left for the caller to fill
+///
+/// # Example
+///
+/// ```
+/// use leaks_store::{group_rows, CredentialRow};
+///
+/// let row = |subdomain: &str, username: &str| CredentialRow {
+///     domain: "corp.com".to_string(),
+///     subdomain: subdomain.to_string(),
+///     username: username.to_string(),
+///     password: "pass".to_string(),
+/// };
+/// let leak = group_rows("corp.com", vec![row("vpn", "a"), row("", "b"), row("vpn", "c")]);
+///
+/// assert_eq!(leak.credentials.len(), 2);
+/// assert_eq!(leak.credentials[0].subdomain, "vpn");
+/// assert_eq!(leak.credentials[0].data.len(), 2);
+/// ```
+pub fn group_rows(domain: &str, rows: impl IntoIterator<Item = CredentialRow>) -> LeakData {
+    let mut credentials: Vec<CredentialData> = Vec::new();
+
+    for row in rows {
+        let pair = (row.username, row.password);
+        match credentials
+            .iter_mut()
+            .find(|x| x.subdomain == row.subdomain)
+        {
+            Some(credential_data) => credential_data.data.push(pair),
+            None => credentials.push(CredentialData {
+                subdomain: row.subdomain,
+                data: vec![pair],
+            }),
+        }
+    }
+
+    LeakData {
+        domain: domain.to_string(),
+        credentials,
+        breaches: Vec::new(),
+        org: String::new(),
+        version: DOCUMENT_VERSION,
+    }
+}
+
+/// Storage of LeakData documents queried by the bot and filled by the importer
+pub trait LeakStore {
+    /// Every document of `domain`
+    fn find_domain(&self, domain: &str) -> impl Future<Output = StoreResult<Vec<LeakData>>> + Send;
+
+    /// Credentials of the domains matching a LIKE `pattern`,
+    /// % matches any run of characters
+    fn find_domain_like(
+        &self,
+        pattern: &str,
+    ) -> impl Future<Output = StoreResult<Vec<CredentialRow>>> + Send;
+
+    /// Credentials of username@subdomain.domain
+    fn find_email(
+        &self,
+        domain: &str,
+        subdomain: &str,
+        username: &str,
+    ) -> impl Future<Output = StoreResult<Vec<CredentialRow>>> + Send;
+
+    /// Credentials of `username` across all domains
+    fn find_username(
+        &self,
+        username: &str,
+    ) -> impl Future<Output = StoreResult<Vec<CredentialRow>>> + Send;
+
+    /// Number of distinct domains and credentials
+    fn stats(&self) -> impl Future<Output = StoreResult<Stats>> + Send;
+
+    /// The `n` domains with the most credentials, biggest first
+    fn top_domains(&self, n: usize) -> impl Future<Output = StoreResult<Vec<DomainCount>>> + Send;
+
+    /// Up to `n` domains resembling `term`, biggest first. Backends without
+    /// fuzzy matching return the domains containing `term`
+    fn search_domains(
+        &self,
+        term: &str,
+        n: usize,
+    ) -> impl Future<Output = StoreResult<Vec<DomainCount>>> + Send;
+
+    /// Credentials of each of `domains` that has any, biggest first,
+    /// counted in one grouped query
+    fn count_domains(
+        &self,
+        domains: &[String],
+    ) -> impl Future<Output = StoreResult<Vec<DomainCount>>> + Send;
+
+    /// Counts of the credentials of `domain`, aggregated by the backend
+    /// so no password is read out of it
+    fn count_domain(
+        &self,
+        domain: &str,
+    ) -> impl Future<Output = StoreResult<DomainExposure>> + Send;
+
+    /// Checks that the backend answers, for health checks
+    fn ping(&self) -> impl Future<Output = StoreResult<()>> + Send;
+
+    /// Domains owned by `org`, matched ignoring case, in alphabetical order
+    fn org_domains(&self, org: &str) -> impl Future<Output = StoreResult<Vec<String>>> + Send;
+
+    /// Stores every credential of `leak`, the breaches it lists and its organization
+    fn insert(&self, leak: &LeakData) -> impl Future<Output = StoreResult<()>> + Send;
+}
+
+/// Lookups by a prefix of the email hash, so clients can check
+/// an email without sending it, see [`email_hash`]
+pub trait HashRangeStore {
+    /// Emails whose hash starts with `prefix`, HASH_PREFIX_LEN uppercase
+    /// hex digits, ordered by suffix
+    fn hash_range(&self, prefix: &str) -> impl Future<Output = StoreResult<Vec<HashCount>>> + Send;
+}
+
+/// Known compromised passwords, loaded from the HaveIBeenPwned Pwned Passwords files
+pub trait PwnedStore {
+    /// Stores `hashes`, counts of hashes stored already are replaced
+    fn insert_pwned(&self, hashes: &[PwnedHash]) -> impl Future<Output = StoreResult<()>> + Send;
+
+    /// Times the password of an uppercase hex `hash` was seen, None when it's unknown
+    fn pwned_count(&self, hash: &str) -> impl Future<Output = StoreResult<Option<u64>>> + Send;
+}
+
+/// Domains saved by users of the bot to check again later
+pub trait WatchStore {
+    /// Saved domains of `user`, in alphabetical order
+    fn watchlist(&self, user: u64) -> impl Future<Output = StoreResult<Vec<String>>> + Send;
+
+    /// Saves `domain` for `user`, returns false when it was saved already
+    fn watch(&self, user: u64, domain: &str) -> impl Future<Output = StoreResult<bool>> + Send;
+
+    /// Forgets `domain` of `user`, returns false when it wasn't saved
+    fn unwatch(&self, user: u64, domain: &str) -> impl Future<Output = StoreResult<bool>> + Send;
+}
+
+/// Domains users of the bot are notified about
+pub trait SubscriptionStore {
+    /// Subscribes to a domain, returns false when the user is subscribed already
+    fn subscribe(
+        &self,
+        subscription: &Subscription,
+    ) -> impl Future<Output = StoreResult<bool>> + Send;
+
+    /// Returns false when `user` wasn't subscribed to `domain`
+    fn unsubscribe(
+        &self,
+        user: u64,
+        domain: &str,
+    ) -> impl Future<Output = StoreResult<bool>> + Send;
+
+    /// Subscriptions of `user`, or of everyone when None, ordered by domain
+    fn subscriptions(
+        &self,
+        user: Option<u64>,
+    ) -> impl Future<Output = StoreResult<Vec<Subscription>>> + Send;
+
+    /// Records the credentials `domain` had at a check, for all its subscriptions
+    fn set_checked(
+        &self,
+        domain: &str,
+        credentials: u64,
+    ) -> impl Future<Output = StoreResult<()>> + Send;
+}
+
+/// Number of credentials stored for `domain` over all its documents, counted
+/// by the backend so checking a big domain doesn't read its documents
+pub async fn domain_credentials<S: LeakStore>(store: &S, domain: &str) -> StoreResult<u64> {
+    Ok(store.count_domain(domain).await?.credentials)
+}
+
+/// Inserts the credentials of `leak` its domain doesn't have yet,
+/// so a new leak can be loaded on top of a filled store. A new organization
+/// is stored even when no credential is. Returns the number of credentials inserted
+pub async fn append<S: LeakStore>(store: &S, leak: &LeakData) -> StoreResult<usize> {
+    let documents = store.find_domain(&leak.domain).await?;
+    let missing = missing_credentials(&documents, leak);
+    let added = missing.credentials.iter().map(|x| x.data.len()).sum();
+    let new_org = !leak.org.is_empty() && documents.iter().all(|x| x.org != leak.org);
+
+    if added > 0 || new_org {
+        store.insert(&missing).await?;
+    }
+    Ok(added)
+}
@@ -0,0 +1,530 @@
+use std::collections::HashMap;
+
+use futures::TryStreamExt;
+use lib::{Breach, LeakData};
+use sqlx::{postgres::PgPoolOptions, PgPool, PgTransaction};
+
+use crate::{
+    contains_pattern, email_hash, group_rows, CredentialRow, DomainCount, DomainExposure,
+    HashCount, HashRangeStore, LeakStore, PwnedHash, PwnedStore, Stats, StoreResult, Subscription,
+    SubscriptionStore, WatchStore, HASH_PREFIX_LEN,
+};
+
+/// Credentials are kept flat, one row per credential
+static SCHEMA: [&str; 11] = [
+    "CREATE TABLE IF NOT EXISTS credentials (
+        domain TEXT NOT NULL,
+        subdomain TEXT NOT NULL,
+        username TEXT NOT NULL,
+        password TEXT NOT NULL
+    )",
+    // text_pattern_ops serves both equality and prefix LIKE lookups
+    "CREATE INDEX IF NOT EXISTS credentials_domain_idx ON credentials (domain text_pattern_ops)",
+    "CREATE INDEX IF NOT EXISTS credentials_username_idx ON credentials (username)",
+    "CREATE TABLE IF NOT EXISTS breaches (
+        domain TEXT NOT NULL,
+        name TEXT NOT NULL,
+        date TEXT NOT NULL,
+        source_file TEXT NOT NULL,
+        PRIMARY KEY (domain, name, date, source_file)
+    )",
+    "CREATE TABLE IF NOT EXISTS organizations (
+        domain TEXT PRIMARY KEY,
+        org TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS organizations_org_idx ON organizations (lower(org))",
+    "CREATE TABLE IF NOT EXISTS watchlist (
+        user_id BIGINT NOT NULL,
+        domain TEXT NOT NULL,
+        PRIMARY KEY (user_id, domain)
+    )",
+    "CREATE TABLE IF NOT EXISTS subscriptions (
+        user_id BIGINT NOT NULL,
+        chat_id BIGINT NOT NULL,
+        domain TEXT NOT NULL,
+        credentials BIGINT NOT NULL,
+        PRIMARY KEY (user_id, domain)
+    )",
+    // A hash per credential, range queries count the rows of each hash
+    "CREATE TABLE IF NOT EXISTS email_hashes (
+        prefix TEXT NOT NULL,
+        suffix TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS email_hashes_prefix_idx ON email_hashes (prefix)",
+    "CREATE TABLE IF NOT EXISTS pwned_passwords (
+        hash TEXT PRIMARY KEY,
+        count BIGINT NOT NULL
+    )",
+];
+
+type Row = (String, String, String, String);
+
+fn into_credential_row((domain, subdomain, username, password): Row) -> CredentialRow {
+    CredentialRow {
+        domain,
+        subdomain,
+        username,
+        password,
+    }
+}
+
+type BreachRow = (String, String, String);
+
+fn into_breach((name, date, source_file): BreachRow) -> Breach {
+    Breach {
+        name,
+        date,
+        source_file,
+    }
+}
+
+/// Credentials hashed per insert when email_hashes is backfilled
+static BACKFILL_BATCH_SIZE: usize = 10000;
+
+/// Stores the hashes of `emails`, username, subdomain and domain triples
+async fn insert_hashes<'a>(
+    tx: &mut PgTransaction<'_>,
+    emails: impl IntoIterator<Item = (&'a str, &'a str, &'a str)>,
+) -> StoreResult<()> {
+    let (prefixes, suffixes): (Vec<String>, Vec<String>) = emails
+        .into_iter()
+        .map(|(username, subdomain, domain)| {
+            let mut prefix = email_hash(username, subdomain, domain);
+            let suffix = prefix.split_off(HASH_PREFIX_LEN);
+            (prefix, suffix)
+        })
+        .unzip();
+
+    sqlx::query(
+        "INSERT INTO email_hashes (prefix, suffix) SELECT * FROM UNNEST($1::text[], $2::text[])",
+    )
+    .bind(prefixes)
+    .bind(suffixes)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    /// Connects to `url` and creates the schema if it's missing
+    pub async fn connect(url: &str) -> StoreResult<PostgresStore> {
+        let pool = PgPoolOptions::new().max_connections(5).connect(url).await?;
+
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&pool).await?;
+        }
+
+        let store = PostgresStore { pool };
+        store.backfill_email_hashes().await?;
+        Ok(store)
+    }
+
+    /// Hashes the emails of a database created before email_hashes was, once.
+    /// Credentials are streamed on one connection and hashed in batches on another,
+    /// in one transaction so an interrupted backfill leaves no hashes behind
+    async fn backfill_email_hashes(&self) -> StoreResult<()> {
+        let (hashed,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM email_hashes)")
+            .fetch_one(&self.pool)
+            .await?;
+        if hashed {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let mut rows = sqlx::query_as::<_, (String, String, String)>(
+            "SELECT username, subdomain, domain FROM credentials",
+        )
+        .fetch(&self.pool);
+        let mut batch = Vec::new();
+        loop {
+            let row = rows.try_next().await?;
+            let done = row.is_none();
+            batch.extend(row);
+
+            if batch.len() >= BACKFILL_BATCH_SIZE || (done && !batch.is_empty()) {
+                let emails = batch.iter().map(|(username, subdomain, domain)| {
+                    (username.as_str(), subdomain.as_str(), domain.as_str())
+                });
+                insert_hashes(&mut tx, emails).await?;
+                batch.clear();
+            }
+            if done {
+                break;
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn fetch_rows(&self, filter: &str, params: &[&str]) -> StoreResult<Vec<CredentialRow>> {
+        let query = format!(
+            "SELECT domain, subdomain, username, password FROM credentials WHERE {}",
+            filter
+        );
+
+        let mut query = sqlx::query_as::<_, Row>(&query);
+        for param in params {
+            query = query.bind(*param);
+        }
+
+        let rows = query.fetch_all(&self.pool).await?;
+        Ok(rows.into_iter().map(into_credential_row).collect())
+    }
+}
+
+impl LeakStore for PostgresStore {
+    async fn find_domain(&self, domain: &str) -> StoreResult<Vec<LeakData>> {
+        let rows = self.fetch_rows("domain = $1", &[domain]).await?;
+        if rows.is_empty() {
+            return Ok(Vec::new());
+        }
+        let breaches: Vec<BreachRow> = sqlx::query_as(
+            "SELECT name, date, source_file FROM breaches WHERE domain = $1
+             ORDER BY date, name, source_file",
+        )
+        .bind(domain)
+        .fetch_all(&self.pool)
+        .await?;
+        let org: Option<(String,)> =
+            sqlx::query_as("SELECT org FROM organizations WHERE domain = $1")
+                .bind(domain)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        let mut leak = group_rows(domain, rows);
+        leak.breaches = breaches.into_iter().map(into_breach).collect();
+        leak.org = org.map(|(org,)| org).unwrap_or_default();
+        Ok(vec![leak])
+    }
+
+    async fn find_domain_like(&self, pattern: &str) -> StoreResult<Vec<CredentialRow>> {
+        self.fetch_rows("domain LIKE $1", &[pattern]).await
+    }
+
+    async fn find_email(
+        &self,
+        domain: &str,
+        subdomain: &str,
+        username: &str,
+    ) -> StoreResult<Vec<CredentialRow>> {
+        self.fetch_rows(
+            "domain = $1 AND subdomain = $2 AND username = $3",
+            &[domain, subdomain, username],
+        )
+        .await
+    }
+
+    async fn find_username(&self, username: &str) -> StoreResult<Vec<CredentialRow>> {
+        self.fetch_rows("username = $1", &[username]).await
+    }
+
+    async fn stats(&self) -> StoreResult<Stats> {
+        let (domains, credentials): (i64, i64) =
+            sqlx::query_as("SELECT COUNT(DISTINCT domain), COUNT(*) FROM credentials")
+                .fetch_one(&self.pool)
+                .await?;
+
+        // Rows of a domain are grouped into a single document
+        Ok(Stats {
+            documents: domains as u64,
+            domains: domains as u64,
+            credentials: credentials as u64,
+        })
+    }
+
+    async fn top_domains(&self, n: usize) -> StoreResult<Vec<DomainCount>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT domain, COUNT(*) AS credentials FROM credentials
+             GROUP BY domain ORDER BY credentials DESC, domain LIMIT $1",
+        )
+        .bind(n as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(domain, credentials)| DomainCount {
+                domain,
+                credentials: credentials as u64,
+            })
+            .collect())
+    }
+
+    async fn search_domains(&self, term: &str, n: usize) -> StoreResult<Vec<DomainCount>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT domain, COUNT(*) AS credentials FROM credentials
+             WHERE domain LIKE $1 ESCAPE '\\'
+             GROUP BY domain ORDER BY credentials DESC, domain LIMIT $2",
+        )
+        .bind(contains_pattern(term))
+        .bind(n as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(domain, credentials)| DomainCount {
+                domain,
+                credentials: credentials as u64,
+            })
+            .collect())
+    }
+
+    async fn count_domains(&self, domains: &[String]) -> StoreResult<Vec<DomainCount>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT domain, COUNT(*) AS credentials FROM credentials
+             WHERE domain = ANY($1)
+             GROUP BY domain ORDER BY credentials DESC, domain",
+        )
+        .bind(domains)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(domain, credentials)| DomainCount {
+                domain,
+                credentials: credentials as u64,
+            })
+            .collect())
+    }
+
+    async fn count_domain(&self, domain: &str) -> StoreResult<DomainExposure> {
+        let (credentials, usernames, subdomains): (i64, i64, i64) = sqlx::query_as(
+            "SELECT COUNT(*), COUNT(DISTINCT username), COUNT(DISTINCT NULLIF(subdomain, ''))
+             FROM credentials WHERE domain = $1",
+        )
+        .bind(domain)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(DomainExposure {
+            credentials: credentials as u64,
+            usernames: usernames as u64,
+            subdomains: subdomains as u64,
+        })
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn org_domains(&self, org: &str) -> StoreResult<Vec<String>> {
+        let domains: Vec<(String,)> = sqlx::query_as(
+            "SELECT domain FROM organizations WHERE lower(org) = lower($1) ORDER BY domain",
+        )
+        .bind(org)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(domains.into_iter().map(|(domain,)| domain).collect())
+    }
+
+    async fn insert(&self, leak: &LeakData) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+        let mut subdomains = Vec::new();
+        let mut usernames = Vec::new();
+        let mut passwords = Vec::new();
+
+        for credential_data in &leak.credentials {
+            for (username, password) in &credential_data.data {
+                subdomains.push(credential_data.subdomain.as_str());
+                usernames.push(username.as_str());
+                passwords.push(password.as_str());
+            }
+        }
+
+        sqlx::query(
+            "INSERT INTO credentials (domain, subdomain, username, password)
+             SELECT $1, * FROM UNNEST($2::text[], $3::text[], $4::text[])",
+        )
+        .bind(&leak.domain)
+        .bind(&subdomains)
+        .bind(&usernames)
+        .bind(passwords)
+        .execute(&mut *tx)
+        .await?;
+
+        let emails = usernames
+            .into_iter()
+            .zip(subdomains)
+            .map(|(username, subdomain)| (username, subdomain, leak.domain.as_str()));
+        insert_hashes(&mut tx, emails).await?;
+
+        for breach in &leak.breaches {
+            sqlx::query(
+                "INSERT INTO breaches (domain, name, date, source_file)
+                 VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+            )
+            .bind(&leak.domain)
+            .bind(&breach.name)
+            .bind(&breach.date)
+            .bind(&breach.source_file)
+            .execute(&mut *tx)
+            .await?;
+        }
+        if !leak.org.is_empty() {
+            sqlx::query(
+                "INSERT INTO organizations (domain, org) VALUES ($1, $2)
+                 ON CONFLICT (domain) DO UPDATE SET org = excluded.org",
+            )
+            .bind(&leak.domain)
+            .bind(&leak.org)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+impl HashRangeStore for PostgresStore {
+    async fn hash_range(&self, prefix: &str) -> StoreResult<Vec<HashCount>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT suffix, COUNT(*) FROM email_hashes WHERE prefix = $1
+             GROUP BY suffix ORDER BY suffix",
+        )
+        .bind(prefix)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(suffix, credentials)| HashCount {
+                suffix,
+                credentials: credentials as u64,
+            })
+            .collect())
+    }
+}
+
+impl PwnedStore for PostgresStore {
+    async fn insert_pwned(&self, hashes: &[PwnedHash]) -> StoreResult<()> {
+        // A row can't be updated twice by one statement, the last count of
+        // a hash wins like it does for one insert after another
+        let counts: HashMap<&str, i64> = hashes
+            .iter()
+            .map(|x| (x.hash.as_str(), x.count as i64))
+            .collect();
+        let (hashes, counts): (Vec<&str>, Vec<i64>) = counts.into_iter().unzip();
+
+        sqlx::query(
+            "INSERT INTO pwned_passwords (hash, count)
+             SELECT * FROM UNNEST($1::text[], $2::bigint[])
+             ON CONFLICT (hash) DO UPDATE SET count = EXCLUDED.count",
+        )
+        .bind(hashes)
+        .bind(counts)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn pwned_count(&self, hash: &str) -> StoreResult<Option<u64>> {
+        let count: Option<(i64,)> =
+            sqlx::query_as("SELECT count FROM pwned_passwords WHERE hash = $1")
+                .bind(hash)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(count.map(|(count,)| count as u64))
+    }
+}
+
+impl WatchStore for PostgresStore {
+    async fn watchlist(&self, user: u64) -> StoreResult<Vec<String>> {
+        let domains: Vec<(String,)> =
+            sqlx::query_as("SELECT domain FROM watchlist WHERE user_id = $1 ORDER BY domain")
+                .bind(user as i64)
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(domains.into_iter().map(|(domain,)| domain).collect())
+    }
+
+    async fn watch(&self, user: u64, domain: &str) -> StoreResult<bool> {
+        let result = sqlx::query(
+            "INSERT INTO watchlist (user_id, domain) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(user as i64)
+        .bind(domain)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn unwatch(&self, user: u64, domain: &str) -> StoreResult<bool> {
+        let result = sqlx::query("DELETE FROM watchlist WHERE user_id = $1 AND domain = $2")
+            .bind(user as i64)
+            .bind(domain)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+type SubscriptionRow = (i64, i64, String, i64);
+
+fn into_subscription((user_id, chat_id, domain, credentials): SubscriptionRow) -> Subscription {
+    Subscription {
+        user_id: user_id as u64,
+        chat_id,
+        domain,
+        credentials: credentials as u64,
+    }
+}
+
+impl SubscriptionStore for PostgresStore {
+    async fn subscribe(&self, subscription: &Subscription) -> StoreResult<bool> {
+        let result = sqlx::query(
+            "INSERT INTO subscriptions (user_id, chat_id, domain, credentials)
+             VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+        )
+        .bind(subscription.user_id as i64)
+        .bind(subscription.chat_id)
+        .bind(&subscription.domain)
+        .bind(subscription.credentials as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn unsubscribe(&self, user: u64, domain: &str) -> StoreResult<bool> {
+        let result = sqlx::query("DELETE FROM subscriptions WHERE user_id = $1 AND domain = $2")
+            .bind(user as i64)
+            .bind(domain)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn subscriptions(&self, user: Option<u64>) -> StoreResult<Vec<Subscription>> {
+        let query = "SELECT user_id, chat_id, domain, credentials FROM subscriptions";
+        let rows: Vec<SubscriptionRow> = match user {
+            Some(user) => {
+                sqlx::query_as(&format!("{} WHERE user_id = $1 ORDER BY domain", query))
+                    .bind(user as i64)
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+            None => {
+                sqlx::query_as(&format!("{} ORDER BY domain, user_id", query))
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+        };
+        Ok(rows.into_iter().map(into_subscription).collect())
+    }
+
+    async fn set_checked(&self, domain: &str, credentials: u64) -> StoreResult<()> {
+        sqlx::query("UPDATE subscriptions SET credentials = $1 WHERE domain = $2")
+            .bind(credentials as i64)
+            .bind(domain)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
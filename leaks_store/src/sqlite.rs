@@ -0,0 +1,572 @@
+use std::str::FromStr;
+
+use lib::{Breach, LeakData};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    Sqlite, SqlitePool, Transaction,
+};
+
+use crate::{
+    contains_pattern, email_hash, group_rows, CredentialRow, DomainCount, DomainExposure,
+    HashCount, HashRangeStore, LeakStore, PwnedHash, PwnedStore, Stats, StoreResult, Subscription,
+    SubscriptionStore, WatchStore, HASH_PREFIX_LEN,
+};
+
+static SCHEMA: [&str; 13] = [
+    "CREATE TABLE IF NOT EXISTS credentials (
+        domain TEXT NOT NULL,
+        subdomain TEXT NOT NULL,
+        username TEXT NOT NULL,
+        password TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS credentials_domain_idx ON credentials (domain)",
+    // External content table, the text itself is only stored in credentials
+    "CREATE VIRTUAL TABLE IF NOT EXISTS credentials_fts USING fts5(
+        domain, username, content='credentials', content_rowid='rowid'
+    )",
+    "CREATE TRIGGER IF NOT EXISTS credentials_fts_insert AFTER INSERT ON credentials BEGIN
+        INSERT INTO credentials_fts (rowid, domain, username)
+        VALUES (new.rowid, new.domain, new.username);
+    END",
+    "CREATE TABLE IF NOT EXISTS breaches (
+        domain TEXT NOT NULL,
+        name TEXT NOT NULL,
+        date TEXT NOT NULL,
+        source_file TEXT NOT NULL,
+        PRIMARY KEY (domain, name, date, source_file)
+    )",
+    "CREATE TABLE IF NOT EXISTS organizations (
+        domain TEXT PRIMARY KEY,
+        org TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS organizations_org_idx ON organizations (org COLLATE NOCASE)",
+    "CREATE TABLE IF NOT EXISTS watchlist (
+        user_id BIGINT NOT NULL,
+        domain TEXT NOT NULL,
+        PRIMARY KEY (user_id, domain)
+    )",
+    "CREATE TABLE IF NOT EXISTS subscriptions (
+        user_id BIGINT NOT NULL,
+        chat_id BIGINT NOT NULL,
+        domain TEXT NOT NULL,
+        credentials BIGINT NOT NULL,
+        PRIMARY KEY (user_id, domain)
+    )",
+    // A hash per credential, range queries count the rows of each hash
+    "CREATE TABLE IF NOT EXISTS email_hashes (
+        prefix TEXT NOT NULL,
+        suffix TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS email_hashes_prefix_idx ON email_hashes (prefix)",
+    // A single row, how far the backfill of email_hashes got
+    "CREATE TABLE IF NOT EXISTS email_hash_backfill (
+        last_rowid BIGINT NOT NULL,
+        done BOOLEAN NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS pwned_passwords (
+        hash TEXT PRIMARY KEY,
+        count BIGINT NOT NULL
+    )",
+];
+
+type Row = (String, String, String, String);
+
+fn into_credential_row((domain, subdomain, username, password): Row) -> CredentialRow {
+    CredentialRow {
+        domain,
+        subdomain,
+        username,
+        password,
+    }
+}
+
+type BreachRow = (String, String, String);
+
+fn into_breach((name, date, source_file): BreachRow) -> Breach {
+    Breach {
+        name,
+        date,
+        source_file,
+    }
+}
+
+/// Quotes `value` as a single fts5 phrase
+fn fts_phrase(column: &str, value: &str) -> String {
+    format!("{}:\"{}\"", column, value.replace('"', "\"\""))
+}
+
+/// Credentials hashed per transaction when email_hashes is backfilled
+static BACKFILL_BATCH_SIZE: i64 = 10000;
+
+async fn insert_hash(tx: &mut Transaction<'_, Sqlite>, hash: &str) -> StoreResult<()> {
+    let (prefix, suffix) = hash.split_at(HASH_PREFIX_LEN);
+    sqlx::query("INSERT INTO email_hashes (prefix, suffix) VALUES (?, ?)")
+        .bind(prefix)
+        .bind(suffix)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Single file store for local investigations
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it and the schema if they're missing.
+    /// `:memory:` gives a private in-memory database
+    pub async fn open(path: &str) -> StoreResult<SqliteStore> {
+        let options = SqliteConnectOptions::from_str(path)?.create_if_missing(true);
+        // Every connection of an in-memory database would get its own copy
+        let max_connections = if path == ":memory:" { 1 } else { 5 };
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .connect_with(options)
+            .await?;
+
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&pool).await?;
+        }
+
+        let store = SqliteStore { pool };
+        store.backfill_email_hashes().await?;
+        Ok(store)
+    }
+
+    /// Hashes the emails of a database created before email_hashes was, once.
+    /// Credentials are walked by rowid, reading while writing would lock the file.
+    /// The last rowid is committed with each batch, so an interrupted backfill
+    /// resumes after it on the next open
+    async fn backfill_email_hashes(&self) -> StoreResult<()> {
+        let progress: Option<(i64, bool)> =
+            sqlx::query_as("SELECT last_rowid, done FROM email_hash_backfill")
+                .fetch_optional(&self.pool)
+                .await?;
+        let mut last = match progress {
+            Some((_, true)) => return Ok(()),
+            Some((last, false)) => last,
+            None => self.start_backfill().await?,
+        };
+
+        loop {
+            let rows: Vec<(i64, String, String, String)> = sqlx::query_as(
+                "SELECT rowid, domain, subdomain, username FROM credentials
+                 WHERE rowid > ? ORDER BY rowid LIMIT ?",
+            )
+            .bind(last)
+            .bind(BACKFILL_BATCH_SIZE)
+            .fetch_all(&self.pool)
+            .await?;
+            let Some((rowid, ..)) = rows.last() else {
+                sqlx::query("UPDATE email_hash_backfill SET done = TRUE")
+                    .execute(&self.pool)
+                    .await?;
+                return Ok(());
+            };
+            last = *rowid;
+
+            let mut tx = self.pool.begin().await?;
+            for (_, domain, subdomain, username) in &rows {
+                insert_hash(&mut tx, &email_hash(username, subdomain, domain)).await?;
+            }
+            sqlx::query("UPDATE email_hash_backfill SET last_rowid = ?")
+                .bind(last)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+        }
+    }
+
+    /// Records the backfill of a database without its progress, returning the
+    /// rowid it starts after. A database with a hash per credential needs none.
+    /// Hashes of a backfill interrupted before its progress was kept can't be
+    /// told from the others, so that one starts over
+    async fn start_backfill(&self) -> StoreResult<i64> {
+        let mut tx = self.pool.begin().await?;
+        let (credentials,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM credentials")
+            .fetch_one(&mut *tx)
+            .await?;
+        let (hashes,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM email_hashes")
+            .fetch_one(&mut *tx)
+            .await?;
+        let done = credentials == hashes;
+        if !done {
+            sqlx::query("DELETE FROM email_hashes")
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("INSERT INTO email_hash_backfill (last_rowid, done) VALUES (0, ?)")
+            .bind(done)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(0)
+    }
+
+    async fn fetch_rows(&self, filter: &str, params: &[&str]) -> StoreResult<Vec<CredentialRow>> {
+        let query = format!(
+            "SELECT domain, subdomain, username, password FROM credentials WHERE {}",
+            filter
+        );
+
+        let mut query = sqlx::query_as::<_, Row>(&query);
+        for param in params {
+            query = query.bind(*param);
+        }
+
+        let rows = query.fetch_all(&self.pool).await?;
+        Ok(rows.into_iter().map(into_credential_row).collect())
+    }
+}
+
+impl LeakStore for SqliteStore {
+    async fn find_domain(&self, domain: &str) -> StoreResult<Vec<LeakData>> {
+        let rows = self.fetch_rows("domain = ?", &[domain]).await?;
+        if rows.is_empty() {
+            return Ok(Vec::new());
+        }
+        let breaches: Vec<BreachRow> = sqlx::query_as(
+            "SELECT name, date, source_file FROM breaches WHERE domain = ?
+             ORDER BY date, name, source_file",
+        )
+        .bind(domain)
+        .fetch_all(&self.pool)
+        .await?;
+        let org: Option<(String,)> =
+            sqlx::query_as("SELECT org FROM organizations WHERE domain = ?")
+                .bind(domain)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        let mut leak = group_rows(domain, rows);
+        leak.breaches = breaches.into_iter().map(into_breach).collect();
+        leak.org = org.map(|(org,)| org).unwrap_or_default();
+        Ok(vec![leak])
+    }
+
+    async fn find_domain_like(&self, pattern: &str) -> StoreResult<Vec<CredentialRow>> {
+        self.fetch_rows("domain LIKE ?", &[pattern]).await
+    }
+
+    async fn find_email(
+        &self,
+        domain: &str,
+        subdomain: &str,
+        username: &str,
+    ) -> StoreResult<Vec<CredentialRow>> {
+        self.fetch_rows(
+            "domain = ? AND subdomain = ? AND username = ?",
+            &[domain, subdomain, username],
+        )
+        .await
+    }
+
+    async fn find_username(&self, username: &str) -> StoreResult<Vec<CredentialRow>> {
+        // The phrase match narrows down candidates, the tokenizer ignores
+        // punctuation, so the exact comparison is still needed
+        let phrase = fts_phrase("username", username);
+        self.fetch_rows(
+            "rowid IN (SELECT rowid FROM credentials_fts WHERE credentials_fts MATCH ?) \
+             AND username = ?",
+            &[&phrase, username],
+        )
+        .await
+    }
+
+    async fn stats(&self) -> StoreResult<Stats> {
+        let (domains, credentials): (i64, i64) =
+            sqlx::query_as("SELECT COUNT(DISTINCT domain), COUNT(*) FROM credentials")
+                .fetch_one(&self.pool)
+                .await?;
+
+        // Rows of a domain are grouped into a single document
+        Ok(Stats {
+            documents: domains as u64,
+            domains: domains as u64,
+            credentials: credentials as u64,
+        })
+    }
+
+    async fn top_domains(&self, n: usize) -> StoreResult<Vec<DomainCount>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT domain, COUNT(*) AS credentials FROM credentials
+             GROUP BY domain ORDER BY credentials DESC, domain LIMIT ?",
+        )
+        .bind(n as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(domain, credentials)| DomainCount {
+                domain,
+                credentials: credentials as u64,
+            })
+            .collect())
+    }
+
+    async fn search_domains(&self, term: &str, n: usize) -> StoreResult<Vec<DomainCount>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT domain, COUNT(*) AS credentials FROM credentials
+             WHERE domain LIKE ? ESCAPE '\\'
+             GROUP BY domain ORDER BY credentials DESC, domain LIMIT ?",
+        )
+        .bind(contains_pattern(term))
+        .bind(n as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(domain, credentials)| DomainCount {
+                domain,
+                credentials: credentials as u64,
+            })
+            .collect())
+    }
+
+    async fn count_domains(&self, domains: &[String]) -> StoreResult<Vec<DomainCount>> {
+        if domains.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query = format!(
+            "SELECT domain, COUNT(*) AS credentials FROM credentials
+             WHERE domain IN ({})
+             GROUP BY domain ORDER BY credentials DESC, domain",
+            vec!["?"; domains.len()].join(", ")
+        );
+
+        let mut query = sqlx::query_as::<_, (String, i64)>(&query);
+        for domain in domains {
+            query = query.bind(domain);
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(domain, credentials)| DomainCount {
+                domain,
+                credentials: credentials as u64,
+            })
+            .collect())
+    }
+
+    async fn count_domain(&self, domain: &str) -> StoreResult<DomainExposure> {
+        let (credentials, usernames, subdomains): (i64, i64, i64) = sqlx::query_as(
+            "SELECT COUNT(*), COUNT(DISTINCT username), COUNT(DISTINCT NULLIF(subdomain, ''))
+             FROM credentials WHERE domain = ?",
+        )
+        .bind(domain)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(DomainExposure {
+            credentials: credentials as u64,
+            usernames: usernames as u64,
+            subdomains: subdomains as u64,
+        })
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn org_domains(&self, org: &str) -> StoreResult<Vec<String>> {
+        let domains: Vec<(String,)> = sqlx::query_as(
+            "SELECT domain FROM organizations WHERE org = ? COLLATE NOCASE ORDER BY domain",
+        )
+        .bind(org)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(domains.into_iter().map(|(domain,)| domain).collect())
+    }
+
+    async fn insert(&self, leak: &LeakData) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for credential_data in &leak.credentials {
+            for (username, password) in &credential_data.data {
+                sqlx::query(
+                    "INSERT INTO credentials (domain, subdomain, username, password)
+                     VALUES (?, ?, ?, ?)",
+                )
+                .bind(&leak.domain)
+                .bind(&credential_data.subdomain)
+                .bind(username)
+                .bind(password)
+                .execute(&mut *tx)
+                .await?;
+                let hash = email_hash(username, &credential_data.subdomain, &leak.domain);
+                insert_hash(&mut tx, &hash).await?;
+            }
+        }
+        for breach in &leak.breaches {
+            sqlx::query(
+                "INSERT INTO breaches (domain, name, date, source_file)
+                 VALUES (?, ?, ?, ?) ON CONFLICT DO NOTHING",
+            )
+            .bind(&leak.domain)
+            .bind(&breach.name)
+            .bind(&breach.date)
+            .bind(&breach.source_file)
+            .execute(&mut *tx)
+            .await?;
+        }
+        if !leak.org.is_empty() {
+            sqlx::query(
+                "INSERT INTO organizations (domain, org) VALUES (?, ?)
+                 ON CONFLICT (domain) DO UPDATE SET org = excluded.org",
+            )
+            .bind(&leak.domain)
+            .bind(&leak.org)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+impl HashRangeStore for SqliteStore {
+    async fn hash_range(&self, prefix: &str) -> StoreResult<Vec<HashCount>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT suffix, COUNT(*) FROM email_hashes WHERE prefix = ?
+             GROUP BY suffix ORDER BY suffix",
+        )
+        .bind(prefix)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(suffix, credentials)| HashCount {
+                suffix,
+                credentials: credentials as u64,
+            })
+            .collect())
+    }
+}
+
+impl PwnedStore for SqliteStore {
+    async fn insert_pwned(&self, hashes: &[PwnedHash]) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+        for pwned in hashes {
+            sqlx::query(
+                "INSERT INTO pwned_passwords (hash, count) VALUES (?, ?)
+                 ON CONFLICT (hash) DO UPDATE SET count = excluded.count",
+            )
+            .bind(&pwned.hash)
+            .bind(pwned.count as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn pwned_count(&self, hash: &str) -> StoreResult<Option<u64>> {
+        let count: Option<(i64,)> =
+            sqlx::query_as("SELECT count FROM pwned_passwords WHERE hash = ?")
+                .bind(hash)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(count.map(|(count,)| count as u64))
+    }
+}
+
+impl WatchStore for SqliteStore {
+    async fn watchlist(&self, user: u64) -> StoreResult<Vec<String>> {
+        let domains: Vec<(String,)> =
+            sqlx::query_as("SELECT domain FROM watchlist WHERE user_id = ? ORDER BY domain")
+                .bind(user as i64)
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(domains.into_iter().map(|(domain,)| domain).collect())
+    }
+
+    async fn watch(&self, user: u64, domain: &str) -> StoreResult<bool> {
+        let result = sqlx::query(
+            "INSERT INTO watchlist (user_id, domain) VALUES (?, ?) ON CONFLICT DO NOTHING",
+        )
+        .bind(user as i64)
+        .bind(domain)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn unwatch(&self, user: u64, domain: &str) -> StoreResult<bool> {
+        let result = sqlx::query("DELETE FROM watchlist WHERE user_id = ? AND domain = ?")
+            .bind(user as i64)
+            .bind(domain)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+type SubscriptionRow = (i64, i64, String, i64);
+
+fn into_subscription((user_id, chat_id, domain, credentials): SubscriptionRow) -> Subscription {
+    Subscription {
+        user_id: user_id as u64,
+        chat_id,
+        domain,
+        credentials: credentials as u64,
+    }
+}
+
+impl SubscriptionStore for SqliteStore {
+    async fn subscribe(&self, subscription: &Subscription) -> StoreResult<bool> {
+        let result = sqlx::query(
+            "INSERT INTO subscriptions (user_id, chat_id, domain, credentials)
+             VALUES (?, ?, ?, ?) ON CONFLICT DO NOTHING",
+        )
+        .bind(subscription.user_id as i64)
+        .bind(subscription.chat_id)
+        .bind(&subscription.domain)
+        .bind(subscription.credentials as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn unsubscribe(&self, user: u64, domain: &str) -> StoreResult<bool> {
+        let result = sqlx::query("DELETE FROM subscriptions WHERE user_id = ? AND domain = ?")
+            .bind(user as i64)
+            .bind(domain)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn subscriptions(&self, user: Option<u64>) -> StoreResult<Vec<Subscription>> {
+        let query = "SELECT user_id, chat_id, domain, credentials FROM subscriptions";
+        let rows: Vec<SubscriptionRow> = match user {
+            Some(user) => {
+                sqlx::query_as(&format!("{} WHERE user_id = ? ORDER BY domain", query))
+                    .bind(user as i64)
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+            None => {
+                sqlx::query_as(&format!("{} ORDER BY domain, user_id", query))
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+        };
+        Ok(rows.into_iter().map(into_subscription).collect())
+    }
+
+    async fn set_checked(&self, domain: &str, credentials: u64) -> StoreResult<()> {
+        sqlx::query("UPDATE subscriptions SET credentials = ? WHERE domain = ?")
+            .bind(credentials as i64)
+            .bind(domain)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
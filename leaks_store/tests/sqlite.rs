@@ -0,0 +1,460 @@
+use leaks_store::{
+    append, domain_credentials, email_hash, DomainCount, DomainExposure, HashCount, HashRangeStore,
+    LeakStore, PwnedHash, PwnedStore, SqliteStore, Stats, Subscription, SubscriptionStore,
+    WatchStore,
+};
+use lib::{Breach, CredentialData, LeakData, DOCUMENT_VERSION};
+
+fn leak(domain: &str, credentials: &[(&str, &str, &str)]) -> LeakData {
+    LeakData {
+        domain: domain.to_string(),
+        credentials: credentials
+            .iter()
+            .map(|(subdomain, username, password)| CredentialData {
+                subdomain: subdomain.to_string(),
+                data: vec![(username.to_string(), password.to_string())],
+            })
+            .collect(),
+        breaches: Vec::new(),
+        org: String::new(),
+        version: DOCUMENT_VERSION,
+    }
+}
+
+async fn store() -> SqliteStore {
+    let store = SqliteStore::open(":memory:").await.unwrap();
+    store
+        .insert(&leak(
+            "corp.com",
+            &[
+                ("", "john.doe", "p1"),
+                ("vpn", "admin", "p2"),
+                ("", "admin", "p3"),
+            ],
+        ))
+        .await
+        .unwrap();
+    store
+        .insert(&leak("corp.org", &[("", "john", "p4")]))
+        .await
+        .unwrap();
+    store
+}
+
+#[tokio::test]
+async fn sqlite_find_domain() {
+    let store = store().await;
+
+    let leaks = store.find_domain("corp.com").await.unwrap();
+    assert_eq!(leaks.len(), 1);
+    let subdomains: Vec<_> = leaks[0]
+        .credentials
+        .iter()
+        .map(|x| (x.subdomain.as_str(), x.data.len()))
+        .collect();
+    assert_eq!(subdomains, vec![("", 2), ("vpn", 1)]);
+
+    assert!(store.find_domain("nope.com").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn sqlite_breaches() {
+    let store = store().await;
+    assert!(store.find_domain("corp.com").await.unwrap()[0]
+        .breaches
+        .is_empty());
+
+    let breach = Breach {
+        name: "combo".to_string(),
+        date: "2024-05-01".to_string(),
+        source_file: "dumps/a.txt".to_string(),
+    };
+    let mut new = leak("corp.com", &[("", "jane", "p5")]);
+    new.breaches = vec![breach.clone()];
+    store.insert(&new).await.unwrap();
+    store.insert(&new).await.unwrap();
+
+    let leaks = store.find_domain("corp.com").await.unwrap();
+    assert_eq!(leaks[0].breaches, vec![breach]);
+    assert!(store.find_domain("corp.org").await.unwrap()[0]
+        .breaches
+        .is_empty());
+}
+
+#[tokio::test]
+async fn sqlite_find_domain_like() {
+    let store = store().await;
+
+    let rows = store.find_domain_like("corp.%").await.unwrap();
+    assert_eq!(rows.len(), 4);
+}
+
+#[tokio::test]
+async fn sqlite_find_email() {
+    let store = store().await;
+
+    let rows = store.find_email("corp.com", "vpn", "admin").await.unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].format(), "admin@vpn.corp.com:p2");
+}
+
+#[tokio::test]
+async fn sqlite_find_username() {
+    let store = store().await;
+
+    // john must not match john.doe although fts tokenizes it as "john doe"
+    let rows = store.find_username("john").await.unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].domain, "corp.org");
+
+    let rows = store.find_username("john.doe").await.unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].password, "p1");
+
+    let rows = store.find_username("\"admin").await.unwrap();
+    assert!(rows.is_empty());
+}
+
+#[tokio::test]
+async fn sqlite_stats() {
+    let store = store().await;
+
+    let stats = store.stats().await.unwrap();
+    assert_eq!(
+        stats,
+        Stats {
+            documents: 2,
+            domains: 2,
+            credentials: 4
+        }
+    );
+}
+
+#[tokio::test]
+async fn sqlite_append() {
+    let store = store().await;
+
+    let added = append(
+        &store,
+        &leak(
+            "corp.com",
+            &[
+                ("vpn", "admin", "p2"),
+                ("vpn", "root", "p5"),
+                ("vpn", "root", "p5"),
+            ],
+        ),
+    )
+    .await
+    .unwrap();
+    assert_eq!(added, 1);
+
+    let added = append(&store, &leak("new.com", &[("", "john", "p6")]))
+        .await
+        .unwrap();
+    assert_eq!(added, 1);
+
+    let stats = store.stats().await.unwrap();
+    assert_eq!(
+        stats,
+        Stats {
+            documents: 3,
+            domains: 3,
+            credentials: 6
+        }
+    );
+}
+
+#[tokio::test]
+async fn sqlite_organizations() {
+    let store = store().await;
+    assert_eq!(store.find_domain("corp.com").await.unwrap()[0].org, "");
+
+    let mut owned = leak("corp.com", &[("", "john.doe", "p1")]);
+    owned.org = "Corp Inc".to_string();
+    assert_eq!(append(&store, &owned).await.unwrap(), 0);
+    let mut owned = leak("corp.org", &[("", "jane", "p5")]);
+    owned.org = "Corp Inc".to_string();
+    store.insert(&owned).await.unwrap();
+
+    assert_eq!(
+        store.find_domain("corp.com").await.unwrap()[0].org,
+        "Corp Inc"
+    );
+    assert_eq!(
+        store.org_domains("corp inc").await.unwrap(),
+        vec!["corp.com", "corp.org"]
+    );
+    assert!(store.org_domains("Corp").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn sqlite_top_domains() {
+    let store = store().await;
+
+    let top = store.top_domains(10).await.unwrap();
+    assert_eq!(
+        top,
+        vec![
+            DomainCount {
+                domain: "corp.com".to_string(),
+                credentials: 3
+            },
+            DomainCount {
+                domain: "corp.org".to_string(),
+                credentials: 1
+            }
+        ]
+    );
+    assert_eq!(store.top_domains(1).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn sqlite_search_domains() {
+    let store = store().await;
+    store
+        .insert(&leak("my_corp.net", &[("", "root", "p5")]))
+        .await
+        .unwrap();
+
+    let found = store.search_domains("corp", 10).await.unwrap();
+    let domains: Vec<_> = found.iter().map(|x| x.domain.as_str()).collect();
+    assert_eq!(domains, ["corp.com", "corp.org", "my_corp.net"]);
+    assert_eq!(found[0].credentials, 3);
+
+    // _ is matched literally rather than as any character
+    assert!(store
+        .search_domains("corp_com", 10)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(store.search_domains("y_c", 10).await.unwrap().len(), 1);
+    assert!(store.search_domains("acme", 10).await.unwrap().is_empty());
+    assert_eq!(store.search_domains("corp", 1).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn sqlite_count_domain() {
+    let store = store().await;
+    assert_eq!(
+        store.count_domain("corp.com").await.unwrap(),
+        DomainExposure {
+            credentials: 3,
+            usernames: 2,
+            subdomains: 1,
+        }
+    );
+    assert_eq!(
+        store.count_domain("acme.com").await.unwrap(),
+        DomainExposure::default()
+    );
+}
+
+#[tokio::test]
+async fn sqlite_ping() {
+    let store = store().await;
+    store.ping().await.unwrap();
+}
+
+#[tokio::test]
+async fn sqlite_watchlist() {
+    let store = store().await;
+
+    assert!(store.watch(1, "corp.org").await.unwrap());
+    assert!(store.watch(1, "corp.com").await.unwrap());
+    assert!(!store.watch(1, "corp.com").await.unwrap());
+    assert!(store.watch(2, "corp.net").await.unwrap());
+    assert_eq!(
+        store.watchlist(1).await.unwrap(),
+        vec!["corp.com".to_string(), "corp.org".to_string()]
+    );
+
+    assert!(store.unwatch(1, "corp.com").await.unwrap());
+    assert!(!store.unwatch(1, "corp.com").await.unwrap());
+    assert_eq!(
+        store.watchlist(1).await.unwrap(),
+        vec!["corp.org".to_string()]
+    );
+    assert_eq!(store.watchlist(3).await.unwrap(), Vec::<String>::new());
+}
+
+#[tokio::test]
+async fn sqlite_count_domains() {
+    let store = store().await;
+    let domains = ["corp.org", "corp.com", "nope.com"].map(String::from);
+    assert_eq!(
+        store.count_domains(&domains).await.unwrap(),
+        [
+            DomainCount {
+                domain: "corp.com".to_string(),
+                credentials: 3,
+            },
+            DomainCount {
+                domain: "corp.org".to_string(),
+                credentials: 1,
+            },
+        ]
+    );
+    assert!(store.count_domains(&[]).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn sqlite_domain_credentials() {
+    let store = store().await;
+
+    assert_eq!(domain_credentials(&store, "corp.com").await.unwrap(), 3);
+    assert_eq!(domain_credentials(&store, "nope.com").await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn sqlite_subscriptions() {
+    let store = store().await;
+    let subscription = |user_id, domain: &str| Subscription {
+        user_id,
+        chat_id: -100,
+        domain: domain.to_string(),
+        credentials: 3,
+    };
+
+    assert!(store.subscribe(&subscription(1, "corp.com")).await.unwrap());
+    assert!(!store.subscribe(&subscription(1, "corp.com")).await.unwrap());
+    assert!(store.subscribe(&subscription(2, "corp.com")).await.unwrap());
+    assert!(store.subscribe(&subscription(2, "corp.org")).await.unwrap());
+
+    store.set_checked("corp.com", 5).await.unwrap();
+    let all = store.subscriptions(None).await.unwrap();
+    assert_eq!(all.len(), 3);
+    assert!(all
+        .iter()
+        .filter(|x| x.domain == "corp.com")
+        .all(|x| x.credentials == 5));
+
+    assert!(store.unsubscribe(2, "corp.com").await.unwrap());
+    assert!(!store.unsubscribe(2, "corp.com").await.unwrap());
+    let user = store.subscriptions(Some(2)).await.unwrap();
+    assert_eq!(user, vec![subscription(2, "corp.org")]);
+}
+
+#[tokio::test]
+async fn sqlite_hash_range() {
+    let store = store().await;
+    store
+        .insert(&leak("corp.com", &[("", "John.Doe", "p5")]))
+        .await
+        .unwrap();
+
+    let hash = email_hash("john.doe", "", "corp.com");
+    let (prefix, suffix) = hash.split_at(5);
+    let range = store.hash_range(prefix).await.unwrap();
+    assert!(range.contains(&HashCount {
+        suffix: suffix.to_string(),
+        credentials: 2
+    }));
+
+    let hash = email_hash("admin", "vpn", "corp.com");
+    let range = store.hash_range(&hash[..5]).await.unwrap();
+    assert!(range.iter().any(|x| x.suffix == hash[5..]));
+}
+
+// Databases created before email_hashes have their credentials hashed on open
+#[tokio::test]
+async fn sqlite_email_hashes_backfill() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("old.db");
+    let path = path.to_str().unwrap();
+
+    let store = SqliteStore::open(path).await.unwrap();
+    store
+        .insert(&leak("corp.com", &[("", "john", "p1")]))
+        .await
+        .unwrap();
+    drop(store);
+
+    // Neither the hashes nor the progress of their backfill existed
+    let pool = sqlx::SqlitePool::connect(path).await.unwrap();
+    for statement in ["DELETE FROM email_hashes", "DROP TABLE email_hash_backfill"] {
+        sqlx::query(statement).execute(&pool).await.unwrap();
+    }
+    pool.close().await;
+
+    let store = SqliteStore::open(path).await.unwrap();
+    let hash = email_hash("john", "", "corp.com");
+    let range = store.hash_range(&hash[..5]).await.unwrap();
+    assert_eq!(
+        range,
+        vec![HashCount {
+            suffix: hash[5..].to_string(),
+            credentials: 1
+        }]
+    );
+}
+
+// An interrupted backfill resumes after the last batch it committed, and one
+// interrupted before its progress was kept starts over
+#[tokio::test]
+async fn sqlite_email_hashes_backfill_resumed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("interrupted.db");
+    let path = path.to_str().unwrap();
+
+    let store = SqliteStore::open(path).await.unwrap();
+    store
+        .insert(&leak("corp.com", &[("", "john", "p1"), ("", "jane", "p2")]))
+        .await
+        .unwrap();
+    drop(store);
+
+    let hashes = [
+        email_hash("john", "", "corp.com"),
+        email_hash("jane", "", "corp.com"),
+    ];
+    let interrupted = [
+        "UPDATE email_hash_backfill SET last_rowid = 1, done = FALSE",
+        "DROP TABLE email_hash_backfill",
+    ];
+    for statement in interrupted {
+        // Only the first credential was hashed
+        let pool = sqlx::SqlitePool::connect(path).await.unwrap();
+        sqlx::query("DELETE FROM email_hashes WHERE prefix || suffix = ?")
+            .bind(&hashes[1])
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(statement).execute(&pool).await.unwrap();
+        pool.close().await;
+
+        let store = SqliteStore::open(path).await.unwrap();
+        for hash in &hashes {
+            let range = store.hash_range(&hash[..5]).await.unwrap();
+            let count = range.iter().find(|x| x.suffix == hash[5..]).unwrap();
+            assert_eq!(count.credentials, 1);
+        }
+        drop(store);
+    }
+}
+
+#[tokio::test]
+async fn sqlite_pwned_passwords() {
+    let store = store().await;
+    let pwned = |hash: &str, count| PwnedHash {
+        hash: hash.to_string(),
+        count,
+    };
+
+    let sha1 = "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8";
+    let ntlm = "8846F7EAEE8FB117AD06BDD830B7586C";
+    store
+        .insert_pwned(&[pwned(sha1, 10), pwned(ntlm, 3)])
+        .await
+        .unwrap();
+    store.insert_pwned(&[pwned(sha1, 52)]).await.unwrap();
+    // The last count of a hash repeated in a batch wins
+    store
+        .insert_pwned(&[pwned(ntlm, 4), pwned(ntlm, 5)])
+        .await
+        .unwrap();
+
+    assert_eq!(store.pwned_count(sha1).await.unwrap(), Some(52));
+    assert_eq!(store.pwned_count(ntlm).await.unwrap(), Some(5));
+    assert_eq!(store.pwned_count(&"0".repeat(40)).await.unwrap(), None);
+}
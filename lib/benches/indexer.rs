@@ -0,0 +1,73 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use lib::{
+    indexer::{Indexer, IndexerOptions},
+    PublicSuffixList,
+};
+
+static LINES: u64 = 1_000_000;
+
+/// Writes a corpus of mostly valid entries with a few broken lines mixed in
+fn generate_corpus(path: &Path) {
+    let mut writer = BufWriter::new(File::create(path).unwrap());
+    let domains = [
+        "gmail.com",
+        "mail.example.co.uk",
+        "yandex.ru",
+        "corp.example.org",
+    ];
+    for i in 0..LINES {
+        let domain = domains[i as usize % domains.len()];
+        match i % 50 {
+            0 => writeln!(writer, "user{}:pass{}@{}", i, i, domain),
+            1 => writeln!(writer, "broken line {}", i),
+            _ => writeln!(writer, "user.{}@{}:password{}", i, domain, i % 1000),
+        }
+        .unwrap();
+    }
+    writer.flush().unwrap();
+}
+
+fn options(threads: usize) -> IndexerOptions {
+    IndexerOptions {
+        threads,
+        ..Default::default()
+    }
+}
+
+fn bench_indexer(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("corpus.txt");
+    let output = dir.path().join("output.csv");
+    let error = dir.path().join("error.txt");
+    generate_corpus(&input);
+
+    let mut group = c.benchmark_group("indexer");
+    group
+        .sample_size(10)
+        .throughput(Throughput::Elements(LINES));
+    for threads in [1, 0] {
+        let name = if threads == 0 {
+            "all_cores"
+        } else {
+            "one_thread"
+        };
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let st = PublicSuffixList::new("com org ru co.uk uk");
+                let mut indexer = Indexer::new(&output, &error, st, options(threads)).unwrap();
+                indexer.process(input.to_str().unwrap()).unwrap();
+                indexer.finish().unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_indexer);
+criterion_main!(benches);
@@ -0,0 +1,60 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use lib::{
+    entry::{parse_entry, regex_extract},
+    parse_domain, PublicSuffixList,
+};
+
+static TLDS: &str = "com net org ru co.uk uk";
+
+static ENTRIES: [&str; 6] = [
+    "wolya@yandex.net:5555",
+    "first.last+tag@mail.example.co.uk:hunter2",
+    "wolya:55@55@yandex.net",
+    "username@yahoo..com:parter",
+    "wolya@яндекс.рф:5555",
+    "not an entry at all",
+];
+
+fn bench_parse_domain(c: &mut Criterion) {
+    let st = PublicSuffixList::new(TLDS);
+    let mut group = c.benchmark_group("parse_domain");
+    for domain in ["example.com", "mail.example.co.uk", "a.b.c.d.example.org"] {
+        group.bench_function(domain, |b| b.iter(|| parse_domain(black_box(domain), &st)));
+    }
+    group.finish();
+}
+
+fn bench_regex_extract(c: &mut Criterion) {
+    let mut group = c.benchmark_group("regex_extract");
+    group.throughput(Throughput::Elements(ENTRIES.len() as u64));
+    group.bench_function("mixed", |b| {
+        b.iter(|| {
+            for entry in ENTRIES {
+                let _ = black_box(regex_extract(black_box(entry)));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_parse_entry(c: &mut Criterion) {
+    let st = PublicSuffixList::new(TLDS);
+    let mut group = c.benchmark_group("parse_entry");
+    group.throughput(Throughput::Elements(ENTRIES.len() as u64));
+    group.bench_function("mixed", |b| {
+        b.iter(|| {
+            for entry in ENTRIES {
+                let _ = black_box(parse_entry(black_box(entry), &st));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_parse_domain,
+    bench_regex_extract,
+    bench_parse_entry
+);
+criterion_main!(benches);
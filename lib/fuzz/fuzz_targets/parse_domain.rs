@@ -0,0 +1,23 @@
+#![no_main]
+
+use lazy_static::lazy_static;
+use lib::{parse_domain, parse_domain_full, PublicSuffixList};
+use libfuzzer_sys::fuzz_target;
+
+lazy_static! {
+    static ref ST: PublicSuffixList =
+        PublicSuffixList::new("com net org ru edu.ru co.uk uk *.ck !www.ck");
+}
+
+fuzz_target!(|data: &[u8]| {
+    let domain = String::from_utf8_lossy(data);
+
+    let (subdomain, registrable) = parse_domain(&domain, &ST);
+    assert!(domain.ends_with(registrable));
+    if !subdomain.is_empty() {
+        assert_eq!(format!("{}.{}", subdomain, registrable), domain);
+    }
+
+    let parts = parse_domain_full(&domain, &ST);
+    assert!(domain.ends_with(parts.suffix));
+});
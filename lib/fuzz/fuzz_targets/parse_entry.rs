@@ -0,0 +1,52 @@
+#![no_main]
+
+use lazy_static::lazy_static;
+use lib::{
+    entry::{parse_entry, parse_line, EntryFormat, ParseOptions},
+    DomainForm, PublicSuffixList, UsernameRules,
+};
+use libfuzzer_sys::fuzz_target;
+use regex::Regex;
+
+lazy_static! {
+    static ref ST: PublicSuffixList = PublicSuffixList::new("com net org ru co.uk uk *.ck !www.ck");
+}
+
+/// The first byte picks the entry format and parser heuristics
+fn options(selector: u8) -> ParseOptions {
+    let format = match selector % 5 {
+        0 => EntryFormat::EmailPass,
+        1 => EntryFormat::EmailHash,
+        2 => EntryFormat::EmailHashSalt,
+        3 => EntryFormat::UserEmailPass,
+        _ => EntryFormat::UrlLoginPass,
+    };
+    let flags = selector / 5;
+
+    let mut options = ParseOptions {
+        format,
+        unicode_usernames: flags & 1 != 0,
+        last_colon: flags & 2 != 0,
+        ..Default::default()
+    };
+    if flags & 4 != 0 {
+        options.domain_form = Some(DomainForm::Ascii);
+        options.username_rules = Some(UsernameRules::default());
+    }
+    if flags & 8 != 0 {
+        options.rules.username_pattern = Some(Regex::new(".").unwrap());
+    }
+    options
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&selector, line)) = data.split_first() else {
+        return;
+    };
+    let entry = String::from_utf8_lossy(line);
+
+    let _ = parse_entry(&entry, &ST);
+    if let Ok(parsed) = parse_line(&entry, &ST, &options(selector)) {
+        assert!(entry.contains(parsed.password));
+    }
+});
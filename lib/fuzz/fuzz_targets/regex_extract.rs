@@ -0,0 +1,13 @@
+#![no_main]
+
+use lib::entry::regex_extract;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let entry = String::from_utf8_lossy(data);
+    if let Ok((username, domain, password)) = regex_extract(&entry) {
+        assert!(entry.contains(username));
+        assert!(entry.contains(domain));
+        assert!(entry.contains(password));
+    }
+});
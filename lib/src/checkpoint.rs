@@ -0,0 +1,62 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use indexmap::IndexSet;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// How far an interrupted indexing run got, written when it's stopped by a
+/// signal. A run resumed from it skips the files and archive members parsed
+/// already and the lines written of the one it stopped in, so its output
+/// only holds the rest of the input
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Input of the interrupted run
+    pub input: String,
+    /// Files and archive members parsed completely, by source file, in the
+    /// order they were parsed
+    pub done: IndexSet<String>,
+    /// Source file being parsed when the run stopped, empty for stdin
+    pub current: String,
+    /// Lines of `current` parsed before the run stopped
+    pub lines: u64,
+    /// Outputs holding the records of the interrupted run
+    pub outputs: Vec<PathBuf>,
+}
+
+impl Checkpoint {
+    pub fn read(path: &Path) -> Result<Checkpoint> {
+        let file = File::open(path).map_err(|source| Error::Open {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).map_err(|source| Error::Create {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer).map_err(Error::Write)?;
+        writer.flush().map_err(Error::Write)
+    }
+
+    /// Lines of `source_file` a resumed run skips, None when it was parsed
+    /// completely
+    pub fn skipped_lines(&self, source_file: &str) -> Option<u64> {
+        if self.done.contains(source_file) {
+            None
+        } else if self.current == source_file {
+            Some(self.lines)
+        } else {
+            Some(0)
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::error::Error;
+
+/// Environment variable with the path of the config file
+pub static CONFIG_VAR: &str = "LEAKS_SUITE_CONFIG";
+
+/// Name of the config file looked up in the working directory,
+/// then in the leaks-suite user config directory
+pub static CONFIG_FILE: &str = "leaks-suite.toml";
+
+/// Settings shared by the binaries, see leaks-suite.toml.template.
+/// Command line flags and environment variables override them
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SuiteConfig {
+    /// Public suffix list file, or auto to download it
+    pub tld_path: Option<String>,
+    /// Also use the private domains section of the public suffix list
+    pub include_private_domains: Option<bool>,
+    /// Directory relative output paths are written to
+    pub output_dir: Option<PathBuf>,
+    /// Toml file with username/password validation rules
+    pub rules: Option<PathBuf>,
+    pub couchbase: CouchbaseConfig,
+    pub bot: BotConfig,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CouchbaseConfig {
+    pub uri: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub namespace: Option<String>,
+    pub bucket: Option<String>,
+    pub scope: Option<String>,
+    pub collection: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    /// Telegram bot token
+    pub token: Option<String>,
+}
+
+impl SuiteConfig {
+    /// Reads the file named by LEAKS_SUITE_CONFIG, otherwise the first
+    /// leaks-suite.toml found. Without a file every setting is unset
+    pub fn load() -> Result<SuiteConfig, Error> {
+        if let Some(path) = env::var_os(CONFIG_VAR) {
+            return SuiteConfig::from_file(Path::new(&path));
+        }
+
+        let candidates = [
+            Some(PathBuf::from(CONFIG_FILE)),
+            dirs::config_dir().map(|dir| dir.join("leaks-suite").join(CONFIG_FILE)),
+        ];
+        match candidates.into_iter().flatten().find(|path| path.is_file()) {
+            Some(path) => SuiteConfig::from_file(&path),
+            None => Ok(SuiteConfig::default()),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<SuiteConfig, Error> {
+        let contents = fs::read_to_string(path).map_err(|source| Error::Open {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        SuiteConfig::from_toml(&contents)
+    }
+
+    pub fn from_toml(s: &str) -> Result<SuiteConfig, Error> {
+        toml::from_str(s).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    /// Puts a relative `path` into output_dir
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use lib::config::SuiteConfig;
+    ///
+    /// let config = SuiteConfig::from_toml("output_dir = \"/data\"").unwrap();
+    /// assert_eq!(config.output_path(Path::new("out.csv")), Path::new("/data/out.csv"));
+    /// assert_eq!(config.output_path(Path::new("/tmp/out.csv")), Path::new("/tmp/out.csv"));
+    /// ```
+    pub fn output_path(&self, path: &Path) -> PathBuf {
+        match &self.output_dir {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// Values of the bot environment variables set in the file
+    pub fn bot_env(&self) -> Vec<(String, String)> {
+        let couchbase = &self.couchbase;
+        let vars = [
+            ("TELOXIDE_TOKEN", &self.bot.token),
+            ("TLD_PATH", &self.tld_path),
+            ("COUCH_URI", &couchbase.uri),
+            ("COUCH_USERNAME", &couchbase.username),
+            ("COUCH_PASSWORD", &couchbase.password),
+            ("COUCH_NAMESPACE", &couchbase.namespace),
+            ("COUCH_BUCKET", &couchbase.bucket),
+            ("COUCH_SCOPE", &couchbase.scope),
+            ("COUCH_COLLECTION", &couchbase.collection),
+        ];
+        vars.into_iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.clone()?)))
+            .collect()
+    }
+}
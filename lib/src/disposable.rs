@@ -0,0 +1,95 @@
+//! Domains of disposable email providers like mailinator.com, whose
+//! throwaway accounts pollute wordlists built from the leaks
+
+use std::{convert::Infallible, fs, path::PathBuf, str::FromStr, time::Duration};
+
+use crate::{domain_filter::DomainList, error::Result};
+
+/// Snapshot of the list shipped with the crate
+static EMBEDDED_DISPOSABLE: &str = include_str!("../data/disposable_domains.txt");
+
+/// Where the disposable domains are read from
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DisposableList {
+    /// The list shipped with the crate
+    #[default]
+    Bundled,
+    /// A file in the format of [`DomainList`]
+    File(PathBuf),
+    /// A list downloaded from an http(s) url, like the raw blocklist of
+    /// disposable-email-domains on GitHub
+    Url(String),
+}
+
+impl FromStr for DisposableList {
+    type Err = Infallible;
+
+    /// bundled, an http(s) url or the path of a file
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(if s == "bundled" {
+            DisposableList::Bundled
+        } else if s.starts_with("http://") || s.starts_with("https://") {
+            DisposableList::Url(s.to_string())
+        } else {
+            DisposableList::File(PathBuf::from(s))
+        })
+    }
+}
+
+impl DisposableList {
+    /// Reads the list. A downloaded list is cached, when the download fails
+    /// the last cached copy is used, then the bundled one
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lib::disposable::DisposableList;
+    ///
+    /// let domains = DisposableList::Bundled.load().unwrap();
+    /// assert!(domains.matches("mailinator.com"));
+    /// assert!(!domains.matches("gmail.com"));
+    /// ```
+    pub fn load(&self) -> Result<DomainList> {
+        match self {
+            DisposableList::Bundled => Ok(DomainList::new(EMBEDDED_DISPOSABLE)),
+            DisposableList::File(path) => DomainList::from_file(path),
+            DisposableList::Url(url) => Ok(DomainList::new(&fetch(url))),
+        }
+    }
+}
+
+fn cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("leaks-suite").join("disposable_domains.txt"))
+}
+
+fn fetch(url: &str) -> String {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(30))
+        .build();
+    let downloaded = agent
+        .get(url)
+        .call()
+        .map_err(|e| e.to_string())
+        .and_then(|response| response.into_string().map_err(|e| e.to_string()));
+
+    match downloaded {
+        Ok(list) => {
+            if let Some(path) = cache_path() {
+                let written = path
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::write(&path, &list));
+                if let Err(e) = written {
+                    tracing::warn!(path = %path.display(), error = %e, "Failed to cache the disposable domains");
+                }
+            }
+            list
+        }
+        Err(e) => {
+            tracing::warn!(url, error = %e, "Failed to download the disposable domains");
+            cache_path()
+                .and_then(|path| fs::read_to_string(path).ok())
+                .unwrap_or_else(|| EMBEDDED_DISPOSABLE.to_string())
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use std::{collections::HashSet, str::FromStr};
+
+use serde_json::Value;
+
+use crate::{
+    error::{self, Error},
+    BreachSet, CredentialData, LeakData, DOCUMENT_VERSION,
+};
+
+/// How oversized domains are split into several documents
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SplitStrategy {
+    /// Spreads credentials evenly
+    #[default]
+    Even,
+    /// Keeps every subdomain within a single document when it fits
+    Subdomain,
+}
+
+impl FromStr for SplitStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "even" => Ok(SplitStrategy::Even),
+            "subdomain" => Ok(SplitStrategy::Subdomain),
+            _ => Err(format!(
+                "unknown split strategy {}, expected even or subdomain",
+                s
+            )),
+        }
+    }
+}
+
+// Function get called very rarely, so i don't think we should
+// spend our time optimizing it. Every split keeps the breaches and the
+// organization of the domain
+pub fn split(leak_data: LeakData, n: usize) -> Vec<LeakData> {
+    let mut splits: Vec<LeakData> = (0..n)
+        .map(|_| LeakData {
+            domain: leak_data.domain.clone(),
+            credentials: Vec::new(),
+            breaches: leak_data.breaches.clone(),
+            org: leak_data.org.clone(),
+            version: leak_data.version,
+        })
+        .collect();
+
+    let total: usize = leak_data.credentials.iter().map(|x| x.data.len()).sum();
+    // The remainder is spread over the first splits, so none gets
+    // more than a credential over the others
+    let mut left: Vec<usize> = (0..n)
+        .map(|i| total / n + usize::from(i < total % n))
+        .collect();
+
+    for mut x in leak_data.credentials.into_iter() {
+        for (i, l) in left.iter_mut().enumerate() {
+            if *l == 0 {
+                continue;
+            };
+
+            let x_len = x.data.len();
+            if x_len <= *l {
+                splits[i].credentials.push(x);
+                *l -= x_len;
+                break;
+            } else {
+                let point = x_len - *l;
+                let cd = CredentialData {
+                    subdomain: x.subdomain.clone(),
+                    data: x.data.split_off(point),
+                };
+                splits[i].credentials.push(cd);
+                *l = 0;
+            }
+        }
+    }
+    splits
+}
+
+// Packs whole subdomains into documents of at most max_size bytes,
+// subdomains that don't fit on their own are split evenly
+pub fn split_by_subdomain(leak_data: LeakData, max_size: usize) -> Vec<LeakData> {
+    let room = credential_room(&leak_data, max_size);
+    let empty = || LeakData {
+        domain: leak_data.domain.clone(),
+        credentials: Vec::new(),
+        breaches: leak_data.breaches.clone(),
+        org: leak_data.org.clone(),
+        version: leak_data.version,
+    };
+
+    let mut splits = Vec::new();
+    let mut current = empty();
+    let mut current_size = 0;
+
+    for x in leak_data.credentials {
+        // With the comma separating it from the previous subdomain
+        let size = serde_json::to_string(&x).unwrap().len() + 1;
+
+        if size > room {
+            let mut oversized = empty();
+            oversized.credentials.push(x);
+            splits.extend(split(oversized, size.div_ceil(room)));
+            continue;
+        }
+
+        if current_size + size > room && !current.credentials.is_empty() {
+            splits.push(std::mem::replace(&mut current, empty()));
+            current_size = 0;
+        }
+        current_size += size;
+        current.credentials.push(x);
+    }
+
+    if !current.credentials.is_empty() {
+        splits.push(current);
+    }
+    splits
+}
+
+/// Splits `leak_data` into documents whose json is at most `max_size` bytes,
+/// a document that fits is returned as is
+pub fn fit_document(
+    leak_data: LeakData,
+    max_size: usize,
+    strategy: SplitStrategy,
+) -> Vec<LeakData> {
+    let size = serde_json::to_string(&leak_data).unwrap().len();
+    if size <= max_size {
+        return vec![leak_data];
+    }
+    split_oversized(leak_data, size, max_size, strategy)
+}
+
+/// Splits `leak_data`, whose json takes `size` bytes, into documents of at
+/// most `max_size` bytes
+pub fn split_oversized(
+    leak_data: LeakData,
+    size: usize,
+    max_size: usize,
+    strategy: SplitStrategy,
+) -> Vec<LeakData> {
+    match strategy {
+        SplitStrategy::Even => {
+            let credentials = size.saturating_sub(shared_size(&leak_data));
+            let n = credentials.div_ceil(credential_room(&leak_data, max_size));
+            split(leak_data, n.max(1))
+        }
+        SplitStrategy::Subdomain => split_by_subdomain(leak_data, max_size),
+    }
+}
+
+/// Json bytes every split of `leak_data` repeats: its domain, breaches and organization
+fn shared_size(leak_data: &LeakData) -> usize {
+    let shared = LeakData {
+        domain: leak_data.domain.clone(),
+        credentials: Vec::new(),
+        breaches: leak_data.breaches.clone(),
+        org: leak_data.org.clone(),
+        version: leak_data.version,
+    };
+    serde_json::to_string(&shared).unwrap().len()
+}
+
+/// Bytes of credentials a split of `leak_data` holds. Breaches are bounded
+/// by [`crate::MAX_BREACHES`], so they rarely take much of `max_size`, at
+/// least half of it is left for credentials regardless
+fn credential_room(leak_data: &LeakData, max_size: usize) -> usize {
+    max_size
+        .saturating_sub(shared_size(leak_data))
+        .max(max_size / 2)
+        .max(1)
+}
+
+fn credential_count(leak_data: &LeakData) -> usize {
+    leak_data.credentials.iter().map(|x| x.data.len()).sum()
+}
+
+fn push_credentials(leak_data: &mut LeakData, credential_data: CredentialData) {
+    match leak_data
+        .credentials
+        .iter_mut()
+        .find(|x| x.subdomain == credential_data.subdomain)
+    {
+        Some(x) => x.data.extend(credential_data.data),
+        None => leak_data.credentials.push(credential_data),
+    }
+}
+
+/// Credentials of `leak` that none of the stored `documents` of its domain
+/// contain, repeated credentials of `leak` are kept once. The breaches of
+/// `leak` are kept as they are
+///
+/// # Example
+///
+/// ```
+/// use lib::{document::missing_credentials, CredentialData, LeakData};
+///
+/// let leak = |data: &[(&str, &str)]| LeakData {
+///     domain: "corp.com".to_string(),
+///     credentials: vec![CredentialData {
+///         subdomain: "vpn".to_string(),
+///         data: data.iter().map(|(u, p)| (u.to_string(), p.to_string())).collect(),
+///     }],
+///     breaches: Vec::new(),
+///     org: String::new(),
+///     version: 3,
+/// };
+/// let stored = leak(&[("admin", "secret")]);
+/// let missing = missing_credentials(&[stored], &leak(&[("admin", "secret"), ("root", "toor")]));
+///
+/// assert_eq!(missing.credentials[0].data, vec![("root".to_string(), "toor".to_string())]);
+/// ```
+pub fn missing_credentials(documents: &[LeakData], leak: &LeakData) -> LeakData {
+    let mut seen: HashSet<(&str, &str, &str)> = documents
+        .iter()
+        .flat_map(|x| &x.credentials)
+        .flat_map(|x| {
+            x.data.iter().map(|(username, password)| {
+                (x.subdomain.as_str(), username.as_str(), password.as_str())
+            })
+        })
+        .collect();
+
+    let mut missing = LeakData {
+        domain: leak.domain.clone(),
+        credentials: Vec::new(),
+        breaches: leak.breaches.clone(),
+        org: leak.org.clone(),
+        version: DOCUMENT_VERSION,
+    };
+    for credential_data in &leak.credentials {
+        let data: Vec<(String, String)> = credential_data
+            .data
+            .iter()
+            .filter(|(username, password)| {
+                seen.insert((&credential_data.subdomain, username, password))
+            })
+            .cloned()
+            .collect();
+
+        if !data.is_empty() {
+            push_credentials(
+                &mut missing,
+                CredentialData {
+                    subdomain: credential_data.subdomain.clone(),
+                    data,
+                },
+            );
+        }
+    }
+    missing
+}
+
+/// Folds the stored `documents` of a domain and the missing credentials of
+/// `leak` into a single document, subdomains keep the order they first appear in
+/// and breaches are listed once, up to [`crate::MAX_BREACHES`]. The organization of `leak` replaces the stored one,
+/// unless it has none. Returns the merged document and the number of credentials added
+pub fn merge_documents(documents: Vec<LeakData>, leak: &LeakData) -> (LeakData, usize) {
+    let missing = missing_credentials(&documents, leak);
+    let added = credential_count(&missing);
+
+    let org = match leak.org.is_empty() {
+        true => documents.iter().find(|x| !x.org.is_empty()),
+        false => Some(leak),
+    };
+    let mut merged = LeakData {
+        domain: leak.domain.clone(),
+        credentials: Vec::new(),
+        breaches: Vec::new(),
+        org: org.map(|x| x.org.clone()).unwrap_or_default(),
+        version: DOCUMENT_VERSION,
+    };
+    let mut breaches = BreachSet::default();
+    for document in documents.into_iter().chain([missing]) {
+        for credential_data in document.credentials {
+            push_credentials(&mut merged, credential_data);
+        }
+        breaches.extend(document.breaches);
+    }
+    merged.breaches = breaches.into_vec();
+    (merged, added)
+}
+
+/// Brings a stored document of an older schema to [`DOCUMENT_VERSION`].
+/// Fields added since it was written get their defaults, a field changing
+/// its meaning would be converted here by the version the document has.
+/// Documents of a newer schema are refused, rewriting them would drop
+/// the fields this build doesn't know
+///
+/// # Example
+///
+/// ```
+/// use lib::{document::upgrade, DOCUMENT_VERSION};
+///
+/// let stored = serde_json::json!({
+///     "domain": "corp.com",
+///     "credentials": [{"subdomain": "vpn", "data": [["admin", "secret"]]}],
+/// });
+/// let leak = upgrade(stored).unwrap();
+/// assert_eq!(leak.version, DOCUMENT_VERSION);
+/// assert!(leak.breaches.is_empty());
+///
+/// assert!(upgrade(serde_json::json!({"domain": "corp.com", "version": 99})).is_err());
+/// ```
+pub fn upgrade(document: Value) -> error::Result<LeakData> {
+    let mut leak: LeakData = serde_json::from_value(document)?;
+    if leak.version > DOCUMENT_VERSION {
+        return Err(Error::DocumentVersion(leak.version));
+    }
+
+    leak.version = DOCUMENT_VERSION;
+    Ok(leak)
+}
@@ -0,0 +1,175 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fs::File,
+    io::{BufReader, BufWriter, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use csv::{Reader, ReaderBuilder, StringRecord, Writer, WriterBuilder};
+
+use crate::error::{Error, Result};
+
+/// Distinct domains counted in memory before they are spilled to disk
+pub const MAX_DOMAINS: usize = 1 << 20;
+
+/// Tally of credentials per registrable domain, written as a
+/// domain,credentials csv sorted by domain.
+///
+/// Counts are kept in a hash map, once it holds more than `max_domains`
+/// domains it's spilled to an anonymous temporary file sorted by domain.
+/// Spilled runs are merged and summed when the tally is finished
+///
+/// # Example
+///
+/// ```
+/// use lib::domain_counts::DomainCounts;
+///
+/// let path = std::env::temp_dir().join("domain_counts_doc.csv");
+/// let mut counts = DomainCounts::new(&path, 2).unwrap();
+/// for domain in ["b.com", "a.com", "c.com", "b.com"] {
+///     counts.add(domain).unwrap();
+/// }
+/// assert_eq!(counts.finish().unwrap(), 3);
+/// assert_eq!(
+///     std::fs::read_to_string(&path).unwrap(),
+///     "domain,credentials\na.com,1\nb.com,2\nc.com,1\n"
+/// );
+/// ```
+pub struct DomainCounts {
+    path: PathBuf,
+    output: File,
+    counts: HashMap<String, u64>,
+    max_domains: usize,
+    runs: Vec<File>,
+}
+
+impl DomainCounts {
+    /// Creates the output at `path` right away, so a bad path fails
+    /// before any input is read
+    pub fn new(path: &Path, max_domains: usize) -> Result<DomainCounts> {
+        let output = File::create(path).map_err(|source| Error::Create {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(DomainCounts {
+            path: path.to_path_buf(),
+            output,
+            counts: HashMap::new(),
+            max_domains,
+            runs: Vec::new(),
+        })
+    }
+
+    pub fn add(&mut self, domain: &str) -> Result<()> {
+        match self.counts.get_mut(domain) {
+            Some(count) => *count += 1,
+            None => {
+                self.counts.insert(domain.to_string(), 1);
+            }
+        }
+        if self.counts.len() > self.max_domains {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Path of the csv written by [`DomainCounts::finish`]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn sorted_counts(&mut self) -> Vec<(String, u64)> {
+        let mut counts: Vec<_> = self.counts.drain().collect();
+        counts.sort_unstable();
+        counts
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        let mut file = tempfile::tempfile().map_err(Error::Write)?;
+        {
+            let mut writer = WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(BufWriter::new(&mut file));
+            for (domain, count) in self.sorted_counts() {
+                writer.serialize((domain, count))?;
+            }
+            writer.flush().map_err(Error::Write)?;
+        }
+        file.seek(SeekFrom::Start(0)).map_err(Error::Write)?;
+        self.runs.push(file);
+        Ok(())
+    }
+
+    /// Writes the csv, returns the number of distinct domains
+    pub fn finish(mut self) -> Result<u64> {
+        // Everything fit into memory, no need to touch the disk
+        let counts = if self.runs.is_empty() {
+            Some(self.sorted_counts())
+        } else {
+            if !self.counts.is_empty() {
+                self.spill()?;
+            }
+            None
+        };
+
+        let mut writer = Writer::from_writer(BufWriter::new(&self.output));
+        writer.write_record(["domain", "credentials"])?;
+        if let Some(counts) = counts {
+            for record in &counts {
+                writer.serialize(record)?;
+            }
+            writer.flush().map_err(Error::Write)?;
+            return Ok(counts.len() as u64);
+        }
+
+        let mut readers: Vec<Reader<BufReader<File>>> = self
+            .runs
+            .drain(..)
+            .map(|run| {
+                ReaderBuilder::new()
+                    .has_headers(false)
+                    .from_reader(BufReader::new(run))
+            })
+            .collect();
+
+        let mut heap = BinaryHeap::new();
+        for (i, reader) in readers.iter_mut().enumerate() {
+            if let Some(head) = next_count(reader)? {
+                heap.push(Reverse((head, i)));
+            }
+        }
+
+        // Runs are sorted, so the counts of a domain pop one after another
+        let mut domains = 0;
+        let mut current: Option<(String, u64)> = None;
+        while let Some(Reverse(((domain, count), i))) = heap.pop() {
+            match &mut current {
+                Some((last, total)) if *last == domain => *total += count,
+                _ => {
+                    if let Some(record) = current.replace((domain, count)) {
+                        writer.serialize(record)?;
+                        domains += 1;
+                    }
+                }
+            }
+            if let Some(head) = next_count(&mut readers[i])? {
+                heap.push(Reverse((head, i)));
+            }
+        }
+        if let Some(record) = current {
+            writer.serialize(record)?;
+            domains += 1;
+        }
+        writer.flush().map_err(Error::Write)?;
+        Ok(domains)
+    }
+}
+
+fn next_count(reader: &mut Reader<BufReader<File>>) -> Result<Option<(String, u64)>> {
+    let mut record = StringRecord::new();
+    if !reader.read_record(&mut record)? {
+        return Ok(None);
+    }
+    Ok(Some(record.deserialize(None)?))
+}
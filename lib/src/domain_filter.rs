@@ -0,0 +1,102 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use crate::error::{Error, Result};
+
+/// Exact domains and `*.suffix` patterns, one per line.
+/// Blank lines and lines starting with # are skipped
+#[derive(Clone, Debug, Default)]
+pub struct DomainList {
+    exact: HashSet<String>,
+    suffixes: HashSet<String>,
+}
+
+impl DomainList {
+    pub fn new(patterns: &str) -> DomainList {
+        let mut list = DomainList::default();
+
+        for line in patterns.lines() {
+            let pattern = line.trim().trim_end_matches('.').to_lowercase();
+            if pattern.is_empty() || pattern.starts_with('#') {
+                continue;
+            }
+
+            match pattern.strip_prefix("*.") {
+                Some(suffix) => list.suffixes.insert(suffix.to_string()),
+                None => list.exact.insert(pattern),
+            };
+        }
+        list
+    }
+
+    pub fn from_file(path: &Path) -> Result<DomainList> {
+        let patterns = fs::read_to_string(path).map_err(|source| Error::Open {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(DomainList::new(&patterns))
+    }
+
+    /// Whether `host` is one of the exact domains or lies under a `*.suffix`,
+    /// `*.example.com` matches mail.example.com but not example.com itself
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lib::domain_filter::DomainList;
+    ///
+    /// let list = DomainList::new("example.com\n*.corp.net");
+    /// assert!(list.matches("example.com"));
+    /// assert!(!list.matches("mail.example.com"));
+    /// assert!(list.matches("vpn.eu.corp.net"));
+    /// assert!(!list.matches("corp.net"));
+    /// ```
+    pub fn matches(&self, host: &str) -> bool {
+        if self.exact.contains(host) {
+            return true;
+        }
+
+        let mut rest = host;
+        while let Some((_, parent)) = rest.split_once('.') {
+            if self.suffixes.contains(parent) {
+                return true;
+            }
+            rest = parent;
+        }
+        false
+    }
+}
+
+/// Domains kept by the indexer, matched against the full host
+/// of an entry once it's split by the public suffix list
+#[derive(Clone, Debug, Default)]
+pub struct DomainFilter {
+    /// Only hosts on this list are kept when set
+    pub only: Option<DomainList>,
+    /// Hosts on this list are dropped, even when they are on `only`
+    pub exclude: Option<DomainList>,
+    /// Disposable email providers, dropped when either the registrable
+    /// domain or the host is on the list
+    pub disposable: Option<DomainList>,
+}
+
+impl DomainFilter {
+    pub fn allows(&self, subdomain: &str, domain: &str) -> bool {
+        if self.only.is_none() && self.exclude.is_none() && self.disposable.is_none() {
+            return true;
+        }
+
+        let host = match subdomain {
+            "" => domain.to_string(),
+            _ => format!("{}.{}", subdomain, domain),
+        };
+        self.only.as_ref().is_none_or(|list| list.matches(&host))
+            && !self
+                .exclude
+                .as_ref()
+                .is_some_and(|list| list.matches(&host))
+            && !self
+                .disposable
+                .as_ref()
+                .is_some_and(|list| list.matches(domain) || list.matches(&host))
+    }
+}
@@ -0,0 +1,106 @@
+//! Misspelled mail domains like gamil.com, corrected to the provider they
+//! were meant for so their credentials don't fragment the stats
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::error::{Error, Result};
+
+/// Table shipped with the crate
+static EMBEDDED_TYPOS: &str = include_str!("../data/domain_typos.txt");
+
+/// Where the correction table is read from
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TypoTable {
+    /// The table shipped with the crate
+    #[default]
+    Bundled,
+    /// A file in the format of [`DomainCorrections::new`]
+    File(PathBuf),
+}
+
+impl FromStr for TypoTable {
+    type Err = Infallible;
+
+    /// bundled or the path of a file
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "bundled" => TypoTable::Bundled,
+            path => TypoTable::File(PathBuf::from(path)),
+        })
+    }
+}
+
+impl TypoTable {
+    pub fn load(&self) -> Result<DomainCorrections> {
+        match self {
+            TypoTable::Bundled => Ok(DomainCorrections::new(EMBEDDED_TYPOS)),
+            TypoTable::File(path) => DomainCorrections::from_file(path),
+        }
+    }
+}
+
+/// Canonical domain of every known typo
+#[derive(Clone, Debug, Default)]
+pub struct DomainCorrections {
+    corrections: HashMap<String, String>,
+}
+
+impl DomainCorrections {
+    /// Reads one "typo canonical" pair per line, separated by whitespace.
+    /// Empty lines, lines starting with # and lines without a pair are skipped
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lib::domain_typos::DomainCorrections;
+    ///
+    /// let corrections = DomainCorrections::new("# typos\nGamil.com gmail.com\nhotmial.com\n");
+    /// assert_eq!(corrections.correct("gamil.com"), Some("gmail.com"));
+    /// assert_eq!(corrections.correct("gmail.com"), None);
+    /// assert_eq!(corrections.len(), 1);
+    /// ```
+    pub fn new(table: &str) -> DomainCorrections {
+        let mut corrections = HashMap::new();
+        for line in table.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            if let (Some(typo), Some(canonical)) = (fields.next(), fields.next()) {
+                let domain = |x: &str| x.trim_end_matches('.').to_lowercase();
+                corrections.insert(domain(typo), domain(canonical));
+            }
+        }
+        DomainCorrections { corrections }
+    }
+
+    pub fn from_file(path: &Path) -> Result<DomainCorrections> {
+        let table = fs::read_to_string(path).map_err(|source| Error::Open {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(DomainCorrections::new(&table))
+    }
+
+    /// Canonical form of `domain`, a lowercase host matched as a whole.
+    /// None when it isn't a known typo
+    pub fn correct(&self, domain: &str) -> Option<&str> {
+        self.corrections.get(domain).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.corrections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.corrections.is_empty()
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    io::{self, BufRead, BufReader},
+    str::FromStr,
+};
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+/// Text encoding of the input files, see [`decode`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputEncoding {
+    /// Detected per file or archive member from a byte order mark,
+    /// or else from the bytes at its start
+    #[default]
+    Auto,
+    /// Every input is read as this encoding
+    Fixed(&'static Encoding),
+}
+
+impl FromStr for InputEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(InputEncoding::Auto);
+        }
+        Encoding::for_label(s.as_bytes())
+            .map(InputEncoding::Fixed)
+            .ok_or_else(|| {
+                format!(
+                    "unknown encoding {}, expected auto or a label like utf-8, windows-1251 or utf-16le",
+                    s
+                )
+            })
+    }
+}
+
+/// UTF-16 without a byte order mark, told apart by the zero
+/// high bytes of ascii characters in every other position
+fn sniff_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let pairs = sample.len() / 2;
+    if pairs < 4 {
+        return None;
+    }
+
+    let zeros = |offset: usize| {
+        sample
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|&&x| x == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    if odd * 2 > pairs && even * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even * 2 > pairs && odd * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Whether `sample` is utf-8, save for a character cut at its end
+fn is_utf8(sample: &[u8]) -> bool {
+    match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+/// Encoding of an input starting with `sample`
+pub fn detect(sample: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return encoding;
+    }
+    if let Some(encoding) = sniff_utf16(sample) {
+        return encoding;
+    }
+    if is_utf8(sample) {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(sample, false);
+    detector.guess(None, true)
+}
+
+/// Wraps `reader` into a transcoder to utf-8 when its encoding isn't utf-8
+/// already, utf-8 input is passed through as is. Auto detection only looks
+/// at the start of the input, as far as `reader` has it buffered.
+/// Returns the encoding the input is read as
+pub fn decode<'a>(
+    mut reader: impl BufRead + 'a,
+    encoding: InputEncoding,
+) -> io::Result<(Box<dyn BufRead + 'a>, &'static Encoding)> {
+    let encoding = match encoding {
+        InputEncoding::Fixed(encoding) => encoding,
+        InputEncoding::Auto => detect(reader.fill_buf()?),
+    };
+
+    if encoding == UTF_8 {
+        return Ok((Box::new(reader), encoding));
+    }
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding))
+        .strip_bom(true)
+        .build(reader);
+    Ok((Box::new(BufReader::new(decoder)), encoding))
+}
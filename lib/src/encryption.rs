@@ -0,0 +1,225 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs::File,
+    io::{self, Read, Write},
+    iter,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use age::{
+    secrecy::SecretString,
+    stream::{StreamReader, StreamWriter},
+    x25519, Decryptor, Encryptor, Identity, IdentityFile,
+};
+
+use crate::error::{Error, Result};
+
+/// Environment variable the passphrase is read from,
+/// so it doesn't show up in the process list or shell history
+pub static PASSPHRASE_VAR: &str = "LEAKS_PASSPHRASE";
+
+/// Key output files are encrypted with
+#[derive(Clone, Debug)]
+pub enum Encryption {
+    /// age x25519 recipient, only the holder of its identity can decrypt
+    Recipient(x25519::Recipient),
+    /// scrypt protected passphrase read from LEAKS_PASSPHRASE
+    Passphrase,
+}
+
+impl FromStr for Encryption {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("age", recipient)) => recipient
+                .parse()
+                .map(Encryption::Recipient)
+                .map_err(|e| format!("invalid age recipient {}: {}", recipient, e)),
+            None if s == "passphrase" => Ok(Encryption::Passphrase),
+            _ => Err(format!(
+                "unknown encryption {}, expected age:<recipient> or passphrase",
+                s
+            )),
+        }
+    }
+}
+
+impl Encryption {
+    pub fn extension(&self) -> &'static str {
+        "age"
+    }
+
+    /// Appends .age unless `path` already ends with it
+    pub fn output_path(&self, path: &Path) -> PathBuf {
+        if path.extension().map(|x| x == self.extension()) == Some(true) {
+            return path.to_path_buf();
+        }
+
+        let mut name = OsString::from(path.as_os_str());
+        name.push(".");
+        name.push(self.extension());
+        PathBuf::from(name)
+    }
+
+    fn encryptor(&self) -> Result<Encryptor> {
+        match self {
+            Encryption::Recipient(recipient) => {
+                Ok(Encryptor::with_recipients(iter::once(recipient as _))?)
+            }
+            Encryption::Passphrase => Ok(Encryptor::with_user_passphrase(passphrase()?)),
+        }
+    }
+}
+
+/// Key of the temporary files of a run, generated for it and never stored.
+/// Keeps plaintext off the disk while the output is encrypted
+///
+/// # Example
+///
+/// ```
+/// use std::io::{Read, Seek, SeekFrom, Write};
+///
+/// use lib::encryption::{OutputFile, TempKey};
+///
+/// let key = TempKey::generate();
+/// let mut file = tempfile::tempfile().unwrap();
+/// let mut sealed = OutputFile::new(file.try_clone().unwrap(), Some(&key.encryption())).unwrap();
+/// sealed.write_all(b"wolya@mail.com:5555\n").unwrap();
+/// sealed.finish().unwrap();
+///
+/// file.seek(SeekFrom::Start(0)).unwrap();
+/// let mut plaintext = String::new();
+/// key.decrypt(file).unwrap().read_to_string(&mut plaintext).unwrap();
+/// assert_eq!(plaintext, "wolya@mail.com:5555\n");
+/// ```
+pub struct TempKey {
+    identity: x25519::Identity,
+}
+
+impl TempKey {
+    pub fn generate() -> TempKey {
+        TempKey {
+            identity: x25519::Identity::generate(),
+        }
+    }
+
+    /// Encryption of the files only this key can decrypt
+    pub fn encryption(&self) -> Encryption {
+        Encryption::Recipient(self.identity.to_public())
+    }
+
+    pub fn decrypt<R: Read>(&self, input: R) -> Result<StreamReader<R>> {
+        let decryptor = Decryptor::new(input)?;
+        Ok(decryptor.decrypt(iter::once(&self.identity as &dyn Identity))?)
+    }
+}
+
+/// Key encrypted files are opened with
+#[derive(Clone, Debug)]
+pub enum Decryption {
+    /// age identity file with AGE-SECRET-KEY-1... lines, as written by age-keygen
+    IdentityFile(PathBuf),
+    /// scrypt protected passphrase read from LEAKS_PASSPHRASE
+    Passphrase,
+}
+
+fn passphrase() -> Result<SecretString> {
+    match env::var(PASSPHRASE_VAR) {
+        Ok(passphrase) if !passphrase.is_empty() => Ok(SecretString::from(passphrase)),
+        _ => Err(Error::MissingPassphrase),
+    }
+}
+
+/// Output file, encrypted on the fly when a key is given
+pub enum OutputFile {
+    Plain(File),
+    Encrypted(Box<StreamWriter<File>>),
+}
+
+impl OutputFile {
+    pub fn create(path: &Path, encryption: Option<&Encryption>) -> Result<OutputFile> {
+        let create_error = |source| Error::Create {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let file = File::create(path).map_err(create_error)?;
+        OutputFile::new(file, encryption).map_err(|e| match e {
+            Error::Write(source) => create_error(source),
+            e => e,
+        })
+    }
+
+    /// Writes to an open file, like an anonymous temporary one
+    pub fn new(file: File, encryption: Option<&Encryption>) -> Result<OutputFile> {
+        Ok(match encryption.map(Encryption::encryptor).transpose()? {
+            None => OutputFile::Plain(file),
+            Some(encryptor) => {
+                OutputFile::Encrypted(Box::new(encryptor.wrap_output(file).map_err(Error::Write)?))
+            }
+        })
+    }
+
+    /// Writes the last chunk of the encrypted stream, dropping the file
+    /// without finishing leaves a file that can't be decrypted
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(mut file) => file.flush(),
+            OutputFile::Encrypted(writer) => writer.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::Plain(file) => file.write(buf),
+            OutputFile::Encrypted(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.flush(),
+            OutputFile::Encrypted(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Wraps an age encrypted `input` into a reader of its plaintext
+///
+/// # Example
+///
+/// ```no_run
+/// use std::{fs::File, io::Read, path::PathBuf};
+/// use lib::encryption::{decrypt, Decryption};
+///
+/// let key = Decryption::IdentityFile(PathBuf::from("key.txt"));
+/// let mut plaintext = String::new();
+/// decrypt(File::open("leak.csv.age").unwrap(), &key)
+///     .unwrap()
+///     .read_to_string(&mut plaintext)
+///     .unwrap();
+/// ```
+pub fn decrypt<R: Read>(input: R, key: &Decryption) -> Result<StreamReader<R>> {
+    let decryptor = Decryptor::new(input)?;
+
+    let identities: Vec<Box<dyn Identity>> = match key {
+        Decryption::IdentityFile(path) => {
+            let file =
+                IdentityFile::from_file(path.to_string_lossy().into_owned()).map_err(|source| {
+                    Error::Open {
+                        path: path.clone(),
+                        source,
+                    }
+                })?;
+            file.into_identities()?
+        }
+        Decryption::Passphrase => vec![Box::new(age::scrypt::Identity::new(passphrase()?))],
+    };
+
+    Ok(decryptor.decrypt(identities.iter().map(|x| x.as_ref() as &dyn Identity))?)
+}
@@ -0,0 +1,991 @@
+use std::{
+    borrow::Cow,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+};
+
+use lazy_static::lazy_static;
+use memchr::{memchr, memchr2, memchr3, memrchr};
+use regex::Regex;
+
+use crate::{
+    domain_typos::DomainCorrections, normalize_domain, parse_domain, parsers::Parsers,
+    rules::ValidationRules, DomainForm, PublicSuffixList, UsernameRules,
+};
+
+/// Layout of the input entries
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EntryFormat {
+    /// username@domain:password or username:password@domain
+    #[default]
+    EmailPass,
+    /// username@domain:hash
+    EmailHash,
+    /// username@domain:hash:salt
+    EmailHashSalt,
+    /// nickname:username@domain:password, nickname is dropped
+    UserEmailPass,
+    /// url:login:password of stealer logs, the url host is kept as the target.
+    /// Logins that aren't emails are filed under the target domain
+    UrlLoginPass,
+}
+
+impl EntryFormat {
+    /// Value of the password_type column
+    pub fn password_type(&self) -> &'static str {
+        match self {
+            EntryFormat::EmailPass | EntryFormat::UserEmailPass | EntryFormat::UrlLoginPass => {
+                "plain"
+            }
+            EntryFormat::EmailHash => "hash",
+            EntryFormat::EmailHashSalt => "salted_hash",
+        }
+    }
+}
+
+impl FromStr for EntryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "email:pass" => Ok(EntryFormat::EmailPass),
+            "email:hash" => Ok(EntryFormat::EmailHash),
+            "email:hash:salt" => Ok(EntryFormat::EmailHashSalt),
+            "user:email:pass" => Ok(EntryFormat::UserEmailPass),
+            "url:login:pass" => Ok(EntryFormat::UrlLoginPass),
+            _ => Err(format!(
+                "unknown format {}, expected one of: email:pass, email:hash, email:hash:salt, user:email:pass, url:login:pass",
+                s
+            )),
+        }
+    }
+}
+
+/// Tunables of entry parsing
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+    pub format: EntryFormat,
+    /// IDN form domains are converted to before suffix matching, None keeps them as is
+    pub domain_form: Option<DomainForm>,
+    /// Collapses +tag and dotted aliases of the listed providers
+    pub username_rules: Option<UsernameRules>,
+    /// Fixes misspelled mail domains like gamil.com before the username rules apply
+    pub domain_corrections: Option<DomainCorrections>,
+    /// Length, charset and blocklist checks of the parsed fields
+    pub rules: ValidationRules,
+    /// Accept letters and digits of any script in usernames, not only ascii ones
+    pub unicode_usernames: bool,
+    /// Split email-first entries at their last : or ;, so the email may follow
+    /// leading fields like a nickname or an id. Entries that can't be split
+    /// this way are parsed as usual
+    pub last_colon: bool,
+    /// Parsers tried on every line, by default the ones of `format`
+    pub parsers: Parsers,
+    /// Characters splitting the username, password and any leading or trailing fields
+    pub separators: Separators,
+}
+
+static DOMAIN_PATTERN: &str = r"((?:[a-zA-Z0-9\x{80}-\x{10FFFF}](?:[a-zA-Z0-9\x{80}-\x{10FFFF}-]{0,61}[a-zA-Z0-9\x{80}-\x{10FFFF}])?\.{1,2})+[a-zA-Z0-9\x{80}-\x{10FFFF}][a-zA-Z0-9\x{80}-\x{10FFFF}]{0,61}[a-zA-Z0-9\x{80}-\x{10FFFF}])";
+
+static UNICODE_USERNAME_PATTERN: &str = r"[\p{L}\p{N}]+(?:[_\-\.\+][\p{L}\p{N}]*)*";
+
+/// RFC 5322 quoted local part, any characters but unescaped quotes
+static QUOTED_USERNAME_PATTERN: &str = r#""(?:[^"\\\r\n]|\\.)+""#;
+
+static PLAIN_USERNAME_PATTERN: &str = r"[a-zA-Z0-9]{1,35}(?:[_\-\.\+][a-zA-Z0-9]{0,35}){0,10}";
+
+/// Regexes of the entry layouts, with fields split by one class of separators
+#[derive(Debug)]
+struct EntryRegexes {
+    cred_first: Regex,
+    cred_last: Regex,
+    // Used with a custom username pattern, any username without separators is taken
+    relaxed_first: Regex,
+    relaxed_last: Regex,
+    unicode_first: Regex,
+    unicode_last: Regex,
+    quoted_first: Regex,
+    quoted_last: Regex,
+    // Emails of the last colon mode, by the username shape in use
+    email: Regex,
+    unicode_email: Regex,
+    relaxed_email: Regex,
+}
+
+impl EntryRegexes {
+    /// `class` is the body of the separator character class, like :;
+    fn new(class: &str) -> EntryRegexes {
+        let first = |username: &str| {
+            Regex::new(&format!(
+                r"^({})[{}](.+)@{}\.{{0,10}}$",
+                username, class, DOMAIN_PATTERN
+            ))
+            .unwrap()
+        };
+        let last = |username: &str| {
+            Regex::new(&format!(
+                r"^({})@{}\.{{0,10}}[{}](.+)$",
+                username, DOMAIN_PATTERN, class
+            ))
+            .unwrap()
+        };
+        let email = |username: &str| {
+            Regex::new(&format!(
+                r"^({}|{})@{}\.{{0,10}}$",
+                username, QUOTED_USERNAME_PATTERN, DOMAIN_PATTERN
+            ))
+            .unwrap()
+        };
+        let relaxed_username = format!(r"[^@{}\s]+", class);
+
+        EntryRegexes {
+            cred_first: first(PLAIN_USERNAME_PATTERN),
+            cred_last: last(PLAIN_USERNAME_PATTERN),
+            relaxed_first: first(&relaxed_username),
+            relaxed_last: last(&relaxed_username),
+            unicode_first: first(UNICODE_USERNAME_PATTERN),
+            unicode_last: last(UNICODE_USERNAME_PATTERN),
+            quoted_first: first(QUOTED_USERNAME_PATTERN),
+            quoted_last: last(QUOTED_USERNAME_PATTERN),
+            email: email(PLAIN_USERNAME_PATTERN),
+            unicode_email: email(UNICODE_USERNAME_PATTERN),
+            relaxed_email: email(&relaxed_username),
+        }
+    }
+}
+
+lazy_static! {
+    static ref DEFAULT_REGEXES: Arc<EntryRegexes> = Arc::new(EntryRegexes::new(":;"));
+}
+
+/// Characters splitting the fields of an entry, : and ; by default.
+/// Dumps split by tabs or other characters parse the same once they're listed.
+/// The first one also tells the layouts apart: entries are login:password@domain
+/// when it comes before the @
+///
+/// # Example
+///
+/// ```
+/// use lib::entry::{parse_line, ParseOptions, Separators};
+/// use lib::PublicSuffixList;
+///
+/// let st = PublicSuffixList::new("com");
+/// let options = ParseOptions {
+///     separators: r":;\t".parse::<Separators>().unwrap(),
+///     ..Default::default()
+/// };
+/// let entry = parse_line("wolya@mail.com\t55:55", &st, &options).unwrap();
+///
+/// assert_eq!(entry.username, "wolya");
+/// assert_eq!(entry.password, "55:55");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Separators {
+    bytes: Vec<u8>,
+    regexes: Arc<EntryRegexes>,
+}
+
+impl Default for Separators {
+    fn default() -> Self {
+        Separators {
+            bytes: b":;".to_vec(),
+            regexes: DEFAULT_REGEXES.clone(),
+        }
+    }
+}
+
+impl Separators {
+    /// Separator of login:password@domain entries
+    fn layout(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        c.is_ascii() && self.bytes.contains(&(c as u8))
+    }
+
+    /// Position of the first separator of `bytes`
+    fn find(&self, bytes: &[u8]) -> Option<usize> {
+        match self.bytes[..] {
+            [a] => memchr(a, bytes),
+            [a, b] => memchr2(a, b, bytes),
+            [a, b, c] => memchr3(a, b, c, bytes),
+            _ => bytes.iter().position(|c| self.bytes.contains(c)),
+        }
+    }
+}
+
+impl FromStr for Separators {
+    type Err = String;
+
+    /// Characters of `s` in order, \t stands for a tab
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes: Vec<u8> = Vec::new();
+        for c in s.replace("\\t", "\t").bytes() {
+            if !bytes.contains(&c) {
+                bytes.push(c);
+            }
+        }
+
+        let valid = !bytes.is_empty()
+            && bytes
+                .iter()
+                .all(|&c| matches!(c, b'\t' | b' ') || (c.is_ascii_punctuation() && c != b'@'));
+        if !valid {
+            return Err(format!(
+                "unknown separators {}, expected ascii punctuation other than @, spaces or \\t",
+                s
+            ));
+        }
+
+        let class: String = bytes
+            .iter()
+            .map(|&c| match c {
+                b'\t' => "\\t".to_string(),
+                c => regex::escape(&char::from(c).to_string()),
+            })
+            .collect();
+        Ok(Separators {
+            regexes: Arc::new(EntryRegexes::new(&class)),
+            bytes,
+        })
+    }
+}
+
+/// Entry split into the fields of an output record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedEntry<'a> {
+    pub username: Cow<'a, str>,
+    pub password: &'a str,
+    pub subdomain: String,
+    pub domain: String,
+    /// Host of the site the credentials are for, empty unless the entry has
+    /// a url or an ip address host. Ports of ip hosts are kept
+    pub target_domain: String,
+    pub target_type: TargetType,
+    /// Whether the domain was a typo fixed by [`ParseOptions::domain_corrections`]
+    pub corrected: bool,
+}
+
+impl<'a> ParsedEntry<'a> {
+    fn into_credentials(self) -> (Cow<'a, str>, &'a str, String, String) {
+        (self.username, self.password, self.subdomain, self.domain)
+    }
+}
+
+/// Kind of host the credentials of an entry are for, the target
+/// when there is one, the domain otherwise
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TargetType {
+    #[default]
+    Domain,
+    /// IPv4 or IPv6 address, filed under the whole address instead of
+    /// a registrable domain
+    Ip,
+}
+
+impl TargetType {
+    /// Value of the target_type column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TargetType::Domain => "domain",
+            TargetType::Ip => "ip",
+        }
+    }
+}
+
+/// Reason an entry was rejected, written to the error file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    NoSeparator,
+    BadFormat,
+    UsernameTooLong,
+    BadDomain,
+    MissingSalt,
+    BadUsername,
+    BadPassword,
+    Blocked,
+}
+
+impl ParseError {
+    /// Machine readable reason code
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ParseError::NoSeparator => "no_separator",
+            ParseError::BadFormat => "bad_format",
+            ParseError::UsernameTooLong => "username_too_long",
+            ParseError::BadDomain => "bad_domain",
+            ParseError::MissingSalt => "missing_salt",
+            ParseError::BadUsername => "bad_username",
+            ParseError::BadPassword => "bad_password",
+            ParseError::Blocked => "blocked",
+        }
+    }
+}
+
+/// Splits an entry into (username, domain, password) without any validation
+/// beyond the shape of the line
+///
+/// Well formed ascii entries are sliced by hand, the regexes only see
+/// the lines the fast path can't decide on
+pub fn regex_extract(entry: &str) -> Result<(&str, &str, &str), ParseError> {
+    let separators = Separators::default();
+    if let Some(fields) = fast_extract(entry, &separators) {
+        return Ok(fields);
+    }
+    let regexes = &separators.regexes;
+    extract(entry, &regexes.cred_first, &regexes.cred_last, &separators)
+}
+
+/// `[a-zA-Z0-9]{1,35}(?:[_\-\.\+][a-zA-Z0-9]{0,35}){0,10}` of the username regexes
+fn is_plain_username(username: &[u8]) -> bool {
+    let mut run = 0;
+    let mut separators = 0;
+
+    for (i, &c) in username.iter().enumerate() {
+        if c.is_ascii_alphanumeric() {
+            run += 1;
+            if run > 35 {
+                return false;
+            }
+        } else if matches!(c, b'_' | b'-' | b'.' | b'+') && i > 0 {
+            run = 0;
+            separators += 1;
+            if separators > 10 {
+                return false;
+            }
+        } else {
+            return false;
+        }
+    }
+    !username.is_empty()
+}
+
+/// Ascii only subset of the domain regex: two or more labels separated by
+/// one or two dots, the last label has no dashes
+fn is_plain_domain(domain: &[u8]) -> bool {
+    let mut labels = 0;
+    let mut last = &domain[..0];
+
+    for label in domain.split(|&c| c == b'.') {
+        if label.is_empty() {
+            // Empty label between two dots, a third dot isn't allowed
+            if last.is_empty() {
+                return false;
+            }
+            last = label;
+            continue;
+        }
+
+        let valid = label.len() <= 63
+            && label
+                .iter()
+                .all(|&c| c.is_ascii_alphanumeric() || c == b'-')
+            && label[0] != b'-'
+            && label[label.len() - 1] != b'-';
+        if !valid {
+            return false;
+        }
+        labels += 1;
+        last = label;
+    }
+
+    labels >= 2 && last.len() >= 2 && !last.contains(&b'-') && domain[0] != b'.'
+}
+
+/// Strips the up to 10 trailing dots the regexes allow after a domain
+fn trim_domain_dots(domain: &[u8]) -> Option<&[u8]> {
+    let end = domain.iter().rposition(|&c| c != b'.')? + 1;
+    (domain.len() - end <= 10).then_some(&domain[..end])
+}
+
+/// Matches the common entry shapes without the regexes, None means undecided
+fn fast_extract<'a>(
+    entry: &'a str,
+    separators: &Separators,
+) -> Option<(&'a str, &'a str, &'a str)> {
+    let bytes = entry.as_bytes();
+    if !bytes.is_ascii() || memchr(b'\n', bytes).is_some() {
+        return None;
+    }
+
+    let at = memchr(b'@', bytes)?;
+    let colon = memchr(separators.layout(), bytes);
+
+    // Slicing ascii bytes always lands on char boundaries
+    let (username, domain, password) = if colon.map(|colon| colon < at) == Some(true) {
+        // login:password@domain, the domain follows the last @
+        let sep = separators.find(bytes)?;
+        let at = memrchr(b'@', bytes)?;
+        let domain = trim_domain_dots(&bytes[at + 1..])?;
+        (
+            &entry[..sep],
+            &entry[at + 1..at + 1 + domain.len()],
+            &entry[sep + 1..at],
+        )
+    } else {
+        // login@domain:password, the domain runs up to the first separator
+        let sep = at + 1 + separators.find(&bytes[at + 1..])?;
+        let domain = trim_domain_dots(&bytes[at + 1..sep])?;
+        (
+            &entry[..at],
+            &entry[at + 1..at + 1 + domain.len()],
+            &entry[sep + 1..],
+        )
+    };
+
+    let valid = !password.is_empty()
+        && is_plain_username(username.as_bytes())
+        && is_plain_domain(domain.as_bytes());
+    valid.then_some((username, domain, password))
+}
+
+/// Specifies used format, by whichever of @ and the layout separator comes first
+/// if true: login:password@domain
+/// if false: login@domain:password
+/// None if the entry has neither
+pub(crate) fn credentials_first(entry: &str, separators: &Separators) -> Option<bool> {
+    let layout = separators.layout();
+    let n = memchr2(b'@', layout, entry.as_bytes())?;
+    Some(entry.as_bytes()[n] == layout)
+}
+
+fn extract<'a>(
+    entry: &'a str,
+    first_re: &Regex,
+    last_re: &Regex,
+    separators: &Separators,
+) -> Result<(&'a str, &'a str, &'a str), ParseError> {
+    let credentials_first = credentials_first(entry, separators).ok_or(ParseError::NoSeparator)?;
+
+    let (username, domain, password) = if credentials_first {
+        let (username, password, domain) = if let Some(caps) = first_re.captures(entry) {
+            if caps.len() < 3 {
+                return Err(ParseError::BadFormat);
+            } else {
+                (
+                    caps.get(1).unwrap().as_str(),
+                    caps.get(2).unwrap().as_str(),
+                    caps.get(3).unwrap().as_str(),
+                )
+            }
+        } else {
+            return Err(ParseError::BadFormat);
+        };
+        (username, domain, password)
+    } else {
+        let (username, domain, password) = if let Some(caps) = last_re.captures(entry) {
+            if caps.len() < 3 {
+                return Err(ParseError::BadFormat);
+            } else {
+                (
+                    caps.get(1).unwrap().as_str(),
+                    caps.get(2).unwrap().as_str(),
+                    caps.get(3).unwrap().as_str(),
+                )
+            }
+        } else {
+            return Err(ParseError::BadFormat);
+        };
+        (username, domain, password)
+    };
+
+    Ok((username, domain, password))
+}
+
+/// Parses an entry like username@domain:password or username:password@domain
+/// into (username, password, subdomain, domain)
+///
+/// # Example
+///
+/// ```
+/// use lib::entry::parse_entry;
+/// use lib::PublicSuffixList;
+///
+/// let st = PublicSuffixList::new("com net");
+/// let (username, password, subdomain, domain) =
+///     parse_entry("wolya@mail.yandex.net:5555", &st).unwrap();
+///
+/// assert_eq!(username, "wolya");
+/// assert_eq!(password, "5555");
+/// assert_eq!(subdomain, "mail");
+/// assert_eq!(domain, "yandex.net");
+/// ```
+pub fn parse_entry<'a>(
+    entry: &'a str,
+    st: &PublicSuffixList,
+) -> Result<(Cow<'a, str>, &'a str, String, String), ParseError> {
+    let entry = parse_credentials(entry, st, &ParseOptions::default())?;
+    Ok(entry.into_credentials())
+}
+
+/// Address and port of an ip host like 10.0.0.1, 10.0.0.1:8080,
+/// 2001:db8::1 or [2001:db8::1]:8080
+fn ip_host(host: &str) -> Option<(IpAddr, Option<u16>)> {
+    if let Some(rest) = host.strip_prefix('[') {
+        let (ip, port) = rest.split_once(']')?;
+        let port = match port {
+            "" => None,
+            port => Some(port.strip_prefix(':')?.parse().ok()?),
+        };
+        return Some((IpAddr::V6(ip.parse::<Ipv6Addr>().ok()?), port));
+    }
+    if let Ok(ip) = host.parse() {
+        return Some((ip, None));
+    }
+    let (ip, port) = host.rsplit_once(':')?;
+    Some((IpAddr::V4(ip.parse().ok()?), Some(port.parse().ok()?)))
+}
+
+/// Whether `username` has the shape `options` ask for, like the username
+/// group of the regexes
+fn is_login(username: &str, options: &ParseOptions) -> bool {
+    match (&options.rules.username_pattern, options.unicode_usernames) {
+        (Some(_), _) => {
+            !username.is_empty()
+                && !username.contains(|c: char| {
+                    c == '@' || c.is_whitespace() || options.separators.contains(c)
+                })
+        }
+        (None, true) => {
+            username.starts_with(char::is_alphanumeric)
+                && username
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '+'))
+        }
+        (None, false) => is_plain_username(username.as_bytes()),
+    }
+}
+
+/// Splits an entry whose host is an ip address: login:password@ip with an
+/// optional port, login@ipv4:password or login@[ipv6]:password
+fn ip_extract<'a>(entry: &'a str, options: &ParseOptions) -> Option<(&'a str, &'a str, &'a str)> {
+    let separators = &options.separators;
+    let (username, host, password) = if credentials_first(entry, separators)? {
+        let at = entry.rfind('@')?;
+        let sep = separators.find(entry.as_bytes())?;
+        (&entry[..sep], &entry[at + 1..], &entry[sep + 1..at])
+    } else {
+        let (username, rest) = entry.split_once('@')?;
+        let host_end = match rest.strip_prefix('[') {
+            Some(ipv6) => ipv6.find(']')? + 2,
+            None => rest.find(|c: char| !(c.is_ascii_digit() || c == '.'))?,
+        };
+        let (host, tail) = rest.split_at(host_end);
+        (
+            username,
+            host,
+            tail.strip_prefix(|c| separators.contains(c))?,
+        )
+    };
+
+    let valid = ip_host(host).is_some() && !password.is_empty() && is_login(username, options);
+    valid.then_some((username, host, password))
+}
+
+fn ip_fields<'a>(
+    username: &'a str,
+    ip: IpAddr,
+    port: Option<u16>,
+    password: &'a str,
+    options: &ParseOptions,
+) -> Result<ParsedEntry<'a>, ParseError> {
+    options.rules.check_username(username)?;
+    options.rules.check_password(password)?;
+
+    let domain = ip.to_string();
+    options.rules.check_domain(&domain)?;
+    let target_domain = match port {
+        Some(port) => SocketAddr::new(ip, port).to_string(),
+        None => domain.clone(),
+    };
+    Ok(ParsedEntry {
+        username: Cow::Borrowed(username),
+        password,
+        subdomain: String::new(),
+        domain,
+        target_domain,
+        target_type: TargetType::Ip,
+        corrected: false,
+    })
+}
+
+/// Fields of credentials for `host`, an ip address with an optional port or a domain
+pub(crate) fn host_fields<'a>(
+    username: &'a str,
+    host: &str,
+    password: &'a str,
+    st: &PublicSuffixList,
+    options: &ParseOptions,
+) -> Result<ParsedEntry<'a>, ParseError> {
+    if let Some((ip, port)) = ip_host(host.trim()) {
+        return ip_fields(username, ip, port, password, options);
+    }
+
+    credential_fields(username, host, password, st, options)
+}
+
+/// Splits an entry whose username is a quoted local part, like
+/// "john doe"@mail.com:pass. The quotes are kept in the username
+fn quoted_extract<'a>(
+    entry: &'a str,
+    regexes: &EntryRegexes,
+) -> Result<(&'a str, &'a str, &'a str), ParseError> {
+    if let Some(caps) = regexes.quoted_last.captures(entry) {
+        return Ok((
+            caps.get(1).unwrap().as_str(),
+            caps.get(2).unwrap().as_str(),
+            caps.get(3).unwrap().as_str(),
+        ));
+    }
+    match regexes.quoted_first.captures(entry) {
+        Some(caps) => Ok((
+            caps.get(1).unwrap().as_str(),
+            caps.get(3).unwrap().as_str(),
+            caps.get(2).unwrap().as_str(),
+        )),
+        None => Err(ParseError::BadFormat),
+    }
+}
+
+/// Splits an entry at its last separator into email and password,
+/// fields in front of the email are dropped
+fn last_colon_extract<'a>(
+    entry: &'a str,
+    options: &ParseOptions,
+) -> Result<(&'a str, &'a str, &'a str), ParseError> {
+    let separators = &options.separators;
+    let is_separator = |c| separators.contains(c);
+    let sep = entry.rfind(is_separator).ok_or(ParseError::NoSeparator)?;
+    let (fields, password) = (&entry[..sep], &entry[sep + 1..]);
+
+    // A quoted local part may hold separators itself
+    let email = match fields.find('"') {
+        Some(quote) if quote == 0 || fields[..quote].ends_with(is_separator) => &fields[quote..],
+        _ => fields.rsplit(is_separator).next().unwrap_or(fields),
+    };
+
+    let regexes = &separators.regexes;
+    let email_re = match (&options.rules.username_pattern, options.unicode_usernames) {
+        (Some(_), _) => &regexes.relaxed_email,
+        (None, true) => &regexes.unicode_email,
+        (None, false) => &regexes.email,
+    };
+    match email_re.captures(email) {
+        Some(caps) if !password.is_empty() => Ok((
+            caps.get(1).unwrap().as_str(),
+            caps.get(2).unwrap().as_str(),
+            password,
+        )),
+        _ => Err(ParseError::BadFormat),
+    }
+}
+
+/// Splits an entry into (username, domain, password) with the username
+/// shape and heuristics `options` ask for
+fn split_fields<'a>(
+    entry: &'a str,
+    options: &ParseOptions,
+) -> Result<(&'a str, &'a str, &'a str), ParseError> {
+    if options.last_colon {
+        if let Ok(fields) = last_colon_extract(entry, options) {
+            return Ok(fields);
+        }
+    }
+
+    let separators = &options.separators;
+    let regexes = &separators.regexes;
+    if entry.starts_with('"') {
+        return quoted_extract(entry, regexes);
+    }
+
+    let (first_re, last_re) = match (&options.rules.username_pattern, options.unicode_usernames) {
+        (Some(_), _) => {
+            return extract(
+                entry,
+                &regexes.relaxed_first,
+                &regexes.relaxed_last,
+                separators,
+            )
+        }
+        (None, true) => (&regexes.unicode_first, &regexes.unicode_last),
+        (None, false) => (&regexes.cred_first, &regexes.cred_last),
+    };
+    match fast_extract(entry, separators) {
+        Some(fields) => Ok(fields),
+        None => extract(entry, first_re, last_re, separators),
+    }
+}
+
+fn parse_credentials<'a>(
+    entry: &'a str,
+    st: &PublicSuffixList,
+    options: &ParseOptions,
+) -> Result<ParsedEntry<'a>, ParseError> {
+    let (username, host, password) = match ip_extract(entry, options) {
+        Some(fields) => fields,
+        None => split_fields(entry, options)?,
+    };
+    host_fields(username, host, password, st, options)
+}
+
+pub(crate) fn credential_fields<'a>(
+    username: &'a str,
+    domain: &str,
+    password: &'a str,
+    st: &PublicSuffixList,
+    options: &ParseOptions,
+) -> Result<ParsedEntry<'a>, ParseError> {
+    options.rules.check_username(username)?;
+    options.rules.check_password(password)?;
+
+    let mut domain = normalize_host(domain, options)?;
+    let canonical = options
+        .domain_corrections
+        .as_ref()
+        .and_then(|corrections| corrections.correct(&domain));
+    let corrected = canonical.is_some();
+    if let Some(canonical) = canonical {
+        domain = canonical.to_string();
+    }
+
+    let username = match &options.username_rules {
+        Some(rules) => rules.normalize(username, &domain),
+        None => Cow::Borrowed(username),
+    };
+
+    let (subdomain, domain) = parse_domain(&domain, st);
+    options.rules.check_domain(domain)?;
+
+    Ok(ParsedEntry {
+        username,
+        password,
+        subdomain: subdomain.to_string(),
+        domain: domain.to_string(),
+        target_domain: String::new(),
+        target_type: TargetType::Domain,
+        corrected,
+    })
+}
+
+fn normalize_host(domain: &str, options: &ParseOptions) -> Result<String, ParseError> {
+    let domain = domain.trim();
+
+    if domain.is_empty() {
+        return Err(ParseError::BadDomain);
+    }
+
+    let domain = domain.to_lowercase().replace("..", ".");
+    // Non ascii domains are only accepted when they get normalized
+    match options.domain_form {
+        Some(form) => normalize_domain(&domain, form).ok_or(ParseError::BadDomain),
+        None if !domain.is_ascii() => Err(ParseError::BadDomain),
+        None => Ok(domain),
+    }
+}
+
+/// Splits a url into the host and everything after it, the scheme is dropped
+pub(crate) fn url_host(url: &str) -> (&str, &str) {
+    let rest = match url.find("://") {
+        Some(n) => &url[n + 3..],
+        None => url,
+    };
+    let host_end = match rest.strip_prefix('[') {
+        // The colons of an ipv6 address are part of the host
+        Some(ipv6) => ipv6.find(']').map_or(rest.len(), |n| n + 2),
+        None => rest.find(['/', ':', '?', '#']).unwrap_or(rest.len()),
+    };
+    rest.split_at(host_end)
+}
+
+/// Splits url:login:password into the url host and login:password.
+/// The scheme, port, path and query of the url are dropped
+fn split_url(entry: &str) -> Result<(&str, &str), ParseError> {
+    let (host, mut tail) = url_host(entry);
+    if tail.is_empty() {
+        return Err(ParseError::NoSeparator);
+    }
+
+    // A port is only told apart from a numeric login by the path after it
+    if let Some(port) = tail.strip_prefix(':') {
+        let digits = port.len() - port.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits > 0 && port[digits..].starts_with(['/', '?', '#']) {
+            tail = &port[digits..];
+        }
+    }
+
+    match tail.find(':') {
+        Some(n) => Ok((host, &tail[n + 1..])),
+        None => Err(ParseError::NoSeparator),
+    }
+}
+
+/// Parses an entry like https://site.com/login:user@mail.com:password
+///
+/// # Example
+///
+/// ```
+/// use lib::entry::parse_url_entry;
+/// use lib::PublicSuffixList;
+///
+/// let st = PublicSuffixList::new("com");
+/// let entry = parse_url_entry("https://site.com/login:wolya@mail.com:5555", &st).unwrap();
+///
+/// assert_eq!(entry.username, "wolya");
+/// assert_eq!(entry.domain, "mail.com");
+/// assert_eq!(entry.target_domain, "site.com");
+/// ```
+pub fn parse_url_entry<'a>(
+    entry: &'a str,
+    st: &PublicSuffixList,
+) -> Result<ParsedEntry<'a>, ParseError> {
+    parse_url_credentials(entry, st, &ParseOptions::default())
+}
+
+pub(crate) fn parse_url_credentials<'a>(
+    entry: &'a str,
+    st: &PublicSuffixList,
+    options: &ParseOptions,
+) -> Result<ParsedEntry<'a>, ParseError> {
+    let (host, credentials) = split_url(entry)?;
+    let (login, password) = credentials
+        .split_once(|c| options.separators.contains(c))
+        .ok_or(ParseError::NoSeparator)?;
+
+    url_fields(host, login, password, st, options)
+}
+
+/// Parses the url, login and password of a stealer log record
+///
+/// # Example
+///
+/// ```
+/// use lib::entry::{parse_url_fields, ParseOptions};
+/// use lib::PublicSuffixList;
+///
+/// let st = PublicSuffixList::new("com");
+/// let options = ParseOptions::default();
+/// let entry = parse_url_fields("https://site.com/a:b", "admin", "5555", &st, &options).unwrap();
+///
+/// assert_eq!(entry.username, "admin");
+/// assert_eq!(entry.domain, "site.com");
+/// assert_eq!(entry.target_domain, "site.com");
+/// ```
+pub fn parse_url_fields<'a>(
+    url: &str,
+    login: &'a str,
+    password: &'a str,
+    st: &PublicSuffixList,
+    options: &ParseOptions,
+) -> Result<ParsedEntry<'a>, ParseError> {
+    url_fields(url_host(url.trim()).0, login.trim(), password, st, options)
+}
+
+pub(crate) fn url_fields<'a>(
+    host: &str,
+    login: &'a str,
+    password: &'a str,
+    st: &PublicSuffixList,
+    options: &ParseOptions,
+) -> Result<ParsedEntry<'a>, ParseError> {
+    let ip = ip_host(host.trim()).map(|(ip, _)| ip);
+    let target_domain = match ip {
+        Some(ip) => ip.to_string(),
+        None => normalize_host(host, options)?,
+    };
+    let valid = target_domain
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '.' | '-' | ':'));
+    if !valid {
+        return Err(ParseError::BadDomain);
+    }
+    let target_type = match ip {
+        Some(_) => TargetType::Ip,
+        None => TargetType::Domain,
+    };
+
+    if login.is_empty() || password.is_empty() {
+        return Err(ParseError::BadFormat);
+    }
+
+    if let Some((username, domain)) = login.rsplit_once('@') {
+        // Like email:pass entries, an email login needs both of its parts
+        if username.is_empty() || domain.is_empty() {
+            return Err(ParseError::BadFormat);
+        }
+        let entry = host_fields(username, domain, password, st, options)?;
+        return Ok(ParsedEntry {
+            target_domain,
+            target_type,
+            ..entry
+        });
+    }
+
+    if let Some(ip) = ip {
+        return ip_fields(login, ip, None, password, options);
+    }
+
+    options.rules.check_username(login)?;
+    options.rules.check_password(password)?;
+
+    let (subdomain, domain) = parse_domain(&target_domain, st);
+    options.rules.check_domain(domain)?;
+    Ok(ParsedEntry {
+        username: Cow::Borrowed(login),
+        password,
+        subdomain: subdomain.to_string(),
+        domain: domain.to_string(),
+        target_domain,
+        target_type,
+        corrected: false,
+    })
+}
+
+/// Parses an entry of any format into the fields of an output record,
+/// trying the parsers of `options` in order
+pub fn parse_line<'a>(
+    entry: &'a str,
+    st: &PublicSuffixList,
+    options: &ParseOptions,
+) -> Result<ParsedEntry<'a>, ParseError> {
+    let (entry, _) = options.parsers.parse(entry, st, options)?;
+    Ok(entry)
+}
+
+/// Same as [`parse_entry`], but respects the entry layout and normalization
+/// described by `options`
+pub fn parse_formatted_entry<'a>(
+    entry: &'a str,
+    st: &PublicSuffixList,
+    options: &ParseOptions,
+) -> Result<(Cow<'a, str>, &'a str, String, String), ParseError> {
+    // The target is dropped, see parse_line
+    Ok(parse_formatted(entry, st, options)?.into_credentials())
+}
+
+pub(crate) fn parse_formatted<'a>(
+    entry: &'a str,
+    st: &PublicSuffixList,
+    options: &ParseOptions,
+) -> Result<ParsedEntry<'a>, ParseError> {
+    let format = options.format;
+    let entry = match format {
+        EntryFormat::UserEmailPass => match entry.split_once(|c| options.separators.contains(c)) {
+            Some((_, rest)) => rest,
+            None => return Err(ParseError::NoSeparator),
+        },
+        EntryFormat::UrlLoginPass => return parse_url_credentials(entry, st, options),
+        _ => entry,
+    };
+
+    let parsed = parse_credentials(entry, st, options)?;
+
+    if format == EntryFormat::EmailHashSalt
+        && !parsed.password.contains(|c| options.separators.contains(c))
+    {
+        return Err(ParseError::MissingSalt);
+    }
+
+    Ok(parsed)
+}
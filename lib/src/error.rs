@@ -0,0 +1,76 @@
+use std::{io, path::PathBuf};
+
+use thiserror::Error;
+
+/// Failures of an indexing run
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("can't open {}: {source}", path.display())]
+    Open {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("can't create {}: {source}", path.display())]
+    Create {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("can't read input: {0}")]
+    Read(#[source] io::Error),
+    #[error("can't write output: {0}")]
+    Write(#[source] io::Error),
+    #[error("can't write csv: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("can't write json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("can't write parquet: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("can't build a record batch: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+    #[error("broken zip archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("can't start the thread pool: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+    #[error("unsupported input type {0}")]
+    UnsupportedInput(String),
+    #[error("invalid rules: {0}")]
+    Rules(String),
+    #[error("invalid config: {0}")]
+    Config(String),
+    #[error("can't encrypt output: {0}")]
+    Encrypt(#[from] age::EncryptError),
+    #[error("can't decrypt input: {0}")]
+    Decrypt(#[from] age::DecryptError),
+    #[error("LEAKS_PASSPHRASE must be set to use a passphrase")]
+    MissingPassphrase,
+    #[error("{} doesn't match any output of the manifest", .0.display())]
+    ManifestMismatch(PathBuf),
+    #[error("document version {0} is newer than this build supports")]
+    DocumentVersion(u32),
+    #[error("missing column {0}")]
+    MissingColumn(String),
+    #[error("interrupted by a signal")]
+    Interrupted,
+    #[cfg(feature = "kafka")]
+    #[error("can't publish to kafka: {0}")]
+    Kafka(#[from] rdkafka::error::KafkaError),
+    #[cfg(feature = "kafka")]
+    #[error("{failed} records couldn't be delivered to kafka: {source}")]
+    KafkaDelivery {
+        failed: u64,
+        #[source]
+        source: rdkafka::error::KafkaError,
+    },
+}
+
+impl Error {
+    /// Whether the error comes from a broken input rather than the output,
+    /// only those can be skipped
+    pub fn is_input(&self) -> bool {
+        matches!(self, Error::Open { .. } | Error::Read(_) | Error::Zip(_))
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
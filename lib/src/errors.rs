@@ -0,0 +1,102 @@
+//! Errors of the binaries: context on what a failed stage was doing and a
+//! report of the whole chain of causes once it reaches main
+
+use std::{error::Error, fmt, process::ExitCode};
+
+use tracing::{error, Level};
+
+use crate::error::Error as LibError;
+
+/// Error of any stage, as returned by the run functions of the binaries.
+/// Errors of async stages are Send + Sync, they are cast with `as BoxError`
+pub type BoxError = Box<dyn Error>;
+
+/// What a stage was doing when `source` failed it. It's Send + Sync,
+/// so `?` turns it into the boxed errors of sync and async stages alike
+#[derive(Debug)]
+pub struct ContextError {
+    context: String,
+    source: Box<dyn Error + Send + Sync>,
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.context)
+    }
+}
+
+impl Error for ContextError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Adds what was being done to the error of a result
+///
+/// # Example
+///
+/// ```
+/// use lib::errors::{report, Context};
+///
+/// let result = std::fs::read("/missing/tld.dat").with_context(|| "can't read the tld file");
+/// let error = result.unwrap_err();
+/// assert!(report(&error).starts_with("can't read the tld file: "));
+/// ```
+pub trait Context<T> {
+    fn context(self, context: impl fmt::Display) -> Result<T, ContextError>;
+
+    /// Like [`Context::context`], the context is only built on errors
+    fn with_context<C: fmt::Display>(self, context: impl FnOnce() -> C) -> Result<T, ContextError>;
+}
+
+impl<T, E: Into<Box<dyn Error + Send + Sync>>> Context<T> for Result<T, E> {
+    fn context(self, context: impl fmt::Display) -> Result<T, ContextError> {
+        self.with_context(|| context)
+    }
+
+    fn with_context<C: fmt::Display>(self, context: impl FnOnce() -> C) -> Result<T, ContextError> {
+        self.map_err(|source| ContextError {
+            context: context().to_string(),
+            source: source.into(),
+        })
+    }
+}
+
+/// The error followed by its causes, like
+/// "can't read the tld file: No such file or directory (os error 2)".
+/// Causes already spelled out by the message before them are skipped
+pub fn report(error: &(dyn Error + 'static)) -> String {
+    let mut report = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        let cause_message = cause.to_string();
+        if !report.ends_with(&cause_message) {
+            report.push_str(": ");
+            report.push_str(&cause_message);
+        }
+        source = cause.source();
+    }
+    report
+}
+
+/// Exit code of a finished run, failures are logged with their causes,
+/// or printed to stderr when logging is turned off. Runs stopped by a
+/// signal exit with 130, like shells report an interrupted command
+pub fn exit_code<E: Into<BoxError>>(result: Result<(), E>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let e = e.into();
+            let report = report(e.as_ref());
+            if tracing::enabled!(Level::ERROR) {
+                error!(error = %report, "Failed");
+            } else {
+                eprintln!("Error: {}", report);
+            }
+            match e.downcast_ref::<LibError>() {
+                Some(LibError::Interrupted) => ExitCode::from(130),
+                _ => ExitCode::FAILURE,
+            }
+        }
+    }
+}
@@ -0,0 +1,1290 @@
+use std::{
+    fs::File,
+    io::{self, prelude::*, BufReader, BufWriter, Cursor},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use bzip2::bufread::BzDecoder;
+use csv::WriterBuilder;
+use flate2::bufread::GzDecoder;
+use growable_bloom_filter::GrowableBloom;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use tar::Archive;
+use tracing::{info, warn};
+use walkdir::WalkDir;
+use xz2::bufread::XzDecoder;
+use zip::{read::read_zipfile_from_stream, ZipArchive};
+
+#[cfg(feature = "kafka")]
+use crate::kafka::{KafkaSink, KafkaTarget};
+use crate::{
+    checkpoint::Checkpoint,
+    domain_counts::{DomainCounts, MAX_DOMAINS},
+    domain_filter::DomainFilter,
+    encoding::{decode, InputEncoding},
+    encryption::Encryption,
+    entry::{parse_url_fields, EntryFormat, ParseOptions, ParsedEntry},
+    error::{Error, Result},
+    output::{
+        Column, CompressedFile, Compression, CsvFile, CsvOptions, OutputFormat, OutputWriter,
+    },
+    progress::{self, TICK},
+    redact::Redaction,
+    report::Stats,
+    rules::ValidationRules,
+    sanitize::Sanitizer,
+    shard::{member_path, ShardKey, Sharding, SplitOutput},
+    stealer::{is_password_file, read_password_file},
+    telemetry::FlushOnPanic,
+    LeakRecord, PublicSuffixList,
+};
+
+/// Amount of lines handed to the thread pool at once
+static CHUNK_SIZE: usize = 65536;
+
+/// Error file format: plain echoes rejected lines with //member markers,
+/// csv writes member,reason,line records
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Plain,
+    Csv,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(ErrorFormat::Plain),
+            "csv" => Ok(ErrorFormat::Csv),
+            _ => Err(format!("unknown error format {}, expected plain or csv", s)),
+        }
+    }
+}
+
+/// Error file, encrypted like the output since rejected lines carry
+/// passwords too
+enum ErrorWriter {
+    Plain(FlushOnPanic<BufWriter<CompressedFile>>),
+    Csv(Box<CsvFile>),
+    /// Error file of a batch, flushed by its [`SharedOutput`]
+    Shared(Arc<Mutex<SharedErrors>>),
+}
+
+/// Error file of a batch along with the member whose errors were written
+/// last. Plain files repeat the marker of a member whenever another
+/// indexer of the batch wrote errors in between
+struct SharedErrors {
+    writer: ErrorWriter,
+    member: String,
+}
+
+impl ErrorWriter {
+    fn new(
+        error_path: &Path,
+        format: ErrorFormat,
+        encryption: Option<&Encryption>,
+    ) -> Result<ErrorWriter> {
+        let error = CompressedFile::encrypted(error_path, None, encryption)?;
+
+        Ok(match format {
+            ErrorFormat::Plain => ErrorWriter::Plain(FlushOnPanic::new(BufWriter::new(error))),
+            ErrorFormat::Csv => {
+                ErrorWriter::Csv(Box::new(CsvFile::new(error, &WriterBuilder::new())))
+            }
+        })
+    }
+
+    /// Flushes the file, an encrypted one gets its last chunk written
+    fn finish(self) -> Result<()> {
+        let file = match self {
+            ErrorWriter::Plain(writer) => writer
+                .into_inner()
+                .into_inner()
+                .map_err(|e| Error::Write(e.into_error()))?,
+            ErrorWriter::Csv(writer) => writer.into_inner()?,
+            ErrorWriter::Shared(_) => return Ok(()),
+        };
+        file.finish().map_err(Error::Write)
+    }
+
+    fn write_member(&mut self, name: &str) -> Result<()> {
+        match self {
+            ErrorWriter::Plain(writer) => writer
+                .write_all((format!("//{}\n", name)).as_bytes())
+                .map_err(Error::Write)?,
+            // Csv records carry the member name themselves
+            ErrorWriter::Csv(_) => {}
+            ErrorWriter::Shared(shared) => {
+                let mut shared = shared.lock().unwrap();
+                shared.writer.write_member(name)?;
+                shared.member = name.to_string();
+            }
+        }
+        Ok(())
+    }
+
+    fn write_error(&mut self, member: &str, error: &str, line: &str) -> Result<()> {
+        match self {
+            ErrorWriter::Plain(writer) => writer
+                .write_all((line.to_owned() + "\n").as_bytes())
+                .map_err(Error::Write)?,
+            ErrorWriter::Csv(writer) => writer.write_record([member, error, line])?,
+            ErrorWriter::Shared(shared) => {
+                let mut shared = shared.lock().unwrap();
+                if shared.member != member {
+                    shared.writer.write_member(member)?;
+                    shared.member = member.to_string();
+                }
+                shared.writer.write_error(member, error, line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `buf`, the start of an input, carries the magic bytes of
+/// one of the compressions [`decompress`] unpacks
+pub fn is_compressed(buf: &[u8]) -> bool {
+    matches!(
+        infer::get(buf).map(|kind| kind.mime_type()),
+        Some("application/gzip" | "application/zstd" | "application/x-xz" | "application/x-bzip2")
+    )
+}
+
+/// Wraps `input_reader` into a decoder of the compression its magic bytes
+/// announce: gzip, zstd, xz or bzip2. Anything else is read as is
+pub fn decompress<'a>(mut input_reader: impl BufRead + 'a) -> Result<Box<dyn Read + 'a>> {
+    let mime = input_reader
+        .fill_buf()
+        .ok()
+        .and_then(infer::get)
+        .map(|kind| kind.mime_type());
+
+    Ok(match mime {
+        Some("application/gzip") => Box::new(GzDecoder::new(input_reader)),
+        Some("application/zstd") => {
+            Box::new(zstd::Decoder::with_buffer(input_reader).map_err(Error::Read)?)
+        }
+        Some("application/x-xz") => Box::new(XzDecoder::new(input_reader)),
+        Some("application/x-bzip2") => Box::new(BzDecoder::new(input_reader)),
+        _ => Box::new(input_reader),
+    })
+}
+
+/// Bytes of the start of an input looked at by auto detection,
+/// a tar archive is told apart by the magic of its first header block
+static SNIFF_SIZE: u64 = 512;
+
+/// Archives and compressed files of archive members that are unpacked,
+/// other archives like rar are skipped
+static NESTED_ARCHIVES: [&str; 6] = [
+    "application/x-tar",
+    "application/zip",
+    "application/gzip",
+    "application/zstd",
+    "application/x-xz",
+    "application/x-bzip2",
+];
+
+/// Reads the start of `reader` for sniffing, returns it along with
+/// a reader of the whole input
+fn sniff<'a>(mut reader: impl Read + 'a) -> Result<(Vec<u8>, impl Read + 'a)> {
+    let mut head = Vec::new();
+    reader
+        .by_ref()
+        .take(SNIFF_SIZE)
+        .read_to_end(&mut head)
+        .map_err(Error::Read)?;
+    Ok((head.clone(), Cursor::new(head).chain(reader)))
+}
+
+/// Knobs of an indexing run that don't involve opening files
+#[derive(Clone)]
+pub struct IndexerOptions {
+    /// tar, tar.gz, tar.zst, tar.xz, tar.bz2, zip, dir, stealer, plain or auto
+    pub input_type: String,
+    pub parse: ParseOptions,
+    pub output_format: OutputFormat,
+    /// Streaming compression of csv and jsonl output
+    pub compression: Option<Compression>,
+    /// Encryption of the output, applied after compression
+    pub encryption: Option<Encryption>,
+    /// Dialect of csv output
+    pub csv: CsvOptions,
+    /// Csv columns in output order, the full record when None
+    pub columns: Option<Vec<Column>>,
+    pub error_format: ErrorFormat,
+    /// Number of parsing threads, 0 means one per available core
+    pub threads: usize,
+    pub dedup: bool,
+    pub dedup_error_rate: f64,
+    /// Log and skip members that can't be read instead of aborting the run
+    pub skip_errors: bool,
+    /// Levels of archives and compressed files nested in archive members
+    /// that are unpacked, deeper ones are skipped. 0 skips them all
+    pub max_depth: usize,
+    /// Toml file with validation rules replacing `parse.rules`
+    pub rules_path: Option<PathBuf>,
+    /// Label written to the source column of every record
+    pub source: String,
+    /// Acquisition date of the leak written to the breach_date column, like 2024-05-01
+    pub breach_date: String,
+    /// Replaces passwords in the output, dedup still sees the originals
+    pub redaction: Option<Redaction>,
+    /// Entries whose host isn't allowed are dropped without an error
+    pub domain_filter: DomainFilter,
+    /// Splits the output into shard files next to the output path
+    pub sharding: Option<Sharding>,
+    /// Text encoding of the input files, transcoded to utf-8 for parsing
+    pub encoding: InputEncoding,
+    /// Writes every archive member or file of a directory to an output of
+    /// its own named after it, plain input still goes to the output path
+    pub split_output: Option<SplitOutput>,
+    /// Number of leading lines of every file or member the parser is picked
+    /// from, the one parsing the most of them is used for the rest.
+    /// None tries every parser on every line
+    pub detect_lines: Option<usize>,
+    /// Csv the written credentials are tallied into per registrable domain
+    pub domain_counts: Option<PathBuf>,
+    /// Checkpoint of an interrupted run, the input it got through is skipped
+    pub resume: Option<Checkpoint>,
+    /// Publishes the records as json to a kafka topic instead of writing
+    /// them to the output path, which can't be sharded or split then
+    #[cfg(feature = "kafka")]
+    pub kafka: Option<KafkaTarget>,
+}
+
+/// Options of a plain input run with the defaults of the indexer command line
+impl Default for IndexerOptions {
+    fn default() -> Self {
+        IndexerOptions {
+            input_type: "plain".to_string(),
+            parse: ParseOptions::default(),
+            output_format: OutputFormat::Csv,
+            compression: None,
+            encryption: None,
+            csv: CsvOptions::default(),
+            columns: None,
+            error_format: ErrorFormat::Plain,
+            threads: 0,
+            dedup: false,
+            dedup_error_rate: 0.0001,
+            skip_errors: false,
+            max_depth: 5,
+            rules_path: None,
+            source: String::new(),
+            breach_date: String::new(),
+            redaction: None,
+            domain_filter: DomainFilter::default(),
+            sharding: None,
+            encoding: InputEncoding::Auto,
+            split_output: None,
+            detect_lines: None,
+            domain_counts: None,
+            resume: None,
+            #[cfg(feature = "kafka")]
+            kafka: None,
+        }
+    }
+}
+
+/// How output files are opened, kept to open the outputs of every member
+struct OutputOptions {
+    format: OutputFormat,
+    csv: CsvOptions,
+    compression: Option<Compression>,
+    encryption: Option<Encryption>,
+    columns: Option<Vec<Column>>,
+    #[cfg(feature = "kafka")]
+    kafka: Option<KafkaTarget>,
+}
+
+impl OutputOptions {
+    fn new(options: &IndexerOptions) -> OutputOptions {
+        OutputOptions {
+            format: options.output_format,
+            csv: options.csv,
+            compression: options.compression,
+            encryption: options.encryption.clone(),
+            columns: options.columns.clone(),
+            #[cfg(feature = "kafka")]
+            kafka: options.kafka.clone(),
+        }
+    }
+
+    /// Writer of the kafka topic records are published to, None when
+    /// they are written to files
+    fn kafka_writer(&self) -> Result<Option<OutputWriter>> {
+        #[cfg(feature = "kafka")]
+        if let Some(target) = &self.kafka {
+            let sink = KafkaSink::new(target)?;
+            return Ok(Some(OutputWriter::Kafka(Box::new(sink))));
+        }
+        Ok(None)
+    }
+
+    fn open(&self, path: &Path) -> Result<OutputWriter> {
+        Ok(match (self.format, &self.columns) {
+            (OutputFormat::Csv, Some(columns)) => OutputWriter::csv_columns(
+                path,
+                self.csv,
+                self.compression,
+                self.encryption.as_ref(),
+                columns.clone(),
+            )?,
+            (format, _) => OutputWriter::encrypted(
+                path,
+                format,
+                self.csv,
+                self.compression,
+                self.encryption.as_ref(),
+            )?,
+        })
+    }
+}
+
+/// Outputs, error file and dedup filter of a batch of inputs, indexed one
+/// after the other or in parallel by an [`Indexer::shared`] per input.
+/// Writers are locked per record
+pub struct SharedOutput {
+    output_path: PathBuf,
+    outputs: Vec<Arc<Mutex<OutputWriter>>>,
+    output_paths: Vec<PathBuf>,
+    errors: Arc<Mutex<SharedErrors>>,
+    dedup: Option<Arc<Mutex<GrowableBloom>>>,
+    progress: MultiProgress,
+}
+
+impl SharedOutput {
+    /// Opens the outputs of `options`. Split output, domain counts and
+    /// resumed runs are tied to a single input and aren't supported
+    pub fn new(
+        output_path: &Path,
+        error_path: &Path,
+        options: &IndexerOptions,
+    ) -> Result<SharedOutput> {
+        if options.split_output.is_some()
+            || options.domain_counts.is_some()
+            || options.resume.is_some()
+        {
+            return Err(Error::Config(
+                "a batch can't split its output, count domains or resume a run".to_string(),
+            ));
+        }
+
+        let output = OutputOptions::new(options);
+        let (outputs, output_paths) = match output.kafka_writer()? {
+            Some(writer) => (vec![writer], Vec::new()),
+            None => {
+                let paths = match &options.sharding {
+                    Some(sharding) => sharding.paths(output_path),
+                    None => vec![output_path.to_path_buf()],
+                };
+                let outputs = paths
+                    .iter()
+                    .map(|path| output.open(path))
+                    .collect::<Result<Vec<_>>>()?;
+                (outputs, paths)
+            }
+        };
+        let errors = SharedErrors {
+            writer: ErrorWriter::new(
+                error_path,
+                options.error_format,
+                options.encryption.as_ref(),
+            )?,
+            member: String::new(),
+        };
+
+        Ok(SharedOutput {
+            output_path: output_path.to_path_buf(),
+            outputs: outputs
+                .into_iter()
+                .map(|writer| Arc::new(Mutex::new(writer)))
+                .collect(),
+            output_paths,
+            errors: Arc::new(Mutex::new(errors)),
+            dedup: options.dedup.then(|| {
+                Arc::new(Mutex::new(GrowableBloom::new(
+                    options.dedup_error_rate,
+                    1_000_000,
+                )))
+            }),
+            progress: MultiProgress::new(),
+        })
+    }
+
+    pub fn output_paths(&self) -> &[PathBuf] {
+        &self.output_paths
+    }
+
+    /// Flushes the outputs and the error file, once the indexers
+    /// of the batch are finished or dropped
+    pub fn finish(self) -> Result<()> {
+        for writer in self.outputs {
+            let Ok(writer) = Arc::try_unwrap(writer) else {
+                panic!("an indexer of the batch is still running");
+            };
+            writer.into_inner().unwrap().finish()?;
+        }
+        let Ok(errors) = Arc::try_unwrap(self.errors) else {
+            panic!("an indexer of the batch is still running");
+        };
+        errors.into_inner().unwrap().writer.finish()
+    }
+}
+
+/// Parses leak dumps into domain,subdomain,username,password,password_type,target_domain,source,
+/// breach_date,source_file,target_type records
+pub struct Indexer {
+    st: PublicSuffixList,
+    output_path: PathBuf,
+    output: OutputOptions,
+    /// A single writer, or one per shard. With split output these are
+    /// the writers of the current member, opened once it's parsed
+    output_writers: Vec<OutputWriter>,
+    /// Every output file opened so far
+    output_paths: Vec<PathBuf>,
+    sharding: Option<Sharding>,
+    split_output: Option<SplitOutput>,
+    /// Directory input, stripped from the member names of split output
+    input_root: PathBuf,
+    error_writer: ErrorWriter,
+    member: String,
+    input_type: String,
+    parse: ParseOptions,
+    pool: rayon::ThreadPool,
+    /// Shared with the other indexers of a batch
+    dedup: Option<Arc<Mutex<GrowableBloom>>>,
+    /// Input bar on top, archive member bars below it
+    progress: MultiProgress,
+    skip_errors: bool,
+    max_depth: usize,
+    /// Levels of nested archives the current member is in
+    depth: usize,
+    /// Path of the member holding the nested archive being walked, its
+    /// members are named below it. Empty at the top level
+    nested: PathBuf,
+    source: String,
+    breach_date: String,
+    /// Input file or archive member being parsed, empty for stdin
+    source_file: String,
+    redaction: Option<Redaction>,
+    domain_filter: DomainFilter,
+    encoding: InputEncoding,
+    detect_lines: Option<usize>,
+    domain_counts: Option<DomainCounts>,
+    /// Set from another thread, like a signal handler, to stop the run
+    stop: Arc<AtomicBool>,
+    /// Progress of this run, along with the one it resumes
+    checkpoint: Checkpoint,
+    resume: Option<Checkpoint>,
+    stats: Stats,
+}
+
+impl Indexer {
+    pub fn new(
+        output_path: &Path,
+        error_path: &Path,
+        st: PublicSuffixList,
+        options: IndexerOptions,
+    ) -> Result<Indexer> {
+        let error_writer = ErrorWriter::new(
+            error_path,
+            options.error_format,
+            options.encryption.as_ref(),
+        )?;
+        let dedup = options.dedup.then(|| {
+            Arc::new(Mutex::new(GrowableBloom::new(
+                options.dedup_error_rate,
+                1_000_000,
+            )))
+        });
+        let mut indexer = Indexer::build(
+            output_path,
+            error_writer,
+            dedup,
+            MultiProgress::new(),
+            st,
+            options,
+        )?;
+        if indexer.split_output.is_none() {
+            indexer.open_outputs(None)?;
+        }
+        Ok(indexer)
+    }
+
+    /// Indexer of one input of a batch, writing to the outputs, the error
+    /// file and the dedup filter it shares with the others. `options` are
+    /// the ones of the batch, only the source may change per input
+    pub fn shared(
+        output: &SharedOutput,
+        st: PublicSuffixList,
+        options: IndexerOptions,
+    ) -> Result<Indexer> {
+        let mut indexer = Indexer::build(
+            &output.output_path,
+            ErrorWriter::Shared(output.errors.clone()),
+            output.dedup.clone(),
+            output.progress.clone(),
+            st,
+            options,
+        )?;
+        indexer.output_writers = output
+            .outputs
+            .iter()
+            .map(|writer| OutputWriter::Shared(writer.clone()))
+            .collect();
+        Ok(indexer)
+    }
+
+    fn build(
+        output_path: &Path,
+        error_writer: ErrorWriter,
+        dedup: Option<Arc<Mutex<GrowableBloom>>>,
+        progress: MultiProgress,
+        st: PublicSuffixList,
+        mut options: IndexerOptions,
+    ) -> Result<Indexer> {
+        if let Some(path) = &options.rules_path {
+            options.parse.rules = ValidationRules::from_file(path)?;
+        }
+
+        let output = OutputOptions::new(&options);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(options.threads)
+            .build()?;
+        let domain_counts = options
+            .domain_counts
+            .map(|path| DomainCounts::new(&path, MAX_DOMAINS))
+            .transpose()?;
+
+        Ok(Indexer {
+            input_type: options.input_type,
+            st,
+            output_path: output_path.to_path_buf(),
+            output,
+            output_writers: Vec::new(),
+            output_paths: Vec::new(),
+            sharding: options.sharding,
+            split_output: options.split_output,
+            input_root: PathBuf::new(),
+            error_writer,
+            member: String::new(),
+            parse: options.parse,
+            pool,
+            dedup,
+            progress,
+            skip_errors: options.skip_errors,
+            max_depth: options.max_depth,
+            depth: 0,
+            nested: PathBuf::new(),
+            source: options.source,
+            breach_date: options.breach_date,
+            source_file: String::new(),
+            redaction: options.redaction,
+            domain_filter: options.domain_filter,
+            encoding: options.encoding,
+            detect_lines: options.detect_lines,
+            domain_counts,
+            stop: Arc::new(AtomicBool::new(false)),
+            checkpoint: Checkpoint {
+                done: options
+                    .resume
+                    .as_ref()
+                    .map(|resume| resume.done.clone())
+                    .unwrap_or_default(),
+                ..Default::default()
+            },
+            resume: options.resume,
+            stats: Stats::default(),
+        })
+    }
+
+    /// Finishes the current outputs and opens the ones of `member`,
+    /// or of the output path itself when None. Member outputs whose
+    /// name is taken already get a -2, -3, ... suffix
+    fn open_outputs(&mut self, member: Option<&Path>) -> Result<()> {
+        for writer in self.output_writers.drain(..) {
+            writer.finish()?;
+        }
+        if let Some(writer) = self.output.kafka_writer()? {
+            self.output_writers.push(writer);
+            return Ok(());
+        }
+
+        let expand = |path: PathBuf| match &self.sharding {
+            Some(sharding) => sharding.paths(&path),
+            None => vec![path],
+        };
+        let paths = match member {
+            Some(member) => {
+                let member = member.strip_prefix(&self.input_root).unwrap_or(member);
+                let name = member.file_name().unwrap_or_default().to_string_lossy();
+                let mut paths = expand(member_path(&self.output_path, member));
+                let mut n = 1;
+                while self.output_paths.contains(&paths[0]) {
+                    n += 1;
+                    let member = member.with_file_name(format!("{}-{}", name, n));
+                    paths = expand(member_path(&self.output_path, &member));
+                }
+                paths
+            }
+            None => expand(self.output_path.clone()),
+        };
+
+        for path in paths {
+            self.output_writers.push(self.output.open(&path)?);
+            self.output_paths.push(path);
+        }
+        Ok(())
+    }
+
+    /// Parses every line of `reader` as an entry.
+    /// Lines are parsed in parallel chunk by chunk, while writing stays
+    /// sequential so the output keeps the input order.
+    /// Input that isn't utf-8 is transcoded and sanitized, lines that still
+    /// aren't valid utf-8 are dropped.
+    /// A resumed run skips the lines parsed before it was interrupted, the
+    /// stop flag is checked before every chunk
+    pub fn entry_reader(&mut self, reader: &mut impl std::io::BufRead) -> Result<()> {
+        let Some(skipped) = self.resumed_lines() else {
+            return Ok(());
+        };
+        let reader = self.decode(reader)?;
+        let mut lines = reader.split(b'\n').peekable();
+        for line in lines.by_ref().take(skipped as usize) {
+            line.map_err(Error::Read)?;
+        }
+        self.checkpoint.current = self.source_file.clone();
+        self.checkpoint.lines = skipped;
+        // Options with the parser picked from the first chunk
+        let mut detected = None;
+        let mut sampled = false;
+
+        while lines.peek().is_some() {
+            if self.stop.load(Ordering::Relaxed) {
+                return Err(Error::Interrupted);
+            }
+            let mut chunk: Vec<String> = Vec::with_capacity(CHUNK_SIZE);
+            let mut read = 0;
+            for line in lines.by_ref().take(CHUNK_SIZE) {
+                read += 1;
+                if let Ok(line) = String::from_utf8(line.map_err(Error::Read)?) {
+                    chunk.push(line);
+                }
+            }
+
+            if let (Some(n), false) = (self.detect_lines, sampled) {
+                sampled = true;
+                detected = self.detect_parser(&chunk, n);
+            }
+
+            let st = &self.st;
+            let options = detected.as_ref().unwrap_or(&self.parse);
+            let parsed: Vec<_> = self.pool.install(|| {
+                chunk
+                    .par_iter()
+                    .map(|line| options.parsers.parse(line.trim(), st, options))
+                    .collect()
+            });
+
+            self.stats.lines_read += chunk.len() as u64;
+            for (line, entry) in chunk.iter().zip(parsed) {
+                match entry {
+                    Ok((entry, password_type)) => self.write_entry(entry, password_type)?,
+                    Err(e) => {
+                        let line = line.trim_end_matches('\r');
+                        self.stats.reject(e.reason());
+                        self.error_writer
+                            .write_error(&self.member, e.reason(), line)?
+                    }
+                }
+            }
+            self.checkpoint.lines += read;
+        }
+        self.member_done()?;
+        Ok(())
+    }
+
+    /// Lines of the current source file parsed by the run being resumed,
+    /// None when it parsed all of them
+    fn resumed_lines(&self) -> Option<u64> {
+        match &self.resume {
+            Some(resume) => resume.skipped_lines(&self.source_file),
+            None => Some(0),
+        }
+    }
+
+    /// Marks the current member done once its records are delivered, a
+    /// resumed run would skip the ones still queued for kafka otherwise
+    fn member_done(&mut self) -> Result<()> {
+        for writer in &mut self.output_writers {
+            writer.sync()?;
+        }
+        self.checkpoint.current.clear();
+        self.checkpoint.lines = 0;
+        self.checkpoint.done.insert(self.source_file.clone());
+        Ok(())
+    }
+
+    /// Scores the parsers on the first `lines` non empty lines of `chunk`,
+    /// returning the parse options of the best one
+    fn detect_parser(&mut self, chunk: &[String], lines: usize) -> Option<ParseOptions> {
+        let sample: Vec<&str> = chunk
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .take(lines)
+            .collect();
+        let member = match (self.member.as_str(), self.source_file.as_str()) {
+            ("", "") => "-",
+            ("", source_file) => source_file,
+            (member, _) => member,
+        }
+        .to_string();
+
+        match self.parse.parsers.detect(&sample, &self.st, &self.parse) {
+            Some((parsers, hits)) => {
+                let parser = parsers.names().join(",");
+                info!(
+                    member = %member,
+                    parser = %parser,
+                    hits,
+                    sampled = sample.len(),
+                    "Detected parser"
+                );
+                self.stats.detected(member, parser);
+                Some(ParseOptions {
+                    parsers,
+                    ..self.parse.clone()
+                })
+            }
+            None => {
+                if !sample.is_empty() {
+                    warn!(member = %member, "No parser fits the first lines");
+                }
+                None
+            }
+        }
+    }
+
+    /// Transcodes `reader` to utf-8 unless it is already, then drops its
+    /// byte order mark and control characters and normalizes line endings
+    fn decode<'a>(&self, reader: impl BufRead + 'a) -> Result<Box<dyn BufRead + 'a>> {
+        let (reader, encoding) = decode(reader, self.encoding).map_err(Error::Read)?;
+        if encoding != encoding_rs::UTF_8 {
+            info!(member = %self.member, encoding = encoding.name(), "Transcoding");
+        }
+        Ok(Box::new(BufReader::new(Sanitizer::new(reader))))
+    }
+
+    /// Writes a parsed entry unless its domain is filtered out
+    /// or dedup has seen it already
+    fn write_entry(&mut self, entry: ParsedEntry, password_type: &str) -> Result<()> {
+        self.stats.parsed += 1;
+        if entry.corrected {
+            self.stats.corrected += 1;
+        }
+        if !self.domain_filter.allows(&entry.subdomain, &entry.domain) {
+            self.stats.filtered += 1;
+            return Ok(());
+        }
+
+        if let Some(dedup) = &self.dedup {
+            let key = (
+                &entry.domain,
+                &entry.subdomain,
+                &entry.username,
+                entry.password,
+                &entry.target_domain,
+            );
+            if !dedup.lock().unwrap().insert(key) {
+                self.stats.duplicates += 1;
+                return Ok(());
+            }
+        }
+
+        self.stats.write(&entry.domain);
+        if let Some(counts) = &mut self.domain_counts {
+            counts.add(&entry.domain)?;
+        }
+
+        let shard = self.sharding.map_or(0, |sharding| match sharding.key {
+            ShardKey::Domain => sharding.index(&entry.domain),
+        });
+        self.output_writers[shard].write(&LeakRecord {
+            domain: entry.domain.into(),
+            subdomain: entry.subdomain.into(),
+            username: entry.username,
+            password: match &self.redaction {
+                Some(redaction) => redaction.redact(entry.password).into(),
+                None => entry.password.into(),
+            },
+            password_type: password_type.into(),
+            target_domain: entry.target_domain.into(),
+            source: self.source.as_str().into(),
+            breach_date: self.breach_date.as_str().into(),
+            source_file: self.source_file.as_str().into(),
+            target_type: entry.target_type.as_str().into(),
+        })
+    }
+
+    /// Swallows input errors of a single member when skipping is enabled
+    fn skip_error(&mut self, member: &Path, result: Result<()>) -> Result<()> {
+        match result {
+            Err(e) if self.skip_errors && e.is_input() => {
+                warn!(member = %member.display(), error = %e, "Skipping member");
+                self.stats.reject("member_error");
+                self.error_writer.write_member(&member.to_string_lossy())?;
+                self.error_writer.write_error(
+                    &member.to_string_lossy(),
+                    "member_error",
+                    &e.to_string(),
+                )
+            }
+            result => result,
+        }
+    }
+
+    /// Walks a tar archive, compression is detected by magic bytes
+    pub fn process_archive(&mut self, input_reader: &mut impl std::io::BufRead) -> Result<()> {
+        let tar = decompress(input_reader)?;
+        self.process_tar(tar)
+    }
+
+    fn process_tar(&mut self, tar: impl Read) -> Result<()> {
+        let mut archive = Archive::new(tar);
+        // Tar has no index, so only the members done so far are known
+        let pb = self.member_progress_bar(None);
+
+        for file in archive.entries().map_err(Error::Read)? {
+            // A broken header leaves no way to find the next member
+            let file = match file {
+                Ok(file) => file,
+                Err(e) => {
+                    let result = Err(Error::Read(e));
+                    self.skip_error(Path::new("tar header"), result)?;
+                    break;
+                }
+            };
+            let path = self.nested.join(file.path().unwrap_or_default());
+            pb.set_message(path.display().to_string());
+
+            let mut reader = BufReader::new(file);
+            let result = self.process_member(&path, &mut reader);
+            self.skip_error(&path, result)?;
+            pb.inc(1);
+        }
+        // Nested archives leave their bars behind otherwise
+        if self.depth > 0 {
+            pb.finish_and_clear();
+        } else {
+            pb.finish_with_message("done");
+        }
+        Ok(())
+    }
+
+    /// Walks every member of a zip archive, descending into nested archives
+    pub fn process_zip<R: Read + Seek>(&mut self, input_reader: R) -> Result<()> {
+        let mut archive = ZipArchive::new(input_reader)?;
+        let pb = self.member_progress_bar(Some(archive.len() as u64));
+
+        for i in 0..archive.len() {
+            pb.inc(1);
+
+            // Encrypted or unsupported members are skipped
+            let file = match archive.by_index(i) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+
+            if !file.is_file() {
+                continue;
+            }
+
+            let path = self.nested.join(file.name());
+            pb.set_message(path.display().to_string());
+            let result = self.process_member(&path, &mut BufReader::new(file));
+            self.skip_error(&path, result)?;
+        }
+        // Nested archives leave their bars behind otherwise
+        pb.finish_and_clear();
+        Ok(())
+    }
+
+    /// Walks a zip archive by the local headers of its members, so it can
+    /// be read from a pipe. Members whose sizes only follow their data,
+    /// as zip writes them when it packs a pipe itself, can't be streamed
+    pub fn process_zip_stream(&mut self, mut input_reader: impl Read) -> Result<()> {
+        let pb = self.member_progress_bar(None);
+
+        loop {
+            // Like a broken tar header, a broken local header ends the walk
+            let file = match read_zipfile_from_stream(&mut input_reader) {
+                Ok(Some(file)) => file,
+                Ok(None) => break,
+                Err(e) => {
+                    self.skip_error(Path::new("zip header"), Err(Error::Zip(e)))?;
+                    break;
+                }
+            };
+            if !file.is_file() {
+                continue;
+            }
+
+            let path = self.nested.join(file.name());
+            pb.set_message(path.display().to_string());
+            let result = self.process_member(&path, &mut BufReader::new(file));
+            self.skip_error(&path, result)?;
+            pb.inc(1);
+        }
+        pb.finish_with_message("done");
+        Ok(())
+    }
+
+    /// Unpacks an archive or compressed file found in a member into the
+    /// same pipeline, as long as it isn't nested deeper than `max_depth`.
+    /// Takes a trait object so the walks it recurses into don't nest types
+    fn process_nested(&mut self, path: &Path, reader: &mut dyn std::io::BufRead) -> Result<()> {
+        if self.depth >= self.max_depth {
+            let name = path.to_string_lossy();
+            warn!(member = %name, max_depth = self.max_depth, "Skipping nested archive");
+            self.stats.reject("max_depth");
+            self.error_writer.write_member(&name)?;
+            return self.error_writer.write_error(
+                &name,
+                "max_depth",
+                &format!("nested archive past max depth {}", self.max_depth),
+            );
+        }
+
+        let outer = std::mem::replace(&mut self.nested, path.to_path_buf());
+        self.depth += 1;
+        let result = self.unpack_nested(path, reader);
+        self.depth -= 1;
+        self.nested = outer;
+        result
+    }
+
+    fn unpack_nested(&mut self, path: &Path, reader: &mut dyn std::io::BufRead) -> Result<()> {
+        let (head, reader) = sniff(decompress(reader)?)?;
+        match infer::get(&head).map(|kind| kind.mime_type()) {
+            Some("application/x-tar") => self.process_tar(reader),
+            // Spilled to a temporary file since zip needs seeking
+            Some("application/zip") => {
+                let mut file = tempfile::tempfile().map_err(Error::Write)?;
+                io::copy(&mut BufReader::new(reader), &mut file).map_err(Error::Read)?;
+                file.rewind().map_err(Error::Read)?;
+                self.process_zip(BufReader::new(file))
+            }
+            // A compressed file is named without its compression extension
+            _ => self.process_member(&path.with_extension(""), &mut BufReader::new(reader)),
+        }
+    }
+
+    /// Tells the input apart by its magic bytes: compressed input is
+    /// unpacked, then walked as a tar or zip archive or read as plain lines
+    pub fn process_auto(&mut self, input_reader: &mut impl std::io::BufRead) -> Result<()> {
+        let (head, reader) = sniff(decompress(input_reader)?)?;
+        let mime = infer::get(&head).map(|kind| kind.mime_type());
+        info!(detected = mime.unwrap_or("text/plain"), "Detected input");
+
+        match mime {
+            Some("application/x-tar") => self.process_tar(reader),
+            Some("application/zip") => self.process_zip_stream(reader),
+            _ => {
+                if self.output_writers.is_empty() {
+                    self.open_outputs(None)?;
+                }
+                self.entry_reader(&mut BufReader::new(reader))
+            }
+        }
+    }
+
+    /// Bar of the members of an archive, `total` is known when the archive has an index
+    fn member_progress_bar(&self, total: Option<u64>) -> ProgressBar {
+        let pb = match total {
+            Some(total) => {
+                let pb = ProgressBar::new(total);
+                pb.set_style(ProgressStyle::default_bar().template("{spinner:.green} {bar:40.green/black} {pos:>7}/{len:<7} members {wide_msg}").unwrap()
+                    .progress_chars("━╾╴─"));
+                pb
+            }
+            None => {
+                let pb = ProgressBar::new_spinner();
+                pb.set_style(
+                    ProgressStyle::default_spinner()
+                        .template("{spinner:.green} {pos:>7} members {wide_msg}")
+                        .unwrap(),
+                );
+                pb
+            }
+        };
+
+        let pb = self.progress.add(pb);
+        pb.enable_steady_tick(Duration::from_millis(TICK));
+        pb
+    }
+
+    fn process_member(&mut self, path: &Path, reader: &mut impl std::io::BufRead) -> Result<()> {
+        // Members of nested archives are marked with the member holding
+        // them, equally named files of two archives would mix up otherwise
+        let name = match self.depth {
+            0 => path.file_name().unwrap_or_default().to_string_lossy(),
+            _ => path.to_string_lossy(),
+        }
+        .into_owned();
+
+        if let Some(kind) = reader.fill_buf().ok().and_then(infer::get) {
+            if NESTED_ARCHIVES.contains(&kind.mime_type()) {
+                return self.process_nested(path, reader);
+            }
+            match kind.matcher_type() {
+                infer::MatcherType::Doc
+                | infer::MatcherType::Image
+                | infer::MatcherType::Text
+                | infer::MatcherType::Archive => return Ok(()),
+                _ => {}
+            }
+        }
+
+        if let Some(ext) = path.extension() {
+            if ext == "csv" {
+                return Ok(());
+            }
+        }
+
+        self.set_source_file(path);
+        if self.resumed_lines().is_none() {
+            return Ok(());
+        }
+        if self.split_output == Some(SplitOutput::Member) {
+            self.open_outputs(Some(path))?;
+        }
+        self.error_writer.write_member(&name)?;
+        self.member = name;
+        self.entry_reader(reader)
+    }
+
+    /// Parses the blocks of a stealer log password file as url:login:pass records
+    fn process_password_file(&mut self, path: &Path, reader: impl std::io::BufRead) -> Result<()> {
+        let name = path.to_string_lossy().into_owned();
+        self.set_source_file(path);
+        if self.resumed_lines().is_none() {
+            return Ok(());
+        }
+        let records = read_password_file(self.decode(reader)?).map_err(Error::Read)?;
+        if self.split_output == Some(SplitOutput::Member) {
+            self.open_outputs(Some(path))?;
+        }
+
+        self.error_writer.write_member(&name)?;
+        let password_type = EntryFormat::UrlLoginPass.password_type();
+
+        self.stats.lines_read += records.len() as u64;
+        for record in records {
+            let entry = parse_url_fields(
+                &record.url,
+                &record.login,
+                &record.password,
+                &self.st,
+                &self.parse,
+            );
+            match entry {
+                Ok(entry) => self.write_entry(entry, password_type)?,
+                Err(e) => {
+                    self.stats.reject(e.reason());
+                    self.error_writer
+                        .write_error(&name, e.reason(), &record.line())?
+                }
+            }
+        }
+        self.member_done()?;
+        Ok(())
+    }
+
+    /// Archive members and files of a directory are named relative to it
+    fn set_source_file(&mut self, path: &Path) {
+        let path = path.strip_prefix(&self.input_root).unwrap_or(path);
+        self.source_file = path.to_string_lossy().into_owned();
+    }
+
+    /// Recursively processes every file under `dir`.
+    /// Stealer input only picks the password files of every log folder
+    pub fn process_dir(&mut self, dir: &Path) -> Result<()> {
+        self.input_root = dir.to_path_buf();
+        let stealer = self.input_type == "stealer";
+        let files: Vec<PathBuf> = WalkDir::new(dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter(|entry| !stealer || is_password_file(&entry.file_name().to_string_lossy()))
+            .map(|entry| entry.into_path())
+            .collect();
+
+        let pb = self.progress.add(ProgressBar::new(files.len() as u64));
+        pb.enable_steady_tick(Duration::from_millis(TICK));
+        pb.set_style(ProgressStyle::default_bar().template("{spinner:.green} {bar:40.green/black} {pos:>7}/{len:<7} files [{elapsed_precise}] eta ({eta:.blue}) {wide_msg}").unwrap()
+            .progress_chars("━╾╴─"));
+
+        for path in files {
+            if self.stop.load(Ordering::Relaxed) {
+                return Err(Error::Interrupted);
+            }
+            pb.set_message(path.display().to_string());
+
+            let result = match File::open(&path) {
+                Ok(file) if stealer => self.process_password_file(&path, BufReader::new(file)),
+                Ok(file) => self.process_member(&path, &mut BufReader::new(file)),
+                Err(source) => Err(Error::Open {
+                    path: path.clone(),
+                    source,
+                }),
+            };
+            self.skip_error(&path, result)?;
+            pb.inc(1);
+        }
+        pb.finish();
+        Ok(())
+    }
+
+    /// Counters of the run so far
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Every output file written so far, shards and member outputs included
+    pub fn output_paths(&self) -> &[PathBuf] {
+        &self.output_paths
+    }
+
+    /// Stops the run with [`Error::Interrupted`] once `stop` is set, before
+    /// the next chunk of lines or file of a directory. The outputs are still
+    /// flushed by [`Indexer::finish`]
+    pub fn stop_on(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = stop;
+    }
+
+    /// How far the run got, to resume it after an interruption
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            outputs: self.output_paths.clone(),
+            ..self.checkpoint.clone()
+        }
+    }
+
+    /// Flushes the outputs, must be called once processing is done.
+    /// Returns the counters of the whole run
+    pub fn finish(mut self) -> Result<Stats> {
+        for writer in self.output_writers.drain(..) {
+            writer.finish()?;
+        }
+        self.error_writer.finish()?;
+        if let Some(counts) = self.domain_counts.take() {
+            let path = counts.path().to_path_buf();
+            let domains = counts.finish()?;
+            info!(path = %path.display(), domains, "Wrote domain counts");
+        }
+
+        self.stats.finish();
+        info!(
+            lines_read = self.stats.lines_read,
+            written = self.stats.written,
+            rejected = self.stats.rejected_total(),
+            elapsed_secs = self.stats.elapsed_secs,
+            "Indexing done"
+        );
+        Ok(self.stats)
+    }
+
+    /// Dispatches `input_reader` according to the configured input type
+    pub fn handle_by_type(&mut self, input_reader: &mut impl std::io::BufRead) -> Result<()> {
+        match self.input_type.as_str() {
+            "tar" | "tar.gz" | "tar.zst" | "tar.xz" | "tar.bz2" => {
+                self.process_archive(input_reader)
+            }
+            "zip" => self.process_zip_stream(input_reader),
+            "auto" => self.process_auto(input_reader),
+            "plain" => {
+                if self.output_writers.is_empty() {
+                    self.open_outputs(None)?;
+                }
+                self.entry_reader(input_reader)
+            }
+            input_type => Err(Error::UnsupportedInput(input_type.to_string())),
+        }
+    }
+
+    /// Processes a file, or stdin when `input_path` is -, with a progress bar
+    pub fn process(&mut self, input_path: &str) -> Result<()> {
+        info!(input = input_path, input_type = %self.input_type, "Indexing");
+        self.checkpoint.input = input_path.to_string();
+        // Errors of the inputs of a batch are marked like archive members,
+        // once the first of them is written
+        if let ErrorWriter::Shared(_) = self.error_writer {
+            self.member = file_name(input_path);
+        }
+
+        if self.input_type == "dir" || self.input_type == "stealer" {
+            return self.process_dir(Path::new(input_path));
+        }
+
+        // Zip files are read through their directory at the end,
+        // stdin can only be streamed through the local headers
+        if self.input_type == "zip" && input_path != "-" {
+            let input_path = Path::new(input_path);
+            let input = open(input_path)?;
+            let pb = self.progress.add(file_progress_bar(&input)?);
+
+            return self.process_zip(BufReader::new(pb.wrap_read(input)));
+        }
+
+        let (input, pb): (Box<dyn Read>, ProgressBar) = match input_path {
+            "-" => {
+                let pb = self.progress.add(ProgressBar::new_spinner());
+                pb.enable_steady_tick(Duration::from_millis(TICK));
+                (Box::new(std::io::stdin().lock()), pb)
+            }
+            _ => {
+                let input = open(Path::new(input_path))?;
+                self.source_file = file_name(input_path);
+                let pb = self.progress.add(file_progress_bar(&input)?);
+                (Box::new(input), pb)
+            }
+        };
+        let input_wrap = pb.wrap_read(input);
+        let mut reader = BufReader::new(input_wrap);
+
+        self.handle_by_type(&mut reader)
+    }
+}
+
+/// Name of the input file without its directories
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map_or_else(|| path.to_string(), |x| x.to_string_lossy().into_owned())
+}
+
+fn open(path: &Path) -> Result<File> {
+    File::open(path).map_err(|source| Error::Open {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn file_progress_bar(input: &File) -> Result<ProgressBar> {
+    Ok(progress::file_progress_bar(
+        input.metadata().map_err(Error::Read)?.len(),
+    ))
+}
@@ -0,0 +1,180 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use rdkafka::{
+    config::ClientConfig,
+    error::KafkaError,
+    producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext},
+    types::RDKafkaErrorCode,
+    util::Timeout,
+    ClientContext,
+};
+
+use crate::{
+    error::{Error, Result},
+    LeakRecord,
+};
+
+/// Producer settings tuned for throughput, records are sent in batches of up
+/// to 10000 once they waited for 100 ms. Idempotence keeps retries from
+/// publishing a record twice
+static DEFAULT_CONFIG: [(&str, &str); 3] = [
+    ("linger.ms", "100"),
+    ("batch.num.messages", "10000"),
+    ("enable.idempotence", "true"),
+];
+
+/// Time waited for the producer queue to drain when it's full
+const QUEUE_FULL_WAIT: Duration = Duration::from_millis(100);
+
+/// Topic given as kafka://broker1:9092,broker2:9092/topic. Query parameters
+/// are librdkafka settings overriding the defaults, like
+/// kafka://broker/topic?linger.ms=500&compression.type=lz4
+///
+/// # Example
+///
+/// ```
+/// use lib::kafka::KafkaTarget;
+///
+/// let target = KafkaTarget::parse("kafka://kafka1:9092,kafka2:9092/leaks?linger.ms=5").unwrap();
+/// assert_eq!(target.brokers, "kafka1:9092,kafka2:9092");
+/// assert_eq!(target.topic, "leaks");
+/// assert_eq!(target.config, [("linger.ms".to_string(), "5".to_string())]);
+/// assert!(KafkaTarget::parse("leaks.csv").is_none());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KafkaTarget {
+    pub brokers: String,
+    pub topic: String,
+    pub config: Vec<(String, String)>,
+}
+
+impl KafkaTarget {
+    /// None unless `output` is a kafka:// url with both brokers and a topic
+    pub fn parse(output: &str) -> Option<KafkaTarget> {
+        let rest = output.strip_prefix("kafka://")?;
+        let (rest, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let (brokers, topic) = rest.split_once('/')?;
+        if brokers.is_empty() || topic.is_empty() || topic.contains('/') {
+            return None;
+        }
+
+        let config = query
+            .split('&')
+            .filter(|x| !x.is_empty())
+            .map(|x| {
+                let (key, value) = x.split_once('=').unwrap_or((x, ""));
+                (key.to_string(), value.to_string())
+            })
+            .collect();
+        Some(KafkaTarget {
+            brokers: brokers.to_string(),
+            topic: topic.to_string(),
+            config,
+        })
+    }
+}
+
+/// Counts the records the brokers didn't acknowledge, librdkafka has
+/// retried them already when they are reported
+#[derive(Default)]
+struct DeliveryContext {
+    failed: AtomicU64,
+    first_error: Mutex<Option<KafkaError>>,
+}
+
+impl ClientContext for DeliveryContext {}
+
+impl ProducerContext for DeliveryContext {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _: ()) {
+        if let Err((e, _)) = result {
+            if self.failed.fetch_add(1, Ordering::Relaxed) == 0 {
+                tracing::warn!(error = %e, "Failed to deliver a record to kafka");
+                *self.first_error.lock().unwrap() = Some(e.clone());
+            }
+        }
+    }
+}
+
+/// Publishes records as json to a kafka topic, keyed by their domain so the
+/// credentials of a domain stay in one partition and in order.
+/// Sending is asynchronous, delivery failures are reported by the next
+/// write or [`KafkaSink::finish`] and stop the run
+pub struct KafkaSink {
+    producer: BaseProducer<DeliveryContext>,
+    topic: String,
+}
+
+impl KafkaSink {
+    pub fn new(target: &KafkaTarget) -> Result<KafkaSink> {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", &target.brokers);
+        for (key, value) in DEFAULT_CONFIG {
+            config.set(key, value);
+        }
+        for (key, value) in &target.config {
+            config.set(key, value);
+        }
+
+        Ok(KafkaSink {
+            producer: config.create_with_context(DeliveryContext::default())?,
+            topic: target.topic.clone(),
+        })
+    }
+
+    pub fn write(&mut self, record: &LeakRecord) -> Result<()> {
+        self.check_deliveries()?;
+
+        let payload = serde_json::to_vec(record)?;
+        let mut message = BaseRecord::to(&self.topic)
+            .key(record.domain.as_ref())
+            .payload(&payload);
+        loop {
+            match self.producer.send(message) {
+                Ok(()) => break,
+                // Backpressure, serving the delivery reports makes room
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), rejected)) => {
+                    message = rejected;
+                    self.producer.poll(QUEUE_FULL_WAIT);
+                }
+                Err((e, _)) => return Err(e.into()),
+            }
+        }
+        self.producer.poll(Duration::ZERO);
+        Ok(())
+    }
+
+    fn check_deliveries(&self) -> Result<()> {
+        let context = self.producer.context();
+        match context.failed.load(Ordering::Relaxed) {
+            0 => Ok(()),
+            failed => Err(Error::KafkaDelivery {
+                failed,
+                source: context
+                    .first_error
+                    .lock()
+                    .unwrap()
+                    .clone()
+                    .expect("the first failure is kept"),
+            }),
+        }
+    }
+
+    /// Waits until every record written so far is delivered or has failed
+    pub fn flush(&mut self) -> Result<()> {
+        self.producer.flush(Timeout::Never)?;
+        self.check_deliveries()
+    }
+
+    /// Waits until every record is delivered or has failed
+    pub fn finish(mut self) -> Result<()> {
+        self.flush()
+    }
+}
@@ -1,61 +1,196 @@
-use std::io::BufRead;
+pub mod checkpoint;
+pub mod config;
+pub mod disposable;
+pub mod document;
+pub mod domain_counts;
+pub mod domain_filter;
+pub mod domain_typos;
+pub mod encoding;
+pub mod encryption;
+pub mod entry;
+pub mod error;
+pub mod errors;
+pub mod indexer;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod manifest;
+pub mod n1ql;
+pub mod org;
+pub mod output;
+pub mod parsers;
+pub mod progress;
+mod psl;
+pub mod redact;
+pub mod report;
+pub mod rules;
+pub mod sanitize;
+pub mod shard;
+pub mod sort;
+pub mod stealer;
+mod suffix_provider;
+pub mod telemetry;
+mod username;
+pub mod wordlist;
+
+pub use psl::PublicSuffixList;
+pub use suffix_provider::SuffixProvider;
+pub use username::{normalize_username, UsernameRule, UsernameRules};
+
+use std::{borrow::Cow, collections::HashSet, io::BufRead, str::FromStr};
 
 use serde::{Deserialize, Serialize};
-use suffix::SuffixTable;
 
 /// Parses domain into the following parts: subdomain, domain, tld
 ///
+/// The longest matching suffix rule wins, `*.` wildcard
+/// and `!` exception rules of the public suffix list are respected
+///
 /// # Arguments
 ///
 /// * `domain` - A domain like cloud.yandex.net
-/// * `st` - SuffixTable populated with subdomain list
+/// * `st` - PublicSuffixList populated with suffix rules
 ///
 /// # Example
 ///
 /// ```
-/// use lib::parse_domain;
-/// use suffix::SuffixTable;
+/// use lib::{parse_domain, PublicSuffixList};
 ///
-/// let st = SuffixTable::new("edu.ru ru");
+/// let st = PublicSuffixList::new("edu.ru ru");
 /// let (subdomain, domain) = parse_domain("cloud.yandex.edu.ru", &st);
 ///
 /// assert_eq!(subdomain, "cloud");
 /// assert_eq!(domain, "yandex.edu.ru");
 /// ```
-pub fn parse_domain<'a>(domain: &'a str, st: &SuffixTable) -> (&'a str, &'a str) {
-    let mut parts = domain.match_indices('.').rev().take(3);
-
-    if let (Some(_), Some(elem)) = (parts.next(), parts.next()) {
-        let sd = elem.0;
-        let sdn = sd + 1;
-
-        let (td, tdn) = if let Some(elem) = parts.next() {
-            (elem.0, elem.0 + 1)
-        } else {
-            (0, 0)
-        };
-
-        let tld_part = &domain[sdn..];
-        let tld_pos = st.positions(tld_part);
-
-        if !tld_pos.is_empty() {
-            (&domain[..td], &domain[tdn..])
-        } else {
-            (&domain[..sd], &domain[sdn..])
+pub fn parse_domain<'a>(domain: &'a str, st: &PublicSuffixList) -> (&'a str, &'a str) {
+    let parts = parse_domain_full(domain, st);
+    (parts.subdomain, parts.registrable_domain)
+}
+
+/// Parts of a domain split around its public suffix
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DomainParts<'a> {
+    /// Labels left of the registrable domain, like cloud
+    pub subdomain: &'a str,
+    /// Second level label and suffix, like yandex.edu.ru
+    pub registrable_domain: &'a str,
+    /// Label registered under the suffix, like yandex
+    pub sld: &'a str,
+    /// Public suffix, like edu.ru
+    pub suffix: &'a str,
+}
+
+/// Parses domain into subdomain, registrable domain, second level label and suffix,
+/// matching suffix rules the same way as [`parse_domain`]
+///
+/// A single label is taken as a bare suffix, and a domain made of suffix
+/// rules only is registrable as a whole, its first label being the sld
+///
+/// # Example
+///
+/// ```
+/// use lib::{parse_domain_full, PublicSuffixList};
+///
+/// let st = PublicSuffixList::new("edu.ru ru");
+/// let parts = parse_domain_full("cloud.yandex.edu.ru", &st);
+///
+/// assert_eq!(parts.subdomain, "cloud");
+/// assert_eq!(parts.registrable_domain, "yandex.edu.ru");
+/// assert_eq!(parts.sld, "yandex");
+/// assert_eq!(parts.suffix, "edu.ru");
+/// ```
+pub fn parse_domain_full<'a>(domain: &'a str, st: &PublicSuffixList) -> DomainParts<'a> {
+    let mut suffix = st.suffix(domain);
+
+    if suffix.len() == domain.len() {
+        match domain.split_once('.') {
+            Some((_, rest)) => suffix = rest,
+            None => {
+                return DomainParts {
+                    subdomain: "",
+                    registrable_domain: domain,
+                    sld: "",
+                    suffix: domain,
+                }
+            }
+        }
+    }
+
+    let suffix_start = domain.len() - suffix.len();
+    // Registrable domain starts after the dot preceding the sld
+    let sld_start = domain[..suffix_start - 1].rfind('.').map_or(0, |i| i + 1);
+
+    DomainParts {
+        subdomain: &domain[..sld_start.saturating_sub(1)],
+        registrable_domain: &domain[sld_start..],
+        sld: &domain[sld_start..suffix_start - 1],
+        suffix,
+    }
+}
+
+/// Form of internationalized domain names
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DomainForm {
+    /// Punycode, like xn--d1acufc.xn--p1ai
+    Ascii,
+    /// Unicode, like домен.рф
+    Unicode,
+}
+
+impl FromStr for DomainForm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(DomainForm::Ascii),
+            "unicode" => Ok(DomainForm::Unicode),
+            _ => Err(format!(
+                "unknown domain form {}, expected ascii or unicode",
+                s
+            )),
         }
-    } else {
-        ("", domain)
     }
 }
 
+/// Converts every label of `domain` into the requested IDN form,
+/// None is returned if `domain` isn't a valid IDN
+///
+/// # Example
+///
+/// ```
+/// use lib::{normalize_domain, DomainForm};
+///
+/// let ascii = normalize_domain("пример.рф", DomainForm::Ascii);
+/// assert_eq!(ascii.as_deref(), Some("xn--e1afmkfd.xn--p1ai"));
+///
+/// let unicode = normalize_domain("xn--e1afmkfd.xn--p1ai", DomainForm::Unicode);
+/// assert_eq!(unicode.as_deref(), Some("пример.рф"));
+/// ```
+pub fn normalize_domain(domain: &str, form: DomainForm) -> Option<String> {
+    match form {
+        DomainForm::Ascii => idna::domain_to_ascii(domain).ok(),
+        DomainForm::Unicode => match idna::domain_to_unicode(domain) {
+            (unicode, Ok(())) => Some(unicode),
+            _ => None,
+        },
+    }
+}
+
+/// Reads the public suffix list into a space separated rules string
+/// suitable for PublicSuffixList, private domains are skipped
 pub fn parse_tld(reader: &mut impl BufRead) -> String {
+    parse_psl(reader, false)
+}
+
+/// Same as [`parse_tld`], with `include_private` the private domains
+/// section (github.io and alike) is read as well
+pub fn parse_psl(reader: &mut impl BufRead, include_private: bool) -> String {
     let mut res = String::with_capacity(84000);
 
     for line in reader.lines() {
         let line = line.unwrap();
         let trimmed = line.trim();
 
-        if trimmed.starts_with("// ===BEGIN PRIVATE DOMAINS") {
+        if !include_private && trimmed.starts_with("// ===BEGIN PRIVATE DOMAINS") {
             break;
         }
 
@@ -68,14 +203,272 @@ pub fn parse_tld(reader: &mut impl BufRead) -> String {
     res
 }
 
-#[derive(Serialize, Deserialize)]
+/// Schema of the LeakData documents written by this build, stored in their
+/// version field. 1 is domain and credentials, 2 adds breaches and the version,
+/// 3 adds the organization
+pub static DOCUMENT_VERSION: u32 = 3;
+
+/// Documents written before the version field have the first schema
+fn legacy_version() -> u32 {
+    1
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CredentialData {
+    #[serde(default)]
     pub subdomain: String,
+    #[serde(default)]
     pub data: Vec<(String, String)>,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Provenance of the credentials of a document: the leak they were indexed
+/// from, when it was acquired and the file that held them. Unknown fields
+/// are empty and left out of the json
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Breach {
+    /// Label given to the indexer with --source-name
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// Acquisition date given to the indexer with --breach-date, like 2024-05-01
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub date: String,
+    /// Input file or archive member the credentials were read from
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub source_file: String,
+}
+
+impl Breach {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty() && self.date.is_empty() && self.source_file.is_empty()
+    }
+}
+
+/// Most breaches a document lists. Stealer logs name a file per archive
+/// member, so without a bound a domain could carry thousands of them
+pub const MAX_BREACHES: usize = 64;
+
+/// Breaches of a document being built, each listed once. Empty breaches
+/// and the ones past [`MAX_BREACHES`] are dropped
+///
+/// # Example
+///
+/// ```
+/// use lib::{Breach, BreachSet};
+///
+/// let breach = Breach { name: "combolist".to_string(), ..Default::default() };
+/// let mut breaches = BreachSet::default();
+/// breaches.insert(breach.clone());
+/// breaches.insert(breach);
+/// breaches.insert(Breach::default());
+/// assert_eq!(breaches.into_vec().len(), 1);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BreachSet {
+    breaches: Vec<Breach>,
+    seen: HashSet<Breach>,
+}
+
+impl BreachSet {
+    /// Adds `breach` unless it's empty, listed already or the set is full
+    pub fn insert(&mut self, breach: Breach) {
+        if !breach.is_empty() && !self.is_full() && self.seen.insert(breach.clone()) {
+            self.breaches.push(breach);
+        }
+    }
+
+    /// Whether [`MAX_BREACHES`] are listed, so further ones are dropped
+    pub fn is_full(&self) -> bool {
+        self.breaches.len() >= MAX_BREACHES
+    }
+
+    /// Breaches in the order they were added
+    pub fn as_slice(&self) -> &[Breach] {
+        &self.breaches
+    }
+
+    pub fn into_vec(self) -> Vec<Breach> {
+        self.breaches
+    }
+
+    pub fn clear(&mut self) {
+        self.breaches.clear();
+        self.seen.clear();
+    }
+}
+
+impl Extend<Breach> for BreachSet {
+    fn extend<T: IntoIterator<Item = Breach>>(&mut self, iter: T) {
+        for breach in iter {
+            self.insert(breach);
+        }
+    }
+}
+
+/// Stored document of a domain. Fields missing from older documents get
+/// their defaults and unknown ones are ignored, so documents of any
+/// schema can be read, see [`document::upgrade`] to rewrite them
+#[derive(Clone, Serialize, Deserialize)]
 pub struct LeakData {
     pub domain: String,
+    #[serde(default)]
     pub credentials: Vec<CredentialData>,
+    /// Leaks the credentials came from, documents of untagged runs have none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub breaches: Vec<Breach>,
+    /// Organization owning the domain, see [`org::OrgMap`]. Empty unless
+    /// the documents were enriched
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub org: String,
+    /// Schema the document was written with, see [`DOCUMENT_VERSION`]
+    #[serde(default = "legacy_version")]
+    pub version: u32,
+}
+
+/// Borrowed [`CredentialData`], serialized to the same json
+#[derive(Serialize, Deserialize)]
+pub struct CredentialDataRef<'a> {
+    #[serde(borrow, default)]
+    pub subdomain: Cow<'a, str>,
+    #[serde(borrow, default)]
+    pub data: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+}
+
+/// Borrowed [`LeakData`], so documents can be serialized straight from the
+/// buffers their fields were parsed into, without a String per field
+///
+/// # Example
+///
+/// ```
+/// use std::borrow::Cow;
+/// use lib::{CredentialDataRef, LeakData, LeakDataRef, DOCUMENT_VERSION};
+///
+/// let leak = LeakDataRef {
+///     domain: Cow::Borrowed("corp.com"),
+///     credentials: vec![CredentialDataRef {
+///         subdomain: Cow::Borrowed("vpn"),
+///         data: vec![(Cow::Borrowed("admin"), Cow::Borrowed("secret"))],
+///     }],
+///     breaches: Vec::new(),
+///     org: Cow::Borrowed("Corp Inc"),
+///     version: DOCUMENT_VERSION,
+/// };
+/// let json = serde_json::to_string(&leak).unwrap();
+/// assert_eq!(
+///     json,
+///     r#"{"domain":"corp.com","credentials":[{"subdomain":"vpn","data":[["admin","secret"]]}],"org":"Corp Inc","version":3}"#
+/// );
+///
+/// let owned: LeakData = serde_json::from_str(&json).unwrap();
+/// assert_eq!(serde_json::to_string(&LeakDataRef::from(&owned)).unwrap(), json);
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct LeakDataRef<'a> {
+    #[serde(borrow)]
+    pub domain: Cow<'a, str>,
+    #[serde(borrow, default)]
+    pub credentials: Vec<CredentialDataRef<'a>>,
+    /// Few per document, so they are kept owned
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub breaches: Vec<Breach>,
+    #[serde(borrow, default, skip_serializing_if = "str::is_empty")]
+    pub org: Cow<'a, str>,
+    #[serde(default = "legacy_version")]
+    pub version: u32,
+}
+
+impl CredentialDataRef<'_> {
+    pub fn into_owned(self) -> CredentialData {
+        CredentialData {
+            subdomain: self.subdomain.into_owned(),
+            data: self
+                .data
+                .into_iter()
+                .map(|(username, password)| (username.into_owned(), password.into_owned()))
+                .collect(),
+        }
+    }
+}
+
+impl LeakDataRef<'_> {
+    pub fn into_owned(self) -> LeakData {
+        LeakData {
+            domain: self.domain.into_owned(),
+            credentials: self
+                .credentials
+                .into_iter()
+                .map(CredentialDataRef::into_owned)
+                .collect(),
+            breaches: self.breaches,
+            org: self.org.into_owned(),
+            version: self.version,
+        }
+    }
+}
+
+impl<'a> From<&'a CredentialData> for CredentialDataRef<'a> {
+    fn from(credential_data: &'a CredentialData) -> Self {
+        CredentialDataRef {
+            subdomain: Cow::Borrowed(&credential_data.subdomain),
+            data: credential_data
+                .data
+                .iter()
+                .map(|(username, password)| {
+                    (Cow::Borrowed(&**username), Cow::Borrowed(&**password))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a LeakData> for LeakDataRef<'a> {
+    fn from(leak_data: &'a LeakData) -> Self {
+        LeakDataRef {
+            domain: Cow::Borrowed(&leak_data.domain),
+            credentials: leak_data.credentials.iter().map(Into::into).collect(),
+            breaches: leak_data.breaches.clone(),
+            org: Cow::Borrowed(&leak_data.org),
+            version: leak_data.version,
+        }
+    }
+}
+
+/// Single parsed credential, the unit of the indexer output
+#[derive(Serialize, Deserialize)]
+pub struct LeakRecord<'a> {
+    #[serde(borrow)]
+    pub domain: Cow<'a, str>,
+    #[serde(borrow)]
+    pub subdomain: Cow<'a, str>,
+    #[serde(borrow)]
+    pub username: Cow<'a, str>,
+    #[serde(borrow)]
+    pub password: Cow<'a, str>,
+    #[serde(borrow)]
+    pub password_type: Cow<'a, str>,
+    /// Site of url:login:pass entries and ip address hosts, empty for other entries
+    #[serde(borrow, default)]
+    pub target_domain: Cow<'a, str>,
+    /// Label of the leak the record was indexed from, empty when untagged
+    #[serde(borrow, default)]
+    pub source: Cow<'a, str>,
+    /// Acquisition date of the leak, empty when unknown
+    #[serde(borrow, default)]
+    pub breach_date: Cow<'a, str>,
+    /// Input file or archive member the record was read from
+    #[serde(borrow, default)]
+    pub source_file: Cow<'a, str>,
+    /// domain, or ip for credentials of an ip address, empty for older records
+    #[serde(borrow, default)]
+    pub target_type: Cow<'a, str>,
+}
+
+impl LeakRecord<'_> {
+    /// Provenance of the record as stored with its document
+    pub fn breach(&self) -> Breach {
+        Breach {
+            name: self.source.to_string(),
+            date: self.breach_date.to_string(),
+            source_file: self.source_file.to_string(),
+        }
+    }
 }
@@ -0,0 +1,201 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::error::{Error, Result};
+
+/// A file a pipeline stage read or wrote
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub path: PathBuf,
+    /// Hex SHA-256 of the file. A directory hashes the relative paths
+    /// and hashes of its files, in path order
+    pub sha256: String,
+    /// Size of the file, or of every file under the directory
+    pub bytes: u64,
+}
+
+fn hash_file(path: &Path) -> Result<(String, u64)> {
+    let mut file = File::open(path).map_err(|source| Error::Open {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 1 << 20];
+    let mut bytes = 0;
+
+    loop {
+        let read = file.read(&mut buf).map_err(Error::Read)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        bytes += read as u64;
+    }
+    Ok((hex(&hasher.finalize()), bytes))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+impl Artifact {
+    /// Hashes the file or directory at `path`, reading it whole
+    pub fn hash(path: &Path) -> Result<Artifact> {
+        if !path.is_dir() {
+            let (sha256, bytes) = hash_file(path)?;
+            return Ok(Artifact {
+                path: path.to_path_buf(),
+                sha256,
+                bytes,
+            });
+        }
+
+        let mut hasher = Sha256::new();
+        let mut bytes = 0;
+        for entry in WalkDir::new(path).sort_by_file_name() {
+            let entry = entry.map_err(|e| Error::Read(io::Error::other(e)))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let (sha256, size) = hash_file(entry.path())?;
+            let relative = entry.path().strip_prefix(path).unwrap_or(entry.path());
+            hasher.update(format!("{} {}\n", sha256, relative.display()));
+            bytes += size;
+        }
+        Ok(Artifact {
+            path: path.to_path_buf(),
+            sha256: hex(&hasher.finalize()),
+            bytes,
+        })
+    }
+}
+
+/// Record of a pipeline run, written next to its outputs so the following
+/// stage can check it consumes what this one produced
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Stage that wrote the manifest, like indexer or ctj
+    pub tool: String,
+    pub version: String,
+    /// Seconds since the unix epoch
+    pub created_at: u64,
+    /// Command line arguments of the run, secrets replaced by ***
+    pub parameters: Vec<String>,
+    pub inputs: Vec<Artifact>,
+    pub outputs: Vec<Artifact>,
+    /// Counters of the stage, like lines read or documents written
+    pub counts: BTreeMap<String, u64>,
+}
+
+impl Manifest {
+    /// Manifest of the running process, with the values of `secret_flags`
+    /// hidden from its arguments
+    pub fn new(tool: &str, version: &str, secret_flags: &[&str]) -> Manifest {
+        Manifest {
+            tool: tool.to_string(),
+            version: version.to_string(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |x| x.as_secs()),
+            parameters: redact_arguments(std::env::args().skip(1), secret_flags),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            counts: BTreeMap::new(),
+        }
+    }
+
+    /// Hashes `path` into the inputs, stdin can't be hashed and is skipped
+    pub fn add_input(&mut self, path: &Path) -> Result<()> {
+        if path != Path::new("-") {
+            self.inputs.push(Artifact::hash(path)?);
+        }
+        Ok(())
+    }
+
+    /// Hashes `path` into the outputs, must be called once it's written
+    pub fn add_output(&mut self, path: &Path) -> Result<()> {
+        self.outputs.push(Artifact::hash(path)?);
+        Ok(())
+    }
+
+    pub fn count(&mut self, name: &str, value: u64) {
+        self.counts.insert(name.to_string(), value);
+    }
+
+    pub fn read(path: &Path) -> Result<Manifest> {
+        let manifest = fs::read_to_string(path).map_err(|source| Error::Open {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(serde_json::from_str(&manifest)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).map_err(|source| Error::Create {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer).map_err(Error::Write)?;
+        writer.flush().map_err(Error::Write)
+    }
+
+    /// Checks that `input` has the content of one of the outputs.
+    /// Files are matched by hash, so they may have been moved since
+    pub fn verify_input(&self, input: &Path) -> Result<()> {
+        let artifact = Artifact::hash(input)?;
+        if self.outputs.iter().any(|x| x.sha256 == artifact.sha256) {
+            Ok(())
+        } else {
+            Err(Error::ManifestMismatch(input.to_path_buf()))
+        }
+    }
+}
+
+/// Replaces the values of `secret_flags` in `args`,
+/// given as `--flag value` or `--flag=value`
+///
+/// # Example
+///
+/// ```
+/// use lib::manifest::redact_arguments;
+///
+/// let args = ["-i", "in.json", "--postgres", "postgres://u:p@db/leaks", "--token=abc"];
+/// assert_eq!(
+///     redact_arguments(args.iter().map(|x| x.to_string()), &["--postgres", "--token"]),
+///     ["-i", "in.json", "--postgres", "***", "--token=***"]
+/// );
+/// ```
+pub fn redact_arguments(args: impl Iterator<Item = String>, secret_flags: &[&str]) -> Vec<String> {
+    let mut redacted = Vec::new();
+    let mut secret = false;
+
+    for arg in args {
+        if secret {
+            redacted.push("***".to_string());
+            secret = false;
+            continue;
+        }
+
+        match arg.split_once('=') {
+            Some((flag, _)) if secret_flags.contains(&flag) => {
+                redacted.push(format!("{}=***", flag));
+            }
+            _ => {
+                secret = secret_flags.contains(&arg.as_str());
+                redacted.push(arg);
+            }
+        }
+    }
+    redacted
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Quotes `name` as a N1QL identifier, a backtick within it is doubled
+///
+/// # Example
+///
+/// ```
+/// use lib::n1ql::identifier;
+///
+/// assert_eq!(identifier("leaks-bucket"), "`leaks-bucket`");
+/// assert_eq!(identifier("a`b"), "`a``b`");
+/// ```
+pub fn identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+/// Collection of a bucket, formatted as a fully qualified keyspace
+/// with every name quoted
+///
+/// # Example
+///
+/// ```
+/// use lib::n1ql::Keyspace;
+///
+/// let leaks = Keyspace::new("default", "leaks-bucket", "_default", "leaks");
+/// assert_eq!(leaks.to_string(), "`default`:`leaks-bucket`.`_default`.`leaks`");
+/// assert_eq!(
+///     leaks.sibling("watchlist").to_string(),
+///     "`default`:`leaks-bucket`.`_default`.`watchlist`"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keyspace {
+    pub namespace: String,
+    pub bucket: String,
+    pub scope: String,
+    pub collection: String,
+}
+
+impl Keyspace {
+    pub fn new(namespace: &str, bucket: &str, scope: &str, collection: &str) -> Keyspace {
+        Keyspace {
+            namespace: namespace.to_string(),
+            bucket: bucket.to_string(),
+            scope: scope.to_string(),
+            collection: collection.to_string(),
+        }
+    }
+
+    /// Another collection of the same bucket and scope
+    pub fn sibling(&self, collection: &str) -> Keyspace {
+        Keyspace {
+            collection: collection.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// First empty name, which the server would refuse
+    pub fn empty_name(&self) -> Option<&'static str> {
+        [
+            ("namespace", &self.namespace),
+            ("bucket", &self.bucket),
+            ("scope", &self.scope),
+            ("collection", &self.collection),
+        ]
+        .into_iter()
+        .find(|(_, name)| name.is_empty())
+        .map(|(kind, _)| kind)
+    }
+}
+
+impl fmt::Display for Keyspace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}.{}.{}",
+            identifier(&self.namespace),
+            identifier(&self.bucket),
+            identifier(&self.scope),
+            identifier(&self.collection)
+        )
+    }
+}
+
+/// Highest positional placeholder $N of `statement`, placeholders
+/// within string literals and quoted identifiers aren't counted
+fn placeholders(statement: &str) -> usize {
+    let mut highest = 0;
+    let mut quote = None;
+    let mut chars = statement.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, '$') => {
+                let digits: String = statement[i + 1..]
+                    .chars()
+                    .take_while(char::is_ascii_digit)
+                    .collect();
+                if let Ok(n) = digits.parse() {
+                    highest = usize::max(highest, n);
+                }
+            }
+            _ => {}
+        }
+    }
+    highest
+}
+
+/// N1QL statement along with the values of its positional parameters.
+/// Values are only ever bound, keyspaces and other names are formatted
+/// into the statement through [`Keyspace`] and [`identifier`]
+///
+/// # Example
+///
+/// ```
+/// use lib::n1ql::{Keyspace, Query};
+/// use serde_json::json;
+///
+/// let leaks = Keyspace::new("default", "leaks-bucket", "_default", "leaks");
+/// let query = Query::new(format!("SELECT * FROM {} WHERE domain = $1 LIMIT $2", leaks))
+///     .bind("corp.com")
+///     .bind(10);
+/// assert_eq!(query.params(), json!(["corp.com", 10]));
+/// assert_eq!(Query::new("SELECT 1").params(), json!(null));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    statement: String,
+    params: Vec<Value>,
+}
+
+impl Query {
+    pub fn new(statement: impl Into<String>) -> Query {
+        Query {
+            statement: statement.into(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Binds `value` to the next placeholder, $1 for the first one
+    pub fn bind(mut self, value: impl Serialize) -> Query {
+        let value = serde_json::to_value(value).expect("query parameters serialize to json");
+        self.params.push(value);
+        self
+    }
+
+    pub fn statement(&self) -> &str {
+        &self.statement
+    }
+
+    /// Parameters as the json array the sdk takes, null without any
+    pub fn params(&self) -> Value {
+        debug_assert!(
+            self.is_bound(),
+            "placeholders and bound values of {:?} differ",
+            self.statement
+        );
+        if self.params.is_empty() {
+            Value::Null
+        } else {
+            Value::Array(self.params.clone())
+        }
+    }
+
+    /// Whether every placeholder has a value bound and every value a placeholder
+    pub fn is_bound(&self) -> bool {
+        placeholders(&self.statement) == self.params.len()
+    }
+}
@@ -0,0 +1,117 @@
+//! Organizations owning domains, read from a csv mapping domains to
+//! organizations like the export of a reverse WHOIS lookup
+
+use std::{collections::HashMap, fs::File, io::Read, path::Path, str::FromStr};
+
+use crate::error::{Error, Result};
+
+/// Header names of the domain and organization columns of a mapping,
+/// given as domain,org. Reverse WHOIS exports name them after their
+/// provider, like domainName,registrantContact.organization
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrgColumns {
+    pub domain: String,
+    pub org: String,
+}
+
+impl Default for OrgColumns {
+    fn default() -> Self {
+        OrgColumns {
+            domain: "domain".to_string(),
+            org: "org".to_string(),
+        }
+    }
+}
+
+impl FromStr for OrgColumns {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once(',') {
+            Some((domain, org)) if !domain.trim().is_empty() && !org.trim().is_empty() => {
+                Ok(OrgColumns {
+                    domain: domain.trim().to_string(),
+                    org: org.trim().to_string(),
+                })
+            }
+            _ => Err(format!(
+                "invalid columns {}, expected <domain column>,<org column>",
+                s
+            )),
+        }
+    }
+}
+
+/// Organization of every domain of a mapping
+#[derive(Clone, Debug, Default)]
+pub struct OrgMap {
+    orgs: HashMap<String, String>,
+}
+
+impl OrgMap {
+    pub fn read(path: &Path, columns: &OrgColumns) -> Result<OrgMap> {
+        let file = File::open(path).map_err(|source| Error::Open {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        OrgMap::from_reader(file, columns)
+    }
+
+    /// Reads a csv whose header names `columns`, ignoring case. Rows
+    /// missing either field are skipped and the first organization
+    /// of a domain wins
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lib::org::{OrgColumns, OrgMap};
+    ///
+    /// let csv = "Domain Name,Registrant Organization,Created\n\
+    ///            Corp.com.,Corp Inc,2004-01-12\n\
+    ///            corp.org,Corp Inc,2009-05-30\n";
+    /// let columns = "domain name,registrant organization".parse().unwrap();
+    /// let orgs = OrgMap::from_reader(csv.as_bytes(), &columns).unwrap();
+    ///
+    /// assert_eq!(orgs.get("corp.com"), Some("Corp Inc"));
+    /// assert_eq!(orgs.get("corp.org"), Some("Corp Inc"));
+    /// assert_eq!(orgs.get("corp.net"), None);
+    /// ```
+    pub fn from_reader(reader: impl Read, columns: &OrgColumns) -> Result<OrgMap> {
+        let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+        let headers = reader.headers()?.clone();
+        let position = |name: &str| {
+            headers
+                .iter()
+                .position(|x| x.trim().eq_ignore_ascii_case(name))
+                .ok_or_else(|| Error::MissingColumn(name.to_string()))
+        };
+        let domain_column = position(&columns.domain)?;
+        let org_column = position(&columns.org)?;
+
+        let mut orgs = HashMap::new();
+        for record in reader.records() {
+            let record = record?;
+            let domain = record.get(domain_column).unwrap_or_default().trim();
+            let org = record.get(org_column).unwrap_or_default().trim();
+            if domain.is_empty() || org.is_empty() {
+                continue;
+            }
+            let domain = domain.trim_end_matches('.').to_lowercase();
+            orgs.entry(domain).or_insert_with(|| org.to_string());
+        }
+        Ok(OrgMap { orgs })
+    }
+
+    /// Organization of `domain`, matched as it's written
+    pub fn get(&self, domain: &str) -> Option<&str> {
+        self.orgs.get(domain).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.orgs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orgs.is_empty()
+    }
+}
@@ -0,0 +1,720 @@
+use std::{
+    borrow::Cow,
+    ffi::OsString,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use arrow_array::{builder::StringBuilder, ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema};
+use csv::{ByteRecord, ReaderBuilder, Writer, WriterBuilder};
+use flate2::write::GzEncoder;
+use parquet::{arrow::ArrowWriter, basic, file::properties::WriterProperties};
+
+#[cfg(feature = "kafka")]
+use crate::kafka::KafkaSink;
+use crate::{
+    encryption::{Encryption, OutputFile},
+    error::Error,
+    telemetry::FlushOnPanic,
+    LeakRecord,
+};
+
+/// Encoding of the indexer output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// domain,subdomain,username,password,password_type,target_domain,source,
+    /// breach_date,source_file,target_type records
+    #[default]
+    Csv,
+    /// One LeakRecord json object per line
+    Jsonl,
+    /// Snappy compressed parquet with a string column per LeakRecord field
+    Parquet,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "parquet" => Ok(OutputFormat::Parquet),
+            _ => Err(format!(
+                "unknown output format {}, expected csv, jsonl or parquet",
+                s
+            )),
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// Streaming compression of csv and jsonl output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(format!("unknown compression {}, expected gzip or zstd", s)),
+        }
+    }
+}
+
+impl Compression {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+        }
+    }
+
+    /// Appends the extension of the compression unless `path` already ends with it
+    pub fn output_path(&self, path: &Path) -> PathBuf {
+        if path.extension().map(|x| x == self.extension()) == Some(true) {
+            return path.to_path_buf();
+        }
+
+        let mut name = OsString::from(path.as_os_str());
+        name.push(".");
+        name.push(self.extension());
+        PathBuf::from(name)
+    }
+}
+
+/// Output file compressed on the fly
+pub enum CompressedFile {
+    Plain(OutputFile),
+    Gzip(GzEncoder<OutputFile>),
+    Zstd(zstd::Encoder<'static, OutputFile>),
+}
+
+impl CompressedFile {
+    pub fn create(path: &Path, compression: Option<Compression>) -> Result<CompressedFile, Error> {
+        CompressedFile::encrypted(path, compression, None)
+    }
+
+    /// Compressed data is encrypted, encrypted data doesn't compress
+    pub fn encrypted(
+        path: &Path,
+        compression: Option<Compression>,
+        encryption: Option<&Encryption>,
+    ) -> Result<CompressedFile, Error> {
+        let file = OutputFile::create(path, encryption)?;
+        Ok(match compression {
+            None => CompressedFile::Plain(file),
+            Some(Compression::Gzip) => {
+                CompressedFile::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            Some(Compression::Zstd) => {
+                CompressedFile::Zstd(zstd::Encoder::new(file, 0).map_err(Error::Write)?)
+            }
+        })
+    }
+
+    /// Writes the trailer of the compressed stream, dropping the file without
+    /// finishing leaves a truncated stream behind
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            CompressedFile::Plain(file) => file.finish(),
+            CompressedFile::Gzip(encoder) => encoder.finish()?.finish(),
+            CompressedFile::Zstd(encoder) => encoder.finish()?.finish(),
+        }
+    }
+}
+
+impl Write for CompressedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedFile::Plain(file) => file.write(buf),
+            CompressedFile::Gzip(encoder) => encoder.write(buf),
+            CompressedFile::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedFile::Plain(file) => file.flush(),
+            CompressedFile::Gzip(encoder) => encoder.flush(),
+            CompressedFile::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Quoting policy of csv fields
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QuoteStyle {
+    Always,
+    /// Only fields containing delimiters, quotes or newlines are quoted
+    #[default]
+    Necessary,
+    NonNumeric,
+    /// Fields are never quoted, the output may become ambiguous
+    Never,
+}
+
+impl FromStr for QuoteStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(QuoteStyle::Always),
+            "necessary" => Ok(QuoteStyle::Necessary),
+            "non-numeric" => Ok(QuoteStyle::NonNumeric),
+            "never" => Ok(QuoteStyle::Never),
+            _ => Err(format!(
+                "unknown quote style {}, expected always, necessary, non-numeric or never",
+                s
+            )),
+        }
+    }
+}
+
+/// Parses a single byte delimiter, tab can be given as \t or tab
+pub fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s {
+        "\\t" | "tab" => Ok(b'\t'),
+        _ if s.len() == 1 => Ok(s.as_bytes()[0]),
+        _ => Err(format!("delimiter must be a single byte, got {}", s)),
+    }
+}
+
+/// Field of the csv output, every LeakRecord field plus the reassembled email
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Column {
+    Domain,
+    Subdomain,
+    Username,
+    Password,
+    PasswordType,
+    TargetDomain,
+    Source,
+    BreachDate,
+    SourceFile,
+    TargetType,
+    /// username@subdomain.domain, or username@domain without a subdomain
+    Email,
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "domain" => Ok(Column::Domain),
+            "subdomain" => Ok(Column::Subdomain),
+            "username" => Ok(Column::Username),
+            "password" => Ok(Column::Password),
+            "password_type" => Ok(Column::PasswordType),
+            "target_domain" => Ok(Column::TargetDomain),
+            "source" => Ok(Column::Source),
+            "breach_date" => Ok(Column::BreachDate),
+            "source_file" => Ok(Column::SourceFile),
+            "target_type" => Ok(Column::TargetType),
+            "email" => Ok(Column::Email),
+            _ => Err(format!(
+                "unknown column {}, expected domain, subdomain, username, password, \
+                 password_type, target_domain, source, breach_date, source_file, \
+                 target_type or email",
+                s
+            )),
+        }
+    }
+}
+
+impl Column {
+    /// Header of the column
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Domain => "domain",
+            Column::Subdomain => "subdomain",
+            Column::Username => "username",
+            Column::Password => "password",
+            Column::PasswordType => "password_type",
+            Column::TargetDomain => "target_domain",
+            Column::Source => "source",
+            Column::BreachDate => "breach_date",
+            Column::SourceFile => "source_file",
+            Column::TargetType => "target_type",
+            Column::Email => "email",
+        }
+    }
+
+    /// Value of the column in `record`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lib::{output::Column, LeakRecord};
+    ///
+    /// let record = LeakRecord {
+    ///     domain: "corp.com".into(),
+    ///     subdomain: "mail".into(),
+    ///     username: "user".into(),
+    ///     password: "pass".into(),
+    ///     password_type: "plain".into(),
+    ///     target_domain: "".into(),
+    ///     source: "".into(),
+    ///     breach_date: "".into(),
+    ///     source_file: "".into(),
+    ///     target_type: "domain".into(),
+    /// };
+    /// assert_eq!(Column::Email.value(&record), "user@mail.corp.com");
+    /// assert_eq!(Column::Password.value(&record), "pass");
+    /// ```
+    pub fn value<'a>(&self, record: &'a LeakRecord) -> Cow<'a, str> {
+        match self {
+            Column::Domain => Cow::Borrowed(&record.domain),
+            Column::Subdomain => Cow::Borrowed(&record.subdomain),
+            Column::Username => Cow::Borrowed(&record.username),
+            Column::Password => Cow::Borrowed(&record.password),
+            Column::PasswordType => Cow::Borrowed(&record.password_type),
+            Column::TargetDomain => Cow::Borrowed(&record.target_domain),
+            Column::Source => Cow::Borrowed(&record.source),
+            Column::BreachDate => Cow::Borrowed(&record.breach_date),
+            Column::SourceFile => Cow::Borrowed(&record.source_file),
+            Column::TargetType => Cow::Borrowed(&record.target_type),
+            Column::Email if record.subdomain.is_empty() => {
+                Cow::Owned(format!("{}@{}", record.username, record.domain))
+            }
+            Column::Email => Cow::Owned(format!(
+                "{}@{}.{}",
+                record.username, record.subdomain, record.domain
+            )),
+        }
+    }
+}
+
+/// Columns of the csv output unless others are requested
+pub static DEFAULT_COLUMNS: [Column; 10] = [
+    Column::Domain,
+    Column::Subdomain,
+    Column::Username,
+    Column::Password,
+    Column::PasswordType,
+    Column::TargetDomain,
+    Column::Source,
+    Column::BreachDate,
+    Column::SourceFile,
+    Column::TargetType,
+];
+
+/// Csv dialect shared by the tools reading and writing csv
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub quote_style: QuoteStyle,
+    /// Whether the first record is a header
+    pub header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            quote_style: QuoteStyle::default(),
+            header: true,
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn writer_builder(&self) -> WriterBuilder {
+        let quote_style = match self.quote_style {
+            QuoteStyle::Always => csv::QuoteStyle::Always,
+            QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+            QuoteStyle::NonNumeric => csv::QuoteStyle::NonNumeric,
+            QuoteStyle::Never => csv::QuoteStyle::Never,
+        };
+
+        let mut builder = WriterBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote_style(quote_style)
+            .has_headers(self.header);
+        builder
+    }
+
+    pub fn reader_builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quoting(self.quote_style != QuoteStyle::Never)
+            .has_headers(self.header);
+        builder
+    }
+}
+
+/// Positions of the columns the tools reading indexer csv output use. The
+/// source, breach_date and source_file columns are optional. target_type is
+/// output-only: documents are keyed by the target, which for an ip address
+/// target is the address itself, so it isn't read back
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CsvColumns {
+    pub domain: usize,
+    pub subdomain: usize,
+    pub username: usize,
+    pub password: usize,
+    pub source: Option<usize>,
+    pub breach_date: Option<usize>,
+    pub source_file: Option<usize>,
+}
+
+impl Default for CsvColumns {
+    /// Default columns of the indexer output
+    fn default() -> Self {
+        CsvColumns {
+            domain: 0,
+            subdomain: 1,
+            username: 2,
+            password: 3,
+            source: Some(6),
+            breach_date: Some(7),
+            source_file: Some(8),
+        }
+    }
+}
+
+impl CsvColumns {
+    /// Columns named by `header`, so any --output-columns of the indexer
+    /// can be read. The credential columns are required
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use csv::ByteRecord;
+    /// use lib::output::CsvColumns;
+    ///
+    /// let header = ByteRecord::from(vec!["email", "password", "username", "subdomain", "domain"]);
+    /// let columns = CsvColumns::from_header(&header).unwrap();
+    /// assert_eq!(columns.domain, 4);
+    /// assert_eq!(columns.source_file, None);
+    ///
+    /// assert!(CsvColumns::from_header(&ByteRecord::from(vec!["domain", "password"])).is_err());
+    /// ```
+    pub fn from_header(header: &ByteRecord) -> Result<CsvColumns, Error> {
+        let position = |name: &str| {
+            header
+                .iter()
+                .position(|x| x.trim_ascii() == name.as_bytes())
+        };
+        let required =
+            |name: &str| position(name).ok_or_else(|| Error::MissingColumn(name.to_string()));
+        Ok(CsvColumns {
+            domain: required("domain")?,
+            subdomain: required("subdomain")?,
+            username: required("username")?,
+            password: required("password")?,
+            source: position("source"),
+            breach_date: position("breach_date"),
+            source_file: position("source_file"),
+        })
+    }
+}
+
+/// Passes the bytes a csv writer buffered on to the writer below when the
+/// csv writer is flushed, without flushing that one too
+struct Unflushed<W>(W);
+
+impl<W: Write> Write for Unflushed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Csv file whose complete records are flushed by the panic hook, see
+/// [`FlushOnPanic`]. Records are moved past the buffer of the csv writer
+/// as soon as they are written, so the hook reaches them
+pub struct CsvFile(Writer<Unflushed<FlushOnPanic<BufWriter<CompressedFile>>>>);
+
+impl CsvFile {
+    pub fn new(file: CompressedFile, builder: &WriterBuilder) -> CsvFile {
+        let writer = FlushOnPanic::new(BufWriter::new(file));
+        CsvFile(builder.from_writer(Unflushed(writer)))
+    }
+
+    /// Adds a field to the record being written, see [`CsvFile::end_record`]
+    pub fn write_field(&mut self, field: impl AsRef<[u8]>) -> Result<(), Error> {
+        Ok(self.0.write_field(field)?)
+    }
+
+    pub fn end_record(&mut self) -> Result<(), Error> {
+        self.0.write_record(None::<&[u8]>)?;
+        self.0.flush().map_err(Error::Write)
+    }
+
+    pub fn write_record<I, T>(&mut self, record: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        self.0.write_record(record)?;
+        self.0.flush().map_err(Error::Write)
+    }
+
+    /// Flushes the records and returns the file, which is left to be finished
+    pub fn into_inner(self) -> Result<CompressedFile, Error> {
+        self.0
+            .into_inner()
+            .map_err(|e| Error::Write(io::Error::new(e.error().kind(), e.error().to_string())))?
+            .0
+            .into_inner()
+            .into_inner()
+            .map_err(|e| Error::Write(e.into_error()))
+    }
+}
+
+/// Destination of parsed records. Csv and jsonl files keep the records
+/// written before a panic, see [`FlushOnPanic`]. Compressed and encrypted
+/// ones can't be read back after a panic though: the compressed stream and
+/// the encrypted chunks are only completed by [`OutputWriter::finish`]
+pub enum OutputWriter {
+    /// Written columns in output order
+    Csv(Box<CsvFile>, Vec<Column>),
+    Jsonl(FlushOnPanic<BufWriter<CompressedFile>>),
+    Parquet(Box<ParquetWriter>),
+    /// Json records published to a topic
+    #[cfg(feature = "kafka")]
+    Kafka(Box<KafkaSink>),
+    /// Writer of a batch shared by its indexers, see
+    /// [`crate::indexer::SharedOutput`] which finishes it
+    Shared(Arc<Mutex<OutputWriter>>),
+}
+
+impl OutputWriter {
+    pub fn new(
+        output_path: &Path,
+        format: OutputFormat,
+        csv: CsvOptions,
+    ) -> Result<OutputWriter, Error> {
+        OutputWriter::compressed(output_path, format, csv, None)
+    }
+
+    /// Csv and jsonl output can be compressed, parquet compresses its pages itself
+    pub fn compressed(
+        output_path: &Path,
+        format: OutputFormat,
+        csv: CsvOptions,
+        compression: Option<Compression>,
+    ) -> Result<OutputWriter, Error> {
+        OutputWriter::encrypted(output_path, format, csv, compression, None)
+    }
+
+    /// Every format can be encrypted
+    pub fn encrypted(
+        output_path: &Path,
+        format: OutputFormat,
+        csv: CsvOptions,
+        compression: Option<Compression>,
+        encryption: Option<&Encryption>,
+    ) -> Result<OutputWriter, Error> {
+        assert!(
+            format != OutputFormat::Parquet || compression.is_none(),
+            "parquet output can't be compressed"
+        );
+
+        let create = || CompressedFile::encrypted(output_path, compression, encryption);
+
+        Ok(match format {
+            OutputFormat::Csv => {
+                return OutputWriter::csv_columns(
+                    output_path,
+                    csv,
+                    compression,
+                    encryption,
+                    DEFAULT_COLUMNS.to_vec(),
+                )
+            }
+            OutputFormat::Jsonl => {
+                OutputWriter::Jsonl(FlushOnPanic::new(BufWriter::new(create()?)))
+            }
+            OutputFormat::Parquet => {
+                let output = OutputFile::create(output_path, encryption)?;
+                OutputWriter::Parquet(Box::new(ParquetWriter::new(output)?))
+            }
+        })
+    }
+
+    /// Csv output with only `columns`, in their order
+    pub fn csv_columns(
+        output_path: &Path,
+        csv: CsvOptions,
+        compression: Option<Compression>,
+        encryption: Option<&Encryption>,
+        columns: Vec<Column>,
+    ) -> Result<OutputWriter, Error> {
+        let file = CompressedFile::encrypted(output_path, compression, encryption)?;
+        let mut writer = CsvFile::new(file, &csv.writer_builder());
+        if csv.header {
+            writer.write_record(columns.iter().map(Column::name))?;
+        }
+        Ok(OutputWriter::Csv(Box::new(writer), columns))
+    }
+
+    pub fn write(&mut self, record: &LeakRecord) -> Result<(), Error> {
+        match self {
+            OutputWriter::Csv(writer, columns) => {
+                for column in columns.iter() {
+                    writer.write_field(column.value(record).as_bytes())?;
+                }
+                writer.end_record()?;
+            }
+            OutputWriter::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, record)?;
+                writer.write_all(b"\n").map_err(Error::Write)?;
+            }
+            OutputWriter::Parquet(writer) => writer.write(record)?,
+            #[cfg(feature = "kafka")]
+            OutputWriter::Kafka(sink) => sink.write(record)?,
+            OutputWriter::Shared(writer) => writer.lock().unwrap().write(record)?,
+        }
+        Ok(())
+    }
+
+    /// Waits until the records written so far are delivered. Only kafka
+    /// output sends them asynchronously, other outputs return right away
+    pub fn sync(&mut self) -> Result<(), Error> {
+        match self {
+            #[cfg(feature = "kafka")]
+            OutputWriter::Kafka(sink) => sink.flush(),
+            OutputWriter::Shared(writer) => writer.lock().unwrap().sync(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Flushes buffered records, formats with a footer get it written
+    pub fn finish(self) -> Result<(), Error> {
+        match self {
+            OutputWriter::Csv(writer, _) => writer.into_inner()?.finish().map_err(Error::Write),
+            OutputWriter::Jsonl(writer) => writer
+                .into_inner()
+                .into_inner()
+                .map_err(|e| Error::Write(e.into_error()))?
+                .finish()
+                .map_err(Error::Write),
+            OutputWriter::Parquet(writer) => writer.finish(),
+            #[cfg(feature = "kafka")]
+            OutputWriter::Kafka(sink) => sink.finish(),
+            OutputWriter::Shared(_) => Ok(()),
+        }
+    }
+}
+
+/// Amount of rows buffered before they are handed to the parquet writer
+static PARQUET_BATCH_SIZE: usize = 65536;
+
+/// Fields of LeakRecord in output order
+pub static COLUMNS: [&str; 10] = [
+    "domain",
+    "subdomain",
+    "username",
+    "password",
+    "password_type",
+    "target_domain",
+    "source",
+    "breach_date",
+    "source_file",
+    "target_type",
+];
+
+/// Buffers records column-wise and writes them in batches,
+/// so memory stays bounded by the row group size
+pub struct ParquetWriter {
+    writer: ArrowWriter<OutputFile>,
+    schema: Arc<Schema>,
+    columns: Vec<StringBuilder>,
+    rows: usize,
+}
+
+impl ParquetWriter {
+    pub fn new(output: OutputFile) -> Result<ParquetWriter, Error> {
+        let fields: Vec<Field> = COLUMNS
+            .iter()
+            .map(|name| Field::new(*name, DataType::Utf8, false))
+            .collect();
+        let schema = Arc::new(Schema::new(fields));
+
+        let props = WriterProperties::builder()
+            .set_compression(basic::Compression::SNAPPY)
+            .build();
+        let writer = ArrowWriter::try_new(output, schema.clone(), Some(props))?;
+
+        Ok(ParquetWriter {
+            writer,
+            schema,
+            columns: COLUMNS.iter().map(|_| StringBuilder::new()).collect(),
+            rows: 0,
+        })
+    }
+
+    pub fn write(&mut self, record: &LeakRecord) -> Result<(), Error> {
+        let values = [
+            &record.domain,
+            &record.subdomain,
+            &record.username,
+            &record.password,
+            &record.password_type,
+            &record.target_domain,
+            &record.source,
+            &record.breach_date,
+            &record.source_file,
+            &record.target_type,
+        ];
+        for (column, value) in self.columns.iter_mut().zip(values) {
+            column.append_value(value);
+        }
+
+        self.rows += 1;
+        if self.rows == PARQUET_BATCH_SIZE {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<(), Error> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+
+        let arrays: Vec<ArrayRef> = self
+            .columns
+            .iter_mut()
+            .map(|column| Arc::new(column.finish()) as ArrayRef)
+            .collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
+
+        self.writer.write(&batch)?;
+        self.rows = 0;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.write_batch()?;
+        // Writes the footer before the encrypted stream gets finished
+        let output = self.writer.into_inner()?;
+        output.finish().map_err(Error::Write)
+    }
+}
@@ -0,0 +1,220 @@
+//! Line parsers of the indexer, each one a layout of leak entries.
+//! A new layout is a [`LineParser`] in a module of its own plus a
+//! [`ParserKind`] to select it by
+
+use std::{fmt, str::FromStr, sync::Arc};
+
+use crate::{
+    entry::{EntryFormat, ParseError, ParseOptions, ParsedEntry},
+    PublicSuffixList,
+};
+
+mod delimited;
+mod email_pass;
+mod phone_pass;
+mod url_user_pass;
+mod user_pass_domain;
+
+pub use delimited::{Delimited, RecordField};
+pub use email_pass::EmailPass;
+pub use phone_pass::{PhonePass, PHONE_DOMAIN};
+pub use url_user_pass::UrlUserPass;
+pub use user_pass_domain::UserPassDomain;
+
+/// Splits lines of one layout into the fields of an output record
+pub trait LineParser: Send + Sync {
+    /// Name the parser is selected by
+    fn name(&self) -> &'static str;
+
+    /// Whether `line` has the layout of the parser. Only parsers that
+    /// accept a line get to parse it, their errors are the ones reported
+    fn accepts(&self, _line: &str, _options: &ParseOptions) -> bool {
+        true
+    }
+
+    fn parse<'a>(
+        &self,
+        line: &'a str,
+        st: &PublicSuffixList,
+        options: &ParseOptions,
+    ) -> Result<ParsedEntry<'a>, ParseError>;
+
+    /// Value of the password_type column of the parsed entries
+    fn password_type(&self, options: &ParseOptions) -> &'static str {
+        options.format.password_type()
+    }
+}
+
+/// Parser selectable on the command line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParserKind {
+    EmailPass,
+    UserPassDomain,
+    UrlUserPass,
+    Delimited,
+    /// Phone number logins, not among the parsers of any format
+    PhonePass,
+}
+
+impl FromStr for ParserKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "email:pass" => Ok(ParserKind::EmailPass),
+            "user:pass@domain" => Ok(ParserKind::UserPassDomain),
+            "url:user:pass" => Ok(ParserKind::UrlUserPass),
+            "delimited" => Ok(ParserKind::Delimited),
+            "phone:pass" => Ok(ParserKind::PhonePass),
+            _ => Err(format!(
+                "unknown parser {}, expected one of: email:pass, user:pass@domain, url:user:pass, delimited, phone:pass",
+                s
+            )),
+        }
+    }
+}
+
+static EMAIL_PARSERS: [&dyn LineParser; 2] = [&EmailPass, &UserPassDomain];
+static URL_PARSERS: [&dyn LineParser; 1] = [&UrlUserPass];
+
+/// Parsers tried in order on every line, the first one to parse it wins.
+/// Without any, the ones of the entry format are used
+///
+/// # Example
+///
+/// ```
+/// use lib::{
+///     entry::{parse_line, ParseOptions},
+///     parsers::{Delimited, ParserKind, Parsers, RecordField},
+///     PublicSuffixList,
+/// };
+///
+/// let st = PublicSuffixList::new("com");
+/// let records = Delimited::new(b'\t', vec![RecordField::Email, RecordField::Password]).unwrap();
+/// let options = ParseOptions {
+///     parsers: Parsers::new(&[ParserKind::EmailPass, ParserKind::Delimited], records),
+///     ..Default::default()
+/// };
+///
+/// let entry = parse_line("wolya@mail.com\t5555", &st, &options).unwrap();
+/// assert_eq!((entry.username.as_ref(), entry.password), ("wolya", "5555"));
+/// let entry = parse_line("wolya@mail.com:5555", &st, &options).unwrap();
+/// assert_eq!(entry.domain, "mail.com");
+/// ```
+#[derive(Clone, Default)]
+pub struct Parsers(Vec<Arc<dyn LineParser>>);
+
+impl Parsers {
+    /// Parsers of `kinds`, delimited ones split records as `records` describes
+    pub fn new(kinds: &[ParserKind], records: Delimited) -> Parsers {
+        let parsers = kinds
+            .iter()
+            .map(|kind| -> Arc<dyn LineParser> {
+                match kind {
+                    ParserKind::EmailPass => Arc::new(EmailPass),
+                    ParserKind::UserPassDomain => Arc::new(UserPassDomain),
+                    ParserKind::UrlUserPass => Arc::new(UrlUserPass),
+                    ParserKind::Delimited => Arc::new(records.clone()),
+                    ParserKind::PhonePass => Arc::new(PhonePass),
+                }
+            })
+            .collect();
+        Parsers(parsers)
+    }
+
+    /// Appends a parser of a layout without a [`ParserKind`]
+    pub fn with(mut self, parser: impl LineParser + 'static) -> Parsers {
+        self.0.push(Arc::new(parser));
+        self
+    }
+
+    /// Parses `line` with the first parser that accepts and parses it,
+    /// returning the entry along with its password type
+    pub fn parse<'a>(
+        &self,
+        line: &'a str,
+        st: &PublicSuffixList,
+        options: &ParseOptions,
+    ) -> Result<(ParsedEntry<'a>, &'static str), ParseError> {
+        if !self.0.is_empty() {
+            return first_parsed(self.0.iter().map(AsRef::as_ref), line, st, options);
+        }
+        let defaults: &[&dyn LineParser] = match options.format {
+            EntryFormat::UrlLoginPass => &URL_PARSERS,
+            _ => &EMAIL_PARSERS,
+        };
+        first_parsed(defaults.iter().copied(), line, st, options)
+    }
+
+    /// Names of the parsers in order
+    pub fn names(&self) -> Vec<&'static str> {
+        self.0.iter().map(|parser| parser.name()).collect()
+    }
+
+    /// The parser that parses the most lines of `sample`, along with its
+    /// number of hits. Earlier parsers win ties, None when none parses a line.
+    /// Without any parsers, the ones of the entry format are scored
+    pub fn detect(
+        &self,
+        sample: &[&str],
+        st: &PublicSuffixList,
+        options: &ParseOptions,
+    ) -> Option<(Parsers, usize)> {
+        let candidates = if self.0.is_empty() {
+            Parsers::new(format_parsers(options.format), Delimited::default())
+        } else {
+            self.clone()
+        };
+        let hits = |parser: &Arc<dyn LineParser>| {
+            sample
+                .iter()
+                .filter(|line| {
+                    parser.accepts(line, options) && parser.parse(line, st, options).is_ok()
+                })
+                .count()
+        };
+
+        let mut best: Option<(&Arc<dyn LineParser>, usize)> = None;
+        for parser in &candidates.0 {
+            let hits = hits(parser);
+            if hits > best.map_or(0, |(_, most)| most) {
+                best = Some((parser, hits));
+            }
+        }
+        best.map(|(parser, hits)| (Parsers(vec![parser.clone()]), hits))
+    }
+}
+
+fn format_parsers(format: EntryFormat) -> &'static [ParserKind] {
+    match format {
+        EntryFormat::UrlLoginPass => &[ParserKind::UrlUserPass],
+        _ => &[ParserKind::EmailPass, ParserKind::UserPassDomain],
+    }
+}
+
+/// Errors of the first parser that accepted the line are reported
+fn first_parsed<'a, 'p>(
+    parsers: impl Iterator<Item = &'p dyn LineParser>,
+    line: &'a str,
+    st: &PublicSuffixList,
+    options: &ParseOptions,
+) -> Result<(ParsedEntry<'a>, &'static str), ParseError> {
+    let mut error = None;
+    for parser in parsers.filter(|parser| parser.accepts(line, options)) {
+        match parser.parse(line, st, options) {
+            Ok(entry) => return Ok((entry, parser.password_type(options))),
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    Err(error.unwrap_or(ParseError::BadFormat))
+}
+
+impl fmt::Debug for Parsers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|parser| parser.name()))
+            .finish()
+    }
+}
@@ -0,0 +1,170 @@
+use std::str::FromStr;
+
+use super::LineParser;
+use crate::{
+    entry::{host_fields, url_fields, url_host, ParseError, ParseOptions, ParsedEntry},
+    PublicSuffixList,
+};
+
+/// Field of a delimited record
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordField {
+    Email,
+    Username,
+    Domain,
+    Password,
+    /// Url of the site the credentials are for, its host is kept as the target
+    Url,
+    Skip,
+}
+
+impl FromStr for RecordField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "email" => Ok(RecordField::Email),
+            "username" => Ok(RecordField::Username),
+            "domain" => Ok(RecordField::Domain),
+            "password" => Ok(RecordField::Password),
+            "url" => Ok(RecordField::Url),
+            "skip" => Ok(RecordField::Skip),
+            _ => Err(format!(
+                "unknown record field {}, expected one of: email, username, domain, password, url, skip",
+                s
+            )),
+        }
+    }
+}
+
+/// Records of fields split by a delimiter, like csv or tsv dumps.
+/// The last field takes the rest of the line, quotes around a field are dropped
+///
+/// # Example
+///
+/// ```
+/// use lib::parsers::{Delimited, LineParser, RecordField};
+/// use lib::{entry::ParseOptions, PublicSuffixList};
+///
+/// let st = PublicSuffixList::new("com");
+/// let fields = vec![RecordField::Skip, RecordField::Password, RecordField::Email];
+/// let records = Delimited::new(b',', fields).unwrap();
+///
+/// let entry = records
+///     .parse("42,\"5555\",wolya@mail.com", &st, &ParseOptions::default())
+///     .unwrap();
+/// assert_eq!(entry.username, "wolya");
+/// assert_eq!(entry.password, "5555");
+/// assert_eq!(entry.domain, "mail.com");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Delimited {
+    delimiter: char,
+    fields: Vec<RecordField>,
+}
+
+impl Delimited {
+    /// Refuses fields without a password or a username to file it under
+    pub fn new(delimiter: u8, fields: Vec<RecordField>) -> Result<Delimited, String> {
+        let count = |field| fields.iter().filter(|&&x| x == field).count();
+        let repeated = [
+            RecordField::Email,
+            RecordField::Username,
+            RecordField::Domain,
+            RecordField::Password,
+            RecordField::Url,
+        ]
+        .into_iter()
+        .find(|&field| count(field) > 1);
+        if let Some(field) = repeated {
+            return Err(format!("record field {:?} is repeated", field));
+        }
+
+        if count(RecordField::Password) == 0 {
+            return Err("records need a password field".to_string());
+        }
+        let has_login = count(RecordField::Email) == 1
+            || (count(RecordField::Username) == 1
+                && count(RecordField::Domain) + count(RecordField::Url) > 0);
+        if !has_login {
+            return Err(
+                "records need an email field, or a username field along with a domain or url one"
+                    .to_string(),
+            );
+        }
+
+        Ok(Delimited {
+            delimiter: char::from(delimiter),
+            fields,
+        })
+    }
+}
+
+impl Default for Delimited {
+    /// email,password records
+    fn default() -> Self {
+        Delimited {
+            delimiter: ',',
+            fields: vec![RecordField::Email, RecordField::Password],
+        }
+    }
+}
+
+fn unquote(field: &str) -> &str {
+    let field = field.trim();
+    field
+        .strip_prefix('"')
+        .and_then(|x| x.strip_suffix('"'))
+        .unwrap_or(field)
+}
+
+impl LineParser for Delimited {
+    fn name(&self) -> &'static str {
+        "delimited"
+    }
+
+    fn accepts(&self, line: &str, _options: &ParseOptions) -> bool {
+        line.contains(self.delimiter)
+    }
+
+    fn parse<'a>(
+        &self,
+        line: &'a str,
+        st: &PublicSuffixList,
+        options: &ParseOptions,
+    ) -> Result<ParsedEntry<'a>, ParseError> {
+        let (mut email, mut username, mut domain, mut password, mut url) =
+            (None, None, None, "", None);
+        let mut values = line.splitn(self.fields.len(), self.delimiter);
+        for field in &self.fields {
+            let value = unquote(values.next().ok_or(ParseError::NoSeparator)?);
+            match field {
+                RecordField::Email => email = Some(value),
+                RecordField::Username => username = Some(value),
+                RecordField::Domain => domain = Some(value),
+                RecordField::Password => password = value,
+                RecordField::Url => url = Some(value),
+                RecordField::Skip => {}
+            }
+        }
+        if password.is_empty() {
+            return Err(ParseError::BadFormat);
+        }
+
+        if let Some(url) = url {
+            let login = email.or(username).ok_or(ParseError::BadFormat)?;
+            return url_fields(url_host(url).0, login, password, st, options);
+        }
+
+        let (username, domain) = match (email, username, domain) {
+            // Like email:pass entries, an email needs both of its parts
+            (Some(email), _, _) => email
+                .rsplit_once('@')
+                .filter(|(username, domain)| !username.is_empty() && !domain.is_empty())
+                .ok_or(ParseError::BadFormat)?,
+            (None, Some(username), Some(domain)) => (username, domain),
+            _ => return Err(ParseError::BadFormat),
+        };
+        host_fields(username, domain, password, st, options)
+    }
+}
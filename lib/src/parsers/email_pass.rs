@@ -0,0 +1,56 @@
+use super::LineParser;
+use crate::{
+    entry::{
+        credentials_first, parse_formatted, EntryFormat, ParseError, ParseOptions, ParsedEntry,
+    },
+    PublicSuffixList,
+};
+
+/// username@domain:password, the password may be a hash as the entry
+/// format says. Takes every line with --last-colon
+pub struct EmailPass;
+
+/// Credentials of `line`, without the nickname of user:email:pass entries
+pub(super) fn credentials<'a>(line: &'a str, options: &ParseOptions) -> Option<&'a str> {
+    match options.format {
+        EntryFormat::UserEmailPass => line
+            .split_once(|c| options.separators.contains(c))
+            .map(|(_, rest)| rest),
+        _ => Some(line),
+    }
+}
+
+pub(super) fn parse_email<'a>(
+    line: &'a str,
+    st: &PublicSuffixList,
+    options: &ParseOptions,
+) -> Result<ParsedEntry<'a>, ParseError> {
+    parse_formatted(line, st, options)
+}
+
+impl LineParser for EmailPass {
+    fn name(&self) -> &'static str {
+        "email:pass"
+    }
+
+    // Quoted local parts are split in either layout
+    fn accepts(&self, line: &str, options: &ParseOptions) -> bool {
+        match credentials(line, options) {
+            Some(entry) => {
+                options.last_colon
+                    || entry.starts_with('"')
+                    || credentials_first(entry, &options.separators) != Some(true)
+            }
+            None => true,
+        }
+    }
+
+    fn parse<'a>(
+        &self,
+        line: &'a str,
+        st: &PublicSuffixList,
+        options: &ParseOptions,
+    ) -> Result<ParsedEntry<'a>, ParseError> {
+        parse_email(line, st, options)
+    }
+}
@@ -0,0 +1,96 @@
+use std::borrow::Cow;
+
+use super::LineParser;
+use crate::{
+    entry::{ParseError, ParseOptions, ParsedEntry, TargetType},
+    PublicSuffixList,
+};
+
+/// Domain the entries of phone number logins are filed under
+pub const PHONE_DOMAIN: &str = "phone";
+
+/// phone:password of regional leaks, logins are E.164 like numbers without
+/// a domain. Spaces, dashes, dots and parentheses are dropped from the number
+///
+/// # Example
+///
+/// ```
+/// use lib::parsers::{LineParser, PhonePass};
+/// use lib::{entry::ParseOptions, PublicSuffixList};
+///
+/// let st = PublicSuffixList::new("com");
+/// let entry = PhonePass
+///     .parse("+7 (916) 123-45-67:5555", &st, &ParseOptions::default())
+///     .unwrap();
+/// assert_eq!(entry.username, "+79161234567");
+/// assert_eq!(entry.password, "5555");
+/// assert_eq!(entry.domain, "phone");
+/// ```
+pub struct PhonePass;
+
+/// Number of digits of the shortest local and the longest E.164 numbers
+const DIGITS: std::ops::RangeInclusive<usize> = 7..=15;
+
+fn is_phone_char(c: char) -> bool {
+    c.is_ascii_digit() || matches!(c, ' ' | '-' | '.' | '(' | ')')
+}
+
+/// `login` without its formatting, None unless it's a phone number
+fn phone_number(login: &str) -> Option<Cow<'_, str>> {
+    let login = login.trim();
+    let digits = login.strip_prefix('+').unwrap_or(login);
+    if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '(')
+        || !digits.chars().all(is_phone_char)
+    {
+        return None;
+    }
+    if !DIGITS.contains(&digits.chars().filter(char::is_ascii_digit).count()) {
+        return None;
+    }
+
+    if digits.chars().all(|c| c.is_ascii_digit()) {
+        return Some(Cow::Borrowed(login));
+    }
+    let plus = if digits.len() < login.len() { "+" } else { "" };
+    let number = digits.chars().filter(char::is_ascii_digit);
+    Some(Cow::Owned(plus.chars().chain(number).collect()))
+}
+
+impl LineParser for PhonePass {
+    fn name(&self) -> &'static str {
+        "phone:pass"
+    }
+
+    fn accepts(&self, line: &str, options: &ParseOptions) -> bool {
+        line.split_once(|c| options.separators.contains(c))
+            .is_some_and(|(login, _)| phone_number(login).is_some())
+    }
+
+    fn parse<'a>(
+        &self,
+        line: &'a str,
+        _st: &PublicSuffixList,
+        options: &ParseOptions,
+    ) -> Result<ParsedEntry<'a>, ParseError> {
+        let (login, password) = line
+            .split_once(|c| options.separators.contains(c))
+            .ok_or(ParseError::NoSeparator)?;
+        let username = phone_number(login).ok_or(ParseError::BadUsername)?;
+        if password.is_empty() {
+            return Err(ParseError::BadFormat);
+        }
+
+        options.rules.check_username(&username)?;
+        options.rules.check_password(password)?;
+        options.rules.check_domain(PHONE_DOMAIN)?;
+        Ok(ParsedEntry {
+            username,
+            password,
+            subdomain: String::new(),
+            domain: PHONE_DOMAIN.to_string(),
+            target_domain: String::new(),
+            target_type: TargetType::Domain,
+            corrected: false,
+        })
+    }
+}
@@ -0,0 +1,28 @@
+use super::LineParser;
+use crate::{
+    entry::{parse_url_credentials, ParseError, ParseOptions, ParsedEntry},
+    PublicSuffixList,
+};
+
+/// url:login:password of stealer logs, the url host is kept as the target.
+/// Logins that aren't emails are filed under the target domain
+pub struct UrlUserPass;
+
+impl LineParser for UrlUserPass {
+    fn name(&self) -> &'static str {
+        "url:user:pass"
+    }
+
+    fn parse<'a>(
+        &self,
+        line: &'a str,
+        st: &PublicSuffixList,
+        options: &ParseOptions,
+    ) -> Result<ParsedEntry<'a>, ParseError> {
+        parse_url_credentials(line, st, options)
+    }
+
+    fn password_type(&self, _options: &ParseOptions) -> &'static str {
+        "plain"
+    }
+}
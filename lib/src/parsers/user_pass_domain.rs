@@ -0,0 +1,36 @@
+use super::{
+    email_pass::{credentials, parse_email},
+    LineParser,
+};
+use crate::{
+    entry::{credentials_first, ParseError, ParseOptions, ParsedEntry},
+    PublicSuffixList,
+};
+
+/// username:password@domain, the domain follows the last @
+pub struct UserPassDomain;
+
+impl LineParser for UserPassDomain {
+    fn name(&self) -> &'static str {
+        "user:pass@domain"
+    }
+
+    fn accepts(&self, line: &str, options: &ParseOptions) -> bool {
+        match credentials(line, options) {
+            Some(entry) => {
+                !entry.starts_with('"')
+                    && credentials_first(entry, &options.separators) == Some(true)
+            }
+            None => false,
+        }
+    }
+
+    fn parse<'a>(
+        &self,
+        line: &'a str,
+        st: &PublicSuffixList,
+        options: &ParseOptions,
+    ) -> Result<ParsedEntry<'a>, ParseError> {
+        parse_email(line, st, options)
+    }
+}
@@ -0,0 +1,16 @@
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Redraw interval of the bars in milliseconds
+pub static TICK: u64 = 500;
+
+/// Bar of the bytes read from an input file of `len` bytes,
+/// the same for every tool so their output looks alike
+pub fn file_progress_bar(len: u64) -> ProgressBar {
+    let pb = ProgressBar::new(len);
+    pb.enable_steady_tick(Duration::from_millis(TICK));
+    pb.set_style(ProgressStyle::default_bar().template("{spinner:.green} {wide_bar:40.green/black} {bytes:>11.green}/{total_bytes:<11.green} {bytes_per_sec:>13.red} [{elapsed_precise}] eta ({eta:.blue})").unwrap()
+        .progress_chars("━╾╴─"));
+    pb
+}
@@ -0,0 +1,82 @@
+use std::{collections::HashSet, io::BufRead};
+
+use crate::parse_psl;
+
+/// Public suffix list rules with exact label-wise matching
+///
+/// Plain rules like `co.uk`, wildcard rules like `*.ck` and exception
+/// rules like `!www.ck` are kept apart, so a rule only ever matches
+/// whole labels at the end of a domain
+///
+/// # Example
+///
+/// ```
+/// use lib::PublicSuffixList;
+///
+/// let psl = PublicSuffixList::new("uk co.uk *.ck !www.ck");
+///
+/// assert_eq!(psl.suffix("mail.corp.co.uk"), "co.uk");
+/// assert_eq!(psl.suffix("shop.any.ck"), "any.ck");
+/// assert_eq!(psl.suffix("www.ck"), "ck");
+/// assert_eq!(psl.suffix("corp.example"), "example");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PublicSuffixList {
+    rules: HashSet<String>,
+    /// Parents of `*.` rules, `ck` for `*.ck`
+    wildcards: HashSet<String>,
+    /// Names of `!` rules without the mark
+    exceptions: HashSet<String>,
+}
+
+impl PublicSuffixList {
+    /// Builds the list from whitespace separated rules,
+    /// the format returned by [`parse_psl`](crate::parse_psl)
+    pub fn new(rules: &str) -> PublicSuffixList {
+        let mut psl = PublicSuffixList::default();
+
+        for rule in rules.split_whitespace() {
+            let rule = rule.to_lowercase();
+            if let Some(exception) = rule.strip_prefix('!') {
+                psl.exceptions.insert(exception.to_string());
+            } else if let Some(parent) = rule.strip_prefix("*.") {
+                psl.wildcards.insert(parent.to_string());
+            } else {
+                psl.rules.insert(rule);
+            }
+        }
+        psl
+    }
+
+    /// Reads a public suffix list file, with `include_private` the private
+    /// domains section (github.io and alike) is read as well
+    pub fn from_reader(reader: &mut impl BufRead, include_private: bool) -> PublicSuffixList {
+        PublicSuffixList::new(&parse_psl(reader, include_private))
+    }
+
+    /// Public suffix of `domain`, the longest matching rule wins and an
+    /// exception rule takes precedence over any other. Without a matching
+    /// rule the top level label is the suffix
+    pub fn suffix<'a>(&self, domain: &'a str) -> &'a str {
+        let mut suffix = match domain.rfind('.') {
+            Some(i) => &domain[i + 1..],
+            None => return domain,
+        };
+
+        // Candidates grow by one label at a time, from the top level label down
+        let starts = domain.match_indices('.').map(|(i, _)| i + 1).rev();
+        let mut parent = suffix;
+        for start in starts.skip(1).chain([0]) {
+            let candidate = &domain[start..];
+
+            if self.exceptions.contains(candidate) {
+                return parent;
+            }
+            if self.rules.contains(candidate) || self.wildcards.contains(parent) {
+                suffix = candidate;
+            }
+            parent = candidate;
+        }
+        suffix
+    }
+}
@@ -0,0 +1,70 @@
+use std::{env, str::FromStr};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Environment variable holding the key of hmac redaction
+pub static HMAC_KEY_VAR: &str = "LEAKS_HMAC_KEY";
+
+/// Replacement of passwords for outputs that must not hold them in plaintext
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Redaction {
+    /// Hex HMAC-SHA256 of the password keyed with LEAKS_HMAC_KEY,
+    /// equal passwords keep equal digests
+    Hmac(Vec<u8>),
+    /// The first and last two characters are kept, the rest become *
+    Mask,
+}
+
+impl FromStr for Redaction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hmac" => match env::var(HMAC_KEY_VAR) {
+                Ok(key) if !key.is_empty() => Ok(Redaction::Hmac(key.into_bytes())),
+                _ => Err(format!("{} must be set for hmac redaction", HMAC_KEY_VAR)),
+            },
+            "mask" => Ok(Redaction::Mask),
+            _ => Err(format!("unknown redaction {}, expected hmac or mask", s)),
+        }
+    }
+}
+
+impl Redaction {
+    /// # Example
+    ///
+    /// ```
+    /// use lib::redact::Redaction;
+    ///
+    /// assert_eq!(Redaction::Mask.redact("password"), "pa****rd");
+    /// assert_eq!(Redaction::Mask.redact("1234"), "****");
+    /// assert_eq!(Redaction::Hmac(b"key".to_vec()).redact("password").len(), 64);
+    /// ```
+    pub fn redact(&self, password: &str) -> String {
+        match self {
+            Redaction::Hmac(key) => {
+                let mut mac =
+                    Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts keys of any length");
+                mac.update(password.as_bytes());
+                mac.finalize()
+                    .into_bytes()
+                    .iter()
+                    .map(|x| format!("{:02x}", x))
+                    .collect()
+            }
+            Redaction::Mask => {
+                let chars: Vec<char> = password.chars().collect();
+                // Short passwords would be given away by their ends
+                if chars.len() <= 4 {
+                    return "*".repeat(chars.len());
+                }
+
+                let mut masked: String = chars[..2].iter().collect();
+                masked.push_str(&"*".repeat(chars.len() - 4));
+                masked.extend(&chars[chars.len() - 2..]);
+                masked
+            }
+        }
+    }
+}
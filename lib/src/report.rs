@@ -0,0 +1,212 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    fmt,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+
+/// Counters of an indexing run, see [`crate::indexer::Indexer::finish`]
+#[derive(Clone, Debug, Serialize)]
+pub struct Stats {
+    /// Input lines, or records of stealer password files
+    pub lines_read: u64,
+    /// Lines parsed into an entry
+    pub parsed: u64,
+    /// Parsed entries whose mail domain was a typo fixed by the correction table
+    pub corrected: u64,
+    /// Records written to the output
+    pub written: u64,
+    /// Entries dropped by dedup
+    pub duplicates: u64,
+    /// Entries dropped by the domain filter
+    pub filtered: u64,
+    /// Rejected lines and skipped members by reason code
+    pub rejected: BTreeMap<String, u64>,
+    /// Distinct registrable domains of the written records, estimated within
+    /// about 1% and set when the stats are finished or merged
+    pub unique_domains: u64,
+    /// Parser picked per file or archive member when detecting them
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub parsers: BTreeMap<String, String>,
+    pub elapsed_secs: f64,
+    pub lines_per_sec: f64,
+    #[serde(skip)]
+    domains: DomainSketch,
+    #[serde(skip)]
+    started: Instant,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats {
+            lines_read: 0,
+            parsed: 0,
+            corrected: 0,
+            written: 0,
+            duplicates: 0,
+            filtered: 0,
+            rejected: BTreeMap::new(),
+            unique_domains: 0,
+            parsers: BTreeMap::new(),
+            elapsed_secs: 0.0,
+            lines_per_sec: 0.0,
+            domains: DomainSketch::new(),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Stats {
+    pub fn reject(&mut self, reason: &str) {
+        *self.rejected.entry(reason.to_string()).or_default() += 1;
+    }
+
+    pub fn write(&mut self, domain: &str) {
+        self.written += 1;
+        self.domains.insert(domain);
+    }
+
+    pub fn detected(&mut self, member: String, parser: String) {
+        self.parsers.insert(member, parser);
+    }
+
+    /// Adds the counters of another run, like the one of an input of a batch.
+    /// The clock keeps running from when these stats were created
+    pub fn merge(&mut self, other: &Stats) {
+        self.lines_read += other.lines_read;
+        self.parsed += other.parsed;
+        self.corrected += other.corrected;
+        self.written += other.written;
+        self.duplicates += other.duplicates;
+        self.filtered += other.filtered;
+        for (reason, count) in &other.rejected {
+            *self.rejected.entry(reason.clone()).or_default() += count;
+        }
+        self.domains.merge(&other.domains);
+        self.unique_domains = self.domains.estimate();
+        self.parsers.extend(other.parsers.clone());
+    }
+
+    pub fn rejected_total(&self) -> u64 {
+        self.rejected.values().sum()
+    }
+
+    /// Stops the clock, throughput is computed over the time since the stats were created
+    pub fn finish(&mut self) {
+        self.unique_domains = self.domains.estimate();
+        self.elapsed_secs = self.started.elapsed().as_secs_f64();
+        if self.elapsed_secs > 0.0 {
+            self.lines_per_sec = self.lines_read as f64 / self.elapsed_secs;
+        }
+    }
+
+    /// Writes the counters as a json object to `path`
+    pub fn write_report(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).map_err(|source| Error::Create {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer).map_err(Error::Write)?;
+        writer.flush().map_err(Error::Write)
+    }
+}
+
+/// Bits of a domain hash picking its register
+const SKETCH_BITS: u32 = 14;
+const SKETCH_REGISTERS: usize = 1 << SKETCH_BITS;
+
+/// HyperLogLog estimate of the distinct domains, so counting them takes
+/// 16 KiB however many there are. Sketches of runs merge into the sketch of
+/// their union
+#[derive(Clone, Debug)]
+struct DomainSketch {
+    /// Longest run of leading zeros plus one of the hashes of each register
+    registers: Vec<u8>,
+}
+
+impl DomainSketch {
+    fn new() -> DomainSketch {
+        DomainSketch {
+            registers: vec![0; SKETCH_REGISTERS],
+        }
+    }
+
+    fn insert(&mut self, domain: &str) {
+        let mut hasher = DefaultHasher::new();
+        domain.hash(&mut hasher);
+        let hash = hasher.finish();
+        let register = (hash >> (64 - SKETCH_BITS)) as usize;
+        // The marker bit caps the run at the bits left after the register
+        let rest = (hash << SKETCH_BITS) | (1 << (SKETCH_BITS - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[register] {
+            self.registers[register] = rank;
+        }
+    }
+
+    fn merge(&mut self, other: &DomainSketch) {
+        for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = SKETCH_REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-i32::from(rank)))
+            .sum();
+        let raw = alpha * m * m / sum;
+        let empty = self.registers.iter().filter(|&&rank| rank == 0).count();
+        // Linear counting is closer while few registers are set
+        let estimate = if raw <= 2.5 * m && empty > 0 {
+            m * (m / empty as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+}
+
+/// Human readable summary, one counter per line
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "lines read      {}", self.lines_read)?;
+        writeln!(f, "parsed          {}", self.parsed)?;
+        writeln!(f, "corrected       {}", self.corrected)?;
+        writeln!(f, "written         {}", self.written)?;
+        writeln!(f, "duplicates      {}", self.duplicates)?;
+        writeln!(f, "filtered        {}", self.filtered)?;
+        writeln!(f, "rejected        {}", self.rejected_total())?;
+        for (reason, count) in &self.rejected {
+            writeln!(f, "  {:<22}{}", reason, count)?;
+        }
+        writeln!(f, "unique domains  {}", self.unique_domains)?;
+        if !self.parsers.is_empty() {
+            let mut members: BTreeMap<&str, u64> = BTreeMap::new();
+            for parser in self.parsers.values() {
+                *members.entry(parser).or_default() += 1;
+            }
+            writeln!(f, "detected parsers")?;
+            for (parser, count) in members {
+                writeln!(f, "  {:<22}{}", parser, count)?;
+            }
+        }
+        write!(
+            f,
+            "elapsed         {:.1}s, {:.0} lines/s",
+            self.elapsed_secs, self.lines_per_sec
+        )
+    }
+}
@@ -0,0 +1,158 @@
+use std::{fs, path::Path};
+
+use regex::{Regex, RegexSet};
+use serde::Deserialize;
+
+use crate::{entry::ParseError, error::Error};
+
+/// Rules file as written by the user, every field is optional
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RulesFile {
+    max_username_length: usize,
+    min_password_length: usize,
+    max_password_length: Option<usize>,
+    username_pattern: Option<String>,
+    password_pattern: Option<String>,
+    blocklist: BlocklistFile,
+}
+
+impl Default for RulesFile {
+    fn default() -> Self {
+        let rules = ValidationRules::default();
+        RulesFile {
+            max_username_length: rules.max_username_length,
+            min_password_length: rules.min_password_length,
+            max_password_length: rules.max_password_length,
+            username_pattern: None,
+            password_pattern: None,
+            blocklist: BlocklistFile::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BlocklistFile {
+    usernames: Vec<String>,
+    passwords: Vec<String>,
+    domains: Vec<String>,
+}
+
+/// Checks applied to the fields of every parsed entry
+///
+/// The defaults match the built in behaviour, a rules file like
+///
+/// ```toml
+/// max_username_length = 64
+/// username_pattern = '^[\w.+-]+$'
+///
+/// [blocklist]
+/// passwords = ['^\$2[aby]\$']
+/// domains = ['^example\.com$']
+/// ```
+///
+/// relaxes the username shape and rejects bcrypt hashes and test domains
+#[derive(Clone, Debug)]
+pub struct ValidationRules {
+    /// Longest accepted username in characters
+    pub max_username_length: usize,
+    pub min_password_length: usize,
+    pub max_password_length: Option<usize>,
+    /// Usernames have to match it. Setting it replaces the built in username
+    /// shape of email entries, so any username without separators is extracted
+    pub username_pattern: Option<Regex>,
+    pub password_pattern: Option<Regex>,
+    pub blocked_usernames: RegexSet,
+    pub blocked_passwords: RegexSet,
+    /// Matched against the registrable domain
+    pub blocked_domains: RegexSet,
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        ValidationRules {
+            max_username_length: 40,
+            min_password_length: 1,
+            max_password_length: None,
+            username_pattern: None,
+            password_pattern: None,
+            blocked_usernames: RegexSet::empty(),
+            blocked_passwords: RegexSet::empty(),
+            blocked_domains: RegexSet::empty(),
+        }
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, Error> {
+    Regex::new(pattern).map_err(|e| Error::Rules(e.to_string()))
+}
+
+fn compile_set(patterns: &[String]) -> Result<RegexSet, Error> {
+    RegexSet::new(patterns).map_err(|e| Error::Rules(e.to_string()))
+}
+
+impl ValidationRules {
+    pub fn from_toml(s: &str) -> Result<ValidationRules, Error> {
+        let file: RulesFile = toml::from_str(s).map_err(|e| Error::Rules(e.to_string()))?;
+
+        Ok(ValidationRules {
+            max_username_length: file.max_username_length,
+            min_password_length: file.min_password_length,
+            max_password_length: file.max_password_length,
+            username_pattern: file.username_pattern.as_deref().map(compile).transpose()?,
+            password_pattern: file.password_pattern.as_deref().map(compile).transpose()?,
+            blocked_usernames: compile_set(&file.blocklist.usernames)?,
+            blocked_passwords: compile_set(&file.blocklist.passwords)?,
+            blocked_domains: compile_set(&file.blocklist.domains)?,
+        })
+    }
+
+    pub fn from_file(path: &Path) -> Result<ValidationRules, Error> {
+        let contents = fs::read_to_string(path).map_err(|source| Error::Open {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        ValidationRules::from_toml(&contents)
+    }
+
+    pub fn check_username(&self, username: &str) -> Result<(), ParseError> {
+        if username.chars().count() > self.max_username_length {
+            return Err(ParseError::UsernameTooLong);
+        }
+        if let Some(pattern) = &self.username_pattern {
+            if !pattern.is_match(username) {
+                return Err(ParseError::BadUsername);
+            }
+        }
+        if self.blocked_usernames.is_match(username) {
+            return Err(ParseError::Blocked);
+        }
+        Ok(())
+    }
+
+    pub fn check_password(&self, password: &str) -> Result<(), ParseError> {
+        let length = password.chars().count();
+        if length < self.min_password_length
+            || self.max_password_length.map(|max| length > max) == Some(true)
+        {
+            return Err(ParseError::BadPassword);
+        }
+        if let Some(pattern) = &self.password_pattern {
+            if !pattern.is_match(password) {
+                return Err(ParseError::BadPassword);
+            }
+        }
+        if self.blocked_passwords.is_match(password) {
+            return Err(ParseError::Blocked);
+        }
+        Ok(())
+    }
+
+    pub fn check_domain(&self, domain: &str) -> Result<(), ParseError> {
+        if self.blocked_domains.is_match(domain) {
+            return Err(ParseError::Blocked);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use std::io::{self, BufRead, Read};
+
+static UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Bytes the sanitizer doesn't pass through as is: carriage returns
+/// and control characters other than tab and newline
+fn is_special(x: u8) -> bool {
+    matches!(x, 0..=0x08 | 0x0b..=0x1f | 0x7f)
+}
+
+/// Cleans utf-8 text up for the line reader: drops a leading byte order mark,
+/// turns CRLF and lone CR line endings into LF and drops NULs and other
+/// control characters but tab. Multibyte utf-8 sequences never contain
+/// ascii bytes, so this works on bytes
+///
+/// # Example
+///
+/// ```
+/// use std::io::Read;
+/// use lib::sanitize::Sanitizer;
+///
+/// let mut text = String::new();
+/// Sanitizer::new(&b"\xef\xbb\xbfa@b.com:1\r\nc@d.com:\x002\re@f.com:3"[..])
+///     .read_to_string(&mut text)
+///     .unwrap();
+/// assert_eq!(text, "a@b.com:1\nc@d.com:2\ne@f.com:3");
+/// ```
+pub struct Sanitizer<R> {
+    inner: R,
+    /// Nothing was read yet, so a byte order mark may follow
+    start: bool,
+    /// The last byte was a CR, written as LF already
+    after_cr: bool,
+}
+
+impl<R: BufRead> Sanitizer<R> {
+    pub fn new(inner: R) -> Sanitizer<R> {
+        Sanitizer {
+            inner,
+            start: true,
+            after_cr: false,
+        }
+    }
+}
+
+impl<R: BufRead> Read for Sanitizer<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            if self.start {
+                self.start = false;
+                if buf.starts_with(UTF8_BOM) {
+                    self.inner.consume(UTF8_BOM.len());
+                    continue;
+                }
+            }
+
+            let mut read = 0;
+            let mut written = 0;
+            while read < buf.len() && written < out.len() {
+                // Runs of ordinary bytes are copied at once
+                let room = (out.len() - written).min(buf.len() - read);
+                let plain = buf[read..read + room]
+                    .iter()
+                    .position(|&x| is_special(x) || (x == b'\n' && self.after_cr))
+                    .unwrap_or(room);
+                if plain > 0 {
+                    out[written..written + plain].copy_from_slice(&buf[read..read + plain]);
+                    read += plain;
+                    written += plain;
+                    self.after_cr = false;
+                    continue;
+                }
+
+                match buf[read] {
+                    b'\r' => {
+                        out[written] = b'\n';
+                        written += 1;
+                        self.after_cr = true;
+                    }
+                    // The LF of a CRLF, the CR stands for both
+                    b'\n' => self.after_cr = false,
+                    _ => {}
+                }
+                read += 1;
+            }
+
+            self.inner.consume(read);
+            if written > 0 {
+                return Ok(written);
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+/// Field the output rows are partitioned by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShardKey {
+    /// Registrable domain, so every credential of a domain lands in one shard
+    #[default]
+    Domain,
+}
+
+impl FromStr for ShardKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "domain" => Ok(ShardKey::Domain),
+            _ => Err(format!("unknown shard key {}, expected domain", s)),
+        }
+    }
+}
+
+/// Unit of the input that gets an output file of its own
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitOutput {
+    /// Every archive member or file of a directory
+    Member,
+}
+
+impl FromStr for SplitOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "member" => Ok(SplitOutput::Member),
+            _ => Err(format!("unknown output split {}, expected member", s)),
+        }
+    }
+}
+
+/// Extensions appended after the format extension by compression and encryption
+static OUTER_EXTENSIONS: [&str; 3] = ["gz", "zst", "age"];
+
+/// Inserts `label` into the file name of `path` before its format extension
+fn insert_label(path: &Path, label: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut parts: Vec<&str> = name.split('.').collect();
+
+    let mut outer = parts.len();
+    while outer > 1 && OUTER_EXTENSIONS.contains(&parts[outer - 1]) {
+        outer -= 1;
+    }
+    // The format extension, unless the name is only a stem
+    let at = if outer > 1 { outer - 1 } else { outer };
+    parts.insert(at, label);
+
+    path.with_file_name(parts.join("."))
+}
+
+/// Path of the output of archive member `member`, named like
+/// leaks.ru_mail.txt.csv.gz. Directories of the member are joined with _
+/// and characters that don't belong in file names are replaced by _
+///
+/// # Example
+///
+/// ```
+/// use std::path::Path;
+/// use lib::shard::member_path;
+///
+/// assert_eq!(
+///     member_path(Path::new("out/leaks.csv.gz"), Path::new("dumps/ru/mail ru.txt")),
+///     Path::new("out/leaks.dumps_ru_mail_ru.txt.csv.gz")
+/// );
+/// ```
+pub fn member_path(path: &Path, member: &Path) -> PathBuf {
+    let label = member
+        .components()
+        .filter_map(|x| match x {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .map(|name| {
+            name.chars()
+                .map(|x| match x {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => x,
+                    _ => '_',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("_");
+    insert_label(path, &label)
+}
+
+/// Splits the output into `count` files by a hash of `key`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sharding {
+    pub count: usize,
+    pub key: ShardKey,
+}
+
+impl Sharding {
+    /// Shard of a row with the given key value. The hash is FNV-1a rather
+    /// than the std hasher, whose seed changes per process, so separate runs
+    /// put a domain into the same shard and their shards can be merged pairwise
+    pub fn index(&self, value: &str) -> usize {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in value.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        (hash % self.count as u64) as usize
+    }
+
+    /// Path of shard `index`: `.shardN` goes before the format extension,
+    /// so leaks.csv.gz becomes leaks.shard03.csv.gz. Numbers are zero padded
+    /// to the width of the largest one to keep the shards sorted by name
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use lib::shard::{ShardKey, Sharding};
+    ///
+    /// let sharding = Sharding { count: 16, key: ShardKey::Domain };
+    /// assert_eq!(
+    ///     sharding.path(Path::new("out/leaks.csv.gz"), 3),
+    ///     Path::new("out/leaks.shard03.csv.gz")
+    /// );
+    /// assert_eq!(sharding.path(Path::new("leaks"), 12), Path::new("leaks.shard12"));
+    /// ```
+    pub fn path(&self, path: &Path, index: usize) -> PathBuf {
+        let width = (self.count.max(1) - 1).to_string().len();
+        insert_label(path, &format!("shard{:0width$}", index, width = width))
+    }
+
+    /// Paths of every shard in order
+    pub fn paths(&self, path: &Path) -> Vec<PathBuf> {
+        (0..self.count)
+            .map(|index| self.path(path, index))
+            .collect()
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    error::Error,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use csv::ByteRecord;
+
+use crate::{
+    encryption::{OutputFile, TempKey},
+    output::CsvOptions,
+};
+
+/// Rough per record overhead of ByteRecord on top of its field bytes
+static RECORD_OVERHEAD: usize = 64;
+
+/// Sorts csv records by the `key` column with bounded memory.
+///
+/// Records are read in chunks of roughly `memory_limit` bytes, every chunk is
+/// sorted and spilled to an anonymous temporary file in `tmp_dir`, then all
+/// chunks are merged into `output`. The sort is stable, the output is written
+/// in the same dialect as the input but without a header. Chunks are
+/// encrypted with `sealed` when given, so no plaintext is spilled
+pub fn external_sort(
+    input: impl Read,
+    output: impl Write,
+    csv: CsvOptions,
+    key: usize,
+    memory_limit: usize,
+    tmp_dir: &Path,
+    sealed: Option<&TempKey>,
+) -> Result<(), Box<dyn Error>> {
+    let mut reader = csv.reader_builder().from_reader(input);
+    let mut chunks: Vec<File> = Vec::new();
+
+    let mut records: Vec<ByteRecord> = Vec::new();
+    let mut used = 0;
+    let mut record = ByteRecord::new();
+
+    while reader.read_byte_record(&mut record)? {
+        used += record.as_slice().len() + RECORD_OVERHEAD;
+        records.push(record.clone());
+
+        if used >= memory_limit {
+            chunks.push(spill(&mut records, csv, key, tmp_dir, sealed)?);
+            used = 0;
+        }
+    }
+
+    let spill_csv = CsvOptions {
+        header: false,
+        ..csv
+    };
+    let mut writer = spill_csv.writer_builder().from_writer(output);
+
+    // Everything fit into memory, no need to touch the disk
+    if chunks.is_empty() {
+        sort_records(&mut records, key);
+        for record in records {
+            writer.write_byte_record(&record)?;
+        }
+        writer.flush()?;
+        return Ok(());
+    }
+
+    if !records.is_empty() {
+        chunks.push(spill(&mut records, csv, key, tmp_dir, sealed)?);
+    }
+
+    let mut readers = Vec::new();
+    for chunk in chunks {
+        let chunk = BufReader::new(chunk);
+        let chunk: Box<dyn Read> = match sealed {
+            Some(sealed) => Box::new(sealed.decrypt(chunk)?),
+            None => Box::new(chunk),
+        };
+        readers.push(spill_csv.reader_builder().from_reader(chunk));
+    }
+
+    let mut heads: Vec<ByteRecord> = vec![ByteRecord::new(); readers.len()];
+    let mut heap = BinaryHeap::new();
+
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if reader.read_byte_record(&mut heads[i])? {
+            heap.push(Reverse((heads[i].get(key).unwrap_or_default().to_vec(), i)));
+        }
+    }
+
+    while let Some(Reverse((_, i))) = heap.pop() {
+        writer.write_byte_record(&heads[i])?;
+
+        if readers[i].read_byte_record(&mut heads[i])? {
+            heap.push(Reverse((heads[i].get(key).unwrap_or_default().to_vec(), i)));
+        }
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn sort_records(records: &mut [ByteRecord], key: usize) {
+    records.sort_by(|a, b| a.get(key).cmp(&b.get(key)));
+}
+
+fn spill(
+    records: &mut Vec<ByteRecord>,
+    csv: CsvOptions,
+    key: usize,
+    tmp_dir: &Path,
+    sealed: Option<&TempKey>,
+) -> Result<File, Box<dyn Error>> {
+    sort_records(records, key);
+
+    let mut file = tempfile::tempfile_in(tmp_dir)?;
+    {
+        let spill_csv = CsvOptions {
+            header: false,
+            ..csv
+        };
+        let encryption = sealed.map(TempKey::encryption);
+        let output = OutputFile::new(file.try_clone()?, encryption.as_ref())?;
+        let mut writer = spill_csv
+            .writer_builder()
+            .from_writer(BufWriter::new(output));
+        for record in records.drain(..) {
+            writer.write_byte_record(&record)?;
+        }
+        writer
+            .into_inner()
+            .map_err(|e| io::Error::new(e.error().kind(), e.error().to_string()))?
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .finish()?;
+    }
+    file.seek(SeekFrom::Start(0))?;
+
+    Ok(file)
+}
@@ -0,0 +1,88 @@
+use std::io::{self, BufRead};
+
+/// Single record of a stealer log password file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StealerRecord {
+    pub url: String,
+    pub login: String,
+    pub password: String,
+}
+
+impl StealerRecord {
+    fn is_complete(&self) -> bool {
+        !self.url.is_empty() && !self.login.is_empty() && !self.password.is_empty()
+    }
+
+    /// Single line form written to the error file
+    pub fn line(&self) -> String {
+        format!("{}:{}:{}", self.url, self.login, self.password)
+    }
+}
+
+/// Whether a file of a stealer log folder holds saved passwords,
+/// like Passwords.txt or All Passwords.txt
+pub fn is_password_file(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.ends_with(".txt") && name.contains("password")
+}
+
+/// Reads the `URL:`/`Username:`/`Password:` blocks of a stealer log password file.
+/// Blocks are separated by blank or ===== lines, unknown fields like
+/// `Application:` are ignored and incomplete blocks are dropped
+///
+/// # Example
+///
+/// ```
+/// use lib::stealer::read_password_file;
+///
+/// let log = "URL: https://site.com/login\nUsername: admin\nPassword: 5555\n===============\n";
+/// let records = read_password_file(log.as_bytes()).unwrap();
+///
+/// assert_eq!(records[0].url, "https://site.com/login");
+/// assert_eq!(records[0].login, "admin");
+/// assert_eq!(records[0].password, "5555");
+/// ```
+pub fn read_password_file(reader: impl BufRead) -> io::Result<Vec<StealerRecord>> {
+    let mut records = Vec::new();
+    let mut record = StealerRecord::default();
+
+    for line in reader.split(b'\n') {
+        let line = String::from_utf8_lossy(&line?).into_owned();
+        let line = line.trim_end_matches('\r');
+
+        let separator = !line.is_empty() && line.chars().all(|c| matches!(c, '=' | '-' | '*'));
+        if line.trim().is_empty() || separator {
+            if record.is_complete() {
+                records.push(std::mem::take(&mut record));
+            }
+            record = StealerRecord::default();
+            continue;
+        }
+
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+            None => continue,
+        };
+
+        match key.as_str() {
+            "url" | "host" | "hostname" | "soft url" => {
+                // Some stealers don't separate blocks at all
+                if !record.url.is_empty() {
+                    if record.is_complete() {
+                        records.push(std::mem::take(&mut record));
+                    }
+                    record = StealerRecord::default();
+                }
+                record.url = value.to_string();
+            }
+            "username" | "user" | "login" | "user login" => record.login = value.to_string(),
+            "password" | "pass" | "user password" => record.password = value.to_string(),
+            _ => {}
+        }
+    }
+
+    if record.is_complete() {
+        records.push(record);
+    }
+    Ok(records)
+}
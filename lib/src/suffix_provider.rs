@@ -0,0 +1,101 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+/// Snapshot of the list shipped with the crate, used when nothing else is available
+static EMBEDDED_PSL: &str = include_str!("../data/public_suffix_list.dat");
+
+static PSL_URL: &str = "https://publicsuffix.org/list/public_suffix_list.dat";
+
+/// Provides the Public Suffix List contents, downloading the current list
+/// and caching it on disk
+///
+/// The list is looked up in the following order:
+/// a fresh cached copy, a download from `url`, a stale cached copy
+/// and finally the embedded snapshot
+///
+/// # Example
+///
+/// ```no_run
+/// use lib::{PublicSuffixList, SuffixProvider};
+///
+/// let psl = SuffixProvider::default().fetch();
+/// let st = PublicSuffixList::from_reader(&mut psl.as_bytes(), false);
+/// ```
+pub struct SuffixProvider {
+    pub url: String,
+    /// Cached copy location, None disables caching
+    pub cache_path: Option<PathBuf>,
+    /// Age after which the cached copy gets downloaded again
+    pub max_age: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for SuffixProvider {
+    fn default() -> Self {
+        SuffixProvider {
+            url: PSL_URL.to_string(),
+            cache_path: dirs::cache_dir()
+                .map(|dir| dir.join("leaks-suite").join("public_suffix_list.dat")),
+            max_age: Duration::from_secs(7 * 24 * 60 * 60),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl SuffixProvider {
+    pub fn fetch(&self) -> String {
+        if let Some(psl) = self.read_cache(true) {
+            return psl;
+        }
+
+        match self.download() {
+            Ok(psl) => {
+                self.write_cache(&psl);
+                psl
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to download the public suffix list");
+                self.read_cache(false)
+                    .unwrap_or_else(|| EMBEDDED_PSL.to_string())
+            }
+        }
+    }
+
+    fn read_cache(&self, fresh_only: bool) -> Option<String> {
+        let path = self.cache_path.as_ref()?;
+
+        if fresh_only {
+            let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+            let age = SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default();
+            if age > self.max_age {
+                return None;
+            }
+        }
+
+        fs::read_to_string(path).ok()
+    }
+
+    fn write_cache(&self, psl: &str) {
+        if let Some(path) = &self.cache_path {
+            let written = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(path, psl));
+
+            if let Err(e) = written {
+                tracing::warn!(path = %path.display(), error = %e, "Failed to cache the public suffix list");
+            }
+        }
+    }
+
+    fn download(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let agent = ureq::AgentBuilder::new().timeout(self.timeout).build();
+        let psl = agent.get(&self.url).call()?.into_string()?;
+        Ok(psl)
+    }
+}
@@ -0,0 +1,187 @@
+//! Startup shared by every binary of the suite: the .env file, logging
+//! and a panic hook that logs the panic and flushes the outputs written so far
+
+use std::{
+    io::{self, Write},
+    panic::{self, PanicHookInfo},
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard, Once, Weak},
+    thread,
+};
+
+use tracing::error;
+use tracing_subscriber::{filter::LevelFilter, EnvFilter};
+
+/// Layout of log lines written to stderr
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable lines with key=value fields
+    #[default]
+    Text,
+    /// One json object per event, for log collectors
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format {}, expected text or json", s)),
+        }
+    }
+}
+
+/// Loads the variables of a .env file in the working directory, installs a
+/// tracing subscriber filtered by RUST_LOG and the panic hook. Warnings and
+/// errors are logged when RUST_LOG is unset, records of the log crate are
+/// forwarded to the subscriber. Later calls only keep the first subscriber
+pub fn init(format: LogFormat) {
+    dotenv::dotenv().ok();
+
+    let filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::WARN.into())
+        .from_env_lossy();
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr);
+    let _ = match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+
+    install_panic_hook();
+}
+
+type Registered = Weak<Mutex<dyn Write + Send>>;
+
+/// Writers flushed by the panic hook, dropped ones are pruned on registration
+static WRITERS: Mutex<Vec<Registered>> = Mutex::new(Vec::new());
+
+static HOOK: Once = Once::new();
+
+/// Logs panics as errors along with the thread and location, then flushes
+/// every [`FlushOnPanic`] writer before the default hook prints the message.
+/// The hook runs before unwinding, so output written so far reaches the disk
+/// even when unwinding aborts
+fn install_panic_hook() {
+    HOOK.call_once(|| {
+        let default = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            log_panic(info);
+            flush_writers();
+            default(info);
+        }));
+    });
+}
+
+fn log_panic(info: &PanicHookInfo) {
+    let message = match info.payload().downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match info.payload().downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "Box<dyn Any>".to_string(),
+        },
+    };
+    let location = info
+        .location()
+        .map(|location| location.to_string())
+        .unwrap_or_default();
+    error!(
+        thread = thread::current().name().unwrap_or("<unnamed>"),
+        location = %location,
+        "Panicked: {}",
+        message
+    );
+}
+
+/// Flushes the registered writers that aren't in use. A writer locked by the
+/// panicking thread itself is skipped rather than waited for
+fn flush_writers() {
+    let writers = match WRITERS.try_lock() {
+        Ok(writers) => writers.clone(),
+        Err(_) => return,
+    };
+    for writer in writers.iter().filter_map(Weak::upgrade) {
+        if let Ok(mut writer) = writer.try_lock() {
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// Writer flushed by the panic hook of [`init`], wrapping the outermost
+/// buffer of an output keeps everything written to it so far.
+/// Buffers above it aren't reached, [`crate::output::CsvFile`] empties
+/// the one of its csv writer after every record for that reason
+///
+/// # Example
+///
+/// ```
+/// use std::io::Write;
+///
+/// use lib::telemetry::FlushOnPanic;
+///
+/// let mut output = FlushOnPanic::new(Vec::new());
+/// output.write_all(b"wolya@mail.com:5555\n").unwrap();
+/// assert_eq!(output.into_inner(), b"wolya@mail.com:5555\n");
+/// ```
+pub struct FlushOnPanic<W: Write + Send + 'static> {
+    writer: Arc<Mutex<Slot<W>>>,
+}
+
+/// Writer of a [`FlushOnPanic`], emptied when it's taken back
+struct Slot<W>(Option<W>);
+
+impl<W: Write> Write for Slot<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.0 {
+            Some(writer) => writer.write(buf),
+            None => Ok(0),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.0 {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: Write + Send + 'static> FlushOnPanic<W> {
+    pub fn new(writer: W) -> FlushOnPanic<W> {
+        let writer = Arc::new(Mutex::new(Slot(Some(writer))));
+        let registered: Arc<Mutex<dyn Write + Send>> = writer.clone();
+
+        let mut writers = WRITERS.lock().unwrap_or_else(|e| e.into_inner());
+        writers.retain(|writer| writer.strong_count() > 0);
+        writers.push(Arc::downgrade(&registered));
+        FlushOnPanic { writer }
+    }
+
+    /// The wrapped writer, it isn't flushed on panics anymore
+    pub fn into_inner(self) -> W {
+        let writer = self.lock().0.take();
+        writer.expect("only into_inner takes the writer")
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Slot<W>> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<W: Write + Send + 'static> Write for FlushOnPanic<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.lock().write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+}
@@ -0,0 +1,91 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use lazy_static::lazy_static;
+
+/// Mailbox aliasing behaviour of a mail provider
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UsernameRule {
+    /// user+tag and user are the same mailbox
+    pub strip_plus_tag: bool,
+    /// u.s.e.r and user are the same mailbox
+    pub strip_dots: bool,
+}
+
+/// Per-provider table of username normalization rules keyed by mail domain
+#[derive(Clone, Debug)]
+pub struct UsernameRules {
+    pub rules: HashMap<String, UsernameRule>,
+}
+
+impl Default for UsernameRules {
+    /// Providers where aliasing is documented and safe to collapse
+    fn default() -> Self {
+        let plus_and_dots = UsernameRule {
+            strip_plus_tag: true,
+            strip_dots: true,
+        };
+        let plus = UsernameRule {
+            strip_plus_tag: true,
+            strip_dots: false,
+        };
+
+        let rules = [
+            ("gmail.com", plus_and_dots),
+            ("googlemail.com", plus_and_dots),
+            ("outlook.com", plus),
+            ("hotmail.com", plus),
+            ("live.com", plus),
+            ("icloud.com", plus),
+            ("fastmail.com", plus),
+            ("protonmail.com", plus),
+            ("proton.me", plus),
+            ("yandex.ru", plus),
+        ]
+        .into_iter()
+        .map(|(domain, rule)| (domain.to_string(), rule))
+        .collect();
+
+        UsernameRules { rules }
+    }
+}
+
+impl UsernameRules {
+    /// Collapses aliases of `username` at the mail domain `domain`,
+    /// usernames of unknown providers are returned as is
+    pub fn normalize<'a>(&self, username: &'a str, domain: &str) -> Cow<'a, str> {
+        let rule = match self.rules.get(domain) {
+            Some(rule) => rule,
+            None => return Cow::Borrowed(username),
+        };
+
+        let username = match username.split_once('+') {
+            Some((base, _)) if rule.strip_plus_tag && !base.is_empty() => base,
+            _ => username,
+        };
+
+        if rule.strip_dots && username.contains('.') {
+            Cow::Owned(username.replace('.', ""))
+        } else {
+            Cow::Borrowed(username)
+        }
+    }
+}
+
+lazy_static! {
+    static ref DEFAULT_RULES: UsernameRules = UsernameRules::default();
+}
+
+/// Normalizes `username` with the built-in provider rules
+///
+/// # Example
+///
+/// ```
+/// use lib::normalize_username;
+///
+/// assert_eq!(normalize_username("j.doe+news", "gmail.com"), "jdoe");
+/// assert_eq!(normalize_username("j.doe+news", "outlook.com"), "j.doe");
+/// assert_eq!(normalize_username("j.doe+news", "example.com"), "j.doe+news");
+/// ```
+pub fn normalize_username<'a>(username: &'a str, domain: &str) -> Cow<'a, str> {
+    DEFAULT_RULES.normalize(username, domain)
+}
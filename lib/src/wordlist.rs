@@ -0,0 +1,73 @@
+use std::{borrow::Cow, collections::HashSet, fmt::Write, str::FromStr};
+
+/// Layout of a cracking wordlist
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WordlistFormat {
+    /// One password per line
+    #[default]
+    Passwords,
+    /// username:password lines, as taken by john and hashcat --username
+    Combo,
+}
+
+impl FromStr for WordlistFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "passwords" => Ok(WordlistFormat::Passwords),
+            "combo" => Ok(WordlistFormat::Combo),
+            _ => Err(format!(
+                "unknown wordlist format {}, expected passwords or combo",
+                s
+            )),
+        }
+    }
+}
+
+/// Encodes a word the way hashcat reads it from a wordlist
+///
+/// Words that can't be written on a line as is, or that would be mistaken
+/// for an encoded one, are written as `$HEX[...]`
+///
+/// # Example
+///
+/// ```
+/// use lib::wordlist::hashcat_word;
+///
+/// assert_eq!(hashcat_word("hunter2"), "hunter2");
+/// assert_eq!(hashcat_word("a\nb"), "$HEX[610a62]");
+/// ```
+pub fn hashcat_word(word: &str) -> Cow<'_, str> {
+    if !word.starts_with("$HEX[") && !word.chars().any(char::is_control) {
+        return Cow::Borrowed(word);
+    }
+
+    let mut res = String::with_capacity(word.len() * 2 + 6);
+    res.push_str("$HEX[");
+    for b in word.bytes() {
+        write!(res, "{:02x}", b).unwrap();
+    }
+    res.push(']');
+    Cow::Owned(res)
+}
+
+/// Builds deduplicated wordlist lines out of (username, password) pairs,
+/// keeping the order words were first seen in
+pub fn wordlist<'a>(
+    credentials: impl IntoIterator<Item = (&'a str, &'a str)>,
+    format: WordlistFormat,
+) -> Vec<String> {
+    let mut seen = HashSet::new();
+    credentials
+        .into_iter()
+        .filter(|(_, password)| !password.is_empty())
+        .map(|(username, password)| match format {
+            WordlistFormat::Passwords => hashcat_word(password).into_owned(),
+            WordlistFormat::Combo => {
+                format!("{}:{}", hashcat_word(username), hashcat_word(password))
+            }
+        })
+        .filter(|line| seen.insert(line.clone()))
+        .collect()
+}
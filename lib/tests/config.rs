@@ -0,0 +1,44 @@
+use lib::config::SuiteConfig;
+
+#[test]
+fn template_parses() {
+    let config = SuiteConfig::from_toml(include_str!("../../leaks-suite.toml.template")).unwrap();
+    assert!(config.tld_path.is_none());
+    assert!(config.bot_env().is_empty());
+}
+
+#[test]
+fn bot_env() {
+    let config = SuiteConfig::from_toml(
+        r#"
+        tld_path = "public_suffix_list.dat"
+
+        [couchbase]
+        uri = "couchbase://db"
+        bucket = "leaks"
+
+        [bot]
+        token = "123:abc"
+        "#,
+    )
+    .unwrap();
+
+    let mut vars = config.bot_env();
+    vars.sort();
+    let vars: Vec<(&str, &str)> = vars.iter().map(|(k, v)| (&**k, &**v)).collect();
+    assert_eq!(
+        vars,
+        vec![
+            ("COUCH_BUCKET", "leaks"),
+            ("COUCH_URI", "couchbase://db"),
+            ("TELOXIDE_TOKEN", "123:abc"),
+            ("TLD_PATH", "public_suffix_list.dat"),
+        ]
+    );
+}
+
+#[test]
+fn unknown_key() {
+    assert!(SuiteConfig::from_toml("tld = \"auto\"").is_err());
+    assert!(SuiteConfig::from_toml("[couchbase]\nhost = \"db\"").is_err());
+}
@@ -0,0 +1,190 @@
+use lib::{
+    document::{fit_document, merge_documents, upgrade, SplitStrategy},
+    Breach, CredentialData, LeakData, LeakDataRef, DOCUMENT_VERSION, MAX_BREACHES,
+};
+
+fn leak(credentials: &[(&str, &str, &str)]) -> LeakData {
+    LeakData {
+        domain: "corp.com".to_string(),
+        credentials: credentials
+            .iter()
+            .map(|(subdomain, username, password)| CredentialData {
+                subdomain: subdomain.to_string(),
+                data: vec![(username.to_string(), password.to_string())],
+            })
+            .collect(),
+        breaches: Vec::new(),
+        org: String::new(),
+        version: DOCUMENT_VERSION,
+    }
+}
+
+fn pairs(credential_data: &CredentialData) -> Vec<(&str, &str)> {
+    credential_data
+        .data
+        .iter()
+        .map(|(username, password)| (username.as_str(), password.as_str()))
+        .collect()
+}
+
+#[test]
+fn merge_skips_stored() {
+    let stored = vec![
+        leak(&[("", "john", "p1"), ("vpn", "admin", "p2")]),
+        leak(&[("", "jane", "p3")]),
+    ];
+    let new = leak(&[
+        ("vpn", "admin", "p2"),
+        ("vpn", "root", "p4"),
+        ("mail", "john", "p1"),
+    ]);
+
+    let (merged, added) = merge_documents(stored, &new);
+    assert_eq!(added, 2);
+    assert_eq!(merged.credentials.len(), 3);
+    assert_eq!(
+        pairs(&merged.credentials[0]),
+        vec![("john", "p1"), ("jane", "p3")]
+    );
+    assert_eq!(merged.credentials[1].subdomain, "vpn");
+    assert_eq!(
+        pairs(&merged.credentials[1]),
+        vec![("admin", "p2"), ("root", "p4")]
+    );
+    assert_eq!(merged.credentials[2].subdomain, "mail");
+}
+
+#[test]
+fn merge_nothing_new() {
+    let stored = vec![leak(&[("", "john", "p1")])];
+    let (merged, added) = merge_documents(stored, &leak(&[("", "john", "p1")]));
+    assert_eq!(added, 0);
+    assert_eq!(merged.credentials.len(), 1);
+}
+
+#[test]
+fn merge_lists_breaches_once() {
+    let breach = |name: &str| Breach {
+        name: name.to_string(),
+        date: "2024-05-01".to_string(),
+        source_file: String::new(),
+    };
+    let mut stored = leak(&[("", "john", "p1")]);
+    stored.breaches = vec![breach("combo")];
+    let mut new = leak(&[("", "jane", "p2")]);
+    new.breaches = vec![breach("stealer"), breach("combo")];
+
+    let (merged, _) = merge_documents(vec![stored], &new);
+    assert_eq!(merged.breaches, vec![breach("combo"), breach("stealer")]);
+
+    let splits = fit_document(merged, 60, SplitStrategy::Even);
+    assert!(splits.len() > 1);
+    assert!(splits.iter().all(|x| x.breaches.len() == 2));
+}
+
+#[test]
+fn merge_bounds_breaches() {
+    let mut new = leak(&[("", "jane", "p2")]);
+    new.breaches = (0..MAX_BREACHES * 2)
+        .map(|i| Breach {
+            name: "stealer".to_string(),
+            date: "2024-05-01".to_string(),
+            source_file: format!("{}/passwords.txt", i),
+        })
+        .collect();
+
+    let (merged, _) = merge_documents(Vec::new(), &new);
+    assert_eq!(merged.breaches.len(), MAX_BREACHES);
+    assert_eq!(merged.breaches[0].source_file, "0/passwords.txt");
+}
+
+#[test]
+fn fit_counts_breaches() {
+    let credentials: Vec<_> = (0..200).map(|_| ("vpn", "john", "p1")).collect();
+    let mut document = leak(&credentials);
+    document.breaches = (0..10)
+        .map(|i| Breach {
+            name: format!("combo{}", i),
+            ..Default::default()
+        })
+        .collect();
+    let size = serde_json::to_string(&document).unwrap().len();
+
+    for strategy in [SplitStrategy::Even, SplitStrategy::Subdomain] {
+        let fitted = fit_document(document.clone(), size / 3, strategy);
+        for x in &fitted {
+            assert!(serde_json::to_string(x).unwrap().len() <= size / 3);
+        }
+    }
+}
+
+#[test]
+fn merge_keeps_org() {
+    let mut stored = leak(&[("", "john", "p1")]);
+    stored.org = "Corp Inc".to_string();
+
+    let (merged, _) = merge_documents(vec![stored.clone()], &leak(&[("", "jane", "p2")]));
+    assert_eq!(merged.org, "Corp Inc");
+
+    let mut new = leak(&[("", "jane", "p2")]);
+    new.org = "Corp Holding".to_string();
+    let (merged, _) = merge_documents(vec![stored], &new);
+    assert_eq!(merged.org, "Corp Holding");
+
+    let splits = fit_document(merged, 60, SplitStrategy::Subdomain);
+    assert!(splits.iter().all(|x| x.org == "Corp Holding"));
+}
+
+#[test]
+fn fit_small_document() {
+    let document = leak(&[("", "john", "p1"), ("vpn", "admin", "p2")]);
+    let fitted = fit_document(document, 16777216, SplitStrategy::Even);
+    assert_eq!(fitted.len(), 1);
+    assert_eq!(fitted[0].credentials.len(), 2);
+}
+
+#[test]
+fn fit_oversized_document() {
+    let credentials: Vec<_> = (0..100).map(|_| ("", "john", "p1")).collect();
+    let document = leak(&credentials);
+    let size = serde_json::to_string(&document).unwrap().len();
+
+    let fitted = fit_document(document, size / 3, SplitStrategy::Subdomain);
+    assert!(fitted.len() >= 3);
+    let total: usize = fitted
+        .iter()
+        .flat_map(|x| &x.credentials)
+        .map(|x| x.data.len())
+        .sum();
+    assert_eq!(total, 100);
+}
+
+#[test]
+fn borrowed_document_json() {
+    let leak = leak(&[("", "john", "p\"1"), ("vpn", "admin", "p2")]);
+    let json = serde_json::to_string(&leak).unwrap();
+    assert_eq!(
+        serde_json::to_string(&LeakDataRef::from(&leak)).unwrap(),
+        json
+    );
+
+    let borrowed: LeakDataRef = serde_json::from_str(&json).unwrap();
+    assert_eq!(borrowed.domain, "corp.com");
+    let owned = borrowed.into_owned();
+    assert_eq!(pairs(&owned.credentials[0]), vec![("john", "p\"1")]);
+}
+
+#[test]
+fn legacy_documents() {
+    let stored = r#"{"domain":"corp.com","credentials":[{"data":[["john","p1"]]}],"owner":"x"}"#;
+    let legacy: LeakData = serde_json::from_str(stored).unwrap();
+    assert_eq!(legacy.version, 1);
+    assert_eq!(legacy.credentials[0].subdomain, "");
+
+    let upgraded = upgrade(serde_json::from_str(stored).unwrap()).unwrap();
+    assert_eq!(upgraded.version, DOCUMENT_VERSION);
+    assert_eq!(pairs(&upgraded.credentials[0]), vec![("john", "p1")]);
+
+    let newer = serde_json::json!({"domain": "corp.com", "version": DOCUMENT_VERSION + 1});
+    assert!(upgrade(newer).is_err());
+}
@@ -0,0 +1,48 @@
+use lib::domain_counts::DomainCounts;
+
+fn tally(name: &str, domains: &[&str], max_domains: usize) -> (u64, String) {
+    let path = std::env::temp_dir().join(format!("leaks_domain_counts_{}.csv", name));
+    let mut counts = DomainCounts::new(&path, max_domains).unwrap();
+    for domain in domains {
+        counts.add(domain).unwrap();
+    }
+    let distinct = counts.finish().unwrap();
+    (distinct, std::fs::read_to_string(&path).unwrap())
+}
+
+#[test]
+fn counts_in_memory() {
+    assert_eq!(
+        tally("memory", &["b.com", "a.com", "b.com"], usize::MAX),
+        (2, "domain,credentials\na.com,1\nb.com,2\n".to_string())
+    );
+    assert_eq!(
+        tally("empty", &[], usize::MAX),
+        (0, "domain,credentials\n".to_string())
+    );
+}
+
+#[test]
+fn spilled_counts_are_summed() {
+    let domains = [
+        "c.com", "a.com", "b.com", "a.com", "d.com", "c.com", "a.com", "e.com", "b.com",
+    ];
+    let expected = "domain,credentials\na.com,3\nb.com,2\nc.com,2\nd.com,1\ne.com,1\n";
+
+    for max_domains in [1, 2, 3] {
+        assert_eq!(
+            tally("spill", &domains, max_domains),
+            (5, expected.to_string()),
+            "{}",
+            max_domains
+        );
+    }
+}
+
+#[test]
+fn quoted_domains() {
+    assert_eq!(
+        tally("quoted", &["a,b.com", "a,b.com"], 0),
+        (1, "domain,credentials\n\"a,b.com\",2\n".to_string())
+    );
+}
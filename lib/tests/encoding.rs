@@ -0,0 +1,53 @@
+use std::io::Read;
+
+use encoding_rs::{UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1251};
+use lib::encoding::{decode, detect, InputEncoding};
+
+fn utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+#[test]
+fn detects_encodings() {
+    assert_eq!(detect(b"user@example.com:pass\n"), UTF_8);
+    assert_eq!(detect("иван@mail.ru:пароль\n".as_bytes()), UTF_8);
+
+    let (cp1251, _, _) = WINDOWS_1251.encode("иван@mail.ru:пароль\nпетр@yandex.ru:секрет\n");
+    assert_eq!(detect(&cp1251), WINDOWS_1251);
+
+    assert_eq!(detect(&utf16le("user@example.com:pass\n")), UTF_16LE);
+    let utf16be: Vec<u8> = "user@example.com:pass\n"
+        .encode_utf16()
+        .flat_map(u16::to_be_bytes)
+        .collect();
+    assert_eq!(detect(&utf16be), UTF_16BE);
+    assert_eq!(detect(b"\xef\xbb\xbfuser@example.com:pass"), UTF_8);
+}
+
+#[test]
+fn transcodes_to_utf8() {
+    let mut input = vec![0xff, 0xfe];
+    input.extend(utf16le("admin@example.com:secret\n"));
+
+    let (mut reader, encoding) = decode(&input[..], InputEncoding::Auto).unwrap();
+    let mut text = String::new();
+    reader.read_to_string(&mut text).unwrap();
+    assert_eq!(encoding, UTF_16LE);
+    assert_eq!(text, "admin@example.com:secret\n");
+
+    // A fixed encoding wins over detection
+    let (mut reader, _) = decode(&b"caf\xe9"[..], "windows-1252".parse().unwrap()).unwrap();
+    let mut text = String::new();
+    reader.read_to_string(&mut text).unwrap();
+    assert_eq!(text, "café");
+}
+
+#[test]
+fn encoding_labels() {
+    assert_eq!("auto".parse::<InputEncoding>(), Ok(InputEncoding::Auto));
+    assert_eq!(
+        "cp1251".parse::<InputEncoding>(),
+        Ok(InputEncoding::Fixed(WINDOWS_1251))
+    );
+    assert!("klingon".parse::<InputEncoding>().is_err());
+}
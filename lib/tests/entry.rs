@@ -0,0 +1,686 @@
+use lib::entry::{
+    parse_entry, parse_formatted_entry, parse_line, regex_extract, EntryFormat, ParseError,
+    ParseOptions, Separators, TargetType,
+};
+use lib::{domain_typos::TypoTable, DomainForm, PublicSuffixList, UsernameRules};
+
+fn gen_test_st() -> PublicSuffixList {
+    PublicSuffixList::new("com net co.uk")
+}
+
+#[test]
+fn simple() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("wolya@yandex.net:5555", &st).unwrap();
+    assert_eq!(username, "wolya");
+    assert_eq!(password, "5555");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "yandex.net");
+}
+
+#[test]
+fn credentials_scary_at() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("username36@yahoo.com:password@", &st).unwrap();
+    assert_eq!(username, "username36");
+    assert_eq!(password, "password@");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "yahoo.com");
+}
+
+#[test]
+fn credentials_first() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("wolya:5555@yandex.net", &st).unwrap();
+    assert_eq!(username, "wolya");
+    assert_eq!(password, "5555");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "yandex.net");
+}
+
+#[test]
+fn credentials_first_double_at() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("wolya:55@55@yandex.net", &st).unwrap();
+    assert_eq!(username, "wolya");
+    assert_eq!(password, "55@55");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "yandex.net");
+}
+
+#[test]
+fn credentials_scary_0() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("wolya@yandex.conm.:5555", &st).unwrap();
+    assert_eq!(username, "wolya");
+    assert_eq!(password, "5555");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "yandex.conm");
+}
+
+#[test]
+fn credentials_scary_1() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("wolya@yandex.com..:5555dd", &st).unwrap();
+    assert_eq!(username, "wolya");
+    assert_eq!(password, "5555dd");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "yandex.com");
+}
+
+#[test]
+fn credentials_scary_2() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("user.name@wanadoo.fr:Password", &st).unwrap();
+    assert_eq!(username, "user.name");
+    assert_eq!(password, "Password");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "wanadoo.fr");
+}
+
+#[test]
+fn credentials_scary_3() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("wolya@gotadsl.co.uk:password!", &st).unwrap();
+    assert_eq!(username, "wolya");
+    assert_eq!(password, "password!");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "gotadsl.co.uk");
+}
+
+#[test]
+fn credentials_scary_4() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("user-name@wanadoo.fr:password2password", &st).unwrap();
+    assert_eq!(username, "user-name");
+    assert_eq!(password, "password2password");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "wanadoo.fr");
+}
+
+#[test]
+fn no_undescore_domain_name() {
+    let st = gen_test_st();
+    assert!(parse_entry("user-name@wana_doo.fr:password2password", &st).is_err());
+}
+
+#[test]
+fn no_undescore_domain_name_2() {
+    let st = gen_test_st();
+    assert!(parse_entry("user-name:password2password@wana_doo.fr", &st).is_err());
+}
+
+#[test]
+fn dash_domain_name() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("user-name@wana-doo.fr:password2password", &st).unwrap();
+    assert_eq!(username, "user-name");
+    assert_eq!(password, "password2password");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "wana-doo.fr");
+}
+
+#[test]
+fn dash_domain_name_2() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("user-name:password2password@wana-doo.fr", &st).unwrap();
+    assert_eq!(username, "user-name");
+    assert_eq!(password, "password2password");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "wana-doo.fr");
+}
+
+#[test]
+fn number_login() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("999999@yahoo.com:112233", &st).unwrap();
+    assert_eq!(username, "999999");
+    assert_eq!(domain, "yahoo.com");
+    assert_eq!(password, "112233");
+    assert!(subdomain.is_empty());
+}
+
+#[test]
+fn domain_case() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("username@AOL.com:password", &st).unwrap();
+    assert_eq!(username, "username");
+    assert_eq!(password, "password");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "aol.com");
+}
+
+#[test]
+fn large_username() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("wqwepqowqeiweyyyteyetetqewwqwqw@yahoo.com:parter", &st).unwrap();
+    assert_eq!(username, "wqwepqowqeiweyyyteyetetqewwqwqw");
+    assert_eq!(password, "parter");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "yahoo.com");
+}
+
+#[test]
+fn dot_dot() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("username@yahoo..com:parter", &st).unwrap();
+    assert_eq!(username, "username");
+    assert_eq!(password, "parter");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "yahoo.com");
+}
+
+#[test]
+fn domain_lowercase() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) =
+        parse_entry("username@DOMAIN.COM:parter", &st).unwrap();
+    assert_eq!(username, "username");
+    assert_eq!(password, "parter");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "domain.com");
+}
+
+#[test]
+fn format_user_email_pass() {
+    let st = gen_test_st();
+    let (username, password, subdomain, domain) = parse_formatted_entry(
+        "nick:wolya@yandex.net:5555",
+        &st,
+        &ParseOptions {
+            format: EntryFormat::UserEmailPass,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(username, "wolya");
+    assert_eq!(password, "5555");
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "yandex.net");
+}
+
+#[test]
+fn format_email_hash_salt() {
+    let st = gen_test_st();
+    let (username, password, _, domain) = parse_formatted_entry(
+        "wolya@yandex.net:5f4dcc3b5aa765d61d8327deb882cf99:x1y2",
+        &st,
+        &ParseOptions {
+            format: EntryFormat::EmailHashSalt,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(username, "wolya");
+    assert_eq!(password, "5f4dcc3b5aa765d61d8327deb882cf99:x1y2");
+    assert_eq!(domain, "yandex.net");
+}
+
+#[test]
+fn format_email_hash_salt_missing() {
+    let st = gen_test_st();
+    assert!(parse_formatted_entry(
+        "wolya@yandex.net:5f4dcc3b5aa765d61d8327deb882cf99",
+        &st,
+        &ParseOptions {
+            format: EntryFormat::EmailHashSalt,
+            ..Default::default()
+        },
+    )
+    .is_err());
+}
+
+#[test]
+fn error_no_separator() {
+    let st = gen_test_st();
+    assert_eq!(
+        parse_entry("wolya yandex.net", &st).unwrap_err(),
+        ParseError::NoSeparator
+    );
+}
+
+#[test]
+fn error_username_too_long() {
+    let st = gen_test_st();
+    let entry = format!("{}@yandex.net:5555", "a".repeat(35) + "." + &"b".repeat(10));
+    assert_eq!(
+        parse_entry(&entry, &st).unwrap_err(),
+        ParseError::UsernameTooLong
+    );
+}
+
+fn idn_options(domain_form: DomainForm) -> ParseOptions {
+    ParseOptions {
+        domain_form: Some(domain_form),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn idn_to_ascii() {
+    let st = gen_test_st();
+    let (_, _, subdomain, domain) = parse_formatted_entry(
+        "wolya@почта.пример.com:5555",
+        &st,
+        &idn_options(DomainForm::Ascii),
+    )
+    .unwrap();
+    assert_eq!(subdomain, "xn--80a1acny");
+    assert_eq!(domain, "xn--e1afmkfd.com");
+}
+
+#[test]
+fn idn_to_unicode() {
+    let st = gen_test_st();
+    let (_, _, subdomain, domain) = parse_formatted_entry(
+        "wolya@xn--e1afmkfd.com:5555",
+        &st,
+        &idn_options(DomainForm::Unicode),
+    )
+    .unwrap();
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "пример.com");
+}
+
+#[test]
+fn idn_required_for_unicode() {
+    let st = gen_test_st();
+    assert_eq!(
+        parse_entry("wolya@пример.com:5555", &st).unwrap_err(),
+        ParseError::BadDomain
+    );
+}
+
+#[test]
+fn plus_tag_username() {
+    let st = gen_test_st();
+    let (username, _, _, domain) = parse_entry("john.doe+shop@gmail.com:5555", &st).unwrap();
+    assert_eq!(username, "john.doe+shop");
+    assert_eq!(domain, "gmail.com");
+}
+
+#[test]
+fn normalized_username() {
+    let st = gen_test_st();
+    let options = ParseOptions {
+        username_rules: Some(UsernameRules::default()),
+        ..Default::default()
+    };
+    let (username, _, _, _) =
+        parse_formatted_entry("john.doe+shop@gmail.com:5555", &st, &options).unwrap();
+    assert_eq!(username, "johndoe");
+
+    let (username, _, _, _) =
+        parse_formatted_entry("john.doe+shop@hotmail.com:5555", &st, &options).unwrap();
+    assert_eq!(username, "john.doe");
+}
+
+#[test]
+fn corrected_domain_typo() {
+    let st = gen_test_st();
+    let options = ParseOptions {
+        username_rules: Some(UsernameRules::default()),
+        domain_corrections: Some(TypoTable::Bundled.load().unwrap()),
+        ..Default::default()
+    };
+    let entry = parse_line("john.doe@GAMIL.com:5555", &st, &options).unwrap();
+    assert_eq!(
+        (entry.username.as_ref(), entry.domain.as_str()),
+        ("johndoe", "gmail.com")
+    );
+    assert!(entry.corrected);
+
+    let entry = parse_line("jane@mail.hotmial.com:5555", &st, &options).unwrap();
+    assert_eq!(entry.domain, "hotmial.com");
+    assert!(!entry.corrected);
+
+    let (_, _, _, domain) =
+        parse_formatted_entry("jane@hotmial.com:5555", &st, &ParseOptions::default()).unwrap();
+    assert_eq!(domain, "hotmial.com");
+}
+
+fn url_options() -> ParseOptions {
+    ParseOptions {
+        format: EntryFormat::UrlLoginPass,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn url_email_login() {
+    let st = gen_test_st();
+    let entry = parse_line(
+        "https://accounts.site.com/login?next=/:wolya@mail.yandex.net:55:55",
+        &st,
+        &url_options(),
+    )
+    .unwrap();
+    assert_eq!(entry.username, "wolya");
+    assert_eq!(entry.password, "55:55");
+    assert_eq!(entry.subdomain, "mail");
+    assert_eq!(entry.domain, "yandex.net");
+    assert_eq!(entry.target_domain, "accounts.site.com");
+}
+
+#[test]
+fn url_plain_login() {
+    let st = gen_test_st();
+    let entry = parse_line(
+        "http://VPN.corp.com:8443/:admin:secret",
+        &st,
+        &url_options(),
+    )
+    .unwrap();
+    assert_eq!(entry.username, "admin");
+    assert_eq!(entry.password, "secret");
+    assert_eq!(entry.subdomain, "vpn");
+    assert_eq!(entry.domain, "corp.com");
+    assert_eq!(entry.target_domain, "vpn.corp.com");
+}
+
+#[test]
+fn url_without_path() {
+    let st = gen_test_st();
+    let entry = parse_line("site.com:1234:pass", &st, &url_options()).unwrap();
+    assert_eq!(entry.username, "1234");
+    assert_eq!(entry.target_domain, "site.com");
+}
+
+#[test]
+fn url_errors() {
+    let st = gen_test_st();
+    assert_eq!(
+        parse_line("https://site.com/login", &st, &url_options()),
+        Err(ParseError::NoSeparator)
+    );
+    assert_eq!(
+        parse_line("https://si_te.com/:user:pass", &st, &url_options()),
+        Err(ParseError::BadDomain)
+    );
+    assert_eq!(
+        parse_line("https://site.com/::pass", &st, &url_options()),
+        Err(ParseError::BadFormat)
+    );
+    for line in [
+        "https://site.com/:@mail.com:pass",
+        "https://site.com/:user@:pass",
+    ] {
+        assert_eq!(
+            parse_line(line, &st, &url_options()),
+            Err(ParseError::BadFormat),
+            "{}",
+            line
+        );
+    }
+}
+
+#[test]
+fn extract_trailing_dots() {
+    assert_eq!(
+        regex_extract("wolya@yandex.net...:5555").unwrap(),
+        ("wolya", "yandex.net", "5555")
+    );
+    assert_eq!(
+        regex_extract("wolya:5555@yandex.net..").unwrap(),
+        ("wolya", "yandex.net", "5555")
+    );
+    assert!(regex_extract(&format!("wolya@yandex.net{}:5555", ".".repeat(11))).is_err());
+}
+
+#[test]
+fn extract_semicolon() {
+    assert_eq!(
+        regex_extract("wolya@yandex.net;55:55").unwrap(),
+        ("wolya", "yandex.net", "55:55")
+    );
+    // The layout is picked by whichever of @ and : comes first
+    assert!(regex_extract("wolya;5555@yandex.net").is_err());
+}
+
+#[test]
+fn extract_unicode_domain() {
+    assert_eq!(
+        regex_extract("wolya@яндекс.рф:5555").unwrap(),
+        ("wolya", "яндекс.рф", "5555")
+    );
+}
+
+#[test]
+fn extract_bad_domains() {
+    assert!(regex_extract("wolya@yandex...net:5555").is_err());
+    assert!(regex_extract("wolya@yandex.n-t:5555").is_err());
+    assert!(regex_extract("wolya@-yandex.net:5555").is_err());
+    assert!(regex_extract("wolya@net:5555").is_err());
+    assert!(regex_extract(&format!("wolya@{}.net:5555", "a".repeat(64))).is_err());
+}
+
+#[test]
+fn extract_bad_usernames() {
+    assert!(regex_extract(".wolya@yandex.net:5555").is_err());
+    assert!(regex_extract(&format!("{}@yandex.net:5555", "a".repeat(36))).is_err());
+    assert!(regex_extract("wo lya@yandex.net:5555").is_err());
+    assert!(regex_extract("wolya@yandex.net:").is_err());
+}
+
+#[test]
+fn quoted_local_part() {
+    let st = gen_test_st();
+    let (username, password, _, domain) =
+        parse_entry(r#""john:doe"@yandex.net:55;55"#, &st).unwrap();
+    assert_eq!(username, r#""john:doe""#);
+    assert_eq!(password, "55;55");
+    assert_eq!(domain, "yandex.net");
+
+    let (username, password, _, _) = parse_entry(r#""john \"d@e\"":5555@yandex.net"#, &st).unwrap();
+    assert_eq!(username, r#""john \"d@e\"""#);
+    assert_eq!(password, "5555");
+
+    assert_eq!(
+        parse_entry(r#""john@yandex.net:5555"#, &st).unwrap_err(),
+        ParseError::BadFormat
+    );
+}
+
+#[test]
+fn unicode_usernames() {
+    let st = gen_test_st();
+    assert!(parse_entry("иван.петров@yandex.net:5555", &st).is_err());
+
+    let options = ParseOptions {
+        unicode_usernames: true,
+        ..Default::default()
+    };
+    let (username, _, _, domain) =
+        parse_formatted_entry("иван.петров@yandex.net:5555", &st, &options).unwrap();
+    assert_eq!(username, "иван.петров");
+    assert_eq!(domain, "yandex.net");
+
+    let (username, password, _, _) =
+        parse_formatted_entry("josé:5555@yandex.net", &st, &options).unwrap();
+    assert_eq!(username, "josé");
+    assert_eq!(password, "5555");
+}
+
+#[test]
+fn last_colon() {
+    let st = gen_test_st();
+    let options = ParseOptions {
+        last_colon: true,
+        ..Default::default()
+    };
+    assert!(parse_entry("1234:wolya@yandex.net:5555", &st).is_err());
+
+    let (username, password, _, domain) =
+        parse_formatted_entry("1234:nick:wolya@yandex.net:5555", &st, &options).unwrap();
+    assert_eq!(username, "wolya");
+    assert_eq!(password, "5555");
+    assert_eq!(domain, "yandex.net");
+
+    let (username, _, _, _) =
+        parse_formatted_entry(r#"nick;"a:b"@yandex.net:5555"#, &st, &options).unwrap();
+    assert_eq!(username, r#""a:b""#);
+
+    // Credentials first entries don't split at the last colon
+    let (username, password, _, _) =
+        parse_formatted_entry("wolya:55:55@yandex.net", &st, &options).unwrap();
+    assert_eq!(username, "wolya");
+    assert_eq!(password, "55:55");
+}
+
+fn separators(separators: &str) -> ParseOptions {
+    ParseOptions {
+        separators: separators.parse().unwrap(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn tab_separated() {
+    let st = gen_test_st();
+    let options = separators(r"\t");
+    let (username, password, _, domain) =
+        parse_formatted_entry("wolya@yandex.net\t55:55", &st, &options).unwrap();
+    assert_eq!(username, "wolya");
+    assert_eq!(password, "55:55");
+    assert_eq!(domain, "yandex.net");
+
+    let (username, password, _, _) =
+        parse_formatted_entry("wolya\t55;55@yandex.net", &st, &options).unwrap();
+    assert_eq!(username, "wolya");
+    assert_eq!(password, "55;55");
+
+    assert_eq!(
+        parse_formatted_entry("wolya@yandex.net:5555", &st, &options).unwrap_err(),
+        ParseError::BadFormat
+    );
+}
+
+#[test]
+fn separator_sets() {
+    let st = gen_test_st();
+    let options = separators(r";\t:");
+    // The first separator tells the layouts apart
+    let (username, password, _, _) =
+        parse_formatted_entry("wolya;5555@yandex.net", &st, &options).unwrap();
+    assert_eq!((username.as_ref(), password), ("wolya", "5555"));
+    let (_, password, _, _) =
+        parse_formatted_entry("wolya@yandex.net\t5555", &st, &options).unwrap();
+    assert_eq!(password, "5555");
+
+    let unicode = ParseOptions {
+        unicode_usernames: true,
+        last_colon: true,
+        format: EntryFormat::UserEmailPass,
+        ..separators("|")
+    };
+    let (username, password, _, _) =
+        parse_formatted_entry("nick|иван@yandex.net|55:55", &st, &unicode).unwrap();
+    assert_eq!((username.as_ref(), password), ("иван", "55:55"));
+
+    let url = ParseOptions {
+        format: EntryFormat::UrlLoginPass,
+        ..separators(r"\t")
+    };
+    let entry = parse_line("https://site.com/login:admin\t55:55", &st, &url).unwrap();
+    assert_eq!(entry.username, "admin");
+    assert_eq!(entry.password, "55:55");
+}
+
+#[test]
+fn bad_separators() {
+    assert!("".parse::<Separators>().is_err());
+    assert!("@".parse::<Separators>().is_err());
+    assert!(":a".parse::<Separators>().is_err());
+    assert!(r":;\t |".parse::<Separators>().is_ok());
+}
+
+fn ip_entry(entry: &str, options: &ParseOptions) -> (String, String, String, String) {
+    let parsed = parse_line(entry, &gen_test_st(), options).unwrap();
+    assert_eq!(parsed.target_type, TargetType::Ip, "{}", entry);
+    assert!(parsed.subdomain.is_empty());
+    (
+        parsed.username.into_owned(),
+        parsed.password.to_string(),
+        parsed.domain,
+        parsed.target_domain,
+    )
+}
+
+fn owned(fields: [&str; 4]) -> (String, String, String, String) {
+    let [username, password, domain, target] = fields.map(str::to_string);
+    (username, password, domain, target)
+}
+
+#[test]
+fn ip_hosts() {
+    let options = ParseOptions::default();
+    assert_eq!(
+        ip_entry("admin:pass@192.168.1.10:8080", &options),
+        owned(["admin", "pass", "192.168.1.10", "192.168.1.10:8080"])
+    );
+    assert_eq!(
+        ip_entry("admin:p@ss@10.0.0.1", &options),
+        owned(["admin", "p@ss", "10.0.0.1", "10.0.0.1"])
+    );
+    assert_eq!(
+        ip_entry("root:toor@[2001:DB8::1]:22", &options),
+        owned(["root", "toor", "2001:db8::1", "[2001:db8::1]:22"])
+    );
+    assert_eq!(
+        ip_entry("root:toor@2001:db8::1", &options),
+        owned(["root", "toor", "2001:db8::1", "2001:db8::1"])
+    );
+    assert_eq!(
+        ip_entry("admin@10.0.0.1:55:55", &options),
+        owned(["admin", "55:55", "10.0.0.1", "10.0.0.1"])
+    );
+    assert_eq!(
+        ip_entry("admin@[::1];secret", &options),
+        owned(["admin", "secret", "::1", "::1"])
+    );
+
+    let st = gen_test_st();
+    let entry = parse_line("admin@mail.yandex.net:pass", &st, &options).unwrap();
+    assert_eq!(entry.target_type, TargetType::Domain);
+    assert!(parse_line("admin:pass@300.1.1.1:80", &st, &options).is_err());
+    assert!(parse_line("admin:pass@10.0.0.1:http", &st, &options).is_err());
+    assert!(parse_line("ad min:pass@10.0.0.1", &st, &options).is_err());
+}
+
+#[test]
+fn ip_urls() {
+    let options = url_options();
+    assert_eq!(
+        ip_entry("http://10.0.0.1:8080/login:admin:pass", &options),
+        owned(["admin", "pass", "10.0.0.1", "10.0.0.1"])
+    );
+    assert_eq!(
+        ip_entry("https://[2001:db8::1]/admin:root:toor", &options),
+        owned(["root", "toor", "2001:db8::1", "2001:db8::1"])
+    );
+
+    let entry = parse_line(
+        "http://192.168.0.1/:wolya@mail.yandex.net:5555",
+        &gen_test_st(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(entry.domain, "yandex.net");
+    assert_eq!(entry.target_domain, "192.168.0.1");
+    assert_eq!(entry.target_type, TargetType::Ip);
+}
@@ -0,0 +1,32 @@
+use std::{fs::File, process::ExitCode};
+
+use lib::{
+    error::Error,
+    errors::{exit_code, report, BoxError, Context},
+};
+
+#[test]
+fn reports_every_cause() {
+    let error = File::open("/missing/dump.txt")
+        .context("can't index dump.txt")
+        .context("stage index failed")
+        .unwrap_err();
+    assert_eq!(
+        report(&error),
+        "stage index failed: can't index dump.txt: No such file or directory (os error 2)"
+    );
+}
+
+#[test]
+fn repeated_causes_are_skipped() {
+    let error = Error::Read(std::io::Error::other("truncated gzip stream"));
+    assert_eq!(error.to_string(), "can't read input: truncated gzip stream");
+    assert_eq!(report(&error), "can't read input: truncated gzip stream");
+}
+
+#[test]
+fn exit_codes() {
+    assert_eq!(exit_code(Ok::<(), BoxError>(())), ExitCode::SUCCESS);
+    assert_eq!(exit_code(Err("no tld file")), ExitCode::FAILURE);
+    assert_eq!(exit_code(Err(Error::Interrupted)), ExitCode::from(130));
+}
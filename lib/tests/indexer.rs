@@ -0,0 +1,834 @@
+use std::{
+    fs::File,
+    io::{Cursor, Read, Write},
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use lib::{
+    checkpoint::Checkpoint,
+    disposable::DisposableList,
+    domain_filter::{DomainFilter, DomainList},
+    domain_typos::DomainCorrections,
+    encryption::TempKey,
+    entry::ParseOptions,
+    error::Error,
+    indexer::{ErrorFormat, Indexer, IndexerOptions, SharedOutput},
+    output::CsvOptions,
+    parsers::{Delimited, ParserKind, Parsers},
+    redact::Redaction,
+    report::Stats,
+    shard::{ShardKey, Sharding, SplitOutput},
+    PublicSuffixList,
+};
+use zip::{write::FileOptions, ZipWriter};
+
+fn options(skip_errors: bool) -> IndexerOptions {
+    IndexerOptions {
+        input_type: "zip".to_string(),
+        csv: CsvOptions {
+            header: false,
+            ..Default::default()
+        },
+        error_format: ErrorFormat::Csv,
+        threads: 1,
+        skip_errors,
+        ..Default::default()
+    }
+}
+
+// A nested member that looks like a zip but can't be opened as one
+fn broken_zip(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(name);
+    let mut zip = ZipWriter::new(File::create(&path).unwrap());
+    zip.start_file("a.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"user@mail.example.com:pass\n").unwrap();
+    zip.start_file("broken.zip", FileOptions::default())
+        .unwrap();
+    zip.write_all(b"PK\x03\x04garbage").unwrap();
+    zip.start_file("b.txt", FileOptions::default()).unwrap();
+    zip.write_all(b"admin@example.com:secret\n").unwrap();
+    zip.finish().unwrap();
+    path
+}
+
+#[test]
+fn broken_member_aborts() {
+    let input = broken_zip("leaks_indexer_abort.zip");
+    let output = std::env::temp_dir().join("leaks_indexer_abort.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_abort.err");
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options(false)).unwrap();
+    let result = indexer.process(input.to_str().unwrap());
+
+    assert!(matches!(result, Err(e) if e.is_input()));
+}
+
+#[test]
+fn broken_member_skipped() {
+    let input = broken_zip("leaks_indexer_skip.zip");
+    let output = std::env::temp_dir().join("leaks_indexer_skip.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_skip.err");
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options(true)).unwrap();
+    indexer.process(input.to_str().unwrap()).unwrap();
+    indexer.finish().unwrap();
+
+    let contents = std::fs::read_to_string(&output).unwrap();
+    assert_eq!(
+        contents,
+        "example.com,mail,user,pass,plain,,,,a.txt,domain\nexample.com,,admin,secret,plain,,,,b.txt,domain\n"
+    );
+
+    let errors = std::fs::read_to_string(&error).unwrap();
+    assert!(errors.starts_with("broken.zip,member_error,"));
+}
+
+#[test]
+fn stealer_folders() {
+    let dir = std::env::temp_dir().join("leaks_indexer_stealer");
+    let log_dir = dir.join("US[0001]").join("Browsers");
+    std::fs::create_dir_all(&log_dir).unwrap();
+    std::fs::write(
+        log_dir.join("Passwords.txt"),
+        "URL: https://shop.com/login\nUsername: admin@example.com\nPassword: secret\n====\n\
+         URL: https://vpn.example.com/\nUsername: root\nPassword: toor\n====\n\
+         URL: https://bad_host/\nUsername: root\nPassword: toor\n",
+    )
+    .unwrap();
+    std::fs::write(log_dir.join("Cookies.txt"), "user@example.com:pass\n").unwrap();
+
+    let output = std::env::temp_dir().join("leaks_indexer_stealer.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_stealer.err");
+    let options = IndexerOptions {
+        input_type: "stealer".to_string(),
+        source: "us_logs".to_string(),
+        breach_date: "2024-05-01".to_string(),
+        ..options(false)
+    };
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.process(dir.to_str().unwrap()).unwrap();
+    indexer.finish().unwrap();
+
+    let contents = std::fs::read_to_string(&output).unwrap();
+    assert_eq!(
+        contents,
+        "example.com,,admin,secret,plain,shop.com,us_logs,2024-05-01,US[0001]/Browsers/Passwords.txt,domain\n\
+         example.com,vpn,root,toor,plain,vpn.example.com,us_logs,2024-05-01,US[0001]/Browsers/Passwords.txt,domain\n"
+    );
+
+    let errors = std::fs::read_to_string(&error).unwrap();
+    assert!(errors.ends_with("bad_domain,https://bad_host/:root:toor\n"));
+}
+
+#[test]
+fn redacted_passwords() {
+    let input = std::env::temp_dir().join("leaks_indexer_redact.txt");
+    std::fs::write(
+        &input,
+        "user@example.com:password\nadmin@example.com:1234\n",
+    )
+    .unwrap();
+    let output = std::env::temp_dir().join("leaks_indexer_redact.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_redact.err");
+    let options = IndexerOptions {
+        input_type: "plain".to_string(),
+        redaction: Some(Redaction::Mask),
+        ..options(false)
+    };
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.process(input.to_str().unwrap()).unwrap();
+    indexer.finish().unwrap();
+
+    let contents = std::fs::read_to_string(&output).unwrap();
+    assert_eq!(
+        contents,
+        "example.com,,user,pa****rd,plain,,,,leaks_indexer_redact.txt,domain\nexample.com,,admin,****,plain,,,,leaks_indexer_redact.txt,domain\n"
+    );
+}
+
+#[test]
+fn filtered_domains() {
+    let input = std::env::temp_dir().join("leaks_indexer_domains.txt");
+    std::fs::write(
+        &input,
+        "a@example.com:1\nb@mail.example.com:2\nc@vpn.corp.com:3\nd@test.corp.com:4\ne@other.com:5\n",
+    )
+    .unwrap();
+    let output = std::env::temp_dir().join("leaks_indexer_domains.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_domains.err");
+    let options = IndexerOptions {
+        input_type: "plain".to_string(),
+        domain_filter: DomainFilter {
+            only: Some(DomainList::new("example.com\n*.corp.com")),
+            exclude: Some(DomainList::new("# staging\ntest.corp.com")),
+            ..Default::default()
+        },
+        ..options(false)
+    };
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.process(input.to_str().unwrap()).unwrap();
+    indexer.finish().unwrap();
+
+    let contents = std::fs::read_to_string(&output).unwrap();
+    assert_eq!(
+        contents,
+        "example.com,,a,1,plain,,,,leaks_indexer_domains.txt,domain\ncorp.com,vpn,c,3,plain,,,,leaks_indexer_domains.txt,domain\n"
+    );
+}
+
+#[test]
+fn run_stats() {
+    let input = std::env::temp_dir().join("leaks_indexer_stats.txt");
+    std::fs::write(
+        &input,
+        "a@example.com:1\na@example.com:1\nb@mail.other.com:2\nbroken\nc@bad_host:3\n",
+    )
+    .unwrap();
+    let output = std::env::temp_dir().join("leaks_indexer_stats.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_stats.err");
+    let report = std::env::temp_dir().join("leaks_indexer_stats.json");
+    let options = IndexerOptions {
+        input_type: "plain".to_string(),
+        dedup: true,
+        ..options(false)
+    };
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.process(input.to_str().unwrap()).unwrap();
+    let stats = indexer.finish().unwrap();
+
+    assert_eq!(stats.lines_read, 5);
+    assert_eq!(stats.parsed, 3);
+    assert_eq!(stats.written, 2);
+    assert_eq!(stats.duplicates, 1);
+    assert_eq!(stats.rejected_total(), 2);
+    assert_eq!(stats.unique_domains, 2);
+
+    stats.write_report(&report).unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    assert_eq!(json["written"], 2);
+    assert_eq!(json["rejected"].as_object().unwrap().len(), 2);
+}
+
+#[test]
+fn estimated_unique_domains() {
+    let mut stats = Stats::default();
+    let mut other = Stats::default();
+    for i in 0..100_000 {
+        stats.write(&format!("domain{}.com", i));
+        // Half of them overlap
+        other.write(&format!("domain{}.com", i + 50_000));
+    }
+    stats.finish();
+    // Within 3% of the exact count
+    assert!(stats.unique_domains.abs_diff(100_000) < 3_000);
+
+    stats.merge(&other);
+    assert!(stats.unique_domains.abs_diff(150_000) < 4_500);
+    assert_eq!(stats.written, 200_000);
+}
+
+#[test]
+fn sharded_by_domain() {
+    let input = std::env::temp_dir().join("leaks_indexer_shards.txt");
+    let lines: String = (0..200)
+        .map(|i| {
+            format!(
+                "user{}@mail.domain{}.com:pass\nadmin@domain{}.com:secret\n",
+                i,
+                i % 50,
+                i % 50
+            )
+        })
+        .collect();
+    std::fs::write(&input, lines).unwrap();
+    let output = std::env::temp_dir().join("leaks_indexer_shards.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_shards.err");
+    let sharding = Sharding {
+        count: 4,
+        key: ShardKey::Domain,
+    };
+    let options = IndexerOptions {
+        input_type: "plain".to_string(),
+        sharding: Some(sharding),
+        ..options(false)
+    };
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.process(input.to_str().unwrap()).unwrap();
+    let stats = indexer.finish().unwrap();
+
+    let mut rows = 0;
+    let mut seen = std::collections::HashMap::new();
+    for (index, path) in sharding.paths(&output).iter().enumerate() {
+        assert!(path
+            .to_str()
+            .unwrap()
+            .ends_with(&format!("shards.shard{}.csv", index)));
+        let contents = std::fs::read_to_string(path).unwrap();
+        assert!(!contents.is_empty());
+        for line in contents.lines() {
+            let domain = line.split(',').next().unwrap().to_string();
+            assert_eq!(sharding.index(&domain), index);
+            assert_eq!(*seen.entry(domain).or_insert(index), index);
+            rows += 1;
+        }
+    }
+    assert_eq!(rows, stats.written);
+    assert_eq!(seen.len(), 50);
+}
+
+#[test]
+fn split_by_member() {
+    let input = std::env::temp_dir().join("leaks_indexer_split.tar");
+    let mut tar = tar::Builder::new(File::create(&input).unwrap());
+    for (path, contents) in [
+        ("ru/mail.txt", "ivan@mail.ru:1\n"),
+        ("de/mail.txt", "hans@web.de:2\n"),
+        ("ru/mail.txt", "petr@mail.ru:3\n"),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_cksum();
+        tar.append_data(&mut header, path, contents.as_bytes())
+            .unwrap();
+    }
+    tar.finish().unwrap();
+
+    let output = std::env::temp_dir().join("leaks_indexer_split.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_split.err");
+    let options = IndexerOptions {
+        input_type: "tar".to_string(),
+        split_output: Some(SplitOutput::Member),
+        ..options(false)
+    };
+
+    let st = PublicSuffixList::new("ru\nde");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.process(input.to_str().unwrap()).unwrap();
+    let outputs = indexer.output_paths().to_vec();
+    indexer.finish().unwrap();
+
+    let names: Vec<_> = outputs
+        .iter()
+        .map(|x| x.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "leaks_indexer_split.ru_mail.txt.csv",
+            "leaks_indexer_split.de_mail.txt.csv",
+            "leaks_indexer_split.ru_mail.txt-2.csv"
+        ]
+    );
+    let contents: Vec<_> = outputs
+        .iter()
+        .map(|x| std::fs::read_to_string(x).unwrap())
+        .collect();
+    assert_eq!(
+        contents,
+        [
+            "mail.ru,,ivan,1,plain,,,,ru/mail.txt,domain\n",
+            "web.de,,hans,2,plain,,,,de/mail.txt,domain\n",
+            "mail.ru,,petr,3,plain,,,,ru/mail.txt,domain\n"
+        ]
+    );
+    assert!(!output.exists());
+}
+
+#[test]
+fn transcoded_members() {
+    let dir = std::env::temp_dir().join("leaks_indexer_encodings");
+    std::fs::create_dir_all(&dir).unwrap();
+    // иван@mail.ru:пароль in windows-1251, and a utf-16 file without a byte order mark
+    let cp1251 = b"\xe8\xe2\xe0\xed@mail.ru:\xef\xe0\xf0\xee\xeb\xfc\n";
+    std::fs::write(dir.join("a.txt"), cp1251).unwrap();
+    let utf16: Vec<u8> = "admin@example.com:secret\n"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    std::fs::write(dir.join("b.txt"), utf16).unwrap();
+
+    let output = std::env::temp_dir().join("leaks_indexer_encodings.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_encodings.err");
+    let mut options = IndexerOptions {
+        input_type: "dir".to_string(),
+        ..options(false)
+    };
+    options.parse.unicode_usernames = true;
+
+    let st = PublicSuffixList::new("com ru");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.process(dir.to_str().unwrap()).unwrap();
+    indexer.finish().unwrap();
+
+    let contents = std::fs::read_to_string(&output).unwrap();
+    assert_eq!(
+        contents,
+        "mail.ru,,иван,пароль,plain,,,,a.txt,domain\nexample.com,,admin,secret,plain,,,,b.txt,domain\n"
+    );
+}
+
+#[test]
+fn sanitized_lines() {
+    let input = std::env::temp_dir().join("leaks_indexer_sanitize.txt");
+    std::fs::write(
+        &input,
+        b"\xef\xbb\xbfuser@example.com:pass\r\nadmin@exa\x00mple.com:secret\rroot@example.com:toor",
+    )
+    .unwrap();
+    let output = std::env::temp_dir().join("leaks_indexer_sanitize.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_sanitize.err");
+    let options = IndexerOptions {
+        input_type: "plain".to_string(),
+        ..options(false)
+    };
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.process(input.to_str().unwrap()).unwrap();
+    indexer.finish().unwrap();
+
+    let contents = std::fs::read_to_string(&output).unwrap();
+    assert_eq!(
+        contents,
+        "example.com,,user,pass,plain,,,,leaks_indexer_sanitize.txt,domain\nexample.com,,admin,secret,plain,,,,leaks_indexer_sanitize.txt,domain\nexample.com,,root,toor,plain,,,,leaks_indexer_sanitize.txt,domain\n"
+    );
+}
+
+fn zip_bytes(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, contents) in members {
+        zip.start_file(*name, FileOptions::default()).unwrap();
+        zip.write_all(contents).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+// Streams the input as if it came from a pipe
+fn index_stream(name: &str, input_type: &str, input: &[u8]) -> String {
+    let output = std::env::temp_dir().join(format!("leaks_indexer_{}.csv", name));
+    let error = std::env::temp_dir().join(format!("leaks_indexer_{}.err", name));
+    let options = IndexerOptions {
+        input_type: input_type.to_string(),
+        ..options(false)
+    };
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.handle_by_type(&mut Cursor::new(input)).unwrap();
+    indexer.finish().unwrap();
+    std::fs::read_to_string(&output).unwrap()
+}
+
+#[test]
+fn streamed_zip() {
+    let nested = zip_bytes(&[("c.txt", b"root@example.com:toor\n")]);
+    let input = zip_bytes(&[
+        ("a.txt", b"user@example.com:pass\n"),
+        ("nested.zip", &nested),
+        ("b.txt", b"admin@example.com:secret\n"),
+    ]);
+
+    assert_eq!(
+        index_stream("zip_stream", "zip", &input),
+        "example.com,,user,pass,plain,,,,a.txt,domain\n\
+         example.com,,root,toor,plain,,,,nested.zip/c.txt,domain\n\
+         example.com,,admin,secret,plain,,,,b.txt,domain\n"
+    );
+}
+
+#[test]
+fn auto_detected_inputs() {
+    let plain = b"user@example.com:pass\n".to_vec();
+
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(&plain).unwrap();
+    let gzip = gzip.finish().unwrap();
+
+    let mut tar = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(plain.len() as u64);
+    header.set_cksum();
+    tar.append_data(&mut header, "dump/a.txt", &plain[..])
+        .unwrap();
+    let tar_zst = zstd::encode_all(&tar.into_inner().unwrap()[..], 0).unwrap();
+
+    let zip = zip_bytes(&[("a.txt", &plain)]);
+
+    for (name, input, source_file) in [
+        ("auto_plain", plain.clone(), ""),
+        ("auto_gzip", gzip, ""),
+        ("auto_tar_zst", tar_zst, "dump/a.txt"),
+        ("auto_zip", zip, "a.txt"),
+    ] {
+        assert_eq!(
+            index_stream(name, "auto", &input),
+            format!("example.com,,user,pass,plain,,,,{},domain\n", source_file),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn detected_parsers() {
+    let input = zip_bytes(&[
+        (
+            "a.txt",
+            b"user@example.com:pass\nadmin:secret@example.com\n",
+        ),
+        (
+            "b.txt",
+            b"user@example.com,pass\nadmin@example.com,secret\nroot:toor@example.com\n",
+        ),
+        ("c.txt", b"https://example.com/login:admin:secret\n"),
+    ]);
+    let output = std::env::temp_dir().join("leaks_indexer_detect.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_detect.err");
+    let kinds = [
+        ParserKind::EmailPass,
+        ParserKind::UserPassDomain,
+        ParserKind::UrlUserPass,
+        ParserKind::Delimited,
+    ];
+    let options = IndexerOptions {
+        parse: ParseOptions {
+            parsers: Parsers::new(&kinds, Delimited::default()),
+            ..Default::default()
+        },
+        detect_lines: Some(10),
+        ..options(false)
+    };
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.handle_by_type(&mut Cursor::new(input)).unwrap();
+    let stats = indexer.finish().unwrap();
+
+    // Ties go to the earlier parser, so a.txt only keeps its email:pass line
+    // and b.txt drops its user:pass@domain one
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "example.com,,user,pass,plain,,,,a.txt,domain\n\
+         example.com,,user,pass,plain,,,,b.txt,domain\n\
+         example.com,,admin,secret,plain,,,,b.txt,domain\n\
+         example.com,,admin,secret,plain,example.com,,,c.txt,domain\n"
+    );
+    assert_eq!(stats.parsers["a.txt"], "email:pass");
+    assert_eq!(stats.parsers["b.txt"], "delimited");
+    assert_eq!(stats.parsers["c.txt"], "url:user:pass");
+    assert_eq!(stats.rejected_total(), 2);
+}
+
+#[test]
+fn ip_targets() {
+    let input = b"admin:pass@192.168.1.10:8080\nroot@[2001:db8::1]:toor\nuser@example.com:pass\n";
+    assert_eq!(
+        index_stream("ip_targets", "plain", input),
+        "192.168.1.10,,admin,pass,plain,192.168.1.10:8080,,,,ip\n\
+         2001:db8::1,,root,toor,plain,2001:db8::1,,,,ip\n\
+         example.com,,user,pass,plain,,,,,domain\n"
+    );
+}
+
+#[test]
+fn domain_counts() {
+    let input = zip_bytes(&[
+        (
+            "a.txt",
+            b"user@example.com:pass\nadmin@mail.example.com:secret\nbroken\n",
+        ),
+        ("b.txt", b"user@example.com:pass\nroot@test.com:toor\n"),
+    ]);
+    let output = std::env::temp_dir().join("leaks_indexer_counts.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_counts.err");
+    let counts = std::env::temp_dir().join("leaks_indexer_counts.counts.csv");
+    let options = IndexerOptions {
+        dedup: true,
+        domain_counts: Some(counts.clone()),
+        ..options(false)
+    };
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.handle_by_type(&mut Cursor::new(input)).unwrap();
+    indexer.finish().unwrap();
+
+    // Counts follow the written records, duplicates aren't counted
+    assert_eq!(
+        std::fs::read_to_string(&counts).unwrap(),
+        "domain,credentials\nexample.com,2\ntest.com,1\n"
+    );
+}
+
+#[test]
+fn disposable_domains() {
+    let input = b"user@example.com:pass\nspam@mailinator.com:pass\nbot@eu.yopmail.com:pass\n";
+    let options = IndexerOptions {
+        domain_filter: DomainFilter {
+            disposable: Some(DisposableList::Bundled.load().unwrap()),
+            ..Default::default()
+        },
+        input_type: "plain".to_string(),
+        ..options(false)
+    };
+    let output = std::env::temp_dir().join("leaks_indexer_disposable.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_disposable.err");
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.handle_by_type(&mut Cursor::new(input)).unwrap();
+    let stats = indexer.finish().unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "example.com,,user,pass,plain,,,,,domain\n"
+    );
+    assert_eq!(stats.filtered, 2);
+}
+
+#[test]
+fn corrected_domain_typos() {
+    let input = b"user@gamil.com:pass\nuser@gmail.com:pass\nfan@hotmial.com:pass\n";
+    let options = IndexerOptions {
+        parse: ParseOptions {
+            domain_corrections: Some(DomainCorrections::new("gamil.com gmail.com")),
+            ..Default::default()
+        },
+        input_type: "plain".to_string(),
+        dedup: true,
+        ..options(false)
+    };
+    let output = std::env::temp_dir().join("leaks_indexer_typos.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_typos.err");
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.handle_by_type(&mut Cursor::new(input)).unwrap();
+    let stats = indexer.finish().unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "gmail.com,,user,pass,plain,,,,,domain\nhotmial.com,,fan,pass,plain,,,,,domain\n"
+    );
+    assert_eq!((stats.parsed, stats.corrected, stats.duplicates), (3, 1, 1));
+    assert!(stats.to_string().contains("corrected       1\n"));
+}
+
+#[test]
+fn interrupted_run() {
+    let input = zip_bytes(&[("a.txt", b"user@example.com:pass\n")]);
+    let output = std::env::temp_dir().join("leaks_indexer_interrupted.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_interrupted.err");
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options(false)).unwrap();
+    indexer.stop_on(Arc::new(AtomicBool::new(true)));
+    let result = indexer.handle_by_type(&mut Cursor::new(input));
+    assert!(matches!(result, Err(Error::Interrupted)));
+
+    let checkpoint = indexer.checkpoint();
+    assert_eq!(checkpoint.current, "a.txt");
+    assert_eq!(checkpoint.lines, 0);
+    assert!(checkpoint.done.is_empty());
+    assert_eq!(checkpoint.outputs, [output]);
+    indexer.finish().unwrap();
+
+    let path = std::env::temp_dir().join("leaks_indexer_interrupted.checkpoint.json");
+    checkpoint.write(&path).unwrap();
+    assert_eq!(Checkpoint::read(&path).unwrap(), checkpoint);
+}
+
+#[test]
+fn resumed_run() {
+    let input = zip_bytes(&[
+        ("a.txt", b"user@example.com:pass\n"),
+        (
+            "b.txt",
+            b"admin@example.com:secret\nbroken\nroot@test.com:toor\n",
+        ),
+        ("c.txt", b"guest@test.com:guest\n"),
+    ]);
+    let output = std::env::temp_dir().join("leaks_indexer_resumed.csv");
+    let error = std::env::temp_dir().join("leaks_indexer_resumed.err");
+    let options = IndexerOptions {
+        resume: Some(Checkpoint {
+            done: ["a.txt".to_string()].into_iter().collect(),
+            current: "b.txt".to_string(),
+            lines: 2,
+            ..Default::default()
+        }),
+        ..options(false)
+    };
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer.handle_by_type(&mut Cursor::new(input)).unwrap();
+    let checkpoint = indexer.checkpoint();
+    indexer.finish().unwrap();
+
+    // Only the lines after the checkpoint are parsed
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "test.com,,root,toor,plain,,,,b.txt,domain\ntest.com,,guest,guest,plain,,,,c.txt,domain\n"
+    );
+    assert_eq!(std::fs::read_to_string(&error).unwrap(), "");
+    assert!(checkpoint.done.iter().eq(["a.txt", "b.txt", "c.txt"]));
+    assert_eq!(checkpoint.current, "");
+}
+
+#[test]
+fn shared_output() {
+    let dir = std::env::temp_dir();
+    let output = dir.join("leaks_indexer_shared.csv");
+    let error = dir.join("leaks_indexer_shared.err");
+    let options = IndexerOptions {
+        input_type: "plain".to_string(),
+        error_format: ErrorFormat::Plain,
+        ..options(false)
+    };
+    let shared = SharedOutput::new(&output, &error, &options).unwrap();
+
+    let st = PublicSuffixList::new("com");
+    let mut stats = lib::report::Stats::default();
+    for (name, lines) in [
+        (
+            "leaks_indexer_shared_a.txt",
+            "user@example.com:pass\nbroken a\n",
+        ),
+        (
+            "leaks_indexer_shared_b.txt",
+            "admin@test.com:secret\nbroken b\n",
+        ),
+    ] {
+        let input = dir.join(name);
+        std::fs::write(&input, lines).unwrap();
+        let options = IndexerOptions {
+            source: name.to_string(),
+            ..options.clone()
+        };
+        let mut indexer = Indexer::shared(&shared, st.clone(), options).unwrap();
+        indexer.process(input.to_str().unwrap()).unwrap();
+        stats.merge(&indexer.finish().unwrap());
+    }
+    shared.finish().unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "example.com,,user,pass,plain,,leaks_indexer_shared_a.txt,,leaks_indexer_shared_a.txt,domain\n\
+         test.com,,admin,secret,plain,,leaks_indexer_shared_b.txt,,leaks_indexer_shared_b.txt,domain\n"
+    );
+    // Every input's errors are marked with its name
+    assert_eq!(
+        std::fs::read_to_string(&error).unwrap(),
+        "//leaks_indexer_shared_a.txt\nbroken a\n//leaks_indexer_shared_b.txt\nbroken b\n"
+    );
+    assert_eq!(stats.written, 2);
+    assert_eq!(stats.unique_domains, 2);
+}
+
+fn gzip_bytes(input: &[u8]) -> Vec<u8> {
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(input).unwrap();
+    gzip.finish().unwrap()
+}
+
+#[test]
+fn nested_archives() {
+    // A zip holding a tar.gz, which holds a gzipped member. Members of
+    // nested archives are named below the member holding them
+    let mut tar = tar::Builder::new(Vec::new());
+    for (name, contents) in [
+        ("a.txt", b"admin@example.com:secret\n".to_vec()),
+        ("c.txt.gz", gzip_bytes(b"root@example.com:toor\n")),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_cksum();
+        tar.append_data(&mut header, name, &contents[..]).unwrap();
+    }
+    let input = zip_bytes(&[
+        ("a.txt", b"user@example.com:pass\n"),
+        ("inner.tar.gz", &gzip_bytes(&tar.into_inner().unwrap())),
+    ]);
+
+    for (max_depth, written, errors) in [
+        (
+            2,
+            "example.com,,user,pass,plain,,,,a.txt,domain\n\
+             example.com,,admin,secret,plain,,,,inner.tar.gz/a.txt,domain\n\
+             example.com,,root,toor,plain,,,,inner.tar.gz/c.txt,domain\n",
+            "",
+        ),
+        (
+            1,
+            "example.com,,user,pass,plain,,,,a.txt,domain\n\
+             example.com,,admin,secret,plain,,,,inner.tar.gz/a.txt,domain\n",
+            "inner.tar.gz/c.txt.gz,max_depth,nested archive past max depth 1\n",
+        ),
+        (
+            0,
+            "example.com,,user,pass,plain,,,,a.txt,domain\n",
+            "inner.tar.gz,max_depth,nested archive past max depth 0\n",
+        ),
+    ] {
+        let output = std::env::temp_dir().join(format!("leaks_indexer_nested_{}.csv", max_depth));
+        let error = std::env::temp_dir().join(format!("leaks_indexer_nested_{}.err", max_depth));
+        let options = IndexerOptions {
+            input_type: "zip".to_string(),
+            max_depth,
+            ..options(false)
+        };
+
+        let st = PublicSuffixList::new("com");
+        let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+        indexer.handle_by_type(&mut Cursor::new(&input)).unwrap();
+        let done = indexer.checkpoint().done;
+        let stats = indexer.finish().unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), written);
+        assert!(std::fs::read_to_string(&error).unwrap().ends_with(errors));
+        assert_eq!(stats.rejected.contains_key("max_depth"), max_depth < 2);
+        assert_eq!(done.len(), written.lines().count());
+    }
+}
+
+#[test]
+fn encrypted_errors() {
+    let output = std::env::temp_dir().join("leaks_indexer_encrypted.csv.age");
+    let error = std::env::temp_dir().join("leaks_indexer_encrypted.err.age");
+    let key = TempKey::generate();
+    let options = IndexerOptions {
+        input_type: "plain".to_string(),
+        encryption: Some(key.encryption()),
+        ..options(false)
+    };
+
+    let st = PublicSuffixList::new("com");
+    let mut indexer = Indexer::new(&output, &error, st, options).unwrap();
+    indexer
+        .handle_by_type(&mut Cursor::new("user@example.com:pass\nbroken:secret\n"))
+        .unwrap();
+    indexer.finish().unwrap();
+
+    // Rejected lines carry passwords, they never reach the disk in plaintext
+    let sealed = std::fs::read(&error).unwrap();
+    assert!(!sealed.windows(6).any(|x| x == b"secret"));
+    let mut errors = String::new();
+    key.decrypt(File::open(&error).unwrap())
+        .unwrap()
+        .read_to_string(&mut errors)
+        .unwrap();
+    assert!(errors.contains("broken:secret"));
+}
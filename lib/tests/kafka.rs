@@ -0,0 +1,45 @@
+use lib::{
+    error::Error,
+    kafka::{KafkaSink, KafkaTarget},
+    LeakRecord,
+};
+
+#[test]
+fn undelivered_records_fail() {
+    // Nothing listens on port 1, records time out in the producer queue
+    let target = KafkaTarget::parse("kafka://127.0.0.1:1/leaks?message.timeout.ms=200").unwrap();
+    let mut sink = KafkaSink::new(&target).unwrap();
+    let record = LeakRecord {
+        domain: "example.com".into(),
+        subdomain: "".into(),
+        username: "user".into(),
+        password: "pass".into(),
+        password_type: "plain".into(),
+        target_domain: "".into(),
+        source: "".into(),
+        breach_date: "".into(),
+        source_file: "".into(),
+        target_type: "domain".into(),
+    };
+    sink.write(&record).unwrap();
+    sink.write(&record).unwrap();
+
+    let result = sink.finish();
+    assert!(matches!(
+        result,
+        Err(Error::KafkaDelivery { failed: 2, .. })
+    ));
+}
+
+#[test]
+fn invalid_targets() {
+    for output in [
+        "kafka://broker:9092",
+        "kafka:///leaks",
+        "kafka://broker:9092/",
+        "kafka://broker:9092/a/b",
+        "leaks.csv",
+    ] {
+        assert_eq!(KafkaTarget::parse(output), None, "{}", output);
+    }
+}
@@ -0,0 +1,62 @@
+use lib::{
+    error::Error,
+    manifest::{Artifact, Manifest},
+};
+
+#[test]
+fn file_hash() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.csv");
+    std::fs::write(&path, "abc").unwrap();
+
+    let artifact = Artifact::hash(&path).unwrap();
+    assert_eq!(
+        artifact.sha256,
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(artifact.bytes, 3);
+}
+
+#[test]
+fn directory_hash_ignores_location() {
+    let hash = |name: &str| {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join(name);
+        std::fs::create_dir_all(root.join("logs")).unwrap();
+        std::fs::write(root.join("a.txt"), "a@example.com:1\n").unwrap();
+        std::fs::write(root.join("logs").join("b.txt"), "b@example.com:2\n").unwrap();
+        Artifact::hash(&root).unwrap()
+    };
+
+    let (first, second) = (hash("first"), hash("second"));
+    assert_eq!(first.sha256, second.sha256);
+    assert_eq!(first.bytes, 32);
+}
+
+#[test]
+fn verify_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("out.csv");
+    let other = dir.path().join("other.csv");
+    std::fs::write(&output, "example.com,,a,1\n").unwrap();
+    std::fs::write(&other, "example.com,,b,2\n").unwrap();
+
+    let mut manifest = Manifest::new("indexer", "0.1.0", &[]);
+    manifest.add_output(&output).unwrap();
+    manifest.count("written", 1);
+    let path = dir.path().join("manifest.json");
+    manifest.write(&path).unwrap();
+
+    let manifest = Manifest::read(&path).unwrap();
+    assert_eq!(manifest.tool, "indexer");
+    assert_eq!(manifest.counts["written"], 1);
+
+    // A moved copy still matches
+    let moved = dir.path().join("moved.csv");
+    std::fs::rename(&output, &moved).unwrap();
+    manifest.verify_input(&moved).unwrap();
+    assert!(matches!(
+        manifest.verify_input(&other),
+        Err(Error::ManifestMismatch(_))
+    ));
+}
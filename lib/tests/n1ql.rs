@@ -0,0 +1,39 @@
+use lib::n1ql::{identifier, Keyspace, Query};
+use serde_json::json;
+
+#[test]
+fn quoted_names() {
+    let keyspace = Keyspace::new("default", "leaks`; DROP", "_default", "leaks");
+    assert_eq!(
+        keyspace.to_string(),
+        "`default`:`leaks``; DROP`.`_default`.`leaks`"
+    );
+    assert_eq!(identifier("``"), "``````");
+
+    assert_eq!(keyspace.empty_name(), None);
+    assert_eq!(keyspace.sibling("").empty_name(), Some("collection"));
+}
+
+#[test]
+fn placeholders_match_values() {
+    let query = Query::new("SELECT * FROM k WHERE a = $1 AND b IN $2").bind("x");
+    assert!(!query.is_bound());
+    let query = query.bind(["y", "z"]);
+    assert!(query.is_bound());
+    assert_eq!(query.params(), json!(["x", ["y", "z"]]));
+
+    // Reused placeholders need a single value
+    assert!(Query::new("UPDATE k SET a = $1 WHERE b = $1")
+        .bind(1)
+        .is_bound());
+}
+
+#[test]
+fn placeholders_in_literals() {
+    let query = Query::new(r#"SELECT "$1", '$2', `$3`, "a\"$4", 'it''s $5' FROM k"#);
+    assert!(query.is_bound());
+    assert_eq!(query.params(), json!(null));
+
+    let query = Query::new("SELECT 'it''s' FROM k WHERE a = $1").bind(1);
+    assert!(query.is_bound());
+}
@@ -0,0 +1,39 @@
+use lib::{
+    error::Error,
+    org::{OrgColumns, OrgMap},
+};
+
+#[test]
+fn org_mapping() {
+    let csv = "domain,org\n\
+               corp.com,Corp Inc\n\
+               CORP.COM,Other\n\
+               corp.net,\n\
+               ,Corp Inc\n\
+               corp.org , Corp Inc \n";
+    let orgs = OrgMap::from_reader(csv.as_bytes(), &OrgColumns::default()).unwrap();
+
+    assert_eq!(orgs.len(), 2);
+    assert_eq!(orgs.get("corp.com"), Some("Corp Inc"));
+    assert_eq!(orgs.get("corp.org"), Some("Corp Inc"));
+    assert_eq!(orgs.get("corp.net"), None);
+}
+
+#[test]
+fn reverse_whois_export() {
+    let csv = "num,domainName,createdDate,registrantContact.organization\n\
+               1,corp.com,2004-01-12,Corp Inc\n\
+               2,corp-mail.com,2011-08-02,Corp Inc\n";
+    let columns: OrgColumns = "domainName,registrantContact.organization".parse().unwrap();
+    let orgs = OrgMap::from_reader(csv.as_bytes(), &columns).unwrap();
+    assert_eq!(orgs.get("corp-mail.com"), Some("Corp Inc"));
+
+    let missing = OrgMap::from_reader(csv.as_bytes(), &OrgColumns::default());
+    assert!(matches!(missing, Err(Error::MissingColumn(column)) if column == "domain"));
+}
+
+#[test]
+fn invalid_org_columns() {
+    assert!("domain".parse::<OrgColumns>().is_err());
+    assert!("domain, ".parse::<OrgColumns>().is_err());
+}
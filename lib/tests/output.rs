@@ -0,0 +1,254 @@
+use std::{fs::File, io::Read};
+
+use age::secrecy::ExposeSecret;
+use arrow_array::{cast::AsArray, RecordBatch};
+use lib::{
+    encryption::{decrypt, Decryption, Encryption},
+    output::{Column, Compression, CsvOptions, OutputFormat, OutputWriter},
+    LeakRecord,
+};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+fn test_record() -> LeakRecord<'static> {
+    LeakRecord {
+        domain: "yandex.net".into(),
+        subdomain: "mail".into(),
+        username: "wolya".into(),
+        password: "55,\"55".into(),
+        password_type: "plain".into(),
+        target_domain: "".into(),
+        source: "combolist".into(),
+        breach_date: "2024-05-01".into(),
+        source_file: "dumps/combo.txt".into(),
+        target_type: "domain".into(),
+    }
+}
+
+#[test]
+fn jsonl_roundtrip() {
+    let path = std::env::temp_dir().join("leaks_output_test.jsonl");
+    let mut writer = OutputWriter::new(&path, OutputFormat::Jsonl, CsvOptions::default()).unwrap();
+    writer.write(&test_record()).unwrap();
+    writer.finish().unwrap();
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let record: LeakRecord = serde_json::from_str(contents.trim_end()).unwrap();
+    assert_eq!(record.domain, "yandex.net");
+    assert_eq!(record.password, "55,\"55");
+    assert_eq!(record.breach().date, "2024-05-01");
+    assert_eq!(record.breach().source_file, "dumps/combo.txt");
+}
+
+#[test]
+fn parquet_roundtrip() {
+    let path = std::env::temp_dir().join("leaks_output_test.parquet");
+    let mut writer =
+        OutputWriter::new(&path, OutputFormat::Parquet, CsvOptions::default()).unwrap();
+    writer.write(&test_record()).unwrap();
+    writer.write(&test_record()).unwrap();
+    writer.finish().unwrap();
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+
+    let usernames = batches[0].column(2).as_string::<i32>();
+    assert_eq!(usernames.value(0), "wolya");
+}
+
+#[test]
+fn csv_dialect() {
+    let path = std::env::temp_dir().join("leaks_output_test.tsv");
+    let csv = CsvOptions {
+        delimiter: b'\t',
+        header: false,
+        ..Default::default()
+    };
+    let mut writer = OutputWriter::new(&path, OutputFormat::Csv, csv).unwrap();
+    writer.write(&test_record()).unwrap();
+    writer.finish().unwrap();
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        contents,
+        "yandex.net\tmail\twolya\t\"55,\"\"55\"\tplain\t\tcombolist\t2024-05-01\tdumps/combo.txt\tdomain\n"
+    );
+}
+
+#[test]
+fn csv_columns() {
+    let path = std::env::temp_dir().join("leaks_output_test_columns.csv");
+    let columns = vec![Column::Email, Column::Password, Column::Source];
+    let mut writer =
+        OutputWriter::csv_columns(&path, CsvOptions::default(), None, None, columns).unwrap();
+    writer.write(&test_record()).unwrap();
+    writer
+        .write(&LeakRecord {
+            subdomain: "".into(),
+            ..test_record()
+        })
+        .unwrap();
+    writer.finish().unwrap();
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        contents,
+        "email,password,source\nwolya@mail.yandex.net,\"55,\"\"55\",combolist\nwolya@yandex.net,\"55,\"\"55\",combolist\n"
+    );
+}
+
+#[test]
+fn compressed_csv() {
+    let path = Compression::Gzip.output_path(&std::env::temp_dir().join("leaks_output_test.csv"));
+    assert_eq!(path.extension().unwrap(), "gz");
+    assert_eq!(Compression::Gzip.output_path(&path), path);
+
+    let mut writer = OutputWriter::compressed(
+        &path,
+        OutputFormat::Csv,
+        CsvOptions::default(),
+        Some(Compression::Gzip),
+    )
+    .unwrap();
+    writer.write(&test_record()).unwrap();
+    writer.finish().unwrap();
+
+    let mut contents = String::new();
+    flate2::read::GzDecoder::new(File::open(&path).unwrap())
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert_eq!(
+        contents,
+        "domain,subdomain,username,password,password_type,target_domain,source,breach_date,source_file,target_type\n\
+         yandex.net,mail,wolya,\"55,\"\"55\",plain,,combolist,2024-05-01,dumps/combo.txt,domain\n"
+    );
+}
+
+#[test]
+fn compressed_jsonl() {
+    let path = Compression::Zstd.output_path(&std::env::temp_dir().join("leaks_output_test.jsonl"));
+    assert_eq!(path.extension().unwrap(), "zst");
+
+    let mut writer = OutputWriter::compressed(
+        &path,
+        OutputFormat::Jsonl,
+        CsvOptions::default(),
+        Some(Compression::Zstd),
+    )
+    .unwrap();
+    writer.write(&test_record()).unwrap();
+    writer.finish().unwrap();
+
+    let contents = zstd::decode_all(File::open(&path).unwrap()).unwrap();
+    let record: LeakRecord = serde_json::from_slice(contents.trim_ascii_end()).unwrap();
+    assert_eq!(record.username, "wolya");
+}
+
+/// Fresh x25519 key pair, the identity is saved to a file like age-keygen does
+fn test_key(name: &str) -> (Encryption, Decryption) {
+    let identity = age::x25519::Identity::generate();
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, identity.to_string().expose_secret()).unwrap();
+    (
+        Encryption::Recipient(identity.to_public()),
+        Decryption::IdentityFile(path),
+    )
+}
+
+#[test]
+fn encrypted_compressed_csv() {
+    let (encryption, decryption) = test_key("leaks_output_test_csv.key");
+    let path = Compression::Gzip.output_path(&std::env::temp_dir().join("leaks_output_test.csv"));
+    let path = encryption.output_path(&path);
+    assert!(path.to_string_lossy().ends_with(".csv.gz.age"));
+    assert_eq!(encryption.output_path(&path), path);
+
+    let mut writer = OutputWriter::encrypted(
+        &path,
+        OutputFormat::Csv,
+        CsvOptions::default(),
+        Some(Compression::Gzip),
+        Some(&encryption),
+    )
+    .unwrap();
+    writer.write(&test_record()).unwrap();
+    writer.finish().unwrap();
+
+    let encrypted = std::fs::read(&path).unwrap();
+    assert!(!encrypted.windows(5).any(|x| x == b"wolya"));
+
+    let plaintext = decrypt(File::open(&path).unwrap(), &decryption).unwrap();
+    let mut contents = String::new();
+    flate2::read::GzDecoder::new(plaintext)
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert!(contents.ends_with(
+        "yandex.net,mail,wolya,\"55,\"\"55\",plain,,combolist,2024-05-01,dumps/combo.txt,domain\n"
+    ));
+}
+
+#[test]
+fn encrypted_parquet() {
+    let (encryption, decryption) = test_key("leaks_output_test_parquet.key");
+    let path = encryption.output_path(&std::env::temp_dir().join("leaks_output_test.parquet"));
+
+    let mut writer = OutputWriter::encrypted(
+        &path,
+        OutputFormat::Parquet,
+        CsvOptions::default(),
+        None,
+        Some(&encryption),
+    )
+    .unwrap();
+    writer.write(&test_record()).unwrap();
+    writer.finish().unwrap();
+
+    let mut plaintext = tempfile::tempfile().unwrap();
+    std::io::copy(
+        &mut decrypt(File::open(&path).unwrap(), &decryption).unwrap(),
+        &mut plaintext,
+    )
+    .unwrap();
+    let reader = ParquetRecordBatchReaderBuilder::try_new(plaintext)
+        .unwrap()
+        .build()
+        .unwrap();
+    let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+    assert_eq!(batches[0].column(2).as_string::<i32>().value(0), "wolya");
+}
+
+#[test]
+fn wrong_key() {
+    let (encryption, _) = test_key("leaks_output_test_wrong.key");
+    let (_, decryption) = test_key("leaks_output_test_other.key");
+    let path = encryption.output_path(&std::env::temp_dir().join("leaks_output_test_wrong.csv"));
+
+    let mut writer = OutputWriter::encrypted(
+        &path,
+        OutputFormat::Csv,
+        CsvOptions::default(),
+        None,
+        Some(&encryption),
+    )
+    .unwrap();
+    writer.write(&test_record()).unwrap();
+    writer.finish().unwrap();
+
+    assert!(decrypt(File::open(&path).unwrap(), &decryption).is_err());
+}
+
+#[test]
+fn parse_encryption() {
+    let identity = age::x25519::Identity::generate();
+    let recipient = identity.to_public().to_string();
+    assert!(matches!(
+        format!("age:{}", recipient).parse(),
+        Ok(Encryption::Recipient(_))
+    ));
+    assert!(matches!("passphrase".parse(), Ok(Encryption::Passphrase)));
+    assert!("age:age1nope".parse::<Encryption>().is_err());
+    assert!("rot13".parse::<Encryption>().is_err());
+}
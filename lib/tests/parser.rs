@@ -1,10 +1,9 @@
-use lib::parse_domain;
-use suffix::SuffixTable;
+use lib::{parse_domain, parse_domain_full, DomainParts, PublicSuffixList};
 
 #[test]
 fn one_subdomain() {
     let tlds = "ru edu.ru com net";
-    let st = SuffixTable::new(tlds);
+    let st = PublicSuffixList::new(tlds);
     let domain = "test.yandex.ru";
     let (subdomain, domain) = parse_domain(domain, &st);
     assert_eq!(subdomain, "test");
@@ -14,7 +13,7 @@ fn one_subdomain() {
 #[test]
 fn two_subdomains() {
     let tlds = "ru edu.ru com net";
-    let st = SuffixTable::new(tlds);
+    let st = PublicSuffixList::new(tlds);
     let domain = "test2.test.yandex.ru";
     let (subdomain, domain) = parse_domain(domain, &st);
     assert_eq!(subdomain, "test2.test");
@@ -24,7 +23,7 @@ fn two_subdomains() {
 #[test]
 fn three_subdomains() {
     let tlds = "ru edu.ru com net";
-    let st = SuffixTable::new(tlds);
+    let st = PublicSuffixList::new(tlds);
     let domain = "test3.test2.test.yandex.edu.ru";
     let (subdomain, domain) = parse_domain(domain, &st);
     assert_eq!(subdomain, "test3.test2.test");
@@ -34,7 +33,7 @@ fn three_subdomains() {
 #[test]
 fn many_subdomains() {
     let tlds = "ru edu.ru com net";
-    let st = SuffixTable::new(tlds);
+    let st = PublicSuffixList::new(tlds);
     let domain = "test5.test4.test3.test2.test.yandex.edu.ru";
     let (subdomain, domain) = parse_domain(domain, &st);
     assert_eq!(subdomain, "test5.test4.test3.test2.test");
@@ -44,7 +43,7 @@ fn many_subdomains() {
 #[test]
 fn domain() {
     let tlds = "ru edu.ru com net";
-    let st = SuffixTable::new(tlds);
+    let st = PublicSuffixList::new(tlds);
     let domain = "yandex.net";
     let (subdomain, domain) = parse_domain(domain, &st);
     assert!(subdomain.is_empty());
@@ -54,7 +53,7 @@ fn domain() {
 #[test]
 fn without_domain() {
     let tlds = "ru edu.ru com net";
-    let st = SuffixTable::new(tlds);
+    let st = PublicSuffixList::new(tlds);
     let domain = "yandexnet";
     let (subdomain, domain) = parse_domain(domain, &st);
     assert!(subdomain.is_empty());
@@ -64,7 +63,7 @@ fn without_domain() {
 #[test]
 fn utf8_str() {
     let tlds = "ru edu.ru com net co.kr";
-    let st = SuffixTable::new(tlds);
+    let st = PublicSuffixList::new(tlds);
     let domain = "Р»вЂћВ¤Р»Сњ РјвЂўв„ў.co.kr";
 
     let (subdomain, domain) = parse_domain(domain, &st);
@@ -72,3 +71,96 @@ fn utf8_str() {
     assert!(subdomain.is_empty());
     assert_eq!(domain, "Р»вЂћВ¤Р»Сњ РјвЂўв„ў.co.kr");
 }
+
+#[test]
+fn wildcard_rule() {
+    let tlds = "ru com *.ck";
+    let st = PublicSuffixList::new(tlds);
+    let (subdomain, domain) = parse_domain("www.shop.co.ck", &st);
+    assert_eq!(subdomain, "www");
+    assert_eq!(domain, "shop.co.ck");
+}
+
+#[test]
+fn exception_rule() {
+    let tlds = "ru com *.ck !www.ck";
+    let st = PublicSuffixList::new(tlds);
+    let (subdomain, domain) = parse_domain("mail.www.ck", &st);
+    assert_eq!(subdomain, "mail");
+    assert_eq!(domain, "www.ck");
+}
+
+#[test]
+fn private_domains() {
+    let psl = "// ===BEGIN ICANN DOMAINS===\nio\n// ===BEGIN PRIVATE DOMAINS===\ngithub.io\n";
+
+    let st = PublicSuffixList::from_reader(&mut psl.as_bytes(), false);
+    let (subdomain, domain) = parse_domain("user.github.io", &st);
+    assert_eq!(subdomain, "user");
+    assert_eq!(domain, "github.io");
+
+    let st = PublicSuffixList::from_reader(&mut psl.as_bytes(), true);
+    let (subdomain, domain) = parse_domain("user.github.io", &st);
+    assert!(subdomain.is_empty());
+    assert_eq!(domain, "user.github.io");
+}
+
+#[test]
+fn full_multi_label_suffix() {
+    let st = PublicSuffixList::new("uk co.uk ru edu.ru");
+    assert_eq!(
+        parse_domain_full("mail.corp.co.uk", &st),
+        DomainParts {
+            subdomain: "mail",
+            registrable_domain: "corp.co.uk",
+            sld: "corp",
+            suffix: "co.uk",
+        }
+    );
+    assert_eq!(
+        parse_domain_full("a.b.yandex.edu.ru", &st),
+        DomainParts {
+            subdomain: "a.b",
+            registrable_domain: "yandex.edu.ru",
+            sld: "yandex",
+            suffix: "edu.ru",
+        }
+    );
+}
+
+#[test]
+fn full_wildcard_suffix() {
+    let st = PublicSuffixList::new("ck *.ck !www.ck");
+    let parts = parse_domain_full("www.shop.co.ck", &st);
+    assert_eq!(parts.sld, "shop");
+    assert_eq!(parts.suffix, "co.ck");
+
+    let parts = parse_domain_full("mail.www.ck", &st);
+    assert_eq!(parts.subdomain, "mail");
+    assert_eq!(parts.sld, "www");
+    assert_eq!(parts.suffix, "ck");
+}
+
+#[test]
+fn full_without_subdomain() {
+    let st = PublicSuffixList::new("com co.uk");
+    let parts = parse_domain_full("corp.com", &st);
+    assert_eq!(parts.subdomain, "");
+    assert_eq!(parts.registrable_domain, "corp.com");
+    assert_eq!(parts.sld, "corp");
+    assert_eq!(parts.suffix, "com");
+}
+
+#[test]
+fn full_bare_suffix() {
+    let st = PublicSuffixList::new("uk co.uk");
+    let parts = parse_domain_full("co.uk", &st);
+    assert_eq!(parts.registrable_domain, "co.uk");
+    assert_eq!(parts.sld, "co");
+    assert_eq!(parts.suffix, "uk");
+
+    let parts = parse_domain_full("localhost", &st);
+    assert_eq!(parts.registrable_domain, "localhost");
+    assert_eq!(parts.sld, "");
+    assert_eq!(parts.suffix, "localhost");
+}
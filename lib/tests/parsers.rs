@@ -0,0 +1,333 @@
+use lib::{
+    entry::{parse_line, EntryFormat, ParseError, ParseOptions, ParsedEntry, TargetType},
+    parsers::{Delimited, LineParser, ParserKind, Parsers, RecordField, PHONE_DOMAIN},
+    PublicSuffixList,
+};
+
+fn gen_test_st() -> PublicSuffixList {
+    PublicSuffixList::new("com net co.uk")
+}
+
+fn options(kinds: &[ParserKind], records: Delimited) -> ParseOptions {
+    ParseOptions {
+        parsers: Parsers::new(kinds, records),
+        ..Default::default()
+    }
+}
+
+fn fields(line: &str, options: &ParseOptions) -> Result<(String, String, String), ParseError> {
+    let entry = parse_line(line, &gen_test_st(), options)?;
+    Ok((
+        entry.username.into_owned(),
+        entry.password.to_string(),
+        entry.domain,
+    ))
+}
+
+fn owned(username: &str, password: &str, domain: &str) -> (String, String, String) {
+    (
+        username.to_string(),
+        password.to_string(),
+        domain.to_string(),
+    )
+}
+
+#[test]
+fn default_parsers_take_both_layouts() {
+    let options = ParseOptions::default();
+    assert_eq!(
+        fields("wolya@yandex.net:5555", &options),
+        Ok(owned("wolya", "5555", "yandex.net"))
+    );
+    assert_eq!(
+        fields("wolya:5555@yandex.net", &options),
+        Ok(owned("wolya", "5555", "yandex.net"))
+    );
+    assert_eq!(fields("wolya", &options), Err(ParseError::NoSeparator));
+}
+
+#[test]
+fn selected_layout_only() {
+    let email = options(&[ParserKind::EmailPass], Delimited::default());
+    assert_eq!(
+        fields("wolya:5555@yandex.net", &email),
+        Err(ParseError::BadFormat)
+    );
+
+    let credentials_first = options(&[ParserKind::UserPassDomain], Delimited::default());
+    assert_eq!(
+        fields("wolya:5555@yandex.net", &credentials_first),
+        Ok(owned("wolya", "5555", "yandex.net"))
+    );
+    assert_eq!(
+        fields("wolya@yandex.net:5555", &credentials_first),
+        Err(ParseError::BadFormat)
+    );
+}
+
+#[test]
+fn first_parser_wins() {
+    let tsv = Delimited::new(
+        b'\t',
+        vec![
+            RecordField::Username,
+            RecordField::Password,
+            RecordField::Domain,
+        ],
+    )
+    .unwrap();
+    let line = "wolya@mail.com:55\t55\tcorp.com";
+
+    let email_first = options(&[ParserKind::EmailPass, ParserKind::Delimited], tsv.clone());
+    assert_eq!(
+        fields(line, &email_first),
+        Ok(owned("wolya", "55\t55\tcorp.com", "mail.com"))
+    );
+
+    let delimited_first = options(&[ParserKind::Delimited, ParserKind::EmailPass], tsv);
+    assert_eq!(
+        fields(line, &delimited_first),
+        Ok(owned("wolya@mail.com:55", "55", "corp.com"))
+    );
+}
+
+#[test]
+fn error_of_first_accepting_parser() {
+    let delimited_first = options(
+        &[ParserKind::Delimited, ParserKind::EmailPass],
+        Delimited::default(),
+    );
+    assert_eq!(
+        fields("wolya,5555", &delimited_first),
+        Err(ParseError::BadFormat)
+    );
+    assert_eq!(
+        fields("wolya", &delimited_first),
+        Err(ParseError::NoSeparator)
+    );
+
+    let email_first = options(
+        &[ParserKind::EmailPass, ParserKind::Delimited],
+        Delimited::default(),
+    );
+    assert_eq!(
+        fields("wolya,5555", &email_first),
+        Err(ParseError::NoSeparator)
+    );
+}
+
+#[test]
+fn delimited_records() {
+    let csv = |fields: Vec<RecordField>| {
+        options(
+            &[ParserKind::Delimited],
+            Delimited::new(b',', fields).unwrap(),
+        )
+    };
+
+    let skipped = csv(vec![
+        RecordField::Skip,
+        RecordField::Email,
+        RecordField::Password,
+    ]);
+    assert_eq!(
+        fields("17,\"wolya@mail.com\",55,55", &skipped),
+        Ok(owned("wolya", "55,55", "mail.com"))
+    );
+    assert_eq!(
+        fields("17,wolya@mail.com", &skipped),
+        Err(ParseError::NoSeparator)
+    );
+    for line in ["17,@mail.com,55", "17,wolya@,55"] {
+        assert_eq!(
+            fields(line, &skipped),
+            Err(ParseError::BadFormat),
+            "{}",
+            line
+        );
+    }
+
+    let url = csv(vec![
+        RecordField::Url,
+        RecordField::Username,
+        RecordField::Password,
+    ]);
+    let entry = parse_line(
+        "https://vpn.corp.com/login,admin,5555",
+        &gen_test_st(),
+        &url,
+    )
+    .unwrap();
+    assert_eq!(entry.username, "admin");
+    assert_eq!(entry.subdomain, "vpn");
+    assert_eq!(entry.domain, "corp.com");
+    assert_eq!(entry.target_domain, "vpn.corp.com");
+}
+
+#[test]
+fn delimited_fields_are_checked() {
+    let new = |fields: &[RecordField]| Delimited::new(b',', fields.to_vec()).is_ok();
+    assert!(new(&[RecordField::Email, RecordField::Password]));
+    assert!(new(&[
+        RecordField::Username,
+        RecordField::Domain,
+        RecordField::Password
+    ]));
+    assert!(!new(&[RecordField::Username, RecordField::Password]));
+    assert!(!new(&[RecordField::Email, RecordField::Skip]));
+    assert!(!new(&[
+        RecordField::Email,
+        RecordField::Password,
+        RecordField::Password
+    ]));
+}
+
+#[test]
+fn password_types() {
+    let st = gen_test_st();
+    let options = ParseOptions {
+        format: EntryFormat::EmailHash,
+        parsers: Parsers::new(
+            &[ParserKind::EmailPass, ParserKind::UrlUserPass],
+            Delimited::default(),
+        ),
+        ..Default::default()
+    };
+
+    let (_, password_type) = options
+        .parsers
+        .parse("wolya@mail.com:5f4dcc3b", &st, &options)
+        .unwrap();
+    assert_eq!(password_type, "hash");
+
+    let (entry, password_type) = options
+        .parsers
+        .parse("https://site.com:admin:5555", &st, &options)
+        .unwrap();
+    assert_eq!(entry.target_domain, "site.com");
+    assert_eq!(password_type, "plain");
+}
+
+/// login|password of a single site, filed under its domain
+struct Pipe;
+
+impl LineParser for Pipe {
+    fn name(&self) -> &'static str {
+        "pipe"
+    }
+
+    fn parse<'a>(
+        &self,
+        line: &'a str,
+        _st: &PublicSuffixList,
+        _options: &ParseOptions,
+    ) -> Result<ParsedEntry<'a>, ParseError> {
+        let (login, password) = line.split_once('|').ok_or(ParseError::NoSeparator)?;
+        Ok(ParsedEntry {
+            username: login.into(),
+            password,
+            subdomain: String::new(),
+            domain: "site.com".to_string(),
+            target_domain: String::new(),
+            target_type: TargetType::Domain,
+            corrected: false,
+        })
+    }
+}
+
+#[test]
+fn custom_parser() {
+    let options = ParseOptions {
+        parsers: Parsers::new(&[ParserKind::EmailPass], Delimited::default()).with(Pipe),
+        ..Default::default()
+    };
+    assert_eq!(
+        fields("wolya|5555", &options),
+        Ok(owned("wolya", "5555", "site.com"))
+    );
+    assert_eq!(
+        format!("{:?}", options.parsers),
+        r#"["email:pass", "pipe"]"#
+    );
+}
+
+#[test]
+fn detected_parser() {
+    let st = gen_test_st();
+    let options = options(
+        &[
+            ParserKind::EmailPass,
+            ParserKind::UserPassDomain,
+            ParserKind::Delimited,
+        ],
+        Delimited::default(),
+    );
+    let detect = |sample: &[&str]| {
+        options
+            .parsers
+            .detect(sample, &st, &options)
+            .map(|(parsers, hits)| (parsers.names(), hits))
+    };
+
+    assert_eq!(
+        detect(&["a:1@mail.com", "b@mail.com:2", "c:3@mail.com"]),
+        Some((vec!["user:pass@domain"], 2))
+    );
+    assert_eq!(
+        detect(&["a@mail.com:1", "b:2@mail.com"]),
+        Some((vec!["email:pass"], 1))
+    );
+    assert_eq!(detect(&["a@mail.com,1"]), Some((vec!["delimited"], 1)));
+    assert_eq!(detect(&["broken"]), None);
+
+    let defaults = ParseOptions::default();
+    let (parsers, _) = defaults
+        .parsers
+        .detect(&["a:1@mail.com"], &st, &defaults)
+        .unwrap();
+    assert_eq!(parsers.names(), ["user:pass@domain"]);
+}
+
+#[test]
+fn parser_names() {
+    assert_eq!("user:pass@domain".parse(), Ok(ParserKind::UserPassDomain));
+    assert!("email:hash".parse::<ParserKind>().is_err());
+}
+
+#[test]
+fn phone_logins() {
+    let phones = options(
+        &[ParserKind::EmailPass, ParserKind::PhonePass],
+        Delimited::default(),
+    );
+    assert_eq!(
+        fields("+79161234567:5555", &phones),
+        Ok(owned("+79161234567", "5555", PHONE_DOMAIN))
+    );
+    assert_eq!(
+        fields("8 (916) 123-45-67;55:55", &phones),
+        Ok(owned("89161234567", "55:55", PHONE_DOMAIN))
+    );
+    assert_eq!(
+        fields("wolya@mail.com:5555", &phones),
+        Ok(owned("wolya", "5555", "mail.com"))
+    );
+
+    let only_phones = options(&[ParserKind::PhonePass], Delimited::default());
+    assert_eq!(
+        fields("+7916:5555", &only_phones),
+        Err(ParseError::BadFormat)
+    );
+    assert_eq!(
+        fields("+79161234567:", &only_phones),
+        Err(ParseError::BadFormat)
+    );
+    assert_eq!(
+        fields("+7916123456x:5555", &only_phones),
+        Err(ParseError::BadFormat)
+    );
+    assert_eq!(
+        fields("+79161234567:5555", &ParseOptions::default()),
+        Err(ParseError::BadFormat)
+    );
+}
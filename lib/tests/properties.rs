@@ -0,0 +1,123 @@
+use std::sync::OnceLock;
+
+use lib::{
+    entry::{parse_entry, parse_line, regex_extract, EntryFormat, ParseOptions, Separators},
+    parse_domain, parse_domain_full, DomainForm, PublicSuffixList, UsernameRules,
+};
+use proptest::prelude::*;
+use regex::Regex;
+
+static TLDS: &str = "com net org ru co.uk uk *.ck !www.ck";
+
+/// Every layout and heuristic of the parser, so each code path sees the input.
+/// Built once, custom separators compile regexes of their own
+fn all_options() -> &'static [ParseOptions] {
+    static ALL: OnceLock<Vec<ParseOptions>> = OnceLock::new();
+    ALL.get_or_init(build_options)
+}
+
+fn build_options() -> Vec<ParseOptions> {
+    let formats = [
+        EntryFormat::EmailPass,
+        EntryFormat::EmailHash,
+        EntryFormat::EmailHashSalt,
+        EntryFormat::UserEmailPass,
+        EntryFormat::UrlLoginPass,
+    ];
+    let any_username = Regex::new(".").unwrap();
+    let tab_separators: Separators = r"\t:|".parse().unwrap();
+    let mut all = Vec::new();
+
+    for format in formats {
+        for flags in 0..32 {
+            let mut options = ParseOptions {
+                format,
+                unicode_usernames: flags & 1 != 0,
+                last_colon: flags & 2 != 0,
+                ..Default::default()
+            };
+            if flags & 4 != 0 {
+                options.domain_form = Some(DomainForm::Ascii);
+                options.username_rules = Some(UsernameRules::default());
+            }
+            if flags & 8 != 0 {
+                options.rules.username_pattern = Some(any_username.clone());
+            }
+            if flags & 16 != 0 {
+                options.separators = tab_separators.clone();
+            }
+            all.push(options);
+        }
+    }
+    all
+}
+
+/// Lines made of the characters the parser splits on, mixed with anything else
+fn entry_like() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<String>(),
+        "[a-z0-9@:;|\t.\"\\\\/ \\-+_яé]{0,40}",
+        "(https?://)?[a-z.]{0,12}(:[0-9]{0,5})?(/[a-z]*)?:[a-z@.:;]{0,20}",
+    ]
+}
+
+proptest! {
+    #[test]
+    fn regex_extract_returns_parts_of_the_entry(entry in entry_like()) {
+        if let Ok((username, domain, password)) = regex_extract(&entry) {
+            prop_assert!(entry.contains(username));
+            prop_assert!(entry.contains(domain));
+            prop_assert!(entry.contains(password));
+            prop_assert!(!password.is_empty());
+        }
+    }
+
+    #[test]
+    fn parse_line_never_panics(entry in entry_like()) {
+        let st = PublicSuffixList::new(TLDS);
+        for options in all_options() {
+            if let Ok(parsed) = parse_line(&entry, &st, options) {
+                prop_assert!(entry.contains(parsed.password));
+                prop_assert!(!parsed.domain.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn parse_domain_splits_at_a_label(domain in "[a-z.\\-яé!*]{0,30}") {
+        let st = PublicSuffixList::new(TLDS);
+        let (subdomain, registrable) = parse_domain(&domain, &st);
+        prop_assert!(domain.ends_with(registrable));
+        if !subdomain.is_empty() {
+            prop_assert_eq!(format!("{}.{}", subdomain, registrable), domain.clone());
+        }
+
+        let parts = parse_domain_full(&domain, &st);
+        prop_assert!(domain.ends_with(parts.suffix));
+    }
+
+    #[test]
+    fn well_formed_entries_round_trip(
+        username in "[a-z0-9]{1,20}([._+-][a-z0-9]{1,10})?",
+        subdomain in "([a-z0-9]{1,10}\\.){0,2}",
+        name in "[a-z0-9]{1,20}",
+        tld in "com|net|org|co\\.uk",
+        password in "[!-?A-~]{1,20}",
+        credentials_first in any::<bool>(),
+    ) {
+        let st = PublicSuffixList::new(TLDS);
+        let host = format!("{}{}.{}", subdomain, name, tld);
+        let entry = if credentials_first {
+            format!("{}:{}@{}", username, password, host)
+        } else {
+            format!("{}@{}:{}", username, host, password)
+        };
+
+        let (parsed_username, parsed_password, parsed_subdomain, domain) =
+            parse_entry(&entry, &st).unwrap();
+        prop_assert_eq!(parsed_username, username);
+        prop_assert_eq!(parsed_password, password);
+        prop_assert_eq!(parsed_subdomain, subdomain.trim_end_matches('.'));
+        prop_assert_eq!(domain, format!("{}.{}", name, tld));
+    }
+}
@@ -0,0 +1,85 @@
+use lib::{
+    entry::{parse_formatted_entry, ParseError, ParseOptions},
+    rules::ValidationRules,
+    PublicSuffixList,
+};
+
+fn options(rules: &str) -> ParseOptions {
+    ParseOptions {
+        rules: ValidationRules::from_toml(rules).unwrap(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn template_parses() {
+    let template = include_str!("../../leaks_indexer/rules.toml.template");
+    let rules = ValidationRules::from_toml(template).unwrap();
+    assert_eq!(rules.max_username_length, 40);
+    assert!(rules.username_pattern.is_none());
+}
+
+#[test]
+fn unknown_field() {
+    assert!(ValidationRules::from_toml("max_user_length = 10").is_err());
+    assert!(ValidationRules::from_toml("username_pattern = '('").is_err());
+}
+
+#[test]
+fn username_pattern() {
+    let st = PublicSuffixList::new("com");
+    let entry = "+tag.пользователь@mail.com:5555";
+
+    assert_eq!(
+        parse_formatted_entry(entry, &st, &ParseOptions::default()),
+        Err(ParseError::BadFormat)
+    );
+
+    let options = options(r"username_pattern = '^[\w.+-]+$'");
+    let (username, _, _, domain) = parse_formatted_entry(entry, &st, &options).unwrap();
+    assert_eq!(username, "+tag.пользователь");
+    assert_eq!(domain, "mail.com");
+
+    assert_eq!(
+        parse_formatted_entry("us$er@mail.com:5555", &st, &options),
+        Err(ParseError::BadUsername)
+    );
+}
+
+#[test]
+fn lengths() {
+    let st = PublicSuffixList::new("com");
+    let options = options("max_username_length = 4\nmin_password_length = 3");
+
+    assert!(parse_formatted_entry("user@mail.com:555", &st, &options).is_ok());
+    assert_eq!(
+        parse_formatted_entry("users@mail.com:555", &st, &options),
+        Err(ParseError::UsernameTooLong)
+    );
+    assert_eq!(
+        parse_formatted_entry("user@mail.com:55", &st, &options),
+        Err(ParseError::BadPassword)
+    );
+}
+
+#[test]
+fn blocklist() {
+    let st = PublicSuffixList::new("com");
+    let options = options(
+        r"
+[blocklist]
+passwords = ['^\$2[aby]\$']
+domains = ['^example\.com$']
+",
+    );
+
+    assert_eq!(
+        parse_formatted_entry("user@mail.com:$2b$10$abc", &st, &options),
+        Err(ParseError::Blocked)
+    );
+    assert_eq!(
+        parse_formatted_entry("user@sub.example.com:5555", &st, &options),
+        Err(ParseError::Blocked)
+    );
+    assert!(parse_formatted_entry("user@example.com.com:5555", &st, &options).is_ok());
+}
@@ -0,0 +1,36 @@
+use std::io::{BufReader, Read};
+
+use lib::sanitize::Sanitizer;
+
+fn sanitize(input: &[u8], capacity: usize) -> Vec<u8> {
+    let mut output = Vec::new();
+    Sanitizer::new(BufReader::with_capacity(capacity, input))
+        .read_to_end(&mut output)
+        .unwrap();
+    output
+}
+
+#[test]
+fn line_endings() {
+    let input = b"a@b.com:1\r\nc@d.com:2\re@f.com:3\n\r\ng@h.com:4\r";
+    let expected = b"a@b.com:1\nc@d.com:2\ne@f.com:3\n\ng@h.com:4\n";
+    // CRLF pairs split between reads still make a single line break
+    for capacity in [1, 2, 3, 8192] {
+        assert_eq!(sanitize(input, capacity), expected, "capacity {}", capacity);
+    }
+}
+
+#[test]
+fn control_characters() {
+    let input = "\u{feff}ivan@mail.ru:\x00pass\x7f\tword\x1b\nпётр@mail.ru:пароль\x00\n";
+    assert_eq!(
+        sanitize(input.as_bytes(), 8192),
+        "ivan@mail.ru:pass\tword\nпётр@mail.ru:пароль\n".as_bytes()
+    );
+}
+
+#[test]
+fn bom_only_at_start() {
+    let input = b"\xef\xbb\xbfa@b.com:1\n\xef\xbb\xbfc@d.com:2\n";
+    assert_eq!(sanitize(input, 8192), b"a@b.com:1\n\xef\xbb\xbfc@d.com:2\n");
+}
@@ -0,0 +1,74 @@
+use lib::{encryption::TempKey, output::CsvOptions, sort::external_sort};
+
+fn sort(input: &str, csv: CsvOptions, memory_limit: usize) -> String {
+    sort_sealed(input, csv, memory_limit, None)
+}
+
+fn sort_sealed(
+    input: &str,
+    csv: CsvOptions,
+    memory_limit: usize,
+    sealed: Option<&TempKey>,
+) -> String {
+    let mut output = Vec::new();
+    external_sort(
+        input.as_bytes(),
+        &mut output,
+        csv,
+        0,
+        memory_limit,
+        &std::env::temp_dir(),
+        sealed,
+    )
+    .unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn sort_in_memory() {
+    let input = "domain,subdomain,username,password,password_type\n\
+                 b.com,,u1,p1,plain\n\
+                 a.com,,u2,p2,plain\n\
+                 b.com,mail,u3,p3,plain\n";
+
+    assert_eq!(
+        sort(input, CsvOptions::default(), usize::MAX),
+        "a.com,,u2,p2,plain\nb.com,,u1,p1,plain\nb.com,mail,u3,p3,plain\n"
+    );
+}
+
+#[test]
+fn sort_spilled_is_stable() {
+    let csv = CsvOptions {
+        delimiter: b'\t',
+        header: false,
+        ..Default::default()
+    };
+    let input: String = (0..100)
+        .map(|i| format!("d{}.com\t\tu{}\tp\tplain\n", i % 7, i))
+        .collect();
+
+    // Every record gets its own chunk
+    let sorted = sort(&input, csv, 1);
+
+    let mut expected: Vec<&str> = input.lines().collect();
+    expected.sort_by_key(|line| line.split('\t').next().unwrap());
+    assert_eq!(sorted.lines().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn sort_spilled_sealed() {
+    let input: String = (0..20)
+        .map(|i| format!("d{}.com,,u{},p,plain\n", i % 3, i))
+        .collect();
+    let csv = CsvOptions {
+        header: false,
+        ..Default::default()
+    };
+
+    let key = TempKey::generate();
+    assert_eq!(
+        sort_sealed(&input, csv, 1, Some(&key)),
+        sort(&input, csv, usize::MAX)
+    );
+}
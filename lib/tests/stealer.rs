@@ -0,0 +1,55 @@
+use lib::stealer::{is_password_file, read_password_file, StealerRecord};
+
+fn record(url: &str, login: &str, password: &str) -> StealerRecord {
+    StealerRecord {
+        url: url.to_string(),
+        login: login.to_string(),
+        password: password.to_string(),
+    }
+}
+
+#[test]
+fn password_files() {
+    assert!(is_password_file("Passwords.txt"));
+    assert!(is_password_file("All Passwords.txt"));
+    assert!(!is_password_file("Cookies.txt"));
+    assert!(!is_password_file("passwords.db"));
+}
+
+#[test]
+fn redline_blocks() {
+    let log = "\
+URL: https://site.com/login\r
+Username: wolya@mail.com\r
+Password: 55:55\r
+Application: Google_[Chrome]_Default\r
+===============\r
+URL: https://vpn.corp.com/\r
+Username: admin\r
+Password: \r
+===============\r
+";
+    assert_eq!(
+        read_password_file(log.as_bytes()).unwrap(),
+        vec![record("https://site.com/login", "wolya@mail.com", "55:55")]
+    );
+}
+
+#[test]
+fn unseparated_blocks() {
+    let log = "\
+Host: site.com
+Login: admin
+Password: secret
+Host: shop.com
+Login: root
+Password: toor
+";
+    assert_eq!(
+        read_password_file(log.as_bytes()).unwrap(),
+        vec![
+            record("site.com", "admin", "secret"),
+            record("shop.com", "root", "toor"),
+        ]
+    );
+}
@@ -0,0 +1,58 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    thread,
+};
+
+use lib::{
+    output::{CompressedFile, CsvFile},
+    telemetry::{self, FlushOnPanic, LogFormat},
+};
+
+#[test]
+fn panics_flush_outputs() {
+    telemetry::init(LogFormat::Text);
+    let path = std::env::temp_dir().join("leaks_telemetry_flush.txt");
+    let mut output = FlushOnPanic::new(BufWriter::new(File::create(&path).unwrap()));
+    output.write_all(b"wolya@mail.com:5555\n").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+    // The output is still buffered and alive when another thread panics
+    assert!(thread::spawn(|| panic!("broken member")).join().is_err());
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "wolya@mail.com:5555\n"
+    );
+
+    output.write_all(b"admin@mail.com:4444\n").unwrap();
+    output.into_inner().flush().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "wolya@mail.com:5555\nadmin@mail.com:4444\n"
+    );
+}
+
+#[test]
+fn panics_flush_csv() {
+    telemetry::init(LogFormat::Text);
+    let path = std::env::temp_dir().join("leaks_telemetry_flush.csv");
+    let file = CompressedFile::create(&path, None).unwrap();
+    let mut output = CsvFile::new(file, &csv::WriterBuilder::new());
+    output.write_record(["mail.com", "wolya", "5555"]).unwrap();
+    output.write_field("mail.com").unwrap();
+
+    assert!(thread::spawn(|| panic!("broken member")).join().is_err());
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "mail.com,wolya,5555\n"
+    );
+
+    output.write_field("admin").unwrap();
+    output.write_field("4444").unwrap();
+    output.end_record().unwrap();
+    output.into_inner().unwrap().finish().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "mail.com,wolya,5555\nmail.com,admin,4444\n"
+    );
+}
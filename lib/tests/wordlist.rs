@@ -0,0 +1,29 @@
+use lib::wordlist::{hashcat_word, wordlist, WordlistFormat};
+
+#[test]
+fn hashcat_escaping() {
+    assert_eq!(hashcat_word("p@ss:word"), "p@ss:word");
+    assert_eq!(hashcat_word("tab\there"), "$HEX[7461620968657265]");
+    assert_eq!(hashcat_word("$HEX[41]"), "$HEX[244845585b34315d]");
+    assert_eq!(hashcat_word("пароль"), "пароль");
+}
+
+#[test]
+fn deduplicated_in_order() {
+    let credentials = [
+        ("admin", "qwerty"),
+        ("root", "123456"),
+        ("admin", "qwerty"),
+        ("user", "qwerty"),
+        ("nopass", ""),
+    ];
+
+    assert_eq!(
+        wordlist(credentials, WordlistFormat::Passwords),
+        vec!["qwerty", "123456"]
+    );
+    assert_eq!(
+        wordlist(credentials, WordlistFormat::Combo),
+        vec!["admin:qwerty", "root:123456", "user:qwerty"]
+    );
+}
@@ -18,7 +18,7 @@ function index(){
   input_path=$1
   indexed_out=$2
   indexed_error_out=$3
-  ./indexer --input-type tar.gz -t ./public_suffix_list.dat -i "${input_path}" -o "${indexed_out}" -e "${indexed_error_out}"
+  ./indexer --input-type tar.gz --no-header -t ./public_suffix_list.dat -i "${input_path}" -o "${indexed_out}" -e "${indexed_error_out}"
 }
 
 function fail_on_rc(){
@@ -61,7 +61,7 @@ rm "${sorted_out}"
 echo "Converting..."
 i=0
 for x in "${sorted_out_dir}/parts."*; do
-  ./ctj -i "${x}" -o "${converted_dir}/${file_name}.${i}.jsonl"
+  ./ctj --no-header -i "${x}" -o "${converted_dir}/${file_name}.${i}.jsonl"
   fail_on_rc $?
 
   rm "${x}"